@@ -1,6 +1,8 @@
 use crate::interop::{new_obj, set_kv};
 use crate::Graph;
-use js_sys::{Float32Array, Uint32Array};
+use contour::layers::ZOrder;
+use contour::model::{BlendMode, GroupItem, GroupMask};
+use js_sys::{Array, Float32Array, Uint32Array};
 use wasm_bindgen::prelude::*;
 type JsValue = wasm_bindgen::JsValue;
 use crate::error;
@@ -11,6 +13,34 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+/// Faces for a snapshot from `Graph.get_region_snapshot()`; pure, so it can run
+/// in a Web Worker. Returns null if the snapshot does not parse or has ids
+/// past the graph caps.
+#[wasm_bindgen]
+pub fn compute_region_snapshot(snapshot: JsValue) -> JsValue {
+    match serde_wasm_bindgen::from_value::<contour::algorithms::region_snapshot::RegionSnapshot>(
+        snapshot,
+    ) {
+        Ok(snap) => match contour::algorithms::region_snapshot::compute_region_snapshot(&snap) {
+            Ok(result) => serde_wasm_bindgen::to_value(&result).unwrap(),
+            Err(_) => JsValue::NULL,
+        },
+        Err(_) => JsValue::NULL,
+    }
+}
+#[wasm_bindgen]
+pub fn compute_region_snapshot_res(snapshot: JsValue) -> JsValue {
+    match serde_wasm_bindgen::from_value::<contour::algorithms::region_snapshot::RegionSnapshot>(
+        snapshot,
+    ) {
+        Ok(snap) => match contour::algorithms::region_snapshot::compute_region_snapshot(&snap) {
+            Ok(result) => error::ok(serde_wasm_bindgen::to_value(&result).unwrap()),
+            Err((code, msg)) => error::err(code, msg, None),
+        },
+        Err(e) => error::err("invalid_snapshot", format!("{}", e), None),
+    }
+}
+
 #[wasm_bindgen]
 impl Graph {
     #[wasm_bindgen(constructor)]
@@ -47,6 +77,9 @@ impl Graph {
         if self.inner.get_node(id).is_none() {
             return error::invalid_id("node", id);
         }
+        if self.inner.is_node_locked(id) {
+            return error::locked("node", id);
+        }
         let ok = self.inner.move_node(id, x, y);
         error::ok(JsValue::from_bool(ok))
     }
@@ -71,6 +104,9 @@ impl Graph {
         if self.inner.get_node(id).is_none() {
             return error::invalid_id("node", id);
         }
+        if self.inner.is_node_locked(id) {
+            return error::locked("node", id);
+        }
         error::ok(JsValue::from_bool(self.inner.remove_node(id)))
     }
     pub fn node_count(&self) -> u32 {
@@ -105,6 +141,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         error::ok(JsValue::from_bool(self.inner.remove_edge(id)))
     }
     pub fn edge_count(&self) -> u32 {
@@ -141,50 +180,24 @@ impl Graph {
             "stroke_widths",
             &crate::interop::arr_f32(&ea.stroke_widths).into(),
         );
+        crate::interop::set_kv(
+            &obj,
+            "construction",
+            &crate::interop::arr_u8(&ea.construction).into(),
+        );
         obj.into()
     }
 
     // Picking + JSON + SVG
     pub fn pick(&self, x: f32, y: f32, tol: f32) -> JsValue {
-        if let Some(p) = self.inner.pick(x, y, tol) {
-            // Flatten to { kind: 'node'|'edge'|'handle', ... }
-            let obj = crate::interop::new_obj();
-            match p {
-                contour::Pick::Node { id, dist } => {
-                    crate::interop::set_kv(&obj, "kind", &JsValue::from_str("node"));
-                    crate::interop::set_kv(&obj, "id", &JsValue::from_f64(id as f64));
-                    crate::interop::set_kv(&obj, "dist", &JsValue::from_f64(dist as f64));
-                }
-                contour::Pick::Edge { id, t, dist } => {
-                    crate::interop::set_kv(&obj, "kind", &JsValue::from_str("edge"));
-                    crate::interop::set_kv(&obj, "id", &JsValue::from_f64(id as f64));
-                    crate::interop::set_kv(&obj, "t", &JsValue::from_f64(t as f64));
-                    crate::interop::set_kv(&obj, "dist", &JsValue::from_f64(dist as f64));
-                }
-                contour::Pick::Handle { edge, end, dist } => {
-                    crate::interop::set_kv(&obj, "kind", &JsValue::from_str("handle"));
-                    crate::interop::set_kv(&obj, "edge", &JsValue::from_f64(edge as f64));
-                    crate::interop::set_kv(&obj, "end", &JsValue::from_f64(end as f64));
-                    crate::interop::set_kv(&obj, "dist", &JsValue::from_f64(dist as f64));
-                }
-            }
-            obj.into()
-        } else {
-            JsValue::NULL
+        match self.inner.pick(x, y, tol) {
+            Some(p) => pick_to_js(p),
+            None => JsValue::NULL,
         }
     }
     pub fn pick_res(&self, x: f32, y: f32, tol: f32) -> JsValue {
-        if !x.is_finite() {
-            return error::non_finite("x");
-        }
-        if !y.is_finite() {
-            return error::non_finite("y");
-        }
-        if !tol.is_finite() {
-            return error::non_finite("tol");
-        }
-        if tol < 0.0 {
-            return error::out_of_range("tol", 0.0, f32::INFINITY, tol);
+        if let Some(e) = check_pick_args(x, y, tol) {
+            return e;
         }
         let v = self.pick(x, y, tol);
         if v.is_null() {
@@ -193,9 +206,83 @@ impl Graph {
             error::ok(v)
         }
     }
+    /// Like `pick`, but falls back to the region under the point.
+    pub fn pick_with_regions(&mut self, x: f32, y: f32, tol: f32) -> JsValue {
+        match self.inner.pick_with_regions(x, y, tol) {
+            Some(p) => pick_to_js(p),
+            None => JsValue::NULL,
+        }
+    }
+    pub fn pick_with_regions_res(&mut self, x: f32, y: f32, tol: f32) -> JsValue {
+        if let Some(e) = check_pick_args(x, y, tol) {
+            return e;
+        }
+        error::ok(self.pick_with_regions(x, y, tol))
+    }
+    pub fn pick_all(&mut self, x: f32, y: f32, tol: f32) -> JsValue {
+        let out: Array = self
+            .inner
+            .pick_all(x, y, tol)
+            .into_iter()
+            .map(pick_to_js)
+            .collect();
+        out.into()
+    }
+    pub fn pick_all_res(&mut self, x: f32, y: f32, tol: f32) -> JsValue {
+        if let Some(e) = check_pick_args(x, y, tol) {
+            return e;
+        }
+        error::ok(self.pick_all(x, y, tol))
+    }
+    pub fn select_in_rect(&self, x0: f32, y0: f32, x1: f32, y1: f32, mode: u8) -> JsValue {
+        let Some(mode) = select_mode(mode) else {
+            return JsValue::NULL;
+        };
+        serde_wasm_bindgen::to_value(&self.inner.select_in_rect(x0, y0, x1, y1, mode)).unwrap()
+    }
+    pub fn select_in_rect_res(&self, x0: f32, y0: f32, x1: f32, y1: f32, mode: u8) -> JsValue {
+        for (name, v) in [("x0", x0), ("y0", y0), ("x1", x1), ("y1", y1)] {
+            if !v.is_finite() {
+                return error::non_finite(name);
+            }
+        }
+        if select_mode(mode).is_none() {
+            return invalid_select_mode();
+        }
+        error::ok(self.select_in_rect(x0, y0, x1, y1, mode))
+    }
+    pub fn select_in_polygon(&self, points: &Float32Array, mode: u8) -> JsValue {
+        let Some(mode) = select_mode(mode) else {
+            return JsValue::NULL;
+        };
+        let pts = to_pairs(points);
+        serde_wasm_bindgen::to_value(&self.inner.select_in_polygon(&pts, mode)).unwrap()
+    }
+    pub fn select_in_polygon_res(&self, points: &Float32Array, mode: u8) -> JsValue {
+        let len = points.length() as usize;
+        if len % 2 == 1 || len < 6 {
+            return error::err(
+                "invalid_array",
+                "points must have even length and at least 3 points",
+                None,
+            );
+        }
+        let mut buf = vec![0.0f32; len];
+        points.copy_to(&mut buf);
+        if buf.iter().any(|v| !v.is_finite()) {
+            return error::non_finite("points");
+        }
+        if select_mode(mode).is_none() {
+            return invalid_select_mode();
+        }
+        error::ok(self.select_in_polygon(points, mode))
+    }
     pub fn to_json(&self) -> JsValue {
         serde_wasm_bindgen::to_value(&self.inner.to_json_value()).unwrap()
     }
+    pub fn to_json_with_regions(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.to_json_value_with_regions()).unwrap()
+    }
     pub fn from_json(&mut self, v: JsValue) -> bool {
         match serde_wasm_bindgen::from_value::<serde_json::Value>(v) {
             Ok(val) => self.inner.from_json_value(val),
@@ -262,6 +349,92 @@ impl Graph {
     pub fn get_regions_res(&mut self) -> JsValue {
         error::ok(self.get_regions())
     }
+    /// { from_ver, to_ver, remaps: [{ old, new, kind }] } from the last
+    /// `get_regions` that saw new geometry.
+    pub fn get_region_remap(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.region_remap_report()).unwrap()
+    }
+    pub fn get_region_remap_res(&self) -> JsValue {
+        error::ok(self.get_region_remap())
+    }
+    /// { geom_ver, flatten_tol, backend, nodes, edges } to post to a worker.
+    pub fn get_region_snapshot(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.region_snapshot()).unwrap()
+    }
+    pub fn get_region_snapshot_res(&self) -> JsValue {
+        error::ok(self.get_region_snapshot())
+    }
+    /// Install a `compute_region_snapshot` result; false if it doesn't parse or
+    /// the geometry changed since the snapshot.
+    pub fn apply_region_result(&mut self, result: JsValue) -> bool {
+        match serde_wasm_bindgen::from_value(result) {
+            Ok(res) => self.inner.apply_region_result(res),
+            Err(_) => false,
+        }
+    }
+    pub fn apply_region_result_res(&mut self, result: JsValue) -> JsValue {
+        let res: contour::algorithms::region_snapshot::RegionSnapshotResult =
+            match serde_wasm_bindgen::from_value(result) {
+                Ok(res) => res,
+                Err(e) => return error::err("invalid_result", format!("{}", e), None),
+            };
+        let (got, current) = (res.geom_ver, self.inner.geom_version());
+        if !self.inner.apply_region_result(res) {
+            let d = new_obj();
+            set_kv(&d, "geom_ver", &JsValue::from_f64(got as f64));
+            set_kv(&d, "current", &JsValue::from_f64(current as f64));
+            return error::err(
+                "stale_result",
+                "geometry changed since the snapshot was taken",
+                Some(d.into()),
+            );
+        }
+        error::ok(JsValue::from_bool(true))
+    }
+    /// Start a budgeted region job; returns the geometry version it is keyed by
+    /// (as a number, matching `geom_ver` in the job status).
+    pub fn begin_regions_job(&mut self) -> f64 {
+        self.inner.begin_regions_job() as f64
+    }
+    pub fn begin_regions_job_res(&mut self) -> JsValue {
+        error::ok(JsValue::from_f64(self.begin_regions_job()))
+    }
+    /// Advance the region job for about `budget_ms`; returns
+    /// { state, phase, done, total, geom_ver }.
+    pub fn step_regions_job(&mut self, budget_ms: f64) -> JsValue {
+        let status = self
+            .inner
+            .step_regions_job_with_clock(budget_ms, js_sys::Date::now);
+        serde_wasm_bindgen::to_value(&status).unwrap()
+    }
+    pub fn step_regions_job_res(&mut self, budget_ms: f64) -> JsValue {
+        if !budget_ms.is_finite() {
+            return error::non_finite("budget_ms");
+        }
+        if budget_ms < 0.0 {
+            return error::out_of_range("budget_ms", 0.0, f32::INFINITY, budget_ms as f32);
+        }
+        error::ok(self.step_regions_job(budget_ms))
+    }
+    pub fn regions_job_status(&mut self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.regions_job_status()).unwrap()
+    }
+    pub fn regions_job_status_res(&mut self) -> JsValue {
+        error::ok(self.regions_job_status())
+    }
+    /// Faces found by the region job so far: [{ key, area, points, holes, stable }].
+    pub fn get_regions_job_result(&mut self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.regions_job_result()).unwrap()
+    }
+    pub fn get_regions_job_result_res(&mut self) -> JsValue {
+        error::ok(self.get_regions_job_result())
+    }
+    pub fn cancel_regions_job(&mut self) -> bool {
+        self.inner.cancel_regions_job()
+    }
+    pub fn cancel_regions_job_res(&mut self) -> JsValue {
+        error::ok(JsValue::from_bool(self.cancel_regions_job()))
+    }
     pub fn toggle_region(&mut self, key: u32) -> bool {
         self.inner.toggle_region(key)
     }
@@ -291,6 +464,75 @@ impl Graph {
         self.inner.set_region_color(key, r, g, b, a);
         error::ok(JsValue::from_bool(true))
     }
+    /// Key of the region under the point, or undefined
+    pub fn region_at(&mut self, x: f32, y: f32) -> Option<u32> {
+        self.inner.region_at(x, y)
+    }
+    pub fn region_at_res(&mut self, x: f32, y: f32) -> JsValue {
+        if !x.is_finite() {
+            return error::non_finite("x");
+        }
+        if !y.is_finite() {
+            return error::non_finite("y");
+        }
+        match self.inner.region_at(x, y) {
+            Some(key) => error::ok(JsValue::from_f64(key as f64)),
+            None => error::ok(JsValue::NULL),
+        }
+    }
+    /// Keys of regions overlapping the rect
+    pub fn regions_in_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> Uint32Array {
+        let keys = self.inner.regions_in_rect(x0, y0, x1, y1);
+        crate::interop::arr_u32(&keys)
+    }
+    pub fn regions_in_rect_res(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> JsValue {
+        for (name, v) in [("x0", x0), ("y0", y0), ("x1", x1), ("y1", y1)] {
+            if !v.is_finite() {
+                return error::non_finite(name);
+            }
+        }
+        error::ok(self.regions_in_rect(x0, y0, x1, y1).into())
+    }
+    /// Pairs of regions sharing boundary: [{ a, b, edges }]
+    pub fn get_region_adjacency(&mut self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.region_adjacency()).unwrap()
+    }
+    pub fn get_region_adjacency_res(&mut self) -> JsValue {
+        error::ok(self.get_region_adjacency())
+    }
+    pub fn region_neighbors(&mut self, key: u32) -> Uint32Array {
+        let keys = self.inner.region_neighbors(key);
+        crate::interop::arr_u32(&keys)
+    }
+    pub fn region_neighbors_res(&mut self, key: u32) -> JsValue {
+        if !region_exists(&mut self.inner, key) {
+            return error::invalid_id("region", key);
+        }
+        error::ok(self.region_neighbors(key).into())
+    }
+    /// { key, area, signed_area, perimeter, centroid, bbox, touches_outer } or null
+    pub fn get_region_metrics(&mut self, key: u32) -> JsValue {
+        match self.inner.region_metrics(key) {
+            Some(m) => serde_wasm_bindgen::to_value(&m).unwrap(),
+            None => JsValue::NULL,
+        }
+    }
+    pub fn get_region_metrics_res(&mut self, key: u32) -> JsValue {
+        match self.inner.region_metrics(key) {
+            Some(m) => error::ok(serde_wasm_bindgen::to_value(&m).unwrap()),
+            None => error::invalid_id("region", key),
+        }
+    }
+    /// Exact region boundary as an SVG path `d` string (curves kept as cubics).
+    pub fn get_region_path(&mut self, key: u32) -> Option<String> {
+        self.inner.get_region_path_d(key)
+    }
+    pub fn get_region_path_res(&mut self, key: u32) -> JsValue {
+        match self.inner.get_region_path_d(key) {
+            Some(d) => error::ok(JsValue::from_str(&d)),
+            None => error::invalid_id("region", key),
+        }
+    }
     pub fn set_flatten_tolerance(&mut self, tol: f32) {
         self.inner.set_flatten_tolerance(tol)
     }
@@ -305,6 +547,67 @@ impl Graph {
         error::ok(JsValue::from_bool(true))
     }
 
+    /// Intersection backend for planarization (0 = auto, 1 = grid (default), 2 = sweep)
+    pub fn set_intersect_backend(&mut self, mode: u8) -> bool {
+        let backend = match mode {
+            0 => contour::algorithms::planarize::IntersectBackend::Auto,
+            1 => contour::algorithms::planarize::IntersectBackend::Grid,
+            2 => contour::algorithms::planarize::IntersectBackend::Sweep,
+            _ => return false,
+        };
+        self.inner.set_intersect_backend(backend);
+        true
+    }
+    pub fn set_intersect_backend_res(&mut self, mode: u8) -> JsValue {
+        if mode > 2 {
+            return error::err(
+                "invalid_backend",
+                "intersect backend must be 0 (auto), 1 (grid) or 2 (sweep)",
+                None,
+            );
+        }
+        error::ok(JsValue::from_bool(self.set_intersect_backend(mode)))
+    }
+    pub fn set_region_fill_mode(&mut self, mode: u8) -> bool {
+        let mode = match mode {
+            0 => contour::model::RegionFillMode::Faces,
+            1 => contour::model::RegionFillMode::Shapes,
+            _ => return false,
+        };
+        self.inner.set_region_fill_mode(mode);
+        true
+    }
+    pub fn set_region_fill_mode_res(&mut self, mode: u8) -> JsValue {
+        if mode > 1 {
+            return error::err(
+                "invalid_fill_mode",
+                "region fill mode must be 0 (faces) or 1 (shapes)",
+                None,
+            );
+        }
+        error::ok(JsValue::from_bool(self.set_region_fill_mode(mode)))
+    }
+    pub fn set_region_scope(&mut self, mode: u8) -> bool {
+        let scope = match mode {
+            0 => contour::model::RegionScope::All,
+            1 => contour::model::RegionScope::Layer,
+            2 => contour::model::RegionScope::Group,
+            _ => return false,
+        };
+        self.inner.set_region_scope(scope);
+        true
+    }
+    pub fn set_region_scope_res(&mut self, mode: u8) -> JsValue {
+        if mode > 2 {
+            return error::err(
+                "invalid_region_scope",
+                "region scope must be 0 (all), 1 (layer) or 2 (group)",
+                None,
+            );
+        }
+        error::ok(JsValue::from_bool(self.set_region_scope(mode)))
+    }
+
     // Styling/handles
     pub fn set_edge_style(&mut self, id: u32, r: u8, g: u8, b: u8, a: u8, width: f32) -> bool {
         self.inner.set_edge_style(id, r, g, b, a, width)
@@ -321,6 +624,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         if !width.is_finite() {
             return error::non_finite("width");
         }
@@ -338,6 +644,23 @@ impl Graph {
             JsValue::NULL
         }
     }
+    pub fn set_edge_construction(&mut self, id: u32, construction: bool) -> bool {
+        self.inner.set_edge_construction(id, construction)
+    }
+    pub fn set_edge_construction_res(&mut self, id: u32, construction: bool) -> JsValue {
+        if !edge_exists(&self.inner, id) {
+            return error::invalid_id("edge", id);
+        }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
+        error::ok(JsValue::from_bool(
+            self.inner.set_edge_construction(id, construction),
+        ))
+    }
+    pub fn is_edge_construction(&self, id: u32) -> bool {
+        self.inner.is_edge_construction(id)
+    }
     pub fn set_edge_cubic(&mut self, id: u32, p1x: f32, p1y: f32, p2x: f32, p2y: f32) -> bool {
         self.inner.set_edge_cubic(id, p1x, p1y, p2x, p2y)
     }
@@ -352,6 +675,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         for (n, v) in [("p1x", p1x), ("p1y", p1y), ("p2x", p2x), ("p2y", p2y)] {
             if !v.is_finite() {
                 return error::non_finite(n);
@@ -368,6 +694,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         error::ok(JsValue::from_bool(self.inner.set_edge_line(id)))
     }
     pub fn get_handles(&self, id: u32) -> JsValue {
@@ -393,6 +722,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         if end > 1 {
             return error::err("invalid_end", "end must be 0 or 1", None);
         }
@@ -414,6 +746,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         if mode > 2 {
             return error::invalid_mode(mode);
         }
@@ -436,6 +771,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         if !t.is_finite() {
             return error::non_finite("t");
         }
@@ -456,7 +794,7 @@ impl Graph {
     }
 
     // Transforms and grouping
-    pub fn transform_all(&mut self, s: f32, tx: f32, ty: f32, scale_stroke: bool) {
+    pub fn transform_all(&mut self, s: f32, tx: f32, ty: f32, scale_stroke: bool) -> bool {
         self.inner.transform_all(s, tx, ty, scale_stroke)
     }
     pub fn transform_all_res(&mut self, s: f32, tx: f32, ty: f32, scale_stroke: bool) -> JsValue {
@@ -465,7 +803,9 @@ impl Graph {
                 return error::non_finite(n);
             }
         }
-        self.inner.transform_all(s, tx, ty, scale_stroke);
+        if !self.inner.transform_all(s, tx, ty, scale_stroke) {
+            return error::err("locked", "a layer or group is locked", None);
+        }
         error::ok(JsValue::from_bool(true))
     }
     pub fn translate_nodes(&mut self, node_ids: &Uint32Array, dx: f32, dy: f32) -> u32 {
@@ -488,6 +828,10 @@ impl Graph {
                 return error::invalid_id("node", *id);
             }
         }
+        let locked = self.inner.locked_nodes();
+        if let Some(&id) = ids.iter().find(|&&id| locked.contains(&id)) {
+            return error::locked("node", id);
+        }
         let moved = self.inner.translate_nodes(&ids, dx, dy);
         error::ok(JsValue::from_f64(moved as f64))
     }
@@ -523,6 +867,9 @@ impl Graph {
                 return error::invalid_id("edge", *id);
             }
         }
+        if let Some(e) = first_locked(&self.inner, &[], &ids, !split_shared) {
+            return e;
+        }
         let moved = self.inner.translate_edges(&ids, dx, dy, split_shared);
         error::ok(JsValue::from_f64(moved as f64))
     }
@@ -583,6 +930,9 @@ impl Graph {
         }
         let nids = to_u32_vec(node_ids);
         let eids = to_u32_vec(edge_ids);
+        if let Some(e) = first_locked(&self.inner, &nids, &eids, true) {
+            return e;
+        }
         let moved = self.inner.rotate_selection(&nids, &eids, cx, cy, angle);
         error::ok(JsValue::from_f64(moved as f64))
     }
@@ -621,6 +971,9 @@ impl Graph {
         }
         let nids = to_u32_vec(node_ids);
         let eids = to_u32_vec(edge_ids);
+        if let Some(e) = first_locked(&self.inner, &nids, &eids, true) {
+            return e;
+        }
         let moved = self
             .inner
             .scale_selection(&nids, &eids, cx, cy, sx, sy, scale_stroke);
@@ -640,6 +993,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(
             self.inner.rotate_text_around(id, cx, cy, angle),
         ))
@@ -665,6 +1021,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(
             self.inner.scale_text_around(id, cx, cy, sx, sy),
         ))
@@ -705,6 +1064,9 @@ impl Graph {
         if !edge_exists(&self.inner, id) {
             return error::invalid_id("edge", id);
         }
+        if self.inner.is_edge_locked(id) {
+            return error::locked("edge", id);
+        }
         let len = points.length() as usize;
         if len % 2 == 1 {
             return error::err("invalid_array", "points must have even length", None);
@@ -965,20 +1327,25 @@ impl Graph {
         ))
     }
 
-    /// Remove a layer (optionally removes its edges)
-    pub fn remove_layer(&mut self, id: u32, remove_edges: bool) -> bool {
-        self.inner.remove_layer(id, remove_edges)
+    /// Remove a layer, either removing its edges, shapes and texts or moving
+    /// them to the default layer
+    pub fn remove_layer(&mut self, id: u32, remove_content: bool) -> bool {
+        self.inner.remove_layer(id, remove_content)
     }
 
-    pub fn remove_layer_res(&mut self, id: u32, remove_edges: bool) -> JsValue {
-        if self.inner.remove_layer(id, remove_edges) {
+    pub fn remove_layer_res(&mut self, id: u32, remove_content: bool) -> JsValue {
+        if self.inner.remove_layer(id, remove_content) {
             error::ok(JsValue::from_bool(true))
+        } else if self.inner.get_layers().iter().any(|l| l.0 == id) {
+            // Exists but refused: it or a group inside is locked
+            error::locked("layer", id)
         } else {
             error::invalid_id("layer", id)
         }
     }
 
-    /// Get all layers as array of {id, name, z_index, visible, opacity}
+    /// Get all layers as array of
+    /// {id, name, z_index, visible, opacity, blend_mode, locked}
     pub fn get_layers(&self) -> JsValue {
         let layers = self.inner.get_layers();
         let arr: Vec<_> = layers
@@ -989,7 +1356,9 @@ impl Graph {
                     "name": name,
                     "z_index": z_index,
                     "visible": visible,
-                    "opacity": opacity
+                    "opacity": opacity,
+                    "blend_mode": self.inner.get_layer_blend_mode(id).unwrap_or_default(),
+                    "locked": self.inner.is_layer_locked(id)
                 })
             })
             .collect();
@@ -1038,6 +1407,22 @@ impl Graph {
         }
     }
 
+    /// Set a layer's blend mode by its CSS name ("normal", "multiply", ...)
+    pub fn set_layer_blend_mode(&mut self, id: u32, mode: &str) -> bool {
+        BlendMode::from_css_name(mode).is_some_and(|m| self.inner.set_layer_blend_mode(id, m))
+    }
+
+    pub fn set_layer_blend_mode_res(&mut self, id: u32, mode: &str) -> JsValue {
+        let Some(mode) = BlendMode::from_css_name(mode) else {
+            return invalid_blend_mode(mode);
+        };
+        if self.inner.set_layer_blend_mode(id, mode) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::invalid_id("layer", id)
+        }
+    }
+
     /// Set layer z-index
     pub fn set_layer_z_index(&mut self, id: u32, z: i32) -> bool {
         self.inner.set_layer_z_index(id, z)
@@ -1073,12 +1458,21 @@ impl Graph {
     pub fn remove_group_res(&mut self, id: u32) -> JsValue {
         if self.inner.remove_group(id) {
             error::ok(JsValue::from_bool(true))
+        } else if self
+            .inner
+            .get_groups()
+            .iter()
+            .any(|g| g.0 == id && g.2.is_some())
+        {
+            // A non-root group that exists but was refused is locked
+            error::locked("group", id)
         } else {
             error::invalid_id("group", id)
         }
     }
 
-    /// Get all groups as array of {id, name, parent, visible, opacity}
+    /// Get all groups as array of
+    /// {id, name, parent, visible, opacity, blend_mode, mask, locked}
     pub fn get_groups(&self) -> JsValue {
         let groups = self.inner.get_groups();
         let arr: Vec<_> = groups
@@ -1089,7 +1483,10 @@ impl Graph {
                     "name": name,
                     "parent": parent,
                     "visible": visible,
-                    "opacity": opacity
+                    "opacity": opacity,
+                    "blend_mode": self.inner.get_group_blend_mode(id).unwrap_or_default(),
+                    "mask": self.inner.get_group_mask(id),
+                    "locked": self.inner.is_group_locked(id)
                 })
             })
             .collect();
@@ -1138,6 +1535,143 @@ impl Graph {
         }
     }
 
+    /// Set a group's blend mode by its CSS name
+    pub fn set_group_blend_mode(&mut self, id: u32, mode: &str) -> bool {
+        BlendMode::from_css_name(mode).is_some_and(|m| self.inner.set_group_blend_mode(id, m))
+    }
+
+    pub fn set_group_blend_mode_res(&mut self, id: u32, mode: &str) -> JsValue {
+        let Some(mode) = BlendMode::from_css_name(mode) else {
+            return invalid_blend_mode(mode);
+        };
+        if self.inner.set_group_blend_mode(id, mode) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::invalid_id("group", id)
+        }
+    }
+
+    /// Clip a group's content to a closed shape, under the shape's fill rule
+    pub fn set_group_clip(&mut self, id: u32, shape: u32) -> bool {
+        self.inner
+            .set_group_mask(id, Some(GroupMask::Clip { shape }))
+    }
+
+    pub fn set_group_clip_res(&mut self, id: u32, shape: u32) -> JsValue {
+        let mask = GroupMask::Clip { shape };
+        if let Err(e) = self.check_group_mask(id) {
+            return e;
+        }
+        match self.inner.get_shape(shape) {
+            None => error::invalid_id("shape", shape),
+            Some(s) if !s.closed => invalid_mask(id, mask, "clip shape must be closed"),
+            Some(_) => error::ok(JsValue::from_bool(
+                self.inner.set_group_mask(id, Some(mask)),
+            )),
+        }
+    }
+
+    /// Mask a group by another group's content; `mode` is "alpha" or
+    /// "luminance". The source group is no longer drawn on its own.
+    pub fn set_group_mask(&mut self, id: u32, source: u32, mode: &str) -> bool {
+        parse_mask(source, mode).is_some_and(|m| self.inner.set_group_mask(id, Some(m)))
+    }
+
+    pub fn set_group_mask_res(&mut self, id: u32, source: u32, mode: &str) -> JsValue {
+        let Some(mask) = parse_mask(source, mode) else {
+            return error::err(
+                "invalid_mask",
+                r#"mode must be "alpha" or "luminance""#,
+                None,
+            );
+        };
+        if let Err(e) = self.check_group_mask(id) {
+            return e;
+        }
+        if group_parent(&self.inner, source).is_none() {
+            return error::invalid_id("group", source);
+        }
+        if self.inner.set_group_mask(id, Some(mask)) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            invalid_mask(
+                id,
+                mask,
+                "mask group must be a non-root group outside the masked group",
+            )
+        }
+    }
+
+    /// Remove a group's clip or mask
+    pub fn clear_group_mask(&mut self, id: u32) -> bool {
+        self.inner.set_group_mask(id, None)
+    }
+
+    pub fn clear_group_mask_res(&mut self, id: u32) -> JsValue {
+        if let Err(e) = self.check_group_mask(id) {
+            return e;
+        }
+        error::ok(JsValue::from_bool(self.inner.set_group_mask(id, None)))
+    }
+
+    /// A group's mask as {kind: "clip", shape} or {kind: "alpha" |
+    /// "luminance", group}, or null
+    pub fn get_group_mask(&self, id: u32) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.get_group_mask(id)).unwrap()
+    }
+
+    fn check_group_mask(&self, id: u32) -> Result<(), JsValue> {
+        if group_parent(&self.inner, id).is_none() {
+            return Err(error::invalid_id("group", id));
+        }
+        if self.inner.is_item_locked(GroupItem::Group(id)) {
+            return Err(error::locked("group", id));
+        }
+        Ok(())
+    }
+
+    /// Lock or unlock a layer; edits to its edges are refused while locked
+    pub fn set_layer_locked(&mut self, id: u32, locked: bool) -> bool {
+        self.inner.set_layer_locked(id, locked)
+    }
+
+    pub fn set_layer_locked_res(&mut self, id: u32, locked: bool) -> JsValue {
+        if self.inner.set_layer_locked(id, locked) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::invalid_id("layer", id)
+        }
+    }
+
+    /// Lock or unlock a group and everything below it
+    pub fn set_group_locked(&mut self, id: u32, locked: bool) -> bool {
+        self.inner.set_group_locked(id, locked)
+    }
+
+    pub fn set_group_locked_res(&mut self, id: u32, locked: bool) -> JsValue {
+        if self.inner.set_group_locked(id, locked) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::invalid_id("group", id)
+        }
+    }
+
+    /// Whether an edge sits in a locked layer or group
+    pub fn is_edge_locked(&self, id: u32) -> bool {
+        self.inner.is_edge_locked(id)
+    }
+
+    /// Whether any edge at the node is locked
+    pub fn is_node_locked(&self, id: u32) -> bool {
+        self.inner.is_node_locked(id)
+    }
+
+    /// Make pick, pick_with_regions, pick_all and the selection queries skip
+    /// locked content
+    pub fn set_pick_skip_locked(&mut self, skip: bool) {
+        self.inner.set_pick_skip_locked(skip)
+    }
+
     /// Add an edge to a specific group
     pub fn add_edge_to_group(&mut self, edge_id: u32, group_id: u32) -> bool {
         self.inner.add_edge_to_group(edge_id, group_id)
@@ -1147,18 +1681,328 @@ impl Graph {
         if !edge_exists(&self.inner, edge_id) {
             return error::invalid_id("edge", edge_id);
         }
+        if self.inner.is_edge_locked(edge_id) {
+            return error::locked("edge", edge_id);
+        }
         if self.inner.add_edge_to_group(edge_id, group_id) {
             error::ok(JsValue::from_bool(true))
+        } else if self.inner.get_groups().iter().any(|g| g.0 == group_id) {
+            error::locked("group", group_id)
         } else {
             error::invalid_id("group", group_id)
         }
     }
 
+    /// Move edges into a group in the given order; all-or-nothing, returns
+    /// the number moved
+    pub fn move_edges_to_group(&mut self, edge_ids: &Uint32Array, group_id: u32) -> u32 {
+        self.inner
+            .move_edges_to_group(&to_u32_vec(edge_ids), group_id) as u32
+    }
+
+    pub fn move_edges_to_group_res(&mut self, edge_ids: &Uint32Array, group_id: u32) -> JsValue {
+        let ids = to_u32_vec(edge_ids);
+        for id in &ids {
+            if !edge_exists(&self.inner, *id) {
+                return error::invalid_id("edge", *id);
+            }
+        }
+        if let Some(e) = first_locked(&self.inner, &[], &ids, false) {
+            return e;
+        }
+        if group_parent(&self.inner, group_id).is_none() {
+            return error::invalid_id("group", group_id);
+        }
+        if ids.is_empty() {
+            return error::ok(JsValue::from_f64(0.0));
+        }
+        match self.inner.move_edges_to_group(&ids, group_id) {
+            0 => error::locked("group", group_id),
+            moved => error::ok(JsValue::from_f64(moved as f64)),
+        }
+    }
+
+    /// Put an edge, shape or text ({kind: "edge" | "shape" | "text", id}) on
+    /// top of a group
+    pub fn add_item_to_group(&mut self, item: JsValue, group_id: u32) -> bool {
+        parse_item(item).is_some_and(|item| self.inner.add_item_to_group(item, group_id))
+    }
+
+    pub fn add_item_to_group_res(&mut self, item: JsValue, group_id: u32) -> JsValue {
+        let item = match serde_wasm_bindgen::from_value(item) {
+            Ok(item) => item,
+            Err(e) => return error::err("invalid_item", format!("{}", e), None),
+        };
+        match self.move_items_to_group_checked(vec![item], group_id) {
+            Ok(_) => error::ok(JsValue::from_bool(true)),
+            Err(e) => e,
+        }
+    }
+
+    /// Move edges, shapes and texts ({kind, id} objects) into a group in the
+    /// given order; all-or-nothing, returns the number moved
+    pub fn move_items_to_group(&mut self, items: JsValue, group_id: u32) -> u32 {
+        serde_wasm_bindgen::from_value::<Vec<GroupItem>>(items).map_or(0, |items| {
+            self.inner.move_items_to_group(&items, group_id) as u32
+        })
+    }
+
+    pub fn move_items_to_group_res(&mut self, items: JsValue, group_id: u32) -> JsValue {
+        let items = match serde_wasm_bindgen::from_value(items) {
+            Ok(items) => items,
+            Err(e) => return error::err("invalid_item", format!("{}", e), None),
+        };
+        match self.move_items_to_group_checked(items, group_id) {
+            Ok(moved) => error::ok(JsValue::from_f64(moved as f64)),
+            Err(e) => e,
+        }
+    }
+
+    fn move_items_to_group_checked(
+        &mut self,
+        items: Vec<GroupItem>,
+        group_id: u32,
+    ) -> Result<usize, JsValue> {
+        for &item in &items {
+            let (kind, id) = item_kind_id(item);
+            if matches!(item, GroupItem::Group(_)) || !self.inner.item_exists(item) {
+                return Err(error::invalid_id(kind, id));
+            }
+            if self.inner.is_item_locked(item) {
+                return Err(error::locked(kind, id));
+            }
+        }
+        if group_parent(&self.inner, group_id).is_none() {
+            return Err(error::invalid_id("group", group_id));
+        }
+        if items.is_empty() {
+            return Ok(0);
+        }
+        match self.inner.move_items_to_group(&items, group_id) {
+            0 => Err(error::locked("group", group_id)),
+            moved => Ok(moved),
+        }
+    }
+
+    /// Bring an item ({kind: "edge" | "shape" | "text" | "group", id}) to the
+    /// top of its siblings
+    pub fn bring_to_front(&mut self, item: JsValue) -> bool {
+        self.reorder_item(item, ZOrder::Front)
+    }
+
+    pub fn bring_to_front_res(&mut self, item: JsValue) -> JsValue {
+        self.reorder_item_res(item, ZOrder::Front)
+    }
+
+    /// Send an item to the bottom of its siblings
+    pub fn send_to_back(&mut self, item: JsValue) -> bool {
+        self.reorder_item(item, ZOrder::Back)
+    }
+
+    pub fn send_to_back_res(&mut self, item: JsValue) -> JsValue {
+        self.reorder_item_res(item, ZOrder::Back)
+    }
+
+    /// Move an item one step up among its siblings
+    pub fn bring_forward(&mut self, item: JsValue) -> bool {
+        self.reorder_item(item, ZOrder::Forward)
+    }
+
+    pub fn bring_forward_res(&mut self, item: JsValue) -> JsValue {
+        self.reorder_item_res(item, ZOrder::Forward)
+    }
+
+    /// Move an item one step down among its siblings
+    pub fn send_backward(&mut self, item: JsValue) -> bool {
+        self.reorder_item(item, ZOrder::Backward)
+    }
+
+    pub fn send_backward_res(&mut self, item: JsValue) -> JsValue {
+        self.reorder_item_res(item, ZOrder::Backward)
+    }
+
+    fn reorder_item(&mut self, item: JsValue, to: ZOrder) -> bool {
+        parse_item(item).is_some_and(|item| self.inner.reorder_item(item, to))
+    }
+
+    fn reorder_item_res(&mut self, item: JsValue, to: ZOrder) -> JsValue {
+        let item: GroupItem = match serde_wasm_bindgen::from_value(item) {
+            Ok(item) => item,
+            Err(e) => return error::err("invalid_item", format!("{}", e), None),
+        };
+        let (kind, id) = item_kind_id(item);
+        // Root groups have no siblings to move among
+        if !self.inner.item_exists(item) || self.inner.get_item_group(item).is_none() {
+            return error::invalid_id(kind, id);
+        }
+        if self.inner.reorder_item(item, to) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::locked(kind, id)
+        }
+    }
+
+    /// Edges, shapes and texts bottom to top as {kind, id}, the order the
+    /// SVG export draws them in; hidden items included
+    pub fn get_draw_order(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.draw_order()).unwrap()
+    }
+
+    /// Move a group and its subtree under another group (possibly in another
+    /// layer) at `index` among its new siblings, bottom to top; omit the
+    /// index to put it on top
+    pub fn move_group(&mut self, id: u32, parent_id: u32, index: Option<u32>) -> bool {
+        self.inner
+            .move_group(id, parent_id, index.map(|i| i as usize))
+    }
+
+    pub fn move_group_res(&mut self, id: u32, parent_id: u32, index: Option<u32>) -> JsValue {
+        if !matches!(group_parent(&self.inner, id), Some(Some(_))) {
+            return error::invalid_id("group", id);
+        }
+        if group_parent(&self.inner, parent_id).is_none() {
+            return error::invalid_id("group", parent_id);
+        }
+        if self.inner.is_group_within(parent_id, id) {
+            return error::cycle(id, parent_id);
+        }
+        if self
+            .inner
+            .move_group(id, parent_id, index.map(|i| i as usize))
+        {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::locked("group", id)
+        }
+    }
+
+    /// Move a group to the top level of a layer
+    pub fn move_group_to_layer(&mut self, id: u32, layer_id: u32, index: Option<u32>) -> bool {
+        self.inner
+            .move_group_to_layer(id, layer_id, index.map(|i| i as usize))
+    }
+
+    pub fn move_group_to_layer_res(
+        &mut self,
+        id: u32,
+        layer_id: u32,
+        index: Option<u32>,
+    ) -> JsValue {
+        if !matches!(group_parent(&self.inner, id), Some(Some(_))) {
+            return error::invalid_id("group", id);
+        }
+        if !self.inner.get_layers().iter().any(|l| l.0 == layer_id) {
+            return error::invalid_id("layer", layer_id);
+        }
+        if self
+            .inner
+            .move_group_to_layer(id, layer_id, index.map(|i| i as usize))
+        {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::locked("group", id)
+        }
+    }
+
+    /// Move a group to a new position among its siblings (bottom to top)
+    pub fn reorder_group(&mut self, id: u32, index: u32) -> bool {
+        self.inner.reorder_group(id, index as usize)
+    }
+
+    pub fn reorder_group_res(&mut self, id: u32, index: u32) -> JsValue {
+        if !matches!(group_parent(&self.inner, id), Some(Some(_))) {
+            return error::invalid_id("group", id);
+        }
+        if self.inner.reorder_group(id, index as usize) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::locked("group", id)
+        }
+    }
+
+    /// Dissolve a group into its parent, keeping its content in place;
+    /// returns the promoted child group IDs
+    pub fn ungroup(&mut self, id: u32) -> Option<Uint32Array> {
+        self.inner
+            .ungroup(id)
+            .map(|children| crate::interop::arr_u32(&children))
+    }
+
+    pub fn ungroup_res(&mut self, id: u32) -> JsValue {
+        if !matches!(group_parent(&self.inner, id), Some(Some(_))) {
+            return error::invalid_id("group", id);
+        }
+        match self.inner.ungroup(id) {
+            Some(children) => error::ok(crate::interop::arr_u32(&children).into()),
+            None => error::locked("group", id),
+        }
+    }
+
+    /// Pull every edge below a group up into it and remove its subgroups
+    pub fn flatten_group(&mut self, id: u32) -> bool {
+        self.inner.flatten_group(id)
+    }
+
+    pub fn flatten_group_res(&mut self, id: u32) -> JsValue {
+        if group_parent(&self.inner, id).is_none() {
+            return error::invalid_id("group", id);
+        }
+        if self.inner.flatten_group(id) {
+            error::ok(JsValue::from_bool(true))
+        } else {
+            error::locked("group", id)
+        }
+    }
+
+    /// The whole layer hierarchy: layers bottom to top, each as
+    /// {id, name, z_index, visible, locked, opacity, blend_mode, root}, where
+    /// groups are {id, name, visible, locked, opacity, blend_mode, mask,
+    /// items};
+    /// items run bottom to top, each a nested group ({kind: "group", ...}) or
+    /// {kind, id} for an edge, shape or text
+    pub fn get_tree(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.get_tree()).unwrap()
+    }
+
     /// Get the group containing an edge
     pub fn get_edge_group(&self, edge_id: u32) -> Option<u32> {
         self.inner.get_edge_group(edge_id)
     }
 
+    /// Get the group holding an edge, shape or text ({kind, id})
+    pub fn get_item_group(&self, item: JsValue) -> Option<u32> {
+        parse_item(item).and_then(|item| self.inner.get_item_group(item))
+    }
+
+    /// Get the layer holding an edge, shape or text ({kind, id})
+    pub fn get_item_layer(&self, item: JsValue) -> Option<u32> {
+        parse_item(item).and_then(|item| self.inner.get_item_layer(item))
+    }
+
+    /// Check if an edge, shape or text is visible through its layer and groups
+    pub fn is_item_visible(&self, item: JsValue) -> bool {
+        parse_item(item).is_some_and(|item| self.inner.is_item_visible(item))
+    }
+
+    /// Check if an edge, shape or text is locked through its layer and groups
+    pub fn is_item_locked(&self, item: JsValue) -> bool {
+        parse_item(item).is_some_and(|item| self.inner.is_item_locked(item))
+    }
+
+    /// Effective opacity of an edge, shape or text from its layer and groups
+    pub fn get_item_opacity(&self, item: JsValue) -> f32 {
+        parse_item(item).map_or(1.0, |item| self.inner.get_item_opacity(item))
+    }
+
+    /// Effects applying to a shape, text or group: its own stack, then each
+    /// enclosing group's, innermost first
+    pub fn get_effective_effects(&self, item: JsValue) -> Uint32Array {
+        let effects = parse_item(item)
+            .map(|item| self.inner.get_effective_effects(item))
+            .unwrap_or_default();
+        crate::interop::arr_u32(&effects)
+    }
+
     /// Get the layer containing an edge
     pub fn get_edge_layer(&self, edge_id: u32) -> Option<u32> {
         self.inner.get_edge_layer(edge_id)
@@ -1347,6 +2191,11 @@ impl Graph {
         }
     }
 
+    /// Gradient bound to a region fill, or undefined
+    pub fn get_region_gradient(&self, key: u32) -> Option<u32> {
+        self.inner.get_region_gradient(key)
+    }
+
     /// Set edge stroke to a gradient
     pub fn set_edge_stroke_gradient(&mut self, edge_id: u32, gradient_id: u32, width: f32) -> bool {
         self.inner
@@ -1362,6 +2211,9 @@ impl Graph {
         if !edge_exists(&self.inner, edge_id) {
             return error::invalid_id("edge", edge_id);
         }
+        if self.inner.is_edge_locked(edge_id) {
+            return error::locked("edge", edge_id);
+        }
         if self.inner.get_gradient(gradient_id).is_none() {
             return error::invalid_id("gradient", gradient_id);
         }
@@ -1419,6 +2271,9 @@ impl Graph {
         if self.inner.get_shape(id).is_none() {
             return error::invalid_id("shape", id);
         }
+        if self.inner.is_shape_locked(id) {
+            return error::locked("shape", id);
+        }
         error::ok(JsValue::from_bool(self.inner.delete_shape(id)))
     }
 
@@ -1465,6 +2320,40 @@ impl Graph {
         error::ok(crate::interop::arr_u32(&ids).into())
     }
 
+    /// Turn a region into a closed shape: { nodes, edges, shape } or null
+    pub fn region_to_shape(&mut self, key: u32, detach: bool) -> JsValue {
+        match self.inner.region_to_shape(key, detach) {
+            Some(result) => serde_wasm_bindgen::to_value(&serde_json::json!({
+                "nodes": result.nodes,
+                "edges": result.edges,
+                "shape": result.shape
+            }))
+            .unwrap(),
+            None => JsValue::NULL,
+        }
+    }
+
+    pub fn region_to_shape_res(&mut self, key: u32, detach: bool) -> JsValue {
+        if !region_exists(&mut self.inner, key) {
+            return error::invalid_id("region", key);
+        }
+        match self.inner.region_to_shape(key, detach) {
+            Some(result) => error::ok(
+                serde_wasm_bindgen::to_value(&serde_json::json!({
+                    "nodes": result.nodes,
+                    "edges": result.edges,
+                    "shape": result.shape
+                }))
+                .unwrap(),
+            ),
+            None => error::err(
+                "region_trace_failed",
+                format!("could not trace region {} as a shape", key),
+                None,
+            ),
+        }
+    }
+
     /// Set the fill rule for a shape (0 = NonZero, 1 = EvenOdd)
     pub fn set_shape_fill_rule(&mut self, id: u32, rule: u8) -> bool {
         let fill_rule = match rule {
@@ -1479,6 +2368,9 @@ impl Graph {
         if self.inner.get_shape(id).is_none() {
             return error::invalid_id("shape", id);
         }
+        if self.inner.is_shape_locked(id) {
+            return error::locked("shape", id);
+        }
         if rule > 1 {
             return error::err("invalid_fill_rule", "fill rule must be 0 or 1", None);
         }
@@ -1492,6 +2384,33 @@ impl Graph {
         ))
     }
 
+    /// Set a shape's blend mode by its CSS name
+    pub fn set_shape_blend_mode(&mut self, id: u32, mode: &str) -> bool {
+        BlendMode::from_css_name(mode).is_some_and(|m| self.inner.set_shape_blend_mode(id, m))
+    }
+
+    pub fn set_shape_blend_mode_res(&mut self, id: u32, mode: &str) -> JsValue {
+        if self.inner.get_shape(id).is_none() {
+            return error::invalid_id("shape", id);
+        }
+        if self.inner.is_shape_locked(id) {
+            return error::locked("shape", id);
+        }
+        let Some(mode) = BlendMode::from_css_name(mode) else {
+            return invalid_blend_mode(mode);
+        };
+        error::ok(JsValue::from_bool(
+            self.inner.set_shape_blend_mode(id, mode),
+        ))
+    }
+
+    /// Get a shape's blend mode as its CSS name
+    pub fn get_shape_blend_mode(&self, id: u32) -> Option<String> {
+        self.inner
+            .get_shape(id)
+            .map(|s| s.blend_mode.css_name().to_string())
+    }
+
     // ========== Boolean Operations ==========
 
     /// Perform union of two shapes (A ∪ B)
@@ -1594,6 +2513,7 @@ impl Graph {
                 }))
                 .unwrap(),
             ),
+            Err(contour::algorithms::boolean::BoolError::Locked(id)) => error::locked("shape", id),
             Err(e) => {
                 let msg = format!("{:?}", e);
                 error::err("boolean_op_failed", &msg, None)
@@ -1601,6 +2521,41 @@ impl Graph {
         }
     }
 
+    // ========== Planarization ==========
+
+    /// Split crossing edges at their intersections so they share real nodes.
+    /// Operates on `edge_ids`, or on every edge when omitted.
+    pub fn planarize_into_graph(&mut self, edge_ids: Option<Uint32Array>) -> JsValue {
+        let ids = edge_ids.as_ref().map(to_u32_vec);
+        let result = self.inner.planarize_into_graph(ids.as_deref());
+        serde_wasm_bindgen::to_value(&serde_json::json!({
+            "nodes": result.nodes,
+            "edges": result.edges,
+            "removed_edges": result.removed_edges
+        }))
+        .unwrap()
+    }
+
+    pub fn planarize_into_graph_res(&mut self, edge_ids: Option<Uint32Array>) -> JsValue {
+        let ids = edge_ids.as_ref().map(to_u32_vec);
+        if let Some(ids) = &ids {
+            for &id in ids {
+                if !edge_exists(&self.inner, id) {
+                    return error::invalid_id("edge", id);
+                }
+            }
+        }
+        let result = self.inner.planarize_into_graph(ids.as_deref());
+        error::ok(
+            serde_wasm_bindgen::to_value(&serde_json::json!({
+                "nodes": result.nodes,
+                "edges": result.edges,
+                "removed_edges": result.removed_edges
+            }))
+            .unwrap(),
+        )
+    }
+
     // ========== Text Management ==========
 
     /// Add a simple text label at the specified position
@@ -1678,6 +2633,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(self.inner.remove_text(id)))
     }
 
@@ -1726,6 +2684,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(self.inner.set_text_content(id, content)))
     }
 
@@ -1744,6 +2705,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(self.inner.set_text_position(id, x, y)))
     }
 
@@ -1759,6 +2723,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(
             self.inner.set_text_rotation(id, radians),
         ))
@@ -1779,6 +2746,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if align > 2 {
             return error::err("invalid_align", "align must be 0, 1, or 2", None);
         }
@@ -1801,6 +2771,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !font_size.is_finite() {
             return error::non_finite("font_size");
         }
@@ -1823,6 +2796,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if weight < 100 || weight > 900 {
             return error::err("invalid_weight", "weight must be 100-900", None);
         }
@@ -1846,6 +2822,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if style > 2 {
             return error::err("invalid_style", "style must be 0, 1, or 2", None);
         }
@@ -1868,6 +2847,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(
             self.inner.set_text_fill_color(id, r, g, b, a),
         ))
@@ -1887,6 +2869,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(
             self.inner.set_text_stroke_color(id, r, g, b, a),
         ))
@@ -1901,6 +2886,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !width.is_finite() {
             return error::non_finite("width");
         }
@@ -1921,6 +2909,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !spacing.is_finite() {
             return error::non_finite("spacing");
         }
@@ -1938,6 +2929,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !line_height.is_finite() {
             return error::non_finite("line_height");
         }
@@ -1958,6 +2952,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !width.is_finite() || !height.is_finite() {
             return error::non_finite("dimensions");
         }
@@ -1995,6 +2992,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         let len = edge_ids.length() as usize;
         let mut ids = vec![0u32; len];
         edge_ids.copy_to(&mut ids);
@@ -2022,6 +3022,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         error::ok(JsValue::from_bool(self.inner.convert_text_to_label(id)))
     }
 
@@ -2034,6 +3037,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !width.is_finite() || !height.is_finite() {
             return error::non_finite("dimensions");
         }
@@ -2064,6 +3070,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if align > 2 {
             return error::err("invalid_align", "align must be 0, 1, or 2", None);
         }
@@ -2092,6 +3101,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if overflow > 2 {
             return error::err("invalid_overflow", "overflow must be 0, 1, or 2", None);
         }
@@ -2114,6 +3126,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         if !offset.is_finite() {
             return error::non_finite("offset");
         }
@@ -2134,6 +3149,9 @@ impl Graph {
         if self.inner.get_text(id).is_none() {
             return error::invalid_id("text", id);
         }
+        if self.inner.is_text_locked(id) {
+            return error::locked("text", id);
+        }
         let len = edge_ids.length() as usize;
         let mut ids = vec![0u32; len];
         edge_ids.copy_to(&mut ids);
@@ -2791,6 +3809,22 @@ impl Graph {
     }
 }
 
+fn select_mode(mode: u8) -> Option<contour::model::SelectMode> {
+    match mode {
+        0 => Some(contour::model::SelectMode::Contained),
+        1 => Some(contour::model::SelectMode::Intersecting),
+        _ => None,
+    }
+}
+
+fn invalid_select_mode() -> JsValue {
+    error::err(
+        "invalid_select_mode",
+        "select mode must be 0 (contained) or 1 (intersecting)",
+        None,
+    )
+}
+
 fn to_pairs(arr: &Float32Array) -> Vec<(f32, f32)> {
     let len = arr.length() as usize;
     let mut buf = vec![0.0f32; len];
@@ -2811,6 +3845,79 @@ fn to_u32_vec(arr: &Uint32Array) -> Vec<u32> {
     buf
 }
 
+/// `locked` error for the first locked node or edge an edit would touch;
+/// with `with_ends`, edge endpoints count as touched.
+fn first_locked(
+    g: &contour::Graph,
+    nodes: &[u32],
+    edges: &[u32],
+    with_ends: bool,
+) -> Option<JsValue> {
+    if let Some(&id) = edges.iter().find(|&&id| g.is_edge_locked(id)) {
+        return Some(error::locked("edge", id));
+    }
+    let ends = edges
+        .iter()
+        .filter(|_| with_ends)
+        .filter_map(|&id| g.get_edge_nodes(id))
+        .flat_map(|(a, b)| [a, b]);
+    let locked = g.locked_nodes();
+    nodes
+        .iter()
+        .copied()
+        .chain(ends)
+        .find(|id| locked.contains(id))
+        .map(|id| error::locked("node", id))
+}
+
+/// A {kind, id} item from JS; groups are addressed through the group APIs
+fn parse_item(item: JsValue) -> Option<GroupItem> {
+    serde_wasm_bindgen::from_value(item).ok()
+}
+
+fn invalid_blend_mode(got: &str) -> JsValue {
+    let d = new_obj();
+    set_kv(&d, "got", &JsValue::from_str(got));
+    error::err(
+        "invalid_blend_mode",
+        "blend mode must be a CSS mix-blend-mode keyword",
+        Some(d.into()),
+    )
+}
+
+/// A group mask from a source group and "alpha" or "luminance"
+fn parse_mask(source: u32, mode: &str) -> Option<GroupMask> {
+    match mode {
+        "alpha" => Some(GroupMask::Alpha { group: source }),
+        "luminance" => Some(GroupMask::Luminance { group: source }),
+        _ => None,
+    }
+}
+
+fn invalid_mask(group: u32, mask: GroupMask, message: &str) -> JsValue {
+    let d = new_obj();
+    set_kv(&d, "group", &JsValue::from_f64(group as f64));
+    set_kv(&d, "mask", &serde_wasm_bindgen::to_value(&mask).unwrap());
+    error::err("invalid_mask", message, Some(d.into()))
+}
+
+fn item_kind_id(item: GroupItem) -> (&'static str, u32) {
+    match item {
+        GroupItem::Group(id) => ("group", id),
+        GroupItem::Edge(id) => ("edge", id),
+        GroupItem::Shape(id) => ("shape", id),
+        GroupItem::Text(id) => ("text", id),
+    }
+}
+
+/// Parent of a group: None if unknown, Some(None) for a layer's root group
+fn group_parent(g: &contour::Graph, id: u32) -> Option<Option<u32>> {
+    g.get_groups()
+        .into_iter()
+        .find(|grp| grp.0 == id)
+        .map(|grp| grp.2)
+}
+
 fn edge_exists(g: &contour::Graph, id: u32) -> bool {
     let ea = g.get_edge_arrays();
     ea.ids.contains(&id)
@@ -2953,3 +4060,61 @@ fn parse_glyph_data(data: &JsValue) -> Vec<contour::model::GlyphOutline> {
         })
         .collect()
 }
+
+// Flatten to { kind: 'node'|'edge'|'handle'|'text'|'shape'|'region', ... }
+fn pick_to_js(p: contour::Pick) -> JsValue {
+    let obj = new_obj();
+    let set = |k: &str, v: f64| set_kv(&obj, k, &JsValue::from_f64(v));
+    let kind = match p {
+        contour::Pick::Node { id, dist } => {
+            set("id", id as f64);
+            set("dist", dist as f64);
+            "node"
+        }
+        contour::Pick::Edge { id, t, dist } => {
+            set("id", id as f64);
+            set("t", t as f64);
+            set("dist", dist as f64);
+            "edge"
+        }
+        contour::Pick::Handle { edge, end, dist } => {
+            set("edge", edge as f64);
+            set("end", end as f64);
+            set("dist", dist as f64);
+            "handle"
+        }
+        contour::Pick::Text { id, dist } => {
+            set("id", id as f64);
+            set("dist", dist as f64);
+            "text"
+        }
+        contour::Pick::Shape { id, dist } => {
+            set("id", id as f64);
+            set("dist", dist as f64);
+            "shape"
+        }
+        contour::Pick::Region { key, dist } => {
+            set("key", key as f64);
+            set("dist", dist as f64);
+            "region"
+        }
+    };
+    set_kv(&obj, "kind", &JsValue::from_str(kind));
+    obj.into()
+}
+
+fn check_pick_args(x: f32, y: f32, tol: f32) -> Option<JsValue> {
+    if !x.is_finite() {
+        return Some(error::non_finite("x"));
+    }
+    if !y.is_finite() {
+        return Some(error::non_finite("y"));
+    }
+    if !tol.is_finite() {
+        return Some(error::non_finite("tol"));
+    }
+    if tol < 0.0 {
+        return Some(error::out_of_range("tol", 0.0, f32::INFINITY, tol));
+    }
+    None
+}
@@ -83,6 +83,30 @@ pub fn not_polyline(edge: u32) -> JsValue {
     invalid_kind("not_polyline", "edge is not polyline", edge)
 }
 
+#[inline]
+pub fn locked(kind: &str, id: u32) -> JsValue {
+    let d = new_obj();
+    set_kv(&d, "kind", &JsValue::from_str(kind));
+    set_kv(&d, "id", &JsValue::from_f64(id as f64));
+    err(
+        "locked",
+        format!("{} {} is in a locked layer or group", kind, id),
+        Some(d.into()),
+    )
+}
+
+#[inline]
+pub fn cycle(group: u32, parent: u32) -> JsValue {
+    let d = new_obj();
+    set_kv(&d, "group", &JsValue::from_f64(group as f64));
+    set_kv(&d, "parent", &JsValue::from_f64(parent as f64));
+    err(
+        "cycle",
+        format!("group {} cannot move into its own subtree", group),
+        Some(d.into()),
+    )
+}
+
 fn invalid_kind(code: &'static str, msg: &str, edge: u32) -> JsValue {
     let d = new_obj();
     set_kv(&d, "edge", &JsValue::from_f64(edge as f64));
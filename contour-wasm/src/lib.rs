@@ -2,6 +2,8 @@ use wasm_bindgen::prelude::*;
 mod api;
 mod error;
 mod interop;
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+mod threads;
 
 #[wasm_bindgen]
 pub struct Graph {
@@ -0,0 +1,43 @@
+//! Web Worker pool for the `threads` feature.
+//!
+//! The host starts the workers itself: each loads this module on the same
+//! shared memory (a `+atomics` build) and is handed a pointer to run with
+//! `thread_pool_worker`. Building the pool blocks until every worker runs,
+//! and so do the region passes that use it, so neither may run on the
+//! browser main thread.
+
+use crate::error;
+use contour::algorithms::ThreadBuilder;
+use wasm_bindgen::prelude::*;
+
+/// Build the region pipeline's pool with `num_threads` workers. `spawn` is
+/// called with one pointer per worker; pass it to `thread_pool_worker` in a
+/// Web Worker that is already running, since a worker started now may not
+/// load while this call blocks.
+#[wasm_bindgen]
+pub fn init_thread_pool_res(num_threads: usize, spawn: &js_sys::Function) -> JsValue {
+    let res = contour::algorithms::init_thread_pool(num_threads, |builder| {
+        let ptr = Box::into_raw(Box::new(builder));
+        spawn
+            .call1(&JsValue::NULL, &JsValue::from(ptr as usize))
+            .map(|_| ())
+            .map_err(|e| {
+                // SAFETY: the pointer was not handed out, the call failed
+                drop(unsafe { Box::from_raw(ptr) });
+                std::io::Error::other(format!("{:?}", e))
+            })
+    });
+    match res {
+        Ok(()) => error::ok(JsValue::TRUE),
+        Err(e) => error::err("thread_pool", e.to_string(), None),
+    }
+}
+
+/// Run the pool worker behind a pointer from `init_thread_pool_res`'s
+/// `spawn`; returns when the pool shuts down.
+#[wasm_bindgen]
+pub fn thread_pool_worker(ptr: usize) {
+    // SAFETY: each pointer from `init_thread_pool_res` is run exactly once
+    let builder = unsafe { Box::from_raw(ptr as *mut ThreadBuilder) };
+    builder.run();
+}
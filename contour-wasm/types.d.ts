@@ -2,6 +2,25 @@ export type Ok<T> = { ok: true; value: T };
 export type Err = { ok: false; error: { code: string; message: string; data?: any } };
 export type Result<T> = Ok<T> | Err;
 
+export type RegionSpan = { edge: number; t_start: number; t_end: number; forward: boolean };
+export type RegionSnapshot = { geom_ver: number; flatten_tol: number; backend: 'Auto' | 'Grid' | 'Sweep'; scoped?: boolean; nodes: [number, number, number][]; edges: Array<{ id: number; a: number; b: number; kind: unknown; scope?: number }> };
+export type RegionSnapshotResult = { geom_ver: number; faces: Array<{ key: number; scope?: number; area: number; bbox: [number, number, number, number]; points: Array<{ x: number; y: number }>; edges: number[]; holes: Array<Array<{ x: number; y: number }>>; hole_edges: number[][]; spans: RegionSpan[]; hole_spans: RegionSpan[][] }> };
+export declare function compute_region_snapshot_res(snapshot: RegionSnapshot): Result<RegionSnapshotResult>;
+// `threads` builds only; call from a worker, see docs/regions.md
+export declare function init_thread_pool_res(num_threads: number, spawn: (ptr: number) => void): Result<true>;
+export declare function thread_pool_worker(ptr: number): void;
+export type RegionRemapReport = { from_ver: number; to_ver: number; remaps: Array<{ old: number; new: number[]; kind: 'renamed' | 'split' | 'merge' | 'removed' }> };
+export type Pick = { kind: 'node'|'edge'|'handle'|'text'|'shape'|'region', [k: string]: number | string };
+export type Selection = { nodes: number[]; edges: number[]; texts: number[]; shapes: number[] };
+export type Item = { kind: 'edge' | 'shape' | 'text'; id: number };
+export type StackItem = Item | { kind: 'group'; id: number };
+export type TreeItem = Item | ({ kind: 'group' } & GroupNode);
+export type BlendMode = 'normal' | 'multiply' | 'screen' | 'overlay' | 'darken' | 'lighten' | 'color-dodge' | 'color-burn' | 'difference' | 'exclusion' | 'hue' | 'saturation' | 'color' | 'luminosity';
+export type GroupMask = { kind: 'clip'; shape: number } | { kind: 'alpha' | 'luminance'; group: number };
+export type GroupNode = { id: number; name: string; visible: boolean; locked: boolean; opacity: number; blend_mode: BlendMode; mask: GroupMask | null; items: TreeItem[] };
+export type LayerNode = { id: number; name: string; z_index: number; visible: boolean; locked: boolean; opacity: number; blend_mode: BlendMode; root: GroupNode };
+export type RegionJobStatus = { state: 'idle' | 'running' | 'done' | 'stale'; phase: 'flatten' | 'intersect' | 'split' | 'walk' | 'nest' | 'done'; done: number; total: number; geom_ver: number };
+
 // Minimal Graph subset with strict methods (non-exhaustive)
 export declare class Graph {
   constructor();
@@ -12,22 +31,65 @@ export declare class Graph {
   get_node_res(id: number): Result<[number, number]>;
   add_edge_res(a: number, b: number): Result<number>;
   remove_edge_res(id: number): Result<boolean>;
-  pick_res(x: number, y: number, tol: number): Result<null | { kind: 'node'|'edge'|'handle', [k: string]: number }>;
+  pick_res(x: number, y: number, tol: number): Result<null | Pick>;
+  pick_with_regions_res(x: number, y: number, tol: number): Result<null | Pick>;
+  pick_all_res(x: number, y: number, tol: number): Result<Pick[]>;
+  select_in_rect_res(x0: number, y0: number, x1: number, y1: number, mode: 0 | 1): Result<Selection>;
+  select_in_polygon_res(points: Float32Array, mode: 0 | 1): Result<Selection>;
   set_edge_cubic_res(id: number, p1x: number, p1y: number, p2x: number, p2y: number): Result<boolean>;
   set_edge_line_res(id: number): Result<boolean>;
   get_handles_res(id: number): Result<[number, number, number, number]>;
   set_handle_pos_res(id: number, end: 0|1, x: number, y: number): Result<boolean>;
   set_handle_mode_res(id: number, mode: 0|1|2): Result<boolean>;
   bend_edge_to_res(id: number, t: number, tx: number, ty: number, stiffness: number): Result<boolean>;
-  get_regions_res(): Result<Array<{ key: number; area: number; filled: boolean; color?: [number,number,number,number]; points: number[] }>>;
+  get_regions_res(): Result<Array<{ key: number; area: number; filled: boolean; color?: [number,number,number,number]; points: number[]; holes: number[][]; spans: RegionSpan[]; hole_spans: RegionSpan[][]; shapes?: number[]; scope?: number }>>;
+  get_region_remap_res(): Result<RegionRemapReport>;
+  get_region_snapshot_res(): Result<RegionSnapshot>;
+  apply_region_result_res(result: RegionSnapshotResult): Result<boolean>;
+  begin_regions_job_res(): Result<number>;
+  step_regions_job_res(budget_ms: number): Result<RegionJobStatus>;
+  regions_job_status_res(): Result<RegionJobStatus>;
+  get_regions_job_result_res(): Result<Array<{ key: number; area: number; points: number[]; holes: number[][]; stable: boolean }>>;
+  cancel_regions_job_res(): Result<boolean>;
+  get_region_path_res(key: number): Result<string>;
+  region_at_res(x: number, y: number): Result<number | null>;
+  regions_in_rect_res(x0: number, y0: number, x1: number, y1: number): Result<Uint32Array>;
+  get_region_adjacency_res(): Result<Array<{ a: number; b: number; edges: number[] }>>;
+  region_neighbors_res(key: number): Result<Uint32Array>;
+  get_region_metrics_res(key: number): Result<{ key: number; area: number; signed_area: number; perimeter: number; centroid: [number, number]; bbox: [number, number, number, number]; touches_outer: boolean }>;
+  region_to_shape_res(key: number, detach: boolean): Result<{ nodes: number[]; edges: number[]; shape: number }>;
   toggle_region_res(key: number): Result<boolean>;
   set_region_fill_res(key: number, filled: boolean): Result<boolean>;
   set_region_color_res(key: number, r: number, g: number, b: number, a: number): Result<boolean>;
   set_flatten_tolerance_res(tol: number): Result<boolean>;
+  set_intersect_backend_res(mode: 0 | 1 | 2): Result<boolean>;
+  set_region_fill_mode_res(mode: 0 | 1): Result<boolean>;
+  set_region_scope_res(mode: 0 | 1 | 2): Result<boolean>;
+  set_layer_locked_res(id: number, locked: boolean): Result<boolean>;
+  set_group_locked_res(id: number, locked: boolean): Result<boolean>;
+  move_group_res(id: number, parent: number, index?: number): Result<boolean>;
+  move_group_to_layer_res(id: number, layer: number, index?: number): Result<boolean>;
+  reorder_group_res(id: number, index: number): Result<boolean>;
+  ungroup_res(id: number): Result<Uint32Array>;
+  flatten_group_res(id: number): Result<boolean>;
+  move_edges_to_group_res(edges: Uint32Array, group: number): Result<number>;
+  add_item_to_group_res(item: Item, group: number): Result<boolean>;
+  move_items_to_group_res(items: Item[], group: number): Result<number>;
+  bring_to_front_res(item: StackItem): Result<boolean>;
+  send_to_back_res(item: StackItem): Result<boolean>;
+  bring_forward_res(item: StackItem): Result<boolean>;
+  send_backward_res(item: StackItem): Result<boolean>;
+  set_layer_blend_mode_res(id: number, mode: BlendMode): Result<boolean>;
+  set_group_blend_mode_res(id: number, mode: BlendMode): Result<boolean>;
+  set_shape_blend_mode_res(id: number, mode: BlendMode): Result<boolean>;
+  set_group_clip_res(group: number, shape: number): Result<boolean>;
+  set_group_mask_res(group: number, source: number, mode: 'alpha' | 'luminance'): Result<boolean>;
+  clear_group_mask_res(group: number): Result<boolean>;
   add_polyline_edge_res(a: number, b: number, points: Float32Array): Result<number>;
   set_edge_polyline_res(id: number, points: Float32Array): Result<boolean>;
   get_polyline_points_res(id: number): Result<Float32Array>;
   add_svg_path_res(d: string): Result<number>;
   to_svg_paths_res(): Result<string[]>;
+  set_edge_construction_res(id: number, construction: boolean): Result<boolean>;
 }
 
@@ -36,6 +36,8 @@ pub enum BoolError {
     EdgeNotFound(u32),
     /// Node not found
     NodeNotFound(u32),
+    /// Shape has edges in a locked layer or group
+    Locked(u32),
     /// Operation failed (generic)
     OperationFailed(String),
 }
@@ -72,6 +74,12 @@ impl Graph {
             .ok_or(BoolError::ShapeNotFound(shape_b))?
             .clone();
 
+        for id in [shape_a, shape_b] {
+            if self.is_shape_locked(id) {
+                return Err(BoolError::Locked(id));
+            }
+        }
+
         if shape_a_data.edges.is_empty() {
             return Err(BoolError::EmptyShape(shape_a));
         }
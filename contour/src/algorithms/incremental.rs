@@ -20,7 +20,7 @@ fn cell_ix(cell: f32, x: f32) -> i32 {
 /// This prevents memory explosion from segments with extreme coordinate ranges.
 const MAX_CELL_SPAN: i32 = 256;
 
-fn choose_cell_size(flatten_tol: f32) -> f32 {
+pub(crate) fn choose_cell_size(flatten_tol: f32) -> f32 {
     (flatten_tol * 8.0).clamp(4.0, 64.0)
 }
 
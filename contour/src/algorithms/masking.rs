@@ -0,0 +1,335 @@
+//! Group masks: where a masked group's content shows.
+//!
+//! Coverage is built from closed shape outlines. A clip lets content through
+//! inside its shape under the shape's fill rule. Hit testing cannot read
+//! paint, so alpha and luminance masks both treat the closed shapes of the
+//! source group and its subgroups as opaque white, and SVG export writes
+//! those shapes into the `<mask>`. Clips whose shape is gone or no longer
+//! closed, and masks whose source group is gone, are ignored.
+
+use std::collections::HashSet;
+
+use crate::algorithms::region_fill::{shape_outline, ShapeFill};
+use crate::algorithms::winding::point_in_rings;
+use crate::model::{GroupItem, GroupMask, LayerId};
+use crate::Graph;
+
+type BBox = (f32, f32, f32, f32);
+
+/// Where one mask lets content through: the union of its outlines.
+pub(crate) struct MaskArea {
+    pub outlines: Vec<ShapeFill>,
+}
+
+impl MaskArea {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.outlines.iter().any(|o| {
+            x >= o.bbox.0
+                && x <= o.bbox.2
+                && y >= o.bbox.1
+                && y <= o.bbox.3
+                && point_in_rings(o.rule, x, y, &o.rings)
+        })
+    }
+
+    /// Bounds of the area, None when it lets nothing through
+    pub fn bbox(&self) -> Option<BBox> {
+        self.outlines
+            .iter()
+            .map(|o| o.bbox)
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    }
+}
+
+/// Shapes in a group and its subgroups
+fn source_shapes(g: &Graph, group: LayerId) -> Vec<u32> {
+    let mut shapes = Vec::new();
+    let mut stack = vec![group];
+    let mut seen = HashSet::new();
+    while let Some(gid) = stack.pop() {
+        let Some(group) = g.layer_system.get_group(gid) else {
+            continue;
+        };
+        if !seen.insert(gid) {
+            continue;
+        }
+        for &item in &group.items {
+            match item {
+                GroupItem::Group(child) => stack.push(child),
+                GroupItem::Shape(id) => shapes.push(id),
+                _ => {}
+            }
+        }
+    }
+    shapes
+}
+
+/// The area a mask lets through; None for masks that are ignored.
+pub(crate) fn mask_area(g: &Graph, mask: GroupMask) -> Option<MaskArea> {
+    let outlines = match mask {
+        GroupMask::Clip { shape } => vec![shape_outline(g, g.get_shape(shape)?)?],
+        GroupMask::Alpha { group } | GroupMask::Luminance { group } => {
+            g.layer_system.get_group(group)?;
+            source_shapes(g, group)
+                .into_iter()
+                .filter_map(|id| shape_outline(g, g.get_shape(id)?))
+                .collect()
+        }
+    };
+    Some(MaskArea { outlines })
+}
+
+/// A clip never hides its own outline: the clip shape and its edges stay
+/// pickable along the boundary.
+fn is_own_clip(g: &Graph, item: GroupItem, mask: GroupMask) -> bool {
+    let GroupMask::Clip { shape } = mask else {
+        return false;
+    };
+    match item {
+        GroupItem::Shape(id) => id == shape,
+        GroupItem::Edge(id) => g.get_shape(shape).is_some_and(|s| s.edges.contains(&id)),
+        _ => false,
+    }
+}
+
+fn item_mask_areas(g: &Graph, item: GroupItem) -> Vec<MaskArea> {
+    g.layer_system
+        .item_masks(item)
+        .into_iter()
+        .filter(|&(_, mask)| !is_own_clip(g, item, mask))
+        .filter_map(|(_, mask)| mask_area(g, mask))
+        .collect()
+}
+
+/// Whether every mask over the item lets the point through.
+pub(crate) fn item_unmasked_at(g: &Graph, item: GroupItem, x: f32, y: f32) -> bool {
+    if !g.layer_system.has_masks() {
+        return true;
+    }
+    item_mask_areas(g, item).iter().all(|a| a.contains(x, y))
+}
+
+/// The part of an item's box its masks can let through, None when they hide
+/// it entirely.
+pub(crate) fn clip_to_masks(g: &Graph, item: GroupItem, bbox: BBox) -> Option<BBox> {
+    if !g.layer_system.has_masks() {
+        return Some(bbox);
+    }
+    item_mask_areas(g, item).iter().try_fold(bbox, |b, area| {
+        let m = area.bbox()?;
+        let r = (b.0.max(m.0), b.1.max(m.1), b.2.min(m.2), b.3.min(m.3));
+        (r.0 <= r.2 && r.1 <= r.3).then_some(r)
+    })
+}
+
+/// Whether a region shows at the point. A mask applies to a region when all
+/// of its boundary edges sit inside the masked group. Fills test one interior
+/// probe, so a mask outline that does not cut the face shows or hides it
+/// whole.
+pub(crate) fn region_unmasked_at(g: &Graph, edges: &[u32], x: f32, y: f32) -> bool {
+    let ls = &g.layer_system;
+    let Some(&first) = edges.first() else {
+        return true;
+    };
+    if !ls.has_masks() {
+        return true;
+    }
+    ls.item_masks(GroupItem::Edge(first))
+        .into_iter()
+        .filter(|&(gid, _)| {
+            edges
+                .iter()
+                .all(|&e| ls.get_edge_group(e).is_some_and(|eg| ls.is_within(eg, gid)))
+        })
+        .filter_map(|(_, mask)| mask_area(g, mask))
+        .all(|a| a.contains(x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{GroupItem, GroupMask, LayerId, SelectMode};
+    use crate::{Graph, Pick, PrimitiveResult};
+
+    fn file(g: &mut Graph, r: &PrimitiveResult, group: LayerId) {
+        let mut items: Vec<GroupItem> = r.edges.iter().map(|&e| GroupItem::Edge(e)).collect();
+        items.push(GroupItem::Shape(r.shape));
+        assert_eq!(g.move_items_to_group(&items, group), items.len());
+    }
+
+    fn shapes_at(g: &mut Graph, x: f32, y: f32) -> Vec<u32> {
+        g.pick_all(x, y, 0.5)
+            .into_iter()
+            .filter_map(|p| match p {
+                Pick::Shape { id, .. } => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn filled_at(g: &mut Graph, x: f32, y: f32) -> bool {
+        let key = g.region_at(x, y).unwrap();
+        g.get_regions()
+            .into_iter()
+            .find(|r| r["key"].as_u64() == Some(key as u64))
+            .unwrap()["filled"]
+            .as_bool()
+            .unwrap()
+    }
+
+    #[test]
+    fn clip_limits_picking_bounds_regions_and_export() {
+        let mut g = Graph::new();
+        let root = g.default_group().unwrap();
+        let art = g.create_group("Art".to_string(), root).unwrap();
+        let big = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let clip = g.add_rectangle(20.0, 20.0, 40.0, 40.0, 0.0);
+        file(&mut g, &big, art);
+        file(&mut g, &clip, art);
+        assert_eq!(shapes_at(&mut g, 5.0, 5.0), vec![big.shape]);
+
+        assert!(g.set_group_mask(art, Some(GroupMask::Clip { shape: clip.shape })));
+        assert_eq!(
+            g.get_group_mask(art),
+            Some(GroupMask::Clip { shape: clip.shape })
+        );
+        assert!(shapes_at(&mut g, 5.0, 5.0).is_empty());
+        assert!(g.pick_with_regions(5.0, 5.0, 0.5).is_none());
+        assert!(g.pick_with_regions(0.0, 50.0, 0.5).is_none());
+        assert!(shapes_at(&mut g, 30.0, 30.0).contains(&big.shape));
+        // The clip outline itself stays pickable
+        assert!(matches!(g.pick(20.0, 30.0, 0.5), Some(Pick::Edge { .. })));
+
+        assert_eq!(
+            g.get_selection_bbox(&[], &[], &[big.shape], &[]),
+            Some((20.0, 20.0, 60.0, 60.0))
+        );
+        assert_eq!(g.get_selection_bbox(&[], &[big.edges[3]], &[], &[]), None);
+
+        assert!(!filled_at(&mut g, 5.0, 5.0));
+        assert!(filled_at(&mut g, 30.0, 30.0));
+
+        let svg = g.to_svg_document();
+        assert!(svg.contains(&format!(r#"<clipPath id="clip-{}"><path d="M 20 20"#, art)));
+        assert!(svg.contains(&format!(
+            r#"data-name="Art" clip-path="url(#clip-{})">"#,
+            art
+        )));
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(g.to_json_value()));
+        assert_eq!(
+            loaded.get_group_mask(art),
+            Some(GroupMask::Clip { shape: clip.shape })
+        );
+        assert!(!filled_at(&mut loaded, 5.0, 5.0));
+
+        assert!(g.set_group_mask(art, None));
+        assert!(filled_at(&mut g, 5.0, 5.0));
+        let (a, b) = (g.add_node(0.0, 0.0), g.add_node(10.0, 10.0));
+        let line = g.add_edge(a, b).unwrap();
+        let open = g.create_shape(&[line], false).unwrap();
+        assert!(!g.set_group_mask(art, Some(GroupMask::Clip { shape: open })));
+        assert!(g.set_group_locked(art, true));
+        assert!(!g.set_group_mask(art, Some(GroupMask::Clip { shape: clip.shape })));
+    }
+
+    #[test]
+    fn mask_groups_hide_their_source_and_limit_content() {
+        let mut g = Graph::new();
+        let root = g.default_group().unwrap();
+        let art = g.create_group("Art".to_string(), root).unwrap();
+        let source = g.create_group("Mask".to_string(), root).unwrap();
+        let big = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let spot = g.add_rectangle(20.0, 20.0, 40.0, 40.0, 0.0);
+        file(&mut g, &big, art);
+        file(&mut g, &spot, source);
+
+        // Sources must be outside the masked group's chain and subtree
+        let inner = g.create_group("Inner".to_string(), art).unwrap();
+        assert!(!g.set_group_mask(art, Some(GroupMask::Alpha { group: art })));
+        assert!(!g.set_group_mask(art, Some(GroupMask::Alpha { group: root })));
+        assert!(!g.set_group_mask(art, Some(GroupMask::Alpha { group: inner })));
+        assert!(!g.set_group_mask(inner, Some(GroupMask::Alpha { group: art })));
+
+        assert!(g.set_group_mask(art, Some(GroupMask::Alpha { group: source })));
+        assert!(!g.set_group_mask(source, Some(GroupMask::Luminance { group: art })));
+        assert_eq!(shapes_at(&mut g, 30.0, 30.0), vec![big.shape]);
+        assert!(shapes_at(&mut g, 5.0, 5.0).is_empty());
+        let sel = g.select_in_rect(-10.0, -10.0, 110.0, 110.0, SelectMode::Contained);
+        assert_eq!(sel.shapes, vec![big.shape]);
+        assert!(spot.edges.iter().all(|e| !sel.edges.contains(e)));
+        // Source content is not drawn, so its faces are gone
+        assert_eq!(g.get_regions().len(), 1);
+        assert!(filled_at(&mut g, 50.0, 50.0));
+
+        let svg = g.to_svg_document();
+        assert!(svg.contains(&format!(
+            r#"<mask id="mask-{}" mask-type="alpha"><path d="M 20 20"#,
+            art
+        )));
+        assert!(svg.contains(r#" Z" fill="white" fill-rule="nonzero"/></mask>"#));
+        assert!(!svg.contains(&format!(r#"data-shape="{}""#, spot.shape)));
+        assert!(!svg.contains(r#"data-name="Mask""#));
+        assert!(svg.contains(&format!(r#"data-name="Art" mask="url(#mask-{})">"#, art)));
+
+        assert!(g.set_group_mask(art, Some(GroupMask::Luminance { group: source })));
+        assert!(g.to_svg_document().contains(&format!(
+            r#"<mask id="mask-{}" mask-type="luminance">"#,
+            art
+        )));
+
+        // An empty mask group hides everything
+        assert!(g.set_group_visibility(source, false));
+        let empty = g.create_group("Empty".to_string(), root).unwrap();
+        assert!(g.set_group_mask(art, Some(GroupMask::Alpha { group: empty })));
+        assert!(!filled_at(&mut g, 50.0, 50.0));
+        assert_eq!(g.get_selection_bbox(&[], &[], &[big.shape], &[]), None);
+    }
+
+    #[test]
+    fn invalid_masks_are_dropped_on_load() {
+        let mut g = Graph::new();
+        let root = g.default_group().unwrap();
+        let art = g.create_group("Art".to_string(), root).unwrap();
+        let source = g.create_group("Mask".to_string(), root).unwrap();
+        let inner = g.create_group("Inner".to_string(), art).unwrap();
+        assert!(g.set_group_mask(art, Some(GroupMask::Alpha { group: source })));
+        let saved = g.to_json_value();
+
+        // Give groups masks set_group_mask refuses
+        let with_masks = |masks: &[(LayerId, GroupMask)]| {
+            let mut doc = saved.clone();
+            for group in doc["groups"].as_array_mut().unwrap() {
+                let id = group["id"].as_u64().unwrap() as LayerId;
+                if let Some(&(_, mask)) = masks.iter().find(|(g, _)| *g == id) {
+                    group["mask"] = serde_json::to_value(mask).unwrap();
+                }
+            }
+            doc
+        };
+        let root_source = with_masks(&[(source, GroupMask::Alpha { group: root })]);
+        let nested = with_masks(&[(inner, GroupMask::Luminance { group: art })]);
+        // Art and Mask masking each other: the lower id loses its mask
+        let cycle = with_masks(&[(source, GroupMask::Alpha { group: art })]);
+        for strict in [false, true] {
+            for doc in [&root_source, &nested, &cycle] {
+                let mut loaded = Graph::new();
+                if strict {
+                    assert_eq!(loaded.from_json_value_strict(doc.clone()), Ok(true));
+                } else {
+                    assert!(loaded.from_json_value(doc.clone()));
+                }
+                assert_eq!(loaded.get_group_mask(inner), None);
+                let masked = [art, source]
+                    .into_iter()
+                    .filter(|&id| loaded.get_group_mask(id).is_some())
+                    .count();
+                assert_eq!(masked, 1);
+            }
+        }
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(cycle));
+        assert_eq!(loaded.get_group_mask(art.min(source)), None);
+    }
+}
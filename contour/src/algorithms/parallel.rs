@@ -0,0 +1,152 @@
+//! Order-preserving parallel map used by the region pipeline.
+//!
+//! With the `threads` feature the work is split into contiguous chunks run on
+//! scoped std threads; results are stitched back in input order, so callers
+//! see exactly what the serial map would produce. Without the feature
+//! everything runs on the calling thread.
+//!
+//! std cannot spawn threads on wasm32, so there the feature maps on a rayon
+//! pool whose threads the host starts as Web Workers sharing the module's
+//! memory (`init_thread_pool`, needs a `+atomics` build). Rayon's indexed
+//! collect keeps input order too. Until the pool is built maps run serially.
+//! Callers block on the pool, so they must not be on the browser main thread.
+
+#[cfg(test)]
+thread_local! {
+    static FORCE_SERIAL: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Run `f` with every `par_map` it makes on this thread mapped serially, to
+/// compare threaded output against the serial path.
+#[cfg(test)]
+pub(crate) fn serially<R>(f: impl FnOnce() -> R) -> R {
+    FORCE_SERIAL.with(|s| s.set(true));
+    let out = f();
+    FORCE_SERIAL.with(|s| s.set(false));
+    out
+}
+
+/// Inputs shorter than this are mapped serially; spawning costs more than it saves.
+#[cfg(feature = "threads")]
+const MIN_PARALLEL_ITEMS: usize = 512;
+
+/// Number of chunks to split `len` items into.
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+fn worker_count(len: usize) -> usize {
+    #[cfg(test)]
+    if FORCE_SERIAL.with(|s| s.get()) {
+        return 1;
+    }
+    if len < MIN_PARALLEL_ITEMS {
+        return 1;
+    }
+    let hw = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    hw.min(len / (MIN_PARALLEL_ITEMS / 2)).max(1)
+}
+
+/// Map `f` over `items`, returning results in input order.
+#[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    let workers = worker_count(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(workers);
+    let f = &f;
+    std::thread::scope(|s| {
+        // A chunk whose worker can't be spawned (e.g. a thread limit) is
+        // mapped inline instead
+        let parts: Vec<Result<_, Vec<R>>> = items
+            .chunks(chunk)
+            .map(|part| {
+                std::thread::Builder::new()
+                    .spawn_scoped(s, move || part.iter().map(f).collect::<Vec<R>>())
+                    .map_err(|_| part.iter().map(f).collect())
+            })
+            .collect();
+        let mut out = Vec::with_capacity(items.len());
+        for part in parts {
+            match part {
+                Ok(h) => match h.join() {
+                    Ok(v) => out.extend(v),
+                    Err(panic) => std::panic::resume_unwind(panic),
+                },
+                Err(v) => out.extend(v),
+            }
+        }
+        out
+    })
+}
+
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+static POOL_READY: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub use rayon::ThreadBuilder;
+
+/// Build the pool `par_map` uses on wasm32 with `num_threads` workers.
+/// `spawn` is called once per worker and must get `ThreadBuilder::run`
+/// called on another thread; it returns once every worker is running.
+/// Fails if the pool was already built or a spawn failed.
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub fn init_thread_pool<S>(num_threads: usize, spawn: S) -> Result<(), rayon::ThreadPoolBuildError>
+where
+    S: FnMut(ThreadBuilder) -> std::io::Result<()>,
+{
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .spawn_handler(spawn)
+        .build_global()?;
+    POOL_READY.store(true, std::sync::atomic::Ordering::Release);
+    Ok(())
+}
+
+/// Map `f` over `items`, returning results in input order.
+#[cfg(all(feature = "threads", target_arch = "wasm32"))]
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    use rayon::prelude::*;
+    #[cfg(test)]
+    if FORCE_SERIAL.with(|s| s.get()) {
+        return items.iter().map(f).collect();
+    }
+    if items.len() < MIN_PARALLEL_ITEMS || !POOL_READY.load(std::sync::atomic::Ordering::Acquire) {
+        return items.iter().map(f).collect();
+    }
+    items.par_iter().map(f).collect()
+}
+
+/// Map `f` over `items`, returning results in input order.
+#[cfg(not(feature = "threads"))]
+pub(crate) fn par_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    items.iter().map(f).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_input_order() {
+        let items: Vec<u32> = (0..5000).collect();
+        let out = par_map(&items, |x| x * 3);
+        assert_eq!(out, items.iter().map(|x| x * 3).collect::<Vec<_>>());
+        assert!(par_map(&[] as &[u32], |x| *x).is_empty());
+    }
+}
@@ -1,6 +1,12 @@
+use crate::algorithms::masking::{item_unmasked_at, region_unmasked_at};
+use crate::algorithms::region_fill::shape_fill;
+use crate::algorithms::region_query::regions_at;
+use crate::algorithms::winding::point_in_rings;
 use crate::geometry::math::{cubic_distance_sq, seg_distance_sq};
 use crate::geometry::tolerance::clamp01;
-use crate::{model::EdgeKind, Graph};
+use crate::model::{Edge, EdgeKind, GroupItem, RegionFillMode, Vec2};
+use crate::{Graph, Pick};
+use std::cell::Ref;
 use std::collections::{HashMap, HashSet};
 
 #[derive(Clone)]
@@ -9,6 +15,257 @@ pub struct PickIndex {
     pub nodes: HashMap<(i32, i32), Vec<u32>>, // node ids
     pub handles: HashMap<(i32, i32), Vec<(u32, u8)>>, // (edge_id, end)
     pub edges: HashMap<(i32, i32), Vec<u32>>, // edge ids by bbox coverage
+    pub shapes: HashMap<(i32, i32), Vec<u32>>, // shape ids by outline bbox
+    pub texts: HashMap<(i32, i32), Vec<u32>>, // text ids by rotated box
+    // Graph::objects_ver the shape and text cells reflect
+    objects_ver: u64,
+    // Where each entry was filed, so it can be removed without a scan
+    node_cells: HashMap<u32, (i32, i32)>,
+    handle_cells: HashMap<(u32, u8), (i32, i32)>,
+    edge_cells: HashMap<u32, (i32, i32, i32, i32)>, // ix0, iy0, ix1, iy1
+    shape_cells: HashMap<u32, FiledShape>,
+    text_cells: HashMap<u32, (i32, i32, i32, i32)>,
+    // Shapes filed under each edge, refiled when the edge changes
+    edge_shapes: HashMap<u32, Vec<u32>>,
+}
+
+/// Where a shape was filed: the union of its edge boxes, if any edge has
+/// one, and the edges it was filed under.
+#[derive(Clone)]
+struct FiledShape {
+    bbox: Option<(f32, f32, f32, f32)>,
+    edges: Vec<u32>,
+}
+
+fn unfile<K: Copy + Eq + std::hash::Hash, T: PartialEq + Copy>(
+    map: &mut HashMap<K, Vec<T>>,
+    key: K,
+    item: T,
+) {
+    if let Some(lst) = map.get_mut(&key) {
+        lst.retain(|&x| x != item);
+        if lst.is_empty() {
+            map.remove(&key);
+        }
+    }
+}
+
+impl PickIndex {
+    fn empty(cell: f32) -> Self {
+        PickIndex {
+            cell,
+            nodes: HashMap::new(),
+            handles: HashMap::new(),
+            edges: HashMap::new(),
+            shapes: HashMap::new(),
+            texts: HashMap::new(),
+            objects_ver: 0,
+            node_cells: HashMap::new(),
+            handle_cells: HashMap::new(),
+            edge_cells: HashMap::new(),
+            shape_cells: HashMap::new(),
+            text_cells: HashMap::new(),
+            edge_shapes: HashMap::new(),
+        }
+    }
+
+    fn span(&self, (minx, miny, maxx, maxy): (f32, f32, f32, f32)) -> (i32, i32, i32, i32) {
+        (
+            cell_ix(self.cell, minx),
+            cell_ix(self.cell, miny),
+            cell_ix(self.cell, maxx),
+            cell_ix(self.cell, maxy),
+        )
+    }
+
+    fn insert_node(&mut self, g: &Graph, id: u32) {
+        if let Some(n) = g.nodes.get(id as usize).and_then(|n| *n) {
+            let c = (cell_ix(self.cell, n.x), cell_ix(self.cell, n.y));
+            self.nodes.entry(c).or_default().push(id);
+            self.node_cells.insert(id, c);
+        }
+    }
+
+    fn remove_node(&mut self, id: u32) {
+        if let Some(c) = self.node_cells.remove(&id) {
+            unfile(&mut self.nodes, c, id);
+        }
+    }
+
+    /// Files the edge's bbox cells and, for cubics, both handles.
+    fn insert_edge(&mut self, g: &Graph, id: u32) {
+        let Some(e) = g.edges.get(id as usize).and_then(|e| e.as_ref()) else {
+            return;
+        };
+        if let EdgeKind::Cubic { ha, hb, .. } = e.kind {
+            let a = g.nodes.get(e.a as usize).and_then(|n| *n);
+            let b = g.nodes.get(e.b as usize).and_then(|n| *n);
+            if let (Some(a), Some(b)) = (a, b) {
+                for (end, px, py) in [(0u8, a.x + ha.x, a.y + ha.y), (1, b.x + hb.x, b.y + hb.y)] {
+                    let c = (cell_ix(self.cell, px), cell_ix(self.cell, py));
+                    self.handles.entry(c).or_default().push((id, end));
+                    self.handle_cells.insert((id, end), c);
+                }
+            }
+        }
+        if let Some(bbox) = bbox_of_edge(g, id as usize) {
+            let span = self.span(bbox);
+            file_span(&mut self.edges, span, id);
+            self.edge_cells.insert(id, span);
+        }
+    }
+
+    fn remove_edge(&mut self, id: u32) {
+        for end in [0u8, 1] {
+            if let Some(c) = self.handle_cells.remove(&(id, end)) {
+                unfile(&mut self.handles, c, (id, end));
+            }
+        }
+        if let Some(span) = self.edge_cells.remove(&id) {
+            unfile_span(&mut self.edges, span, id);
+        }
+    }
+
+    /// Files the shape under the union of its edge boxes and remembers its
+    /// edges, so moving one of them refiles the shape.
+    fn insert_shape(&mut self, g: &Graph, id: u32) {
+        let Some(shape) = g.get_shape(id) else {
+            return;
+        };
+        let edges = dedup(shape.edges.clone());
+        for &e in &edges {
+            self.edge_shapes.entry(e).or_default().push(id);
+        }
+        let bbox = edges
+            .iter()
+            .filter_map(|&e| bbox_of_edge(g, e as usize))
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)));
+        if let Some(bbox) = bbox {
+            let span = self.span(bbox);
+            file_span(&mut self.shapes, span, id);
+        }
+        self.shape_cells.insert(id, FiledShape { bbox, edges });
+    }
+
+    fn remove_shape(&mut self, id: u32) {
+        let Some(FiledShape { bbox, edges }) = self.shape_cells.remove(&id) else {
+            return;
+        };
+        for e in edges {
+            unfile(&mut self.edge_shapes, e, id);
+        }
+        if let Some(bbox) = bbox {
+            let span = self.span(bbox);
+            unfile_span(&mut self.shapes, span, id);
+        }
+    }
+
+    /// Files the text under the bounds of its rotated box.
+    fn insert_text(&mut self, g: &Graph, id: u32) {
+        let Some(t) = g.get_text(id) else {
+            return;
+        };
+        let (minx, miny, maxx, maxy) = Graph::text_aabb(t);
+        let (sin, cos) = t.rotation.sin_cos();
+        let (ax, ay) = (t.position.x, t.position.y);
+        let mut bbox = (
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        );
+        for (cx, cy) in [(minx, miny), (maxx, miny), (maxx, maxy), (minx, maxy)] {
+            let (dx, dy) = (cx - ax, cy - ay);
+            let (px, py) = (ax + dx * cos - dy * sin, ay + dx * sin + dy * cos);
+            bbox = (
+                bbox.0.min(px),
+                bbox.1.min(py),
+                bbox.2.max(px),
+                bbox.3.max(py),
+            );
+        }
+        // A non-finite box would span the whole grid; such texts never hit
+        if ![bbox.0, bbox.1, bbox.2, bbox.3]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            return;
+        }
+        let span = self.span(bbox);
+        file_span(&mut self.texts, span, id);
+        self.text_cells.insert(id, span);
+    }
+
+    fn remove_text(&mut self, id: u32) {
+        if let Some(span) = self.text_cells.remove(&id) {
+            unfile_span(&mut self.texts, span, id);
+        }
+    }
+
+    /// Brings the index up to date from `g.dirty`, which must cover every
+    /// change since the index was built. Entries are re-read from the
+    /// current geometry, so extra ids in the dirty sets are harmless.
+    fn apply_dirty(&mut self, g: &Graph) {
+        let d = &g.dirty;
+        for &id in d
+            .nodes_removed
+            .iter()
+            .chain(&d.nodes_moved)
+            .chain(&d.nodes_added)
+        {
+            self.remove_node(id);
+            self.insert_node(g, id);
+        }
+        for &id in d
+            .edges_removed
+            .iter()
+            .chain(&d.edges_modified)
+            .chain(&d.edges_added)
+        {
+            self.remove_edge(id);
+            self.insert_edge(g, id);
+        }
+        let mut shapes: HashSet<u32> = d.shapes_touched.clone();
+        for id in d
+            .edges_removed
+            .iter()
+            .chain(&d.edges_modified)
+            .chain(&d.edges_added)
+        {
+            if let Some(ids) = self.edge_shapes.get(id) {
+                shapes.extend(ids);
+            }
+        }
+        for id in shapes {
+            self.remove_shape(id);
+            self.insert_shape(g, id);
+        }
+        for &id in &d.texts_touched {
+            self.remove_text(id);
+            self.insert_text(g, id);
+        }
+        self.objects_ver = g.objects_ver;
+    }
+}
+
+fn file_span<T: Copy>(map: &mut HashMap<(i32, i32), Vec<T>>, span: (i32, i32, i32, i32), item: T) {
+    for ix in span.0..=span.2 {
+        for iy in span.1..=span.3 {
+            map.entry((ix, iy)).or_default().push(item);
+        }
+    }
+}
+
+fn unfile_span<T: PartialEq + Copy>(
+    map: &mut HashMap<(i32, i32), Vec<T>>,
+    span: (i32, i32, i32, i32),
+    item: T,
+) {
+    for ix in span.0..=span.2 {
+        for iy in span.1..=span.3 {
+            unfile(map, (ix, iy), item);
+        }
+    }
 }
 
 fn cell_ix(cell: f32, x: f32) -> i32 {
@@ -58,60 +315,25 @@ fn bbox_of_edge(g: &Graph, eid: usize) -> Option<(f32, f32, f32, f32)> {
 }
 
 pub fn build_pick_index(g: &Graph, cell: f32) -> PickIndex {
-    let mut nodes: HashMap<(i32, i32), Vec<u32>> = HashMap::new();
+    let mut idx = PickIndex::empty(cell);
     for (i, n) in g.nodes.iter().enumerate() {
-        if let Some(n) = n {
-            let ix = cell_ix(cell, n.x);
-            let iy = cell_ix(cell, n.y);
-            nodes.entry((ix, iy)).or_default().push(i as u32);
+        if n.is_some() {
+            idx.insert_node(g, i as u32);
         }
     }
-
-    let mut handles: HashMap<(i32, i32), Vec<(u32, u8)>> = HashMap::new();
     for (i, e) in g.edges.iter().enumerate() {
-        if let Some(e) = e {
-            if let EdgeKind::Cubic { ha, hb, .. } = e.kind {
-                let a = g.nodes.get(e.a as usize).and_then(|n| *n);
-                let b = g.nodes.get(e.b as usize).and_then(|n| *n);
-                if let (Some(a), Some(b)) = (a, b) {
-                    let p1x = a.x + ha.x;
-                    let p1y = a.y + ha.y;
-                    let p2x = b.x + hb.x;
-                    let p2y = b.y + hb.y;
-                    let ix1 = cell_ix(cell, p1x);
-                    let iy1 = cell_ix(cell, p1y);
-                    let ix2 = cell_ix(cell, p2x);
-                    let iy2 = cell_ix(cell, p2y);
-                    handles.entry((ix1, iy1)).or_default().push((i as u32, 0));
-                    handles.entry((ix2, iy2)).or_default().push((i as u32, 1));
-                }
-            }
+        if e.is_some() {
+            idx.insert_edge(g, i as u32);
         }
     }
-
-    let mut edges: HashMap<(i32, i32), Vec<u32>> = HashMap::new();
-    for (i, _e) in g.edges.iter().enumerate() {
-        if g.edges[i].is_some() {
-            if let Some((minx, miny, maxx, maxy)) = bbox_of_edge(g, i) {
-                let ix0 = cell_ix(cell, minx);
-                let ix1 = cell_ix(cell, maxx);
-                let iy0 = cell_ix(cell, miny);
-                let iy1 = cell_ix(cell, maxy);
-                for ix in ix0..=ix1 {
-                    for iy in iy0..=iy1 {
-                        edges.entry((ix, iy)).or_default().push(i as u32);
-                    }
-                }
-            }
-        }
+    for id in g.get_shape_ids() {
+        idx.insert_shape(g, id);
     }
-
-    PickIndex {
-        cell,
-        nodes,
-        handles,
-        edges,
+    for id in g.get_text_ids() {
+        idx.insert_text(g, id);
     }
+    idx.objects_ver = g.objects_ver;
+    idx
 }
 
 fn choose_cell_size(g: &Graph) -> f32 {
@@ -166,11 +388,30 @@ fn query_ids<T: Copy>(
     y: f32,
     tol: f32,
 ) -> Vec<T> {
-    let ix0 = cell_ix(cell, x - tol);
-    let ix1 = cell_ix(cell, x + tol);
-    let iy0 = cell_ix(cell, y - tol);
-    let iy1 = cell_ix(cell, y + tol);
+    query_rect(map, cell, (x - tol, y - tol, x + tol, y + tol))
+}
+
+/// Entries of every cell overlapping `rect` (may repeat ids). Walks the map
+/// instead of the cell range when the range has more cells than the map.
+pub(crate) fn query_rect<T: Copy>(
+    map: &HashMap<(i32, i32), Vec<T>>,
+    cell: f32,
+    rect: (f32, f32, f32, f32),
+) -> Vec<T> {
+    let ix0 = cell_ix(cell, rect.0);
+    let ix1 = cell_ix(cell, rect.2);
+    let iy0 = cell_ix(cell, rect.1);
+    let iy1 = cell_ix(cell, rect.3);
     let mut out = Vec::new();
+    let span = (ix1 as i64 - ix0 as i64 + 1) * (iy1 as i64 - iy0 as i64 + 1);
+    if span > map.len() as i64 {
+        for (&(ix, iy), lst) in map {
+            if (ix0..=ix1).contains(&ix) && (iy0..=iy1).contains(&iy) {
+                out.extend_from_slice(lst);
+            }
+        }
+        return out;
+    }
     for ix in ix0..=ix1 {
         for iy in iy0..=iy1 {
             if let Some(lst) = map.get(&(ix, iy)) {
@@ -181,187 +422,574 @@ fn query_ids<T: Copy>(
     out
 }
 
-pub fn pick_impl(g: &Graph, x: f32, y: f32, tol: f32) -> Option<crate::Pick> {
-    // Use spatial index with lazy rebuild keyed by geom_ver
-    let cell = choose_cell_size(g);
-    let mut idx_guard = g.pick_index.borrow_mut();
-    let use_idx = if let Some((ver, _)) = idx_guard.as_ref() {
-        *ver == g.geom_version()
-    } else {
-        false
+/// Brings an existing pick index up to the current geom_ver. Patches it
+/// from the dirty state when that covers every change since the index was
+/// built; rebuilds on `dirty.full`, when the dirty state was cleared in the
+/// meantime, or when most entries changed anyway. Called before the region
+/// pass clears the dirty state so drags keep the incremental path.
+pub(crate) fn sync_pick_index(g: &Graph) {
+    let mut guard = g.pick_index.borrow_mut();
+    let Some((ver, idx)) = guard.as_mut() else {
+        return;
     };
-    if !use_idx {
-        let idx = build_pick_index(g, cell);
-        *idx_guard = Some((g.geom_version(), idx));
+    if *ver == g.geom_version() && idx.objects_ver == g.objects_ver {
+        return;
     }
-    let (_, idx) = idx_guard.as_ref().unwrap();
+    let d = &g.dirty;
+    let changed = d.nodes_added.len()
+        + d.nodes_removed.len()
+        + d.nodes_moved.len()
+        + d.edges_added.len()
+        + d.edges_removed.len()
+        + d.edges_modified.len()
+        + d.shapes_touched.len()
+        + d.texts_touched.len();
+    let live =
+        idx.node_cells.len() + idx.edge_cells.len() + idx.shape_cells.len() + idx.text_cells.len();
+    if d.full || d.since_ver > *ver || changed * 2 > live.max(16) {
+        *idx = build_pick_index(g, choose_cell_size(g));
+    } else {
+        idx.apply_dirty(g);
+    }
+    *ver = g.geom_version();
+}
 
-    let tol2 = tol * tol;
-    // Nodes first
-    let mut best_node: Option<(u32, f32)> = None;
-    let node_cands = dedup(query_ids(&idx.nodes, idx.cell, x, y, tol));
-    for id in node_cands {
-        if let Some(n) = g.nodes.get(id as usize).and_then(|n| *n) {
-            let dx = n.x - x;
-            let dy = n.y - y;
-            let d2 = dx * dx + dy * dy;
-            if d2 <= tol2 {
-                if best_node.map_or(true, |(_, bd)| d2 < bd) {
-                    best_node = Some((id, d2));
+/// The pick index for the current geometry, built on first use and kept in
+/// step with geom_ver by [`sync_pick_index`].
+pub(crate) fn current_pick_index(g: &Graph) -> Ref<'_, PickIndex> {
+    sync_pick_index(g);
+    {
+        let mut guard = g.pick_index.borrow_mut();
+        if guard.is_none() {
+            *guard = Some((g.geom_version(), build_pick_index(g, choose_cell_size(g))));
+        }
+    }
+    Ref::map(g.pick_index.borrow(), |i| &i.as_ref().unwrap().1)
+}
+
+/// Squared distance from `(x, y)` to an edge and the edge parameter there.
+fn edge_distance_sq(g: &Graph, e: &Edge, x: f32, y: f32) -> Option<(f32, f32)> {
+    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    match e.kind {
+        EdgeKind::Line => Some(seg_distance_sq(x, y, a.x, a.y, b.x, b.y)),
+        EdgeKind::Cubic { ha, hb, .. } => {
+            let p1x = a.x + ha.x;
+            let p1y = a.y + ha.y;
+            let p2x = b.x + hb.x;
+            let p2y = b.y + hb.y;
+            let (d2, t) = cubic_distance_sq(x, y, a.x, a.y, p1x, p1y, p2x, p2y, b.x, b.y);
+            Some((d2, clamp01(t)))
+        }
+        EdgeKind::Polyline { ref points } => {
+            let mut prevx = a.x;
+            let mut prevy = a.y;
+            let mut length = 0.0;
+            let mut segs = Vec::new();
+            for p in points
+                .iter()
+                .chain(std::iter::once(&Vec2 { x: b.x, y: b.y }))
+            {
+                let seg_len = ((p.x - prevx).powi(2) + (p.y - prevy).powi(2)).sqrt();
+                if seg_len > 0.0 {
+                    segs.push((prevx, prevy, p.x, p.y, seg_len));
+                    length += seg_len;
+                }
+                prevx = p.x;
+                prevy = p.y;
+            }
+            #[cfg(feature = "simd")]
+            let dists = {
+                let ends: Vec<(f32, f32, f32, f32)> =
+                    segs.iter().map(|s| (s.0, s.1, s.2, s.3)).collect();
+                let mut dists = Vec::with_capacity(ends.len());
+                crate::geometry::math::seg_distance_sq_many(x, y, &ends, &mut dists);
+                dists
+            };
+            #[cfg(not(feature = "simd"))]
+            let dists = segs
+                .iter()
+                .map(|s| seg_distance_sq(x, y, s.0, s.1, s.2, s.3));
+            let mut acc = 0.0;
+            let mut best: Option<(f32, f32)> = None;
+            for (&(_, _, _, _, sl), (d2, ts)) in segs.iter().zip(dists) {
+                if best.is_none_or(|(bd, _)| d2 < bd) {
+                    let t_along = if length > 0.0 {
+                        (acc + ts * sl) / length
+                    } else {
+                        0.0
+                    };
+                    best = Some((d2, t_along));
                 }
+                acc += sl;
             }
+            best
         }
     }
-    if let Some((id, d2)) = best_node {
-        return Some(crate::Pick::Node {
-            id,
-            dist: d2.sqrt(),
-        });
-    }
-    // Handles
-    let mut best_handle: Option<(u32, u8, f32)> = None;
-    let handle_cands = dedup(query_ids(&idx.handles, idx.cell, x, y, tol));
-    for (edge, end) in handle_cands {
-        if let Some(e) = g.edges.get(edge as usize).and_then(|ee| ee.as_ref()) {
-            if let EdgeKind::Cubic { ha, hb, .. } = e.kind {
-                let a = if let Some(n) = g.nodes.get(e.a as usize).and_then(|n| *n) {
-                    n
-                } else {
-                    continue;
-                };
-                let b = if let Some(n) = g.nodes.get(e.b as usize).and_then(|n| *n) {
-                    n
-                } else {
-                    continue;
-                };
-                let (px, py) = if end == 0 {
-                    (a.x + ha.x, a.y + ha.y)
-                } else {
-                    (b.x + hb.x, b.y + hb.y)
-                };
-                let d2 = (px - x).powi(2) + (py - y).powi(2);
-                if d2 <= tol2 && best_handle.map_or(true, |(_, _, bd)| d2 < bd) {
-                    best_handle = Some((edge, end, d2));
-                }
+}
+
+/// A pick with its ranking inputs.
+struct Hit {
+    pick: Pick,
+    z: i32,
+    kind: u8,
+    /// Paint key of the edge, text or shape; empty for the rest
+    order: Vec<usize>,
+    opacity: f32,
+    dist: f32,
+}
+
+fn edge_pickable(g: &Graph, eid: u32) -> bool {
+    g.item_hittable(GroupItem::Edge(eid))
+}
+
+fn default_z(g: &Graph) -> i32 {
+    g.layer_system.layers.first().map_or(0, |l| l.z_index)
+}
+
+/// Nodes, handles and edges within `tol`, skipping unpickable edges and
+/// nodes whose every edge is unpickable.
+fn stroke_hits(g: &Graph, x: f32, y: f32, tol: f32, out: &mut Vec<Hit>) {
+    let idx = current_pick_index(g);
+    let tol2 = tol * tol;
+    let ls = &g.layer_system;
+
+    // (z, opacity) of the pickable edges at each node near the point
+    let mut node_top: HashMap<u32, Option<(i32, f32)>> = HashMap::new();
+    for eid in dedup(query_ids(&idx.edges, idx.cell, x, y, tol)) {
+        let Some(e) = g.edges.get(eid as usize).and_then(|e| e.as_ref()) else {
+            continue;
+        };
+        let visible = edge_pickable(g, eid);
+        let (z, opacity) = (ls.edge_z_index(eid), ls.edge_opacity(eid));
+        for n in [e.a, e.b] {
+            let top = node_top.entry(n).or_insert(None);
+            if visible && top.is_none_or(|(tz, to)| (z, opacity) > (tz, to)) {
+                *top = Some((z, opacity));
+            }
+        }
+        if !visible {
+            continue;
+        }
+        if let Some((d2, t)) = edge_distance_sq(g, e, x, y) {
+            if d2 <= tol2 && item_unmasked_at(g, GroupItem::Edge(eid), x, y) {
+                let dist = d2.sqrt();
+                out.push(Hit {
+                    pick: Pick::Edge { id: eid, t, dist },
+                    z,
+                    kind: 2,
+                    order: ls.paint_key(GroupItem::Edge(eid)).unwrap_or_default(),
+                    opacity,
+                    dist,
+                });
             }
         }
     }
-    if let Some((edge, end, d2)) = best_handle {
-        return Some(crate::Pick::Handle {
-            edge,
-            end,
-            dist: d2.sqrt(),
+
+    for id in dedup(query_ids(&idx.nodes, idx.cell, x, y, tol)) {
+        let Some(n) = g.nodes.get(id as usize).and_then(|n| *n) else {
+            continue;
+        };
+        let d2 = (n.x - x).powi(2) + (n.y - y).powi(2);
+        if d2 > tol2 {
+            continue;
+        }
+        let (z, opacity) = match node_top.get(&id) {
+            Some(Some(top)) => *top,
+            Some(None) => continue,
+            None => (default_z(g), 1.0),
+        };
+        let dist = d2.sqrt();
+        out.push(Hit {
+            pick: Pick::Node { id, dist },
+            z,
+            kind: 0,
+            order: Vec::new(),
+            opacity,
+            dist,
         });
     }
-    // Edges
-    let mut best_edge: Option<(u32, f32, f32)> = None;
-    let edge_cands = dedup(query_ids(&idx.edges, idx.cell, x, y, tol));
-    for eid in edge_cands {
-        if let Some(e) = g.edges.get(eid as usize).and_then(|ee| ee.as_ref()) {
-            match e.kind {
-                EdgeKind::Line => {
-                    let a = if let Some(n) = g.nodes.get(e.a as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let b = if let Some(n) = g.nodes.get(e.b as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let (d2, t) = seg_distance_sq(x, y, a.x, a.y, b.x, b.y);
-                    if d2 <= tol2 {
-                        if best_edge.map_or(true, |(_, bd, _)| d2 < bd) {
-                            best_edge = Some((eid, d2, t));
-                        }
-                    }
-                }
-                EdgeKind::Cubic { ha, hb, .. } => {
-                    let a = if let Some(n) = g.nodes.get(e.a as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let b = if let Some(n) = g.nodes.get(e.b as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let p1x = a.x + ha.x;
-                    let p1y = a.y + ha.y;
-                    let p2x = b.x + hb.x;
-                    let p2y = b.y + hb.y;
-                    let (d2, t) = cubic_distance_sq(x, y, a.x, a.y, p1x, p1y, p2x, p2y, b.x, b.y);
-                    if d2 <= tol2 {
-                        if best_edge.map_or(true, |(_, bd, _)| d2 < bd) {
-                            best_edge = Some((eid, d2, clamp01(t)));
-                        }
-                    }
-                }
-                EdgeKind::Polyline { ref points } => {
-                    let a = if let Some(n) = g.nodes.get(e.a as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let b = if let Some(n) = g.nodes.get(e.b as usize).and_then(|n| *n) {
-                        n
-                    } else {
-                        continue;
-                    };
-                    let mut prevx = a.x;
-                    let mut prevy = a.y;
-                    let mut length = 0.0;
-                    let mut segs = Vec::new();
-                    for p in points {
-                        let x2 = p.x;
-                        let y2 = p.y;
-                        let seg_len = ((x2 - prevx).powi(2) + (y2 - prevy).powi(2)).sqrt();
-                        if seg_len > 0.0 {
-                            segs.push((prevx, prevy, x2, y2, seg_len));
-                            length += seg_len;
-                        }
-                        prevx = x2;
-                        prevy = y2;
-                    }
-                    let seg_len = ((b.x - prevx).powi(2) + (b.y - prevy).powi(2)).sqrt();
-                    if seg_len > 0.0 {
-                        segs.push((prevx, prevy, b.x, b.y, seg_len));
-                        length += seg_len;
-                    }
-                    let mut acc = 0.0;
-                    for (x1, y1, x2, y2, sl) in segs.into_iter() {
-                        let (d2, ts) = seg_distance_sq(x, y, x1, y1, x2, y2);
-                        if d2 <= tol2 {
-                            let t_along = if length > 0.0 {
-                                (acc + ts * sl) / length
-                            } else {
-                                0.0
-                            };
-                            if best_edge.map_or(true, |(_, bd, _)| d2 < bd) {
-                                best_edge = Some((eid, d2, t_along));
-                            }
-                        }
-                        acc += sl;
-                    }
-                }
-            }
+
+    for (edge, end) in dedup(query_ids(&idx.handles, idx.cell, x, y, tol)) {
+        if !edge_pickable(g, edge) {
+            continue;
+        }
+        let Some(e) = g.edges.get(edge as usize).and_then(|e| e.as_ref()) else {
+            continue;
+        };
+        let EdgeKind::Cubic { ha, hb, .. } = e.kind else {
+            continue;
+        };
+        let (Some(a), Some(b)) = (
+            g.nodes.get(e.a as usize).and_then(|n| *n),
+            g.nodes.get(e.b as usize).and_then(|n| *n),
+        ) else {
+            continue;
+        };
+        let (px, py) = if end == 0 {
+            (a.x + ha.x, a.y + ha.y)
+        } else {
+            (b.x + hb.x, b.y + hb.y)
+        };
+        let d2 = (px - x).powi(2) + (py - y).powi(2);
+        if d2 <= tol2 {
+            let dist = d2.sqrt();
+            out.push(Hit {
+                pick: Pick::Handle { edge, end, dist },
+                z: ls.edge_z_index(edge),
+                kind: 1,
+                order: Vec::new(),
+                opacity: ls.edge_opacity(edge),
+                dist,
+            });
+        }
+    }
+}
+
+/// Texts whose (rotated) box lies within `tol`.
+fn text_hits(g: &Graph, x: f32, y: f32, tol: f32, out: &mut Vec<Hit>) {
+    let idx = current_pick_index(g);
+    let ls = &g.layer_system;
+    for i in dedup(query_ids(&idx.texts, idx.cell, x, y, tol)) {
+        let Some(t) = g.get_text(i) else { continue };
+        let item = GroupItem::Text(i);
+        if !g.item_hittable(item) {
+            continue;
+        }
+        // Into the text's unrotated frame around its anchor
+        let (sin, cos) = (-t.rotation).sin_cos();
+        let (dx, dy) = (x - t.position.x, y - t.position.y);
+        let lx = t.position.x + dx * cos - dy * sin;
+        let ly = t.position.y + dx * sin + dy * cos;
+        let (minx, miny, maxx, maxy) = Graph::text_aabb(t);
+        let ox = (minx - lx).max(lx - maxx).max(0.0);
+        let oy = (miny - ly).max(ly - maxy).max(0.0);
+        let dist = (ox * ox + oy * oy).sqrt();
+        if dist <= tol && item_unmasked_at(g, item, x, y) {
+            out.push(Hit {
+                pick: Pick::Text { id: i, dist },
+                z: ls.item_z_index(item),
+                kind: 3,
+                order: ls.paint_key(item).unwrap_or_default(),
+                opacity: ls.item_opacity(item),
+                dist,
+            });
         }
     }
-    if let Some((id, d2, t)) = best_edge {
-        return Some(crate::Pick::Edge {
-            id,
-            t,
-            dist: d2.sqrt(),
+}
+
+/// Closed shapes covering the point under their fill rule.
+fn shape_hits(g: &Graph, x: f32, y: f32, out: &mut Vec<Hit>) {
+    let idx = current_pick_index(g);
+    let ls = &g.layer_system;
+    for id in dedup(query_ids(&idx.shapes, idx.cell, x, y, 0.0)) {
+        let Some(shape) = g.get_shape(id) else {
+            continue;
+        };
+        let item = GroupItem::Shape(id);
+        if !shape.closed
+            || !g.item_hittable(item)
+            || !shape.edges.iter().all(|&e| edge_pickable(g, e))
+        {
+            continue;
+        }
+        // Cheap reject on the union of the edge boxes before flattening
+        let bounds = idx.shape_cells.get(&id).and_then(|c| c.bbox);
+        if !bounds.is_some_and(|b| x >= b.0 && x <= b.2 && y >= b.1 && y <= b.3) {
+            continue;
+        }
+        let Some(fill) = shape_fill(g, shape) else {
+            continue;
+        };
+        if !point_in_rings(fill.rule, x, y, &fill.rings) || !item_unmasked_at(g, item, x, y) {
+            continue;
+        }
+        // Outlined by its edges, a shape never ranks above them
+        let order = shape
+            .edges
+            .iter()
+            .filter_map(|&e| ls.paint_key(GroupItem::Edge(e)))
+            .chain(ls.paint_key(item))
+            .min()
+            .unwrap_or_default();
+        out.push(Hit {
+            pick: Pick::Shape {
+                id: shape.id,
+                dist: 0.0,
+            },
+            z: ls.item_z_index(item),
+            kind: 4,
+            order,
+            opacity: ls.item_opacity(item),
+            dist: 0.0,
         });
     }
-    None
+}
+
+/// Hits other than regions, which need the region cache.
+fn object_hits(g: &Graph, x: f32, y: f32, tol: f32) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    stroke_hits(g, x, y, tol, &mut hits);
+    text_hits(g, x, y, tol, &mut hits);
+    shape_hits(g, x, y, &mut hits);
+    hits
+}
+
+fn region_hits(g: &mut Graph, x: f32, y: f32, hits: &mut Vec<Hit>) {
+    let regions = regions_at(g, x, y);
+    let covered = hits.iter().any(|h| matches!(h.pick, Pick::Shape { .. }));
+    let by_shapes = g.region_fill_mode == RegionFillMode::Shapes;
+    let ls = &g.layer_system;
+    for (key, edges) in regions {
+        if g.pick_skip_locked && !edges.is_empty() && edges.iter().all(|&e| ls.is_edge_locked(e)) {
+            continue;
+        }
+        if !region_unmasked_at(g, &edges, x, y) {
+            continue;
+        }
+        let filled = g.fills.get(&key).is_none_or(|f| f.filled) && (!by_shapes || covered);
+        let z = edges.iter().map(|&e| ls.edge_z_index(e)).max();
+        let opacity = edges
+            .iter()
+            .map(|&e| ls.edge_opacity(e))
+            .fold(0.0, f32::max);
+        hits.push(Hit {
+            pick: Pick::Region { key, dist: 0.0 },
+            z: if filled {
+                z.unwrap_or_else(|| default_z(g))
+            } else {
+                i32::MIN
+            },
+            kind: 5,
+            order: Vec::new(),
+            opacity,
+            dist: 0.0,
+        });
+    }
+}
+
+fn ranked(mut hits: Vec<Hit>) -> Vec<Pick> {
+    hits.retain(|h| h.opacity > 0.0);
+    // Nodes and handles above drawn objects, regions below
+    let band = |h: &Hit| match h.kind {
+        0 | 1 => 0,
+        5 => 2,
+        _ => 1,
+    };
+    hits.sort_by(|a, b| {
+        b.z.cmp(&a.z)
+            .then(band(a).cmp(&band(b)))
+            .then(b.order.cmp(&a.order))
+            .then(a.kind.cmp(&b.kind))
+            .then(b.opacity.total_cmp(&a.opacity))
+            .then(a.dist.total_cmp(&b.dist))
+    });
+    hits.into_iter().map(|h| h.pick).collect()
+}
+
+/// Every object at the point, topmost first: higher layer z-index, then
+/// nodes and handles, then edges, texts and shapes in drawing order (see
+/// [`crate::layers::LayerSystem::draw_order`]), then regions; ties go to
+/// node, handle, edge, text, shape, region, then higher opacity, then nearer.
+/// Hidden and fully transparent objects are skipped, as are points a group
+/// mask hides (see [`crate::model::GroupMask`]) and mask source content, and
+/// locked ones when `pick_skip_locked` is set (regions only when all their
+/// edges are locked); regions switched off
+/// (see `get_regions`' `filled`) rank below everything.
+pub fn pick_all_impl(g: &mut Graph, x: f32, y: f32, tol: f32) -> Vec<Pick> {
+    if !x.is_finite() || !y.is_finite() || !tol.is_finite() {
+        return Vec::new();
+    }
+    let mut hits = object_hits(g, x, y, tol);
+    region_hits(g, x, y, &mut hits);
+    ranked(hits)
+}
+
+/// Topmost node, handle, edge, text or shape, ranked as in
+/// [`pick_all_impl`]. Never looks at regions, so it needs no region cache.
+pub fn pick_impl(g: &Graph, x: f32, y: f32, tol: f32) -> Option<Pick> {
+    if !x.is_finite() || !y.is_finite() || !tol.is_finite() {
+        return None;
+    }
+    ranked(object_hits(g, x, y, tol)).into_iter().next()
+}
+
+/// First entry of [`pick_all_impl`]. Regions rank last within a z-index, so
+/// a hit on the topmost layer wins without running the region pass.
+pub fn pick_with_regions_impl(g: &mut Graph, x: f32, y: f32, tol: f32) -> Option<Pick> {
+    if !x.is_finite() || !y.is_finite() || !tol.is_finite() {
+        return None;
+    }
+    let mut hits = object_hits(g, x, y, tol);
+    let top_z = g.layer_system.layers.iter().map(|l| l.z_index).max();
+    let settled = hits
+        .iter()
+        .any(|h| h.opacity > 0.0 && top_z.is_none_or(|z| h.z >= z));
+    if !settled {
+        region_hits(g, x, y, &mut hits);
+    }
+    ranked(hits).into_iter().next()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Graph;
+    use super::build_pick_index;
+    use crate::model::FillRule;
+    use crate::{Graph, Pick};
+    use std::collections::HashMap;
+
+    fn sorted<T: Ord + Clone>(m: &HashMap<(i32, i32), Vec<T>>) -> Vec<((i32, i32), Vec<T>)> {
+        let mut v: Vec<_> = m
+            .iter()
+            .map(|(k, l)| {
+                let mut l = l.clone();
+                l.sort();
+                (*k, l)
+            })
+            .collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn incremental_index_matches_rebuild() {
+        let mut g = Graph::new();
+        let ids: Vec<u32> = (0..40)
+            .map(|i| g.add_node((i % 8) as f32 * 30.0, (i / 8) as f32 * 30.0))
+            .collect();
+        let edges: Vec<u32> = (0..39)
+            .filter_map(|i| g.add_edge(ids[i], ids[i + 1]))
+            .collect();
+        let outline = g.create_shape(&edges[..8], true).unwrap();
+        let label = g.add_text("label", 40.0, 40.0);
+        g.pick(0.0, 0.0, 1.0);
+        let built_at = g.pick_index.borrow().as_ref().unwrap().0;
+
+        for step in 0..20 {
+            g.move_node(ids[step], step as f32 * 7.0, 200.0 - step as f32);
+            if step % 5 == 0 {
+                g.set_edge_cubic(edges[step + 10], 0.0, -40.0, 90.0, 300.0);
+            }
+            if step % 4 == 0 {
+                g.set_text_position(label, step as f32 * 11.0, 60.0);
+                g.set_text_rotation(label, step as f32 * 0.3);
+            }
+            // Region passes in between clear the dirty state
+            if step % 7 == 0 {
+                g.get_regions();
+            }
+            g.pick(1.0, 1.0, 2.0);
+        }
+        g.remove_edge(edges[30]);
+        g.remove_node(ids[35]);
+        let n = g.add_node(500.0, 500.0);
+        g.add_edge(ids[0], n);
+        g.pick(1.0, 1.0, 2.0);
+        assert!(g.delete_shape(outline));
+        g.create_shape(&edges[10..20], false).unwrap();
+        g.add_text_box("box", 300.0, 10.0, 80.0, 40.0);
+        g.pick(1.0, 1.0, 2.0);
+
+        let guard = g.pick_index.borrow();
+        let (ver, idx) = guard.as_ref().unwrap();
+        assert_eq!(*ver, g.geom_version());
+        assert!(*ver > built_at);
+        let fresh = build_pick_index(&g, idx.cell);
+        assert_eq!(sorted(&idx.nodes), sorted(&fresh.nodes));
+        assert_eq!(sorted(&idx.edges), sorted(&fresh.edges));
+        assert_eq!(sorted(&idx.handles), sorted(&fresh.handles));
+        assert_eq!(sorted(&idx.shapes), sorted(&fresh.shapes));
+        assert_eq!(sorted(&idx.texts), sorted(&fresh.texts));
+    }
+
+    #[test]
+    fn skips_locked_content_when_asked() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(20.0, 0.0);
+        let e = g.add_edge(a, b).unwrap();
+        let layer = g.layer_system.layers[0].id;
+        g.set_layer_locked(layer, true);
+
+        assert!(matches!(g.pick(10.0, 0.0, 1.0), Some(Pick::Edge { id, .. }) if id == e));
+        g.set_pick_skip_locked(true);
+        assert!(g.pick(10.0, 0.0, 1.0).is_none());
+        assert!(g.pick(0.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn picks_text_boxes() {
+        let mut g = Graph::new();
+        let t = g.add_text("abc", 0.0, 0.0);
+        assert!(matches!(g.pick(5.0, 5.0, 1.0), Some(Pick::Text { id, .. }) if id == t));
+        assert!(g.pick(-3.0, 5.0, 2.0).is_none());
+        match g.pick(-3.0, 5.0, 4.0) {
+            Some(Pick::Text { dist, .. }) => assert!((dist - 3.0).abs() < 1e-4),
+            _ => panic!("expected text pick"),
+        }
+    }
+
+    #[test]
+    fn even_odd_star_centre_is_region_not_shape() {
+        let mut g = Graph::new();
+        let pts: Vec<u32> = (0..5)
+            .map(|i| {
+                let a = std::f32::consts::FRAC_PI_2 + i as f32 * std::f32::consts::TAU / 5.0;
+                g.add_node(50.0 * a.cos(), 50.0 * a.sin())
+            })
+            .collect();
+        let edges: Vec<u32> = (0..5)
+            .map(|i| g.add_edge(pts[i * 2 % 5], pts[(i * 2 + 2) % 5]).unwrap())
+            .collect();
+        let shape = g
+            .create_shape_with_fill_rule(&edges, true, FillRule::EvenOdd)
+            .unwrap();
+
+        let centre = g.pick_all(0.0, 0.0, 1.0);
+        assert!(centre.iter().all(|p| !matches!(p, Pick::Shape { .. })));
+        assert!(matches!(centre.first(), Some(Pick::Region { .. })));
+        // Regions are opt-in for the topmost pick
+        assert!(g.pick(0.0, 0.0, 1.0).is_none());
+        assert!(matches!(
+            g.pick_with_regions(0.0, 0.0, 1.0),
+            Some(Pick::Region { .. })
+        ));
+
+        // A point inside one of the star's tips is covered by the shape
+        let tip = g.pick_all(0.0, 40.0, 1.0);
+        assert!(matches!(tip.first(), Some(Pick::Shape { id, .. }) if *id == shape));
+        assert!(matches!(tip.get(1), Some(Pick::Region { .. })));
+    }
+
+    #[test]
+    fn higher_layer_wins_and_hidden_layers_are_skipped() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(20.0, 0.0);
+        g.add_edge(a, b).unwrap();
+
+        let top = g.create_layer("top".to_string());
+        g.set_layer_z_index(top, 5);
+        let c = g.add_node(0.0, -10.0);
+        let d = g.add_node(0.0, 10.0);
+        let e = g.add_edge(c, d).unwrap();
+        let root = g.layer_system.get_layer(top).unwrap().root_group;
+        assert!(g.add_edge_to_group(e, root));
+
+        let stack = g.pick_all(0.0, 0.0, 2.0);
+        assert!(matches!(stack[0], Pick::Edge { id, .. } if id == e));
+        assert!(matches!(stack[1], Pick::Node { id, .. } if id == a));
+        assert!(stack
+            .iter()
+            .any(|p| matches!(p, Pick::Edge { id, .. } if *id != e)));
+
+        g.set_layer_visibility(top, false);
+        let stack = g.pick_all(0.0, 0.0, 2.0);
+        assert!(matches!(stack[0], Pick::Node { id, .. } if id == a));
+        assert!(stack
+            .iter()
+            .all(|p| !matches!(p, Pick::Edge { id, .. } if *id == e)));
+    }
     #[test]
     fn bench_pick_grid() {
         // Build 5k edges as grid-like random lines
@@ -1,9 +1,14 @@
+use crate::algorithms::incremental::{choose_cell_size, IncrPlan};
+use crate::algorithms::parallel::par_map;
+use crate::algorithms::region_scope::EdgeScopes;
 use crate::algorithms::regions::FlattenCache;
+use crate::algorithms::sweep::{sweep_candidate_pairs, Sweep};
 use crate::geometry::flatten::flatten_cubic;
 use crate::geometry::intersect::{intersect_segments, SegIntersection};
 use crate::geometry::tolerance::{EPS_DENOM, EPS_POS, QUANT_SCALE};
 use crate::model::{EdgeKind, Vec2};
 use crate::Graph;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
 /// Maximum cells a segment can span in one dimension before we skip grid insertion.
@@ -15,15 +20,24 @@ pub struct Planarized {
     pub half_from: Vec<usize>,
     pub half_to: Vec<usize>,
     pub half_eid: Vec<u32>,
+    /// Flattened-polyline positions (segment index + local t) at each half-edge's from/to ends.
+    pub half_flat: Vec<(f32, f32)>,
 }
 
 #[derive(Clone, Copy)]
-struct Seg {
-    ax: f32,
-    ay: f32,
-    bx: f32,
-    by: f32,
+pub(crate) struct Seg {
+    pub(crate) ax: f32,
+    pub(crate) ay: f32,
+    pub(crate) bx: f32,
+    pub(crate) by: f32,
     eid: u32,
+    idx: u32, // segment index within the edge's flattened polyline
+}
+
+impl Seg {
+    pub(crate) fn ends(&self) -> [f32; 4] {
+        [self.ax, self.ay, self.bx, self.by]
+    }
 }
 
 fn seg_point(s: &Seg, t: f64) -> (f32, f32) {
@@ -32,6 +46,34 @@ fn seg_point(s: &Seg, t: f64) -> (f32, f32) {
     (x as f32, y as f32)
 }
 
+/// Append the segments of one flattened edge polyline.
+pub(crate) fn push_edge_segs(segs: &mut Vec<Seg>, eid: u32, pts: &[Vec2]) {
+    for (idx, w) in pts.windows(2).enumerate() {
+        segs.push(Seg {
+            ax: w[0].x,
+            ay: w[0].y,
+            bx: w[1].x,
+            by: w[1].y,
+            eid,
+            idx: idx as u32,
+        });
+    }
+}
+
+/// Intersect two segments after a quick bbox reject.
+fn intersect_pair(s: &Seg, o: &Seg, ep: f32, ed: f32) -> SegIntersection {
+    let (ax, ay, bx, by) = (s.ax, s.ay, s.bx, s.by);
+    let (cx, cy, dx, dy) = (o.ax, o.ay, o.bx, o.by);
+    if ax.max(bx) < cx.min(dx) - ep
+        || cx.max(dx) < ax.min(bx) - ep
+        || ay.max(by) < cy.min(dy) - ep
+        || cy.max(dy) < ay.min(by) - ep
+    {
+        return SegIntersection::None;
+    }
+    intersect_segments(ax, ay, bx, by, cx, cy, dx, dy, ep, ed)
+}
+
 fn flatten_points_for_edge(g: &Graph, cache: Option<&FlattenCache>, eid: u32) -> Option<Vec<Vec2>> {
     let edge = g.edges.get(eid as usize).and_then(|e| e.as_ref())?;
     let a = g.nodes.get(edge.a as usize).and_then(|n| *n)?;
@@ -77,7 +119,235 @@ fn flatten_points_for_edge(g: &Graph, cache: Option<&FlattenCache>, eid: u32) ->
     }
 }
 
+/// Intersection candidate search used by `planarize_graph` and incremental
+/// region updates.
+///
+/// `Grid` (the default) buckets segments into uniform cells; segments
+/// spanning more than `MAX_CELL_SPAN` cells are left out of full passes, so
+/// their crossings are missed. `Sweep` runs a Bentley–Ottmann sweep whose cost
+/// does not depend on segment length and keeps every segment. Both feed the
+/// same exact pair test, so their `Planarized` output matches unless the grid
+/// dropped a segment. `Auto` picks the sweep when the grid would drop
+/// segments or overload its cells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntersectBackend {
+    Auto,
+    #[default]
+    Grid,
+    Sweep,
+}
+
+/// Average cell insertions per segment above which `Auto` prefers the sweep.
+const AUTO_SWEEP_CELL_LOAD: u64 = 16;
+
+/// The backend `Auto` stands for on segments `[ax, ay, bx, by]`.
+pub(crate) fn auto_backend(
+    segs: impl Iterator<Item = [f32; 4]>,
+    cell: f32,
+    ep: f32,
+) -> IntersectBackend {
+    let cell_ix = |x: f32| -> i64 { (x / cell).floor() as i64 };
+    let (mut load, mut count): (u64, u64) = (0, 0);
+    for [ax, ay, bx, by] in segs {
+        let sx = cell_ix(ax.max(bx) + ep) - cell_ix(ax.min(bx) - ep);
+        let sy = cell_ix(ay.max(by) + ep) - cell_ix(ay.min(by) - ep);
+        if sx > MAX_CELL_SPAN as i64 || sy > MAX_CELL_SPAN as i64 {
+            return IntersectBackend::Sweep;
+        }
+        load += ((sx + 1) * (sy + 1)) as u64;
+        count += 1;
+    }
+    if load > AUTO_SWEEP_CELL_LOAD * count {
+        IntersectBackend::Sweep
+    } else {
+        IntersectBackend::Grid
+    }
+}
+
+/// Grid search state between calls to `step`: segments are bucketed first,
+/// then the pairs of each bucket are listed.
+pub(crate) struct GridCandidates {
+    cell: f32,
+    ep: f32,
+    buckets: HashMap<(i32, i32), Vec<usize>>,
+    lists: Vec<Vec<usize>>,
+    inserted: usize,
+    paired: usize,
+    tested: HashSet<(usize, usize)>,
+    pairs: Vec<(usize, usize)>,
+}
+
+impl GridCandidates {
+    pub(crate) fn new(cell: f32, ep: f32) -> Self {
+        GridCandidates {
+            cell,
+            ep,
+            buckets: HashMap::new(),
+            lists: Vec::new(),
+            inserted: 0,
+            paired: 0,
+            tested: HashSet::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Segments bucketed plus buckets paired so far.
+    pub(crate) fn done(&self) -> usize {
+        self.inserted + self.paired
+    }
+
+    pub(crate) fn total(&self, segs: &[Seg]) -> usize {
+        segs.len() + self.lists.len()
+    }
+
+    /// Bucket or pair up to `max_items` segments or buckets; true once every
+    /// bucket is paired.
+    pub(crate) fn step(&mut self, segs: &[Seg], max_items: usize) -> bool {
+        if self.inserted < segs.len() {
+            let end = self.inserted.saturating_add(max_items).min(segs.len());
+            for (i, s) in segs.iter().enumerate().take(end).skip(self.inserted) {
+                self.insert(i, s);
+            }
+            self.inserted = end;
+            if end == segs.len() {
+                self.lists.extend(
+                    self.buckets
+                        .drain()
+                        .map(|(_, list)| list)
+                        .filter(|l| l.len() >= 2),
+                );
+            }
+            return false;
+        }
+        let end = self.paired.saturating_add(max_items).min(self.lists.len());
+        for list in &self.lists[self.paired..end] {
+            for a in 0..list.len() {
+                let i = list[a];
+                for &j in &list[a + 1..] {
+                    let (lo, hi) = if i < j { (i, j) } else { (j, i) };
+                    if self.tested.insert((lo, hi)) {
+                        self.pairs.push((lo, hi));
+                    }
+                }
+            }
+        }
+        self.paired = end;
+        end == self.lists.len()
+    }
+
+    /// Unique `(lo, hi)` pairs in discovery order.
+    pub(crate) fn finish(self) -> Vec<(usize, usize)> {
+        self.pairs
+    }
+
+    fn insert(&mut self, i: usize, s: &Seg) {
+        let cell_ix = |x: f32| -> i32 { (x / self.cell).floor() as i32 };
+        let ep = self.ep;
+        let ix0 = cell_ix(s.ax.min(s.bx) - ep);
+        let ix1 = cell_ix(s.ax.max(s.bx) + ep);
+        let iy0 = cell_ix(s.ay.min(s.by) - ep);
+        let iy1 = cell_ix(s.ay.max(s.by) + ep);
+        // Skip grid insertion for segments spanning too many cells
+        if (ix1 - ix0) > MAX_CELL_SPAN || (iy1 - iy0) > MAX_CELL_SPAN {
+            return;
+        }
+        for ix in ix0..=ix1 {
+            for iy in iy0..=iy1 {
+                self.buckets.entry((ix, iy)).or_default().push(i);
+            }
+        }
+    }
+}
+
+/// Unique `(lo, hi)` segment pairs sharing a grid cell.
+fn grid_candidate_pairs(
+    segs: &[Seg],
+    plan_opt: Option<&IncrPlan>,
+    seg_index_map: &HashMap<(u32, usize), usize>,
+    cell: f32,
+    ep: f32,
+) -> Vec<(usize, usize)> {
+    let mut grid = GridCandidates::new(cell, ep);
+    if let Some(plan) = plan_opt {
+        for (_cell, entries) in plan.seg_cells.iter() {
+            let mut list: Vec<usize> = Vec::with_capacity(entries.len());
+            for &(eid, seg_idx) in entries {
+                if let Some(&global_idx) = seg_index_map.get(&(eid, seg_idx)) {
+                    list.push(global_idx);
+                }
+            }
+            if list.len() >= 2 {
+                grid.lists.push(list);
+            }
+        }
+        grid.inserted = segs.len();
+    }
+    while !grid.step(segs, usize::MAX) {}
+    grid.finish()
+}
+
+/// A candidate search without an incremental plan that runs in chunks, for
+/// region jobs. Pairs across region scopes are left for the caller to drop.
+pub(crate) enum CandidateSearch {
+    Grid(GridCandidates),
+    Sweep(Sweep),
+}
+
+impl CandidateSearch {
+    /// Start a search over `segs` with `backend`, `Auto` resolved against them.
+    pub(crate) fn new(g: &Graph, segs: &[Seg], backend: IntersectBackend) -> Self {
+        let ep = EPS_POS;
+        let cell = choose_cell_size(g.flatten_tol);
+        let backend = match backend {
+            IntersectBackend::Auto => auto_backend(segs.iter().map(Seg::ends), cell, ep),
+            chosen => chosen,
+        };
+        if backend == IntersectBackend::Sweep {
+            CandidateSearch::Sweep(Sweep::new(segs.iter().map(Seg::ends), ep))
+        } else {
+            CandidateSearch::Grid(GridCandidates::new(cell, ep))
+        }
+    }
+
+    /// Run up to `max_items` work items; true once the search is finished.
+    pub(crate) fn step(&mut self, segs: &[Seg], max_items: usize) -> bool {
+        match self {
+            CandidateSearch::Grid(grid) => grid.step(segs, max_items),
+            CandidateSearch::Sweep(sweep) => sweep.step(max_items),
+        }
+    }
+
+    pub(crate) fn progress(&self, segs: &[Seg]) -> (usize, usize) {
+        match self {
+            CandidateSearch::Grid(grid) => (grid.done(), grid.total(segs)),
+            CandidateSearch::Sweep(sweep) => (sweep.done(), sweep.total()),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<(usize, usize)> {
+        match self {
+            CandidateSearch::Grid(grid) => grid.finish(),
+            CandidateSearch::Sweep(sweep) => sweep.finish(),
+        }
+    }
+}
+
+/// Planarize with the graph's configured intersection backend.
 pub fn planarize_graph(g: &Graph) -> Planarized {
+    planarize_graph_with(g, g.intersect_backend)
+}
+
+/// Planarize with an explicit intersection backend.
+pub fn planarize_graph_with(g: &Graph, backend: IntersectBackend) -> Planarized {
+    planarize_scoped(g, backend, &EdgeScopes::capture(g))
+}
+
+/// Planarize keeping edges of different region scopes apart.
+pub(crate) fn planarize_scoped(
+    g: &Graph,
+    backend: IntersectBackend,
+    scopes: &EdgeScopes,
+) -> Planarized {
     #[cfg(feature = "region_prof")]
     let t_start = std::time::Instant::now();
 
@@ -91,8 +361,8 @@ pub fn planarize_graph(g: &Graph) -> Planarized {
 
     if let Some(plan) = plan_opt {
         for (eid, seg_vec) in plan.edge_segments.iter() {
-            // Skip hidden edges (from invisible layers/groups)
-            if !g.layer_system.is_edge_visible(*eid) {
+            // Skip hidden and construction edges
+            if !g.edge_in_regions(*eid) {
                 continue;
             }
             for (idx, &(ax, ay, bx, by)) in seg_vec.iter().enumerate() {
@@ -103,6 +373,7 @@ pub fn planarize_graph(g: &Graph) -> Planarized {
                     bx,
                     by,
                     eid: *eid,
+                    idx: idx as u32,
                 });
                 seg_index_map.insert((*eid, idx), global_idx);
             }
@@ -115,24 +386,12 @@ pub fn planarize_graph(g: &Graph) -> Planarized {
                 continue;
             }
             let eid_u32 = eid as u32;
-            // Skip hidden edges (from invisible layers/groups)
-            if !g.layer_system.is_edge_visible(eid_u32) {
+            // Skip hidden and construction edges
+            if !g.edge_in_regions(eid_u32) {
                 continue;
             }
             if let Some(pts) = flatten_points_for_edge(g, flatten_cache, eid_u32) {
-                for w in pts.windows(2) {
-                    let ax = w[0].x;
-                    let ay = w[0].y;
-                    let bx = w[1].x;
-                    let by = w[1].y;
-                    segs.push(Seg {
-                        ax,
-                        ay,
-                        bx,
-                        by,
-                        eid: eid_u32,
-                    });
-                }
+                push_edge_segs(&mut segs, eid_u32, &pts);
             }
         }
         drop(flatten_cache_guard);
@@ -144,162 +403,152 @@ pub fn planarize_graph(g: &Graph) -> Planarized {
     // 2) Intersections with uniform grid acceleration
     #[cfg(feature = "region_prof")]
     let t_grid = std::time::Instant::now();
-    let n = segs.len();
-    let mut splits: Vec<Vec<f64>> = vec![vec![0.0f64, 1.0f64]; n];
-    let ep = EPS_POS;
-    let ed = EPS_DENOM;
-
-    // Grid cell size heuristic based on flattening tolerance
-    let cell = (g.flatten_tol * 2.0).max(0.5);
-    let cell_ix = |x: f32| -> i32 { (x / cell).floor() as i32 };
-    let mut bucket_lists: Vec<Vec<usize>> = Vec::new();
-
-    if let Some(plan) = plan_opt {
-        for (_cell, entries) in plan.seg_cells.iter() {
-            let mut list: Vec<usize> = Vec::with_capacity(entries.len());
-            for &(eid, seg_idx) in entries {
-                if let Some(&global_idx) = seg_index_map.get(&(eid, seg_idx)) {
-                    list.push(global_idx);
-                }
-            }
-            if list.len() >= 2 {
-                bucket_lists.push(list);
-            }
-        }
-    } else {
-        let mut buckets: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
-        for (i, s) in segs.iter().enumerate() {
-            let minx = s.ax.min(s.bx);
-            let maxx = s.ax.max(s.bx);
-            let miny = s.ay.min(s.by);
-            let maxy = s.ay.max(s.by);
-            let ix0 = cell_ix(minx - ep);
-            let ix1 = cell_ix(maxx + ep);
-            let iy0 = cell_ix(miny - ep);
-            let iy1 = cell_ix(maxy + ep);
-            // Skip grid insertion for segments spanning too many cells
-            if (ix1 - ix0) > MAX_CELL_SPAN || (iy1 - iy0) > MAX_CELL_SPAN {
-                continue;
-            }
-            for ix in ix0..=ix1 {
-                for iy in iy0..=iy1 {
-                    buckets.entry((ix, iy)).or_default().push(i);
-                }
-            }
-        }
-        bucket_lists.extend(buckets.into_values());
-    }
+    let pairs = candidate_pairs(g, &segs, plan_opt, &seg_index_map, backend, scopes);
 
     #[cfg(feature = "region_prof")]
     let elapsed_grid = t_grid.elapsed().as_secs_f64() * 1000.0;
 
     #[cfg(feature = "region_prof")]
     let t_intersections = std::time::Instant::now();
+    let mut splits: Vec<Vec<f64>> = vec![vec![0.0f64, 1.0f64]; segs.len()];
+    intersect_pairs(&segs, &pairs, &mut splits);
 
-    let mut tested: HashSet<(usize, usize)> = HashSet::new();
-    for list in bucket_lists.into_iter() {
-        if list.len() < 2 {
-            continue;
-        }
-        for a in 0..list.len() {
-            let i = list[a];
-            for b in (a + 1)..list.len() {
-                let j = list[b];
-                let (lo, hi) = if i < j { (i, j) } else { (j, i) };
-                if !tested.insert((lo, hi)) {
-                    continue;
-                }
+    #[cfg(feature = "region_prof")]
+    let elapsed_intersections = t_intersections.elapsed().as_secs_f64() * 1000.0;
 
-                // Quick bbox reject per pair
-                let (ax, ay, bx, by) = (segs[i].ax, segs[i].ay, segs[i].bx, segs[i].by);
-                let (cx, cy, dx, dy) = (segs[j].ax, segs[j].ay, segs[j].bx, segs[j].by);
-                let minx1 = ax.min(bx);
-                let maxx1 = ax.max(bx);
-                let miny1 = ay.min(by);
-                let maxy1 = ay.max(by);
-                let minx2 = cx.min(dx);
-                let maxx2 = cx.max(dx);
-                let miny2 = cy.min(dy);
-                let maxy2 = cy.max(dy);
-                if maxx1 < minx2 - ep
-                    || maxx2 < minx1 - ep
-                    || maxy1 < miny2 - ep
-                    || maxy2 < miny1 - ep
-                {
-                    continue;
-                }
+    #[cfg(feature = "region_prof")]
+    let t_faces = std::time::Instant::now();
 
-                match intersect_segments(ax, ay, bx, by, cx, cy, dx, dy, ep, ed) {
-                    SegIntersection::None => {}
-                    SegIntersection::Proper { t, u, .. } => {
-                        if t > (ep as f64) && t < 1.0 - (ep as f64) {
-                            splits[i].push(t);
-                        }
-                        if u > (ep as f64) && u < 1.0 - (ep as f64) {
-                            splits[j].push(u);
-                        }
-                    }
-                    SegIntersection::Touch { t, u, .. } => {
-                        if t > (ep as f64) && t < 1.0 - (ep as f64) {
-                            splits[i].push(t);
-                        }
-                        if u > (ep as f64) && u < 1.0 - (ep as f64) {
-                            splits[j].push(u);
-                        }
-                    }
-                    SegIntersection::CollinearOverlap { t0, t1, u0, u1 } => {
-                        for &t in &[t0, t1] {
-                            if t > (ep as f64) && t < 1.0 - (ep as f64) {
-                                splits[i].push(t);
-                            }
-                        }
-                        for &u in &[u0, u1] {
-                            if u > (ep as f64) && u < 1.0 - (ep as f64) {
-                                splits[j].push(u);
-                            }
-                        }
-                    }
+    // 3) Quantization and vertex creation
+    let mut builder = HalfEdgeBuilder::scoped(scopes.clone());
+    for (s, ts) in segs.iter().zip(&splits) {
+        builder.add_seg(s, ts);
+    }
+    let out = builder.finish();
+
+    #[cfg(feature = "region_prof")]
+    let elapsed_faces = t_faces.elapsed().as_secs_f64() * 1000.0;
+    #[cfg(feature = "region_prof")]
+    let total_ms = t_start.elapsed().as_secs_f64() * 1000.0;
+    #[cfg(feature = "region_prof")]
+    eprintln!(
+        "planarize full flatten_ms={:.3} grid_ms={:.3} pairs_ms={:.3} faces_ms={:.3} total_ms={:.3}",
+        elapsed_flatten,
+        elapsed_grid,
+        elapsed_intersections,
+        elapsed_faces,
+        total_ms
+    );
+
+    out
+}
+
+/// Candidate pairs from the chosen backend, with `Auto` resolved against `segs`.
+/// Pairs across region scopes are dropped.
+pub(crate) fn candidate_pairs(
+    g: &Graph,
+    segs: &[Seg],
+    plan_opt: Option<&IncrPlan>,
+    seg_index_map: &HashMap<(u32, usize), usize>,
+    backend: IntersectBackend,
+    scopes: &EdgeScopes,
+) -> Vec<(usize, usize)> {
+    let ep = EPS_POS;
+    // Same cells as the incremental plan, so passes without one (region jobs,
+    // snapshots) keep the same long segments the plan does
+    let cell = plan_opt.map_or_else(|| choose_cell_size(g.flatten_tol), |p| p.cell);
+    let backend = match backend {
+        IntersectBackend::Auto => auto_backend(segs.iter().map(Seg::ends), cell, ep),
+        chosen => chosen,
+    };
+    let mut pairs = if backend == IntersectBackend::Sweep {
+        sweep_candidate_pairs(segs.iter().map(Seg::ends), ep)
+    } else {
+        grid_candidate_pairs(segs, plan_opt, seg_index_map, cell, ep)
+    };
+    if scopes.is_scoped() {
+        pairs.retain(|&pair| same_scope(segs, scopes, pair));
+    }
+    pairs
+}
+
+/// Whether both segments of a candidate pair belong to the same region scope.
+pub(crate) fn same_scope(segs: &[Seg], scopes: &EdgeScopes, (i, j): (usize, usize)) -> bool {
+    scopes.of(segs[i].eid) == scopes.of(segs[j].eid)
+}
+
+/// Test candidate pairs and record interior split parameters on both segments.
+///
+/// Pairs are tested in parallel, then applied in order so split lists match
+/// the serial path.
+pub(crate) fn intersect_pairs(segs: &[Seg], pairs: &[(usize, usize)], splits: &mut [Vec<f64>]) {
+    let ep = EPS_POS;
+    let ed = EPS_DENOM;
+    let hits = par_map(pairs, |&(i, j)| intersect_pair(&segs[i], &segs[j], ep, ed));
+    let inner = |t: f64| t > (ep as f64) && t < 1.0 - (ep as f64);
+    for (&(i, j), hit) in pairs.iter().zip(hits) {
+        match hit {
+            SegIntersection::None => {}
+            SegIntersection::Proper { t, u, .. } | SegIntersection::Touch { t, u, .. } => {
+                if inner(t) {
+                    splits[i].push(t);
                 }
+                if inner(u) {
+                    splits[j].push(u);
+                }
+            }
+            SegIntersection::CollinearOverlap { t0, t1, u0, u1 } => {
+                splits[i].extend([t0, t1].into_iter().filter(|&t| inner(t)));
+                splits[j].extend([u0, u1].into_iter().filter(|&u| inner(u)));
             }
         }
     }
+}
 
-    #[cfg(feature = "region_prof")]
-    let elapsed_intersections = t_intersections.elapsed().as_secs_f64() * 1000.0;
+/// Quantized vertices and half-edge pairs, fed one split segment at a time.
+/// Vertices are per region scope, so faces never join edges across scopes.
+#[derive(Default)]
+pub(crate) struct HalfEdgeBuilder {
+    scopes: EdgeScopes,
+    key_to_vid: HashMap<(i32, i32, u32), usize>,
+    verts: Vec<(f32, f32)>,
+    accum: HashMap<usize, (f64, f64, u32)>,
+    half_from: Vec<usize>,
+    half_to: Vec<usize>,
+    half_eid: Vec<u32>,
+    half_flat: Vec<(f32, f32)>,
+}
 
-    #[cfg(feature = "region_prof")]
-    let t_faces = std::time::Instant::now();
+impl HalfEdgeBuilder {
+    pub(crate) fn scoped(scopes: EdgeScopes) -> Self {
+        HalfEdgeBuilder {
+            scopes,
+            ..Default::default()
+        }
+    }
 
-    // 3) Quantization and vertex creation
-    let scale = QUANT_SCALE;
-    let mut key_to_vid: HashMap<(i32, i32), usize> = HashMap::new();
-    let mut verts: Vec<(f32, f32)> = Vec::new();
-    let mut accum: HashMap<usize, (f64, f64, u32)> = HashMap::new();
-
-    let mut half_from: Vec<usize> = Vec::new();
-    let mut half_to: Vec<usize> = Vec::new();
-    let mut half_eid: Vec<u32> = Vec::new();
-
-    let mut get_vid = |x: f32, y: f32| -> usize {
-        let kx = (x * scale).round() as i32;
-        let ky = (y * scale).round() as i32;
-        if let Some(&vid) = key_to_vid.get(&(kx, ky)) {
+    fn vid(&mut self, x: f32, y: f32, scope: u32) -> usize {
+        let kx = (x * QUANT_SCALE).round() as i32;
+        let ky = (y * QUANT_SCALE).round() as i32;
+        if let Some(&vid) = self.key_to_vid.get(&(kx, ky, scope)) {
             // accumulate new sample
-            let entry = accum.entry(vid).or_insert((0.0, 0.0, 0));
+            let entry = self.accum.entry(vid).or_insert((0.0, 0.0, 0));
             entry.0 += x as f64;
             entry.1 += y as f64;
             entry.2 += 1;
             return vid;
         }
-        let vid = verts.len();
-        key_to_vid.insert((kx, ky), vid);
-        verts.push((x, y));
-        accum.insert(vid, (x as f64, y as f64, 1));
+        let vid = self.verts.len();
+        self.key_to_vid.insert((kx, ky, scope), vid);
+        self.verts.push((x, y));
+        self.accum.insert(vid, (x as f64, y as f64, 1));
         vid
-    };
+    }
 
-    for (idx, s) in segs.iter().enumerate() {
-        let mut ts = splits[idx].clone();
+    /// Cut `s` at its split parameters and add a half-edge pair per piece.
+    pub(crate) fn add_seg(&mut self, s: &Seg, splits: &[f64]) {
+        let scope = self.scopes.of(s.eid);
+        let mut ts = splits.to_vec();
         ts.sort_by(|a, b| a.partial_cmp(b).unwrap());
         ts.dedup_by(|a, b| (*a - *b).abs() < 1e-12);
         for w in ts.windows(2) {
@@ -315,46 +564,37 @@ pub fn planarize_graph(g: &Graph) -> Planarized {
             if dx * dx + dy * dy <= EPS_POS * EPS_POS {
                 continue;
             }
-            let u = get_vid(x0, y0);
-            let v = get_vid(x1, y1);
+            let u = self.vid(x0, y0, scope);
+            let v = self.vid(x1, y1, scope);
             if u == v {
                 continue;
             }
-            half_from.push(u);
-            half_to.push(v);
-            half_eid.push(s.eid);
-            half_from.push(v);
-            half_to.push(u);
-            half_eid.push(s.eid);
+            let (f0, f1) = (s.idx as f32 + t0 as f32, s.idx as f32 + t1 as f32);
+            self.half_from.push(u);
+            self.half_to.push(v);
+            self.half_eid.push(s.eid);
+            self.half_flat.push((f0, f1));
+            self.half_from.push(v);
+            self.half_to.push(u);
+            self.half_eid.push(s.eid);
+            self.half_flat.push((f1, f0));
         }
     }
 
-    // Average vertex positions per quantization key
-    for (vid, (sx, sy, cnt)) in accum.into_iter() {
-        if cnt > 0 {
-            verts[vid] = ((sx / (cnt as f64)) as f32, (sy / (cnt as f64)) as f32);
+    /// Average vertex positions per quantization key and hand out the plan.
+    pub(crate) fn finish(mut self) -> Planarized {
+        for (vid, (sx, sy, cnt)) in self.accum.into_iter() {
+            if cnt > 0 {
+                self.verts[vid] = ((sx / (cnt as f64)) as f32, (sy / (cnt as f64)) as f32);
+            }
+        }
+        Planarized {
+            verts: self.verts,
+            half_from: self.half_from,
+            half_to: self.half_to,
+            half_eid: self.half_eid,
+            half_flat: self.half_flat,
         }
-    }
-
-    #[cfg(feature = "region_prof")]
-    let elapsed_faces = t_faces.elapsed().as_secs_f64() * 1000.0;
-    #[cfg(feature = "region_prof")]
-    let total_ms = t_start.elapsed().as_secs_f64() * 1000.0;
-    #[cfg(feature = "region_prof")]
-    eprintln!(
-        "planarize full flatten_ms={:.3} grid_ms={:.3} pairs_ms={:.3} faces_ms={:.3} total_ms={:.3}",
-        elapsed_flatten,
-        elapsed_grid,
-        elapsed_intersections,
-        elapsed_faces,
-        total_ms
-    );
-
-    Planarized {
-        verts,
-        half_from,
-        half_to,
-        half_eid,
     }
 }
 
@@ -450,4 +690,95 @@ mod tests {
             assert!(rev >= 1, "missing reverse half-edge for {}->{}", u, v);
         }
     }
+
+    fn assert_same_plan(a: &Planarized, b: &Planarized) {
+        assert_eq!(a.verts, b.verts);
+        assert_eq!(a.half_from, b.half_from);
+        assert_eq!(a.half_to, b.half_to);
+        assert_eq!(a.half_eid, b.half_eid);
+        assert_eq!(a.half_flat, b.half_flat);
+    }
+
+    #[test]
+    fn sweep_matches_grid() {
+        let mut g = Graph::new();
+        // Short random segments (kept within the grid's cell span)
+        let mut seed = 0x0BAD_5EEDu64;
+        let mut rng = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) as u32) as f32 / (1u64 << 31) as f32
+        };
+        for _ in 0..200 {
+            let (x, y) = (100.0 * rng(), 100.0 * rng());
+            let a = g.add_node(x, y);
+            let b = g.add_node(x + 40.0 * rng() - 20.0, y + 40.0 * rng() - 20.0);
+            g.add_edge(a, b);
+        }
+        // Verticals, collinear overlaps, T-junctions and curves
+        for i in 0..6 {
+            let x = 10.0 + i as f32 * 15.0;
+            let a = g.add_node(x, 5.0);
+            let b = g.add_node(x, 60.0);
+            g.add_edge(a, b);
+        }
+        let a = g.add_node(0.0, 30.0);
+        let b = g.add_node(50.0, 30.0);
+        let c = g.add_node(25.0, 30.0);
+        let d = g.add_node(90.0, 30.0);
+        g.add_edge(a, b);
+        g.add_edge(c, d);
+        let t = g.add_node(40.0, 10.0);
+        g.add_edge(t, b);
+        g.add_ellipse(50.0, 50.0, 30.0, 20.0);
+        let grid = planarize_graph_with(&g, IntersectBackend::Grid);
+        let sweep = planarize_graph_with(&g, IntersectBackend::Sweep);
+        assert_same_plan(&grid, &sweep);
+    }
+
+    #[test]
+    fn sweep_swaps_dense_and_concurrent_crossings() {
+        let mut g = Graph::new();
+        // Long chords crossing each other many times
+        let mut seed = 0x5EED_0034u64;
+        let mut rng = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) as u32) as f32 / (1u64 << 31) as f32
+        };
+        for _ in 0..60 {
+            let a = g.add_node(0.0, 100.0 * rng());
+            let b = g.add_node(100.0, 100.0 * rng());
+            g.add_edge(a, b);
+        }
+        // A fan of lines all through one point
+        for k in 0..8 {
+            let dy = k as f32 * 6.0 - 21.0;
+            let a = g.add_node(20.0, 50.0 - dy);
+            let b = g.add_node(80.0, 50.0 + dy);
+            g.add_edge(a, b);
+        }
+        let grid = planarize_graph_with(&g, IntersectBackend::Grid);
+        let sweep = planarize_graph_with(&g, IntersectBackend::Sweep);
+        assert_same_plan(&grid, &sweep);
+    }
+
+    #[test]
+    fn sweep_keeps_long_segments_the_grid_drops() {
+        // A giant frame around a small detailed square: frame edges span far
+        // more than MAX_CELL_SPAN cells
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 10.0, 10.0, 0.0);
+        g.add_rectangle(-1500.0, -1500.0, 3000.0, 3000.0, 0.0);
+        let a = g.add_node(-1490.0, -1480.0);
+        let b = g.add_node(1490.0, 1495.0);
+        g.add_edge(a, b);
+        let grid = planarize_graph_with(&g, IntersectBackend::Grid);
+        let sweep = planarize_graph_with(&g, IntersectBackend::Sweep);
+        // The diagonal crosses two sides of the small square
+        assert!(sweep.half_from.len() > grid.half_from.len());
+        assert_eq!(sweep.half_from.len(), 2 * (4 + 2 + 4 + 3));
+        assert_same_plan(&sweep, &planarize_graph_with(&g, IntersectBackend::Auto));
+        // The grid stays the default
+        assert_eq!(g.get_intersect_backend(), IntersectBackend::Grid);
+        assert_same_plan(&grid, &planarize_graph(&g));
+    }
 }
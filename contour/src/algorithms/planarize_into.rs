@@ -0,0 +1,810 @@
+//! Permanent planarization: bake edge crossings into the graph itself.
+//!
+//! `planarize_graph` only splits flattened segments internally for region
+//! finding. `Graph::planarize_into_graph` instead rewrites the graph:
+//! 1. Every crossing edge is split at the (curve-refined) intersection
+//! 2. Pieces meeting at a crossing share a single node
+//! 3. Pieces left duplicated by collinear overlaps are collapsed to one edge
+
+use crate::geometry::cubic::{flat_position_to_cubic_t, CubicBezier};
+use crate::geometry::flatten::flatten_cubic;
+use crate::geometry::intersect::{intersect_segments, SegIntersection};
+use crate::geometry::tolerance::{EPS_DENOM, EPS_POS, QUANT_SCALE};
+use crate::model::{Edge, EdgeKind, GroupItem, HandleMode, Node, TextType, Vec2};
+use crate::Graph;
+use std::collections::{HashMap, HashSet};
+
+/// Distance under which intersection points and nodes are merged (0.1 px).
+const SNAP_DIST: f32 = 1.0 / QUANT_SCALE;
+
+/// Newton iterations used to refine intersections involving curves.
+const REFINE_ITERS: usize = 8;
+
+/// Parameters at which two pieces are sampled to detect duplicates.
+const DUP_SAMPLES: [f32; 3] = [0.25, 0.5, 0.75];
+
+/// Result of baking intersections into the graph
+#[derive(Clone, Debug, Default)]
+pub struct PlanarizeResult {
+    /// IDs of newly created nodes (intersection points)
+    pub nodes: Vec<u32>,
+    /// IDs of newly created edges (split pieces)
+    pub edges: Vec<u32>,
+    /// IDs of removed edges (split originals and overlapping duplicates)
+    pub removed_edges: Vec<u32>,
+}
+
+/// Exact geometry of an edge over its own parameter domain.
+#[derive(Clone, Debug)]
+enum EdgeGeom {
+    Line(Vec2, Vec2),
+    Cubic(CubicBezier),
+    /// All vertices including endpoints; the domain is `[0, len - 1]`.
+    Polyline(Vec<Vec2>),
+}
+
+impl EdgeGeom {
+    fn from_parts(a: Vec2, b: Vec2, kind: &EdgeKind) -> Self {
+        match kind {
+            EdgeKind::Line => EdgeGeom::Line(a, b),
+            EdgeKind::Cubic { ha, hb, .. } => EdgeGeom::Cubic(CubicBezier::new(
+                a,
+                Vec2 {
+                    x: a.x + ha.x,
+                    y: a.y + ha.y,
+                },
+                Vec2 {
+                    x: b.x + hb.x,
+                    y: b.y + hb.y,
+                },
+                b,
+            )),
+            EdgeKind::Polyline { points } => {
+                let mut pts = Vec::with_capacity(points.len() + 2);
+                pts.push(a);
+                pts.extend(points.iter().copied());
+                pts.push(b);
+                EdgeGeom::Polyline(pts)
+            }
+        }
+    }
+
+    fn max_param(&self) -> f32 {
+        match self {
+            EdgeGeom::Polyline(pts) => (pts.len().max(2) - 1) as f32,
+            _ => 1.0,
+        }
+    }
+
+    fn is_cubic(&self) -> bool {
+        matches!(self, EdgeGeom::Cubic(_))
+    }
+
+    fn polyline_span(pts: &[Vec2], p: f32) -> (usize, f32) {
+        let last = pts.len().saturating_sub(2);
+        let p = p.max(0.0);
+        let k = (p.floor() as usize).min(last);
+        (k, p - k as f32)
+    }
+
+    fn eval(&self, p: f32) -> Vec2 {
+        match self {
+            EdgeGeom::Line(a, b) => lerp(*a, *b, p),
+            EdgeGeom::Cubic(c) => c.eval(p),
+            EdgeGeom::Polyline(pts) => {
+                let (k, local) = Self::polyline_span(pts, p);
+                lerp(pts[k], pts[k + 1], local)
+            }
+        }
+    }
+
+    fn deriv(&self, p: f32) -> Vec2 {
+        match self {
+            EdgeGeom::Line(a, b) => Vec2 {
+                x: b.x - a.x,
+                y: b.y - a.y,
+            },
+            EdgeGeom::Cubic(c) => c.tangent(p),
+            EdgeGeom::Polyline(pts) => {
+                let (k, _) = Self::polyline_span(pts, p);
+                Vec2 {
+                    x: pts[k + 1].x - pts[k].x,
+                    y: pts[k + 1].y - pts[k].y,
+                }
+            }
+        }
+    }
+
+    /// Flattened points used for the segment-level intersection pass.
+    fn flatten(&self, tol: f32) -> Vec<Vec2> {
+        match self {
+            EdgeGeom::Line(a, b) => vec![*a, *b],
+            EdgeGeom::Cubic(c) => {
+                let mut pts = vec![c.p0];
+                flatten_cubic(
+                    &mut pts, c.p0.x, c.p0.y, c.p1.x, c.p1.y, c.p2.x, c.p2.y, c.p3.x, c.p3.y, tol,
+                    0,
+                );
+                pts
+            }
+            EdgeGeom::Polyline(pts) => pts.clone(),
+        }
+    }
+
+    /// Map a position on flattened segment `seg` to this edge's parameter.
+    fn param_from_flat(&self, flat: &[Vec2], seg: usize, local_t: f32, tol: f32) -> f32 {
+        match self {
+            EdgeGeom::Line(..) => local_t,
+            EdgeGeom::Polyline(_) => seg as f32 + local_t,
+            EdgeGeom::Cubic(c) => {
+                let segs: Vec<(Vec2, Vec2)> = flat.windows(2).map(|w| (w[0], w[1])).collect();
+                if local_t <= 0.0 && seg == 0 {
+                    return 0.0;
+                }
+                if local_t >= 1.0 && seg + 1 == segs.len() {
+                    return 1.0;
+                }
+                flat_position_to_cubic_t(c, &segs, seg, local_t, tol)
+            }
+        }
+    }
+
+    /// Piece of this edge between two parameters, as a kind relative to the
+    /// piece endpoints `a` and `b`.
+    fn piece_kind(&self, p0: f32, p1: f32, a: Vec2, b: Vec2) -> EdgeKind {
+        match self {
+            EdgeGeom::Line(..) => EdgeKind::Line,
+            EdgeGeom::Cubic(c) => {
+                let sub = c.subcurve(p0, p1);
+                EdgeKind::Cubic {
+                    ha: Vec2 {
+                        x: sub.p1.x - a.x,
+                        y: sub.p1.y - a.y,
+                    },
+                    hb: Vec2 {
+                        x: sub.p2.x - b.x,
+                        y: sub.p2.y - b.y,
+                    },
+                    mode: HandleMode::Free,
+                }
+            }
+            EdgeGeom::Polyline(pts) => {
+                let points: Vec<Vec2> = pts
+                    .iter()
+                    .enumerate()
+                    .filter(|(k, _)| {
+                        let k = *k as f32;
+                        k > p0 + 1e-4 && k < p1 - 1e-4
+                    })
+                    .map(|(_, p)| *p)
+                    .collect();
+                if points.is_empty() {
+                    EdgeKind::Line
+                } else {
+                    EdgeKind::Polyline { points }
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn lerp(a: Vec2, b: Vec2, t: f32) -> Vec2 {
+    Vec2 {
+        x: a.x + (b.x - a.x) * t,
+        y: a.y + (b.y - a.y) * t,
+    }
+}
+
+#[inline]
+fn dist2(a: Vec2, b: Vec2) -> f32 {
+    let dx = a.x - b.x;
+    let dy = a.y - b.y;
+    dx * dx + dy * dy
+}
+
+/// Parameter of the projection of `p` onto segment `a`-`b`.
+fn project_param(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    let len2 = dx * dx + dy * dy;
+    if len2 <= EPS_DENOM {
+        return 0.0;
+    }
+    (((p.x - a.x) * dx + (p.y - a.y) * dy) / len2).clamp(0.0, 1.0)
+}
+
+/// Refine an approximate crossing of `a` at `s` and `b` at `t` with Newton
+/// steps on `A(s) - B(t) = 0`. Returns the best parameters and the midpoint.
+fn refine_crossing(a: &EdgeGeom, s: f32, b: &EdgeGeom, t: f32) -> (f32, f32, Vec2) {
+    let (mut s, mut t) = (s, t);
+    let mut best = (s, t, dist2(a.eval(s), b.eval(t)));
+    for _ in 0..REFINE_ITERS {
+        if best.2 <= EPS_POS * EPS_POS * 1e-2 {
+            break;
+        }
+        let pa = a.eval(s);
+        let pb = b.eval(t);
+        let fx = pa.x - pb.x;
+        let fy = pa.y - pb.y;
+        let da = a.deriv(s);
+        let db = b.deriv(t);
+        // Solve [da, -db] * [ds, dt] = -F
+        let det = db.x * da.y - da.x * db.y;
+        if det.abs() <= EPS_DENOM {
+            break;
+        }
+        let ds = (fx * db.y - db.x * fy) / det;
+        let dt = (fx * da.y - da.x * fy) / det;
+        s = (s + ds).clamp(0.0, a.max_param());
+        t = (t + dt).clamp(0.0, b.max_param());
+        let d2 = dist2(a.eval(s), b.eval(t));
+        if d2 < best.2 {
+            best = (s, t, d2);
+        }
+    }
+    let pa = a.eval(best.0);
+    let pb = b.eval(best.1);
+    (best.0, best.1, lerp(pa, pb, 0.5))
+}
+
+/// Vertices of the planarized network: existing nodes plus new crossings,
+/// merged within `SNAP_DIST` through a uniform grid.
+#[derive(Default)]
+struct VertexTable {
+    pos: Vec<Vec2>,
+    node: Vec<Option<u32>>,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl VertexTable {
+    fn cell(p: Vec2) -> (i32, i32) {
+        (
+            (p.x / SNAP_DIST).floor() as i32,
+            (p.y / SNAP_DIST).floor() as i32,
+        )
+    }
+
+    fn find(&self, p: Vec2) -> Option<usize> {
+        let (cx, cy) = Self::cell(p);
+        let mut best: Option<(usize, f32)> = None;
+        for ix in (cx - 1)..=(cx + 1) {
+            for iy in (cy - 1)..=(cy + 1) {
+                if let Some(list) = self.grid.get(&(ix, iy)) {
+                    for &v in list {
+                        let d2 = dist2(self.pos[v], p);
+                        if d2 <= SNAP_DIST * SNAP_DIST && best.is_none_or(|(_, bd)| d2 < bd) {
+                            best = Some((v, d2));
+                        }
+                    }
+                }
+            }
+        }
+        best.map(|(v, _)| v)
+    }
+
+    fn insert(&mut self, p: Vec2, node: Option<u32>) -> usize {
+        let v = self.pos.len();
+        self.pos.push(p);
+        self.node.push(node);
+        self.grid.entry(Self::cell(p)).or_default().push(v);
+        v
+    }
+
+    fn resolve(&mut self, p: Vec2) -> usize {
+        match self.find(p) {
+            Some(v) => v,
+            None => self.insert(p, None),
+        }
+    }
+}
+
+/// A flattened segment of a target edge.
+struct FlatSeg {
+    chain: usize,
+    idx: usize,
+    a: Vec2,
+    b: Vec2,
+    minx: f32,
+    maxx: f32,
+    miny: f32,
+    maxy: f32,
+}
+
+/// Samples of a piece along its path, oriented from its lower vertex.
+type DupSig = [Vec2; 3];
+
+/// (target index, piece index) of a planned piece.
+type PieceRef = (usize, usize);
+
+/// Planned piece of a split edge, before dedupe and creation.
+struct Piece {
+    va: usize,
+    vb: usize,
+    kind: EdgeKind,
+}
+
+/// Sample points of an edge oriented from its lower to its higher vertex, used
+/// to detect pieces that trace the same path.
+fn dup_signature(geom: &EdgeGeom, reversed: bool) -> DupSig {
+    let max = geom.max_param();
+    DUP_SAMPLES.map(|s| {
+        let s = if reversed { 1.0 - s } else { s };
+        geom.eval(s * max)
+    })
+}
+
+/// Whether two duplicate signatures trace the same path.
+fn same_path(a: &DupSig, b: &DupSig) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .all(|(p, q)| dist2(*p, *q) <= SNAP_DIST * SNAP_DIST)
+}
+
+impl Graph {
+    /// Bake intersections of `edge_ids` (or every edge when `None`) into the graph.
+    ///
+    /// Crossing edges are split at the exact intersection and their pieces share a
+    /// new node; T-junctions reuse the touching endpoint and coincident endpoint
+    /// nodes are merged. Collinear overlaps keep a single edge. Shapes and on-path
+    /// texts referencing a split edge are rewritten to reference its pieces.
+    pub fn planarize_into_graph(&mut self, edge_ids: Option<&[u32]>) -> PlanarizeResult {
+        let targets: Vec<u32> = match edge_ids {
+            Some(ids) => ids.to_vec(),
+            None => (0..self.edges.len() as u32).collect(),
+        };
+        self.bake_edges(targets, None)
+    }
+
+    /// Split edges only at the given `(edge, parameter)` corners. Crossings
+    /// among the edges are kept where they land on a corner, so edges meeting
+    /// there share one node; corners no crossing reaches (an edge outside the
+    /// list touching there) are cut at the parameter's point.
+    pub(crate) fn split_edges_at_corners(&mut self, corners: &[(u32, f32)]) -> PlanarizeResult {
+        let targets: Vec<u32> = corners.iter().map(|&(eid, _)| eid).collect();
+        self.bake_edges(targets, Some(corners))
+    }
+
+    fn bake_edges(
+        &mut self,
+        mut targets: Vec<u32>,
+        corners: Option<&[(u32, f32)]>,
+    ) -> PlanarizeResult {
+        let mut result = PlanarizeResult::default();
+        targets.sort_unstable();
+        targets.dedup();
+        let mut edges: Vec<(u32, Edge, EdgeGeom)> = Vec::with_capacity(targets.len());
+        for eid in targets {
+            let Some(e) = self.edges.get(eid as usize).and_then(|e| e.clone()) else {
+                continue;
+            };
+            // Locked edges are neither split nor rewired
+            if self.is_edge_locked(eid) {
+                continue;
+            }
+            let (Some(a), Some(b)) = (
+                self.nodes.get(e.a as usize).and_then(|n| *n),
+                self.nodes.get(e.b as usize).and_then(|n| *n),
+            ) else {
+                continue;
+            };
+            let geom =
+                EdgeGeom::from_parts(Vec2 { x: a.x, y: a.y }, Vec2 { x: b.x, y: b.y }, &e.kind);
+            edges.push((eid, e, geom));
+        }
+        if edges.is_empty() {
+            return result;
+        }
+
+        // 1) Seed vertices with existing endpoint nodes, aliasing coincident ones
+        let mut table = VertexTable::default();
+        let mut node_vertex: HashMap<u32, usize> = HashMap::new();
+        let mut merged_nodes: HashSet<u32> = HashSet::new();
+        let mut endpoint_ids: Vec<u32> = edges.iter().flat_map(|(_, e, _)| [e.a, e.b]).collect();
+        endpoint_ids.sort_unstable();
+        endpoint_ids.dedup();
+        for nid in endpoint_ids {
+            let Some(n) = self.nodes.get(nid as usize).and_then(|n| *n) else {
+                continue;
+            };
+            let p = Vec2 { x: n.x, y: n.y };
+            let v = match table.find(p) {
+                Some(v) => {
+                    merged_nodes.insert(nid);
+                    v
+                }
+                None => table.insert(p, Some(nid)),
+            };
+            node_vertex.insert(nid, v);
+        }
+
+        // 2) Flatten and intersect segments of different edges (sweep over x)
+        let tol = self.flatten_tol;
+        let flats: Vec<Vec<Vec2>> = edges.iter().map(|(_, _, g)| g.flatten(tol)).collect();
+        let mut segs: Vec<FlatSeg> = Vec::new();
+        for (chain, pts) in flats.iter().enumerate() {
+            for (idx, w) in pts.windows(2).enumerate() {
+                segs.push(FlatSeg {
+                    chain,
+                    idx,
+                    a: w[0],
+                    b: w[1],
+                    minx: w[0].x.min(w[1].x),
+                    maxx: w[0].x.max(w[1].x),
+                    miny: w[0].y.min(w[1].y),
+                    maxy: w[0].y.max(w[1].y),
+                });
+            }
+        }
+        segs.sort_by(|a, b| a.minx.total_cmp(&b.minx));
+
+        let mut cuts: Vec<Vec<(f32, usize)>> = vec![Vec::new(); edges.len()];
+        let ep = EPS_POS;
+        for i in 0..segs.len() {
+            for j in (i + 1)..segs.len() {
+                let (si, sj) = (&segs[i], &segs[j]);
+                if sj.minx > si.maxx + ep {
+                    break;
+                }
+                if si.chain == sj.chain || sj.miny > si.maxy + ep || si.miny > sj.maxy + ep {
+                    continue;
+                }
+                let (gi, gj) = (&edges[si.chain].2, &edges[sj.chain].2);
+                let hit = intersect_segments(
+                    si.a.x, si.a.y, si.b.x, si.b.y, sj.a.x, sj.a.y, sj.b.x, sj.b.y, ep, EPS_DENOM,
+                );
+                let mut pairs: Vec<(f32, f32, Vec2)> = Vec::new();
+                match hit {
+                    SegIntersection::None => {}
+                    SegIntersection::Proper { t, u, x, y }
+                    | SegIntersection::Touch { t, u, x, y } => {
+                        let s = gi.param_from_flat(&flats[si.chain], si.idx, t as f32, tol);
+                        let r = gj.param_from_flat(&flats[sj.chain], sj.idx, u as f32, tol);
+                        if gi.is_cubic() || gj.is_cubic() {
+                            pairs.push(refine_crossing(gi, s, gj, r));
+                        } else {
+                            pairs.push((
+                                s,
+                                r,
+                                Vec2 {
+                                    x: x as f32,
+                                    y: y as f32,
+                                },
+                            ));
+                        }
+                    }
+                    SegIntersection::CollinearOverlap { t0, t1, .. } => {
+                        for t in [t0 as f32, t1 as f32] {
+                            let p = lerp(si.a, si.b, t);
+                            let u = project_param(sj.a, sj.b, p);
+                            let s = gi.param_from_flat(&flats[si.chain], si.idx, t, tol);
+                            let r = gj.param_from_flat(&flats[sj.chain], sj.idx, u, tol);
+                            pairs.push((s, r, p));
+                        }
+                    }
+                }
+                for (s, r, p) in pairs {
+                    let v = table.resolve(p);
+                    cuts[si.chain].push((s, v));
+                    cuts[sj.chain].push((r, v));
+                }
+            }
+        }
+
+        // Only cut at corners: crossings within the flattening tolerance of
+        // one, then the corners left over
+        if let Some(corners) = corners {
+            let near = (tol + SNAP_DIST) * (tol + SNAP_DIST);
+            let chain_of: HashMap<u32, usize> = edges
+                .iter()
+                .enumerate()
+                .map(|(c, (eid, _, _))| (*eid, c))
+                .collect();
+            let mut points: Vec<Vec<(f32, Vec2)>> = vec![Vec::new(); edges.len()];
+            for &(eid, t) in corners {
+                let Some(&c) = chain_of.get(&eid) else {
+                    continue;
+                };
+                let (_, e, geom) = &edges[c];
+                let p = geom.eval(t.clamp(0.0, geom.max_param()));
+                let at_end = [e.a, e.b]
+                    .iter()
+                    .any(|n| dist2(table.pos[node_vertex[n]], p) <= SNAP_DIST * SNAP_DIST);
+                if !at_end {
+                    points[c].push((t, p));
+                }
+            }
+            for (list, points) in cuts.iter_mut().zip(&points) {
+                list.retain(|&(_, v)| points.iter().any(|&(_, p)| dist2(p, table.pos[v]) <= near));
+                for &(t, p) in points {
+                    if !list.iter().any(|&(_, v)| dist2(p, table.pos[v]) <= near) {
+                        list.push((t, table.resolve(p)));
+                    }
+                }
+            }
+        }
+
+        // 3) Plan pieces per edge, deduping pieces that trace the same path
+        let mut plans: Vec<Vec<Piece>> = Vec::with_capacity(edges.len());
+        let mut changed: Vec<bool> = Vec::with_capacity(edges.len());
+        for (chain, (_, e, geom)) in edges.iter().enumerate() {
+            let va = node_vertex[&e.a];
+            let vb = node_vertex[&e.b];
+            let max = geom.max_param();
+            let mut interior = std::mem::take(&mut cuts[chain]);
+            interior.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut stops: Vec<(f32, usize)> = vec![(0.0, va)];
+            for (p, v) in interior {
+                if stops.last().is_some_and(|&(_, lv)| lv == v) {
+                    continue;
+                }
+                stops.push((p.clamp(0.0, max), v));
+            }
+            if stops.last().is_some_and(|&(_, lv)| lv == vb) && stops.len() > 1 {
+                stops.pop();
+            }
+            stops.push((max, vb));
+            let aliased = table.node[va] != Some(e.a) || table.node[vb] != Some(e.b);
+            let mut pieces = Vec::new();
+            if stops.len() == 2 {
+                pieces.push(Piece {
+                    va,
+                    vb,
+                    kind: e.kind.clone(),
+                });
+            } else {
+                for w in stops.windows(2) {
+                    let ((p0, v0), (p1, v1)) = (w[0], w[1]);
+                    if v0 == v1 || p1 <= p0 {
+                        continue;
+                    }
+                    let kind = geom.piece_kind(p0, p1, table.pos[v0], table.pos[v1]);
+                    pieces.push(Piece {
+                        va: v0,
+                        vb: v1,
+                        kind,
+                    });
+                }
+            }
+            changed.push(stops.len() > 2 || aliased);
+            plans.push(pieces);
+        }
+
+        // Unchanged edges claim their path first so overlaps collapse onto them
+        let mut order: Vec<usize> = (0..edges.len()).filter(|&c| !changed[c]).collect();
+        order.extend((0..edges.len()).filter(|&c| changed[c]));
+        // Pieces tracing an earlier path map to the piece that owns it
+        let mut kept: HashMap<(usize, usize), Vec<(DupSig, PieceRef)>> = HashMap::new();
+        let mut owner_piece: HashMap<PieceRef, PieceRef> = HashMap::new();
+        for &chain in &order {
+            for (pi, piece) in plans[chain].iter().enumerate() {
+                let key = (piece.va.min(piece.vb), piece.va.max(piece.vb));
+                let geom =
+                    EdgeGeom::from_parts(table.pos[piece.va], table.pos[piece.vb], &piece.kind);
+                let sig = dup_signature(&geom, piece.va > piece.vb);
+                let list = kept.entry(key).or_default();
+                let existing = list.iter().find(|(other, _)| same_path(other, &sig));
+                match existing {
+                    Some(&(_, owner)) => {
+                        owner_piece.insert((chain, pi), owner);
+                    }
+                    None => list.push((sig, (chain, pi))),
+                }
+            }
+        }
+
+        // 4) Apply: create nodes and pieces, rewrite or remove originals
+        let mut vertex_node: Vec<Option<u32>> = table.node.clone();
+        let mut piece_ids: HashMap<(usize, usize), u32> = HashMap::new();
+        let mut replacements: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut any_change = false;
+        for &chain in &order {
+            let (eid, e, _) = &edges[chain];
+            let is_dup = |pi: usize| owner_piece.contains_key(&(chain, pi));
+            if !changed[chain] && !is_dup(0) {
+                piece_ids.insert((chain, 0), *eid);
+                continue;
+            }
+            any_change = true;
+            let mut ids = Vec::with_capacity(plans[chain].len());
+            let mut created = Vec::new();
+            for (pi, piece) in plans[chain].iter().enumerate() {
+                if let Some(owner) = owner_piece.get(&(chain, pi)) {
+                    if let Some(&id) = piece_ids.get(owner) {
+                        ids.push(id);
+                    }
+                    continue;
+                }
+                let mut node_for = |v: usize, g: &mut Graph| -> u32 {
+                    if let Some(n) = vertex_node[v] {
+                        return n;
+                    }
+                    let id = g.nodes.len() as u32;
+                    g.nodes.push(Some(Node {
+                        x: table.pos[v].x,
+                        y: table.pos[v].y,
+                    }));
+                    result.nodes.push(id);
+                    vertex_node[v] = Some(id);
+                    id
+                };
+                let a = node_for(piece.va, self);
+                let b = node_for(piece.vb, self);
+                let id = self.edges.len() as u32;
+                self.edges.push(Some(Edge {
+                    a,
+                    b,
+                    kind: piece.kind.clone(),
+                    stroke: e.stroke,
+                    stroke_width: e.stroke_width,
+                    construction: e.construction,
+                }));
+                created.push(GroupItem::Edge(id));
+                result.edges.push(id);
+                piece_ids.insert((chain, pi), id);
+                ids.push(id);
+            }
+            if let Some(slot) = self.edges.get_mut(*eid as usize) {
+                *slot = None;
+            }
+            // Pieces draw where the original did among its siblings
+            self.layer_system
+                .replace_item(GroupItem::Edge(*eid), &created);
+            result.removed_edges.push(*eid);
+            replacements.insert(*eid, ids);
+        }
+        if !any_change {
+            return result;
+        }
+
+        // Rewrite shape and on-path text references to the replacement pieces
+        let expand = |list: &[u32]| -> Vec<u32> {
+            let mut out: Vec<u32> = Vec::with_capacity(list.len());
+            for id in list {
+                match replacements.get(id) {
+                    Some(rep) => {
+                        for r in rep {
+                            if out.last() != Some(r) {
+                                out.push(*r);
+                            }
+                        }
+                    }
+                    None => out.push(*id),
+                }
+            }
+            out
+        };
+        for shape in self.shapes.iter_mut().flatten() {
+            if shape.edges.iter().any(|id| replacements.contains_key(id)) {
+                shape.edges = expand(&shape.edges);
+            }
+        }
+        for text in self.texts.iter_mut().flatten() {
+            if let TextType::OnPath { edge_ids, .. } = &mut text.text_type {
+                if edge_ids.iter().any(|id| replacements.contains_key(id)) {
+                    *edge_ids = expand(edge_ids);
+                }
+            }
+        }
+
+        // Drop merged endpoint nodes that no edge references anymore
+        if !merged_nodes.is_empty() {
+            let mut used: HashSet<u32> = HashSet::new();
+            for e in self.edges.iter().flatten() {
+                used.insert(e.a);
+                used.insert(e.b);
+            }
+            for nid in merged_nodes {
+                if !used.contains(&nid) {
+                    if let Some(slot) = self.nodes.get_mut(nid as usize) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+
+        self.mark_full_dirty();
+        self.bump();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::GroupItem;
+    use crate::Graph;
+
+    #[test]
+    fn crossing_lines_share_node() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(10.0, 10.0);
+        let c = g.add_node(0.0, 10.0);
+        let d = g.add_node(10.0, 0.0);
+        g.add_edge(a, b).unwrap();
+        g.add_edge(c, d).unwrap();
+        let r = g.planarize_into_graph(None);
+        assert_eq!(r.nodes.len(), 1);
+        assert_eq!(r.edges.len(), 4);
+        assert_eq!(r.removed_edges.len(), 2);
+        assert_eq!(g.edge_count(), 4);
+        let (x, y) = g.get_node(r.nodes[0]).unwrap();
+        assert!((x - 5.0).abs() < 1e-4 && (y - 5.0).abs() < 1e-4);
+        // Idempotent once planar
+        let r2 = g.planarize_into_graph(None);
+        assert!(r2.edges.is_empty() && r2.nodes.is_empty());
+    }
+
+    #[test]
+    fn t_junction_reuses_endpoint() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(10.0, 0.0);
+        let c = g.add_node(5.0, 0.0);
+        let d = g.add_node(5.0, 8.0);
+        g.add_edge(a, b).unwrap();
+        g.add_edge(c, d).unwrap();
+        let r = g.planarize_into_graph(None);
+        assert!(r.nodes.is_empty());
+        assert_eq!(r.edges.len(), 2);
+        assert_eq!(g.edge_count(), 3);
+    }
+
+    #[test]
+    fn collinear_overlap_is_deduped() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(30.0, 0.0);
+        let c = g.add_node(10.0, 0.0);
+        let d = g.add_node(20.0, 0.0);
+        g.add_edge(a, b).unwrap();
+        let inner = g.add_edge(c, d).unwrap();
+        let r = g.planarize_into_graph(None);
+        // Outer line becomes a-c and d-b; the overlap collapses onto c-d
+        assert_eq!(r.edges.len(), 2);
+        assert_eq!(g.edge_count(), 3);
+        assert!(g.edges[inner as usize].is_some());
+    }
+
+    #[test]
+    fn cubic_split_is_exact_and_updates_shapes() {
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(100.0, 0.0);
+        let e = g.add_edge(a, b).unwrap();
+        g.set_edge_cubic(e, 30.0, 60.0, 70.0, 60.0);
+        let c = g.add_node(50.0, -10.0);
+        let d = g.add_node(50.0, 100.0);
+        g.add_edge(c, d).unwrap();
+        let shape = g.create_shape(&[e], false).unwrap();
+        let r = g.planarize_into_graph(None);
+        assert_eq!(r.nodes.len(), 1);
+        let (x, y) = g.get_node(r.nodes[0]).unwrap();
+        // Symmetric curve peaks at t=0.5: y = 0.75 * 60
+        assert!((x - 50.0).abs() < 1e-3 && (y - 45.0).abs() < 1e-3);
+        let edges = g.get_shape_edges(shape).unwrap();
+        assert_eq!(edges.len(), 2);
+        assert!(edges.iter().all(|id| r.edges.contains(id)));
+    }
+
+    #[test]
+    fn pieces_keep_their_source_draw_position() {
+        let mut g = Graph::new();
+        let n: Vec<u32> = [(0.0, 0.0), (10.0, 10.0), (0.0, 10.0), (10.0, 0.0)]
+            .iter()
+            .map(|&(x, y)| g.add_node(x, y))
+            .collect();
+        let e0 = g.add_edge(n[0], n[1]).unwrap();
+        let e1 = g.add_edge(n[2], n[3]).unwrap();
+        let (c, d) = (g.add_node(20.0, 0.0), g.add_node(30.0, 0.0));
+        let e2 = g.add_edge(c, d).unwrap();
+        let root = g.default_group().unwrap();
+
+        let r = g.planarize_into_graph(None);
+        assert_eq!(r.removed_edges, vec![e0, e1]);
+        let mut expect: Vec<GroupItem> = r.edges.iter().map(|&e| GroupItem::Edge(e)).collect();
+        expect.push(GroupItem::Edge(e2));
+        assert_eq!(g.layer_system.get_group(root).unwrap().items, expect);
+        assert!(r.edges.iter().all(|&e| g.get_edge_group(e) == Some(root)));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::algorithms::planarize::Planarized;
+use crate::algorithms::planarize::{auto_backend, IntersectBackend, Planarized};
+use crate::algorithms::sweep::sweep_candidate_pairs;
 use crate::geometry::flatten::flatten_cubic;
 use crate::geometry::intersect::{intersect_segments, SegIntersection};
 use crate::geometry::tolerance::{EPS_DENOM, EPS_POS, QUANT_SCALE};
@@ -12,6 +13,7 @@ struct Seg {
     bx: f32,
     by: f32,
     eid: u32,
+    idx: u32, // segment index within the edge's flattened polyline
 }
 
 fn seg_point(s: &Seg, t: f64) -> (f32, f32) {
@@ -48,7 +50,7 @@ pub fn planarize_subset_with_bbox(
             // Use flattened cache if available for all kinds
             if let Some(fc) = g.flatten_cache.borrow().as_ref() {
                 if let Some(pts) = fc.per_edge.get(&eid) {
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -62,6 +64,7 @@ pub fn planarize_subset_with_bbox(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                     }
@@ -78,6 +81,7 @@ pub fn planarize_subset_with_bbox(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: 0,
                         });
                     }
                 }
@@ -112,7 +116,7 @@ pub fn planarize_subset_with_bbox(
                         g.flatten_tol,
                         0,
                     );
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -126,6 +130,7 @@ pub fn planarize_subset_with_bbox(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                     }
@@ -133,7 +138,7 @@ pub fn planarize_subset_with_bbox(
                 EdgeKind::Polyline { points } => {
                     let mut prevx = a.x;
                     let mut prevy = a.y;
-                    for p in points {
+                    for (idx, p) in points.iter().enumerate() {
                         let seg_aabb = (
                             prevx.min(p.x),
                             prevy.min(p.y),
@@ -147,6 +152,7 @@ pub fn planarize_subset_with_bbox(
                                 bx: p.x,
                                 by: p.y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                         prevx = p.x;
@@ -165,6 +171,7 @@ pub fn planarize_subset_with_bbox(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: points.len() as u32,
                         });
                     }
                 }
@@ -335,6 +342,7 @@ pub fn planarize_subset_with_bbox(
     let mut half_from: Vec<usize> = Vec::new();
     let mut half_to: Vec<usize> = Vec::new();
     let mut half_eid: Vec<u32> = Vec::new();
+    let mut half_flat: Vec<(f32, f32)> = Vec::new();
 
     let mut get_vid = |x: f32, y: f32| -> usize {
         let kx = (x * scale).round() as i32;
@@ -375,12 +383,15 @@ pub fn planarize_subset_with_bbox(
             if u == v {
                 continue;
             }
+            let (f0, f1) = (s.idx as f32 + t0 as f32, s.idx as f32 + t1 as f32);
             half_from.push(u);
             half_to.push(v);
             half_eid.push(s.eid);
+            half_flat.push((f0, f1));
             half_from.push(v);
             half_to.push(u);
             half_eid.push(s.eid);
+            half_flat.push((f1, f0));
         }
     }
 
@@ -395,6 +406,7 @@ pub fn planarize_subset_with_bbox(
         half_from,
         half_to,
         half_eid,
+        half_flat,
     }
 }
 
@@ -402,6 +414,54 @@ pub fn planarize_subset(g: &Graph, edges: &[u32]) -> Planarized {
     planarize_subset_with_bbox(g, edges, None)
 }
 
+/// Unique `(lo, hi)` pairs sharing a grid cell, None when the estimated pair
+/// count exceeds `pairs_limit` (overestimates are fine).
+fn grid_pairs(segs: &[Seg], cell: f32, ep: f32, pairs_limit: usize) -> Option<Vec<(usize, usize)>> {
+    let cell_ix = |x: f32| -> i32 { (x / cell).floor() as i32 };
+    let mut buckets: HashMap<(i32, i32), Vec<usize>> = HashMap::with_capacity(segs.len() * 2 + 16);
+    for (i, s) in segs.iter().enumerate() {
+        let minx = s.ax.min(s.bx);
+        let maxx = s.ax.max(s.bx);
+        let miny = s.ay.min(s.by);
+        let maxy = s.ay.max(s.by);
+        let ix0 = cell_ix(minx - ep);
+        let ix1 = cell_ix(maxx + ep);
+        let iy0 = cell_ix(miny - ep);
+        let iy1 = cell_ix(maxy + ep);
+        for ix in ix0..=ix1 {
+            for iy in iy0..=iy1 {
+                buckets.entry((ix, iy)).or_default().push(i);
+            }
+        }
+    }
+    let mut est_pairs: usize = 0;
+    for (_k, list) in buckets.iter() {
+        let m = list.len();
+        if m >= 2 {
+            // m choose 2
+            est_pairs = est_pairs.saturating_add(m.saturating_sub(1) * m / 2);
+            if est_pairs > pairs_limit {
+                return None;
+            }
+        }
+    }
+
+    let mut tested: HashSet<(usize, usize)> = HashSet::new();
+    let mut pairs = Vec::new();
+    for (_key, list) in buckets.into_iter() {
+        for a in 0..list.len() {
+            for b in (a + 1)..list.len() {
+                let (i, j) = (list[a], list[b]);
+                let pair = (i.min(j), i.max(j));
+                if tested.insert(pair) {
+                    pairs.push(pair);
+                }
+            }
+        }
+    }
+    Some(pairs)
+}
+
 /// Like `planarize_subset_with_bbox`, but returns None early when the estimated
 /// bucket pair count or segment count exceed provided limits. This prevents
 /// pathological O(k^2) intersection explosions during incremental updates.
@@ -428,7 +488,7 @@ pub fn planarize_subset_with_bbox_guard(
             };
             if let Some(fc) = g.flatten_cache.borrow().as_ref() {
                 if let Some(pts) = fc.per_edge.get(&eid) {
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -442,6 +502,7 @@ pub fn planarize_subset_with_bbox_guard(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                     }
@@ -458,6 +519,7 @@ pub fn planarize_subset_with_bbox_guard(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: 0,
                         });
                     }
                 }
@@ -492,7 +554,7 @@ pub fn planarize_subset_with_bbox_guard(
                         g.flatten_tol,
                         0,
                     );
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -506,6 +568,7 @@ pub fn planarize_subset_with_bbox_guard(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                     }
@@ -513,7 +576,7 @@ pub fn planarize_subset_with_bbox_guard(
                 EdgeKind::Polyline { points } => {
                     let mut prevx = a.x;
                     let mut prevy = a.y;
-                    for p in points {
+                    for (idx, p) in points.iter().enumerate() {
                         let seg_aabb = (
                             prevx.min(p.x),
                             prevy.min(p.y),
@@ -527,6 +590,7 @@ pub fn planarize_subset_with_bbox_guard(
                                 bx: p.x,
                                 by: p.y,
                                 eid,
+                                idx: idx as u32,
                             });
                         }
                         prevx = p.x;
@@ -545,6 +609,7 @@ pub fn planarize_subset_with_bbox_guard(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: points.len() as u32,
                         });
                     }
                 }
@@ -558,93 +623,60 @@ pub fn planarize_subset_with_bbox_guard(
         return None;
     }
 
-    // 2) Intersections with uniform grid and budget guard
+    // 2) Candidate pairs from the graph's backend, with a budget guard
     let n = segs.len();
     let mut splits: Vec<Vec<f64>> = vec![vec![0.0f64, 1.0f64]; n];
     let ep = EPS_POS;
     let ed = EPS_DENOM;
     let cell = (g.flatten_tol * 1.5).max(0.4);
-    let cell_ix = |x: f32| -> i32 { (x / cell).floor() as i32 };
-    let mut buckets: HashMap<(i32, i32), Vec<usize>> = HashMap::with_capacity(segs.len() * 2 + 16);
-    for (i, s) in segs.iter().enumerate() {
-        let minx = s.ax.min(s.bx);
-        let maxx = s.ax.max(s.bx);
-        let miny = s.ay.min(s.by);
-        let maxy = s.ay.max(s.by);
-        let ix0 = cell_ix(minx - ep);
-        let ix1 = cell_ix(maxx + ep);
-        let iy0 = cell_ix(miny - ep);
-        let iy1 = cell_ix(maxy + ep);
-        for ix in ix0..=ix1 {
-            for iy in iy0..=iy1 {
-                buckets.entry((ix, iy)).or_default().push(i);
-            }
-        }
-    }
-    // Estimate pair budget; bail early if too high (overestimates are fine)
-    let mut est_pairs: usize = 0;
-    for (_k, list) in buckets.iter() {
-        let m = list.len();
-        if m >= 2 {
-            // m choose 2
-            est_pairs = est_pairs.saturating_add(m.saturating_sub(1) * m / 2);
-            if est_pairs > pairs_limit {
-                return None;
-            }
+    let ends = || segs.iter().map(|s| [s.ax, s.ay, s.bx, s.by]);
+    let backend = match g.intersect_backend {
+        IntersectBackend::Auto => auto_backend(ends(), cell, ep),
+        chosen => chosen,
+    };
+    let pairs = if backend == IntersectBackend::Sweep {
+        let pairs = sweep_candidate_pairs(ends(), ep);
+        if pairs.len() > pairs_limit {
+            return None;
         }
-    }
+        pairs
+    } else {
+        grid_pairs(&segs, cell, ep, pairs_limit)?
+    };
 
-    let mut tested: HashSet<(usize, usize)> = HashSet::new();
-    for (_key, list) in buckets.into_iter() {
-        if list.len() < 2 {
+    for (i, j) in pairs {
+        let (ax, ay, bx, by) = (segs[i].ax, segs[i].ay, segs[i].bx, segs[i].by);
+        let (cx, cy, dx, dy) = (segs[j].ax, segs[j].ay, segs[j].bx, segs[j].by);
+        let minx1 = ax.min(bx);
+        let maxx1 = ax.max(bx);
+        let miny1 = ay.min(by);
+        let maxy1 = ay.max(by);
+        let minx2 = cx.min(dx);
+        let maxx2 = cx.max(dx);
+        let miny2 = cy.min(dy);
+        let maxy2 = cy.max(dy);
+        if maxx1 < minx2 - ep || maxx2 < minx1 - ep || maxy1 < miny2 - ep || maxy2 < miny1 - ep {
             continue;
         }
-        for a in 0..list.len() {
-            let i = list[a];
-            for b in (a + 1)..list.len() {
-                let j = list[b];
-                let (lo, hi) = if i < j { (i, j) } else { (j, i) };
-                if !tested.insert((lo, hi)) {
-                    continue;
+        match intersect_segments(ax, ay, bx, by, cx, cy, dx, dy, ep, ed) {
+            SegIntersection::None => {}
+            SegIntersection::Proper { t, u, .. } | SegIntersection::Touch { t, u, .. } => {
+                if t > (ep as f64) && t < 1.0 - (ep as f64) {
+                    splits[i].push(t);
                 }
-                let (ax, ay, bx, by) = (segs[i].ax, segs[i].ay, segs[i].bx, segs[i].by);
-                let (cx, cy, dx, dy) = (segs[j].ax, segs[j].ay, segs[j].bx, segs[j].by);
-                let minx1 = ax.min(bx);
-                let maxx1 = ax.max(bx);
-                let miny1 = ay.min(by);
-                let maxy1 = ay.max(by);
-                let minx2 = cx.min(dx);
-                let maxx2 = cx.max(dx);
-                let miny2 = cy.min(dy);
-                let maxy2 = cy.max(dy);
-                if maxx1 < minx2 - ep
-                    || maxx2 < minx1 - ep
-                    || maxy1 < miny2 - ep
-                    || maxy2 < miny1 - ep
-                {
-                    continue;
+                if u > (ep as f64) && u < 1.0 - (ep as f64) {
+                    splits[j].push(u);
                 }
-                match intersect_segments(ax, ay, bx, by, cx, cy, dx, dy, ep, ed) {
-                    SegIntersection::None => {}
-                    SegIntersection::Proper { t, u, .. } | SegIntersection::Touch { t, u, .. } => {
-                        if t > (ep as f64) && t < 1.0 - (ep as f64) {
-                            splits[i].push(t);
-                        }
-                        if u > (ep as f64) && u < 1.0 - (ep as f64) {
-                            splits[j].push(u);
-                        }
+            }
+            SegIntersection::CollinearOverlap { t0, t1, u0, u1 } => {
+                for &t in &[t0, t1] {
+                    if t > (ep as f64) && t < 1.0 - (ep as f64) {
+                        splits[i].push(t);
                     }
-                    SegIntersection::CollinearOverlap { t0, t1, u0, u1 } => {
-                        for &t in &[t0, t1] {
-                            if t > (ep as f64) && t < 1.0 - (ep as f64) {
-                                splits[i].push(t);
-                            }
-                        }
-                        for &u in &[u0, u1] {
-                            if u > (ep as f64) && u < 1.0 - (ep as f64) {
-                                splits[j].push(u);
-                            }
-                        }
+                }
+                for &u in &[u0, u1] {
+                    if u > (ep as f64) && u < 1.0 - (ep as f64) {
+                        splits[j].push(u);
                     }
                 }
             }
@@ -659,6 +691,7 @@ pub fn planarize_subset_with_bbox_guard(
     let mut half_from: Vec<usize> = Vec::new();
     let mut half_to: Vec<usize> = Vec::new();
     let mut half_eid: Vec<u32> = Vec::new();
+    let mut half_flat: Vec<(f32, f32)> = Vec::new();
     let mut get_vid = |x: f32, y: f32| -> usize {
         let kx = (x * scale).round() as i32;
         let ky = (y * scale).round() as i32;
@@ -697,12 +730,15 @@ pub fn planarize_subset_with_bbox_guard(
             if u == v {
                 continue;
             }
+            let (f0, f1) = (s.idx as f32 + t0 as f32, s.idx as f32 + t1 as f32);
             half_from.push(u);
             half_to.push(v);
             half_eid.push(s.eid);
+            half_flat.push((f0, f1));
             half_from.push(v);
             half_to.push(u);
             half_eid.push(s.eid);
+            half_flat.push((f1, f0));
         }
     }
     for (vid, (sx, sy, cnt)) in accum.into_iter() {
@@ -715,6 +751,7 @@ pub fn planarize_subset_with_bbox_guard(
         half_from,
         half_to,
         half_eid,
+        half_flat,
     })
 }
 
@@ -748,7 +785,7 @@ pub fn planarize_subset_pruned(
             // Try flattened cache
             if let Some(fc) = g.flatten_cache.borrow().as_ref() {
                 if let Some(pts) = fc.per_edge.get(&eid) {
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -762,6 +799,7 @@ pub fn planarize_subset_pruned(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                             is_primary.push(prim);
                         }
@@ -779,6 +817,7 @@ pub fn planarize_subset_pruned(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: 0,
                         });
                         is_primary.push(prim);
                     }
@@ -814,7 +853,7 @@ pub fn planarize_subset_pruned(
                         g.flatten_tol,
                         0,
                     );
-                    for w in pts.windows(2) {
+                    for (idx, w) in pts.windows(2).enumerate() {
                         let seg_aabb = (
                             w[0].x.min(w[1].x),
                             w[0].y.min(w[1].y),
@@ -828,6 +867,7 @@ pub fn planarize_subset_pruned(
                                 bx: w[1].x,
                                 by: w[1].y,
                                 eid,
+                                idx: idx as u32,
                             });
                             is_primary.push(prim);
                         }
@@ -836,7 +876,7 @@ pub fn planarize_subset_pruned(
                 EdgeKind::Polyline { points } => {
                     let mut prevx = a.x;
                     let mut prevy = a.y;
-                    for p in points {
+                    for (idx, p) in points.iter().enumerate() {
                         let seg_aabb = (
                             prevx.min(p.x),
                             prevy.min(p.y),
@@ -850,6 +890,7 @@ pub fn planarize_subset_pruned(
                                 bx: p.x,
                                 by: p.y,
                                 eid,
+                                idx: idx as u32,
                             });
                             is_primary.push(prim);
                         }
@@ -869,6 +910,7 @@ pub fn planarize_subset_pruned(
                             bx: b.x,
                             by: b.y,
                             eid,
+                            idx: points.len() as u32,
                         });
                         is_primary.push(prim);
                     }
@@ -968,6 +1010,7 @@ pub fn planarize_subset_pruned(
     let mut half_from: Vec<usize> = Vec::new();
     let mut half_to: Vec<usize> = Vec::new();
     let mut half_eid: Vec<u32> = Vec::new();
+    let mut half_flat: Vec<(f32, f32)> = Vec::new();
     let mut get_vid = |x: f32, y: f32| -> usize {
         let kx = (x * scale).round() as i32;
         let ky = (y * scale).round() as i32;
@@ -1007,12 +1050,15 @@ pub fn planarize_subset_pruned(
             if u == v {
                 continue;
             }
+            let (f0, f1) = (s.idx as f32 + t0 as f32, s.idx as f32 + t1 as f32);
             half_from.push(u);
             half_to.push(v);
             half_eid.push(s.eid);
+            half_flat.push((f0, f1));
             half_from.push(v);
             half_to.push(u);
             half_eid.push(s.eid);
+            half_flat.push((f1, f0));
         }
     }
     for (vid, (sx, sy, cnt)) in accum.into_iter() {
@@ -1025,5 +1071,6 @@ pub fn planarize_subset_pruned(
         half_from,
         half_to,
         half_eid,
+        half_flat,
     }
 }
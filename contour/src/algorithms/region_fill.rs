@@ -0,0 +1,180 @@
+//! Shape-driven region fills: which closed shapes cover each face.
+//!
+//! Every face of the planar map lies entirely inside or outside each shape
+//! (the shape's edges are part of the map), so one interior probe per face is
+//! enough. The probe's winding number against the shape's rings, read through
+//! the shape's `FillRule`, decides membership.
+
+use crate::algorithms::regions::{edge_polyline, face_interior_point, polygon_bbox, Region};
+use crate::algorithms::winding::point_in_rings;
+use crate::model::{FillRule, GroupItem, Shape, Vec2};
+use crate::Graph;
+
+/// A closed shape flattened into oriented rings.
+pub(crate) struct ShapeFill {
+    pub id: u32,
+    pub rule: FillRule,
+    pub rings: Vec<Vec<Vec2>>,
+    pub bbox: (f32, f32, f32, f32),
+}
+
+/// Flatten a shape's edge list into rings, following each chain head to tail
+/// and reversing edges that are stored against the walk direction.
+fn shape_rings(g: &Graph, edges: &[u32]) -> Option<Vec<Vec<Vec2>>> {
+    let ends: Vec<(u32, u32)> = edges
+        .iter()
+        .map(|&eid| {
+            g.edges
+                .get(eid as usize)
+                .and_then(|e| e.as_ref())
+                .map(|e| (e.a, e.b))
+        })
+        .collect::<Option<_>>()?;
+    let mut rings: Vec<Vec<Vec2>> = Vec::new();
+    let mut ring: Vec<Vec2> = Vec::new();
+    let mut at: Option<u32> = None;
+    for (i, (&eid, &(a, b))) in edges.iter().zip(&ends).enumerate() {
+        let forward = match at {
+            Some(n) if n == a => true,
+            Some(n) if n == b => false,
+            _ => {
+                // Start of a new chain: orient toward the next edge
+                if ring.len() >= 3 {
+                    rings.push(std::mem::take(&mut ring));
+                }
+                ring.clear();
+                !ends
+                    .get(i + 1)
+                    .is_some_and(|&(na, nb)| (na == a || nb == a) && na != b && nb != b)
+            }
+        };
+        let mut pts = edge_polyline(g, eid)?;
+        if !forward {
+            pts.reverse();
+        }
+        pts.pop();
+        ring.extend(pts);
+        at = Some(if forward { b } else { a });
+    }
+    if ring.len() >= 3 {
+        rings.push(ring);
+    }
+    Some(rings)
+}
+
+/// Visible closed shapes whose edges are all visible and not construction,
+/// ready for coverage tests.
+pub(crate) fn closed_shape_fills(g: &Graph) -> Vec<ShapeFill> {
+    g.shapes
+        .iter()
+        .flatten()
+        .filter_map(|shape| shape_fill(g, shape))
+        .collect()
+}
+
+/// One shape's rings, if it is closed, visible, and all its edges feed
+/// regions.
+pub(crate) fn shape_fill(g: &Graph, shape: &Shape) -> Option<ShapeFill> {
+    if !shape.closed || shape.edges.is_empty() {
+        return None;
+    }
+    if !g.layer_system.is_item_visible(GroupItem::Shape(shape.id)) {
+        return None;
+    }
+    if !shape.edges.iter().all(|&e| g.edge_in_regions(e)) {
+        return None;
+    }
+    shape_outline(g, shape)
+}
+
+/// One closed shape's rings whatever its visibility, as used for clipping.
+pub(crate) fn shape_outline(g: &Graph, shape: &Shape) -> Option<ShapeFill> {
+    if !shape.closed || shape.edges.is_empty() {
+        return None;
+    }
+    let rings = shape_rings(g, &shape.edges)?;
+    if rings.is_empty() {
+        return None;
+    }
+    let all: Vec<Vec2> = rings.iter().flatten().copied().collect();
+    Some(ShapeFill {
+        id: shape.id,
+        rule: shape.fill_rule,
+        bbox: polygon_bbox(&all),
+        rings,
+    })
+}
+
+/// Ids of the shapes whose fill covers the face, ascending.
+pub(crate) fn face_owners(fills: &[ShapeFill], region: &Region) -> Vec<u32> {
+    let p = face_interior_point(&region.points, &region.holes);
+    let mut owners: Vec<u32> = fills
+        .iter()
+        .filter(|s| p.x >= s.bbox.0 && p.x <= s.bbox.2 && p.y >= s.bbox.1 && p.y <= s.bbox.3)
+        .filter(|s| point_in_rings(s.rule, p.x, p.y, &s.rings))
+        .map(|s| s.id)
+        .collect();
+    owners.sort_unstable();
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{FillRule, RegionFillMode};
+    use crate::Graph;
+
+    /// Five-point star drawn as one closed self-crossing loop.
+    fn star(g: &mut Graph) -> u32 {
+        let pts: Vec<(f32, f32)> = (0..5)
+            .map(|i| {
+                let a = std::f32::consts::FRAC_PI_2 + i as f32 * 4.0 * std::f32::consts::PI / 5.0;
+                (100.0 * a.cos(), 100.0 * a.sin())
+            })
+            .collect();
+        let ids: Vec<u32> = pts.iter().map(|&(x, y)| g.add_node(x, y)).collect();
+        let edges: Vec<u32> = (0..5)
+            .map(|i| g.add_edge(ids[i], ids[(i + 1) % 5]).unwrap())
+            .collect();
+        g.create_shape(&edges, true).unwrap()
+    }
+
+    fn filled_at(g: &mut Graph, x: f32, y: f32) -> (bool, Vec<u32>) {
+        let key = g.region_at(x, y).unwrap();
+        let r = g
+            .get_regions()
+            .into_iter()
+            .find(|r| r["key"].as_u64() == Some(key as u64))
+            .unwrap();
+        let owners = r["shapes"]
+            .as_array()
+            .map(|a| a.iter().map(|v| v.as_u64().unwrap() as u32).collect())
+            .unwrap_or_default();
+        (r["filled"].as_bool().unwrap(), owners)
+    }
+
+    #[test]
+    fn even_odd_star_leaves_center_empty() {
+        let mut g = Graph::new();
+        let sid = star(&mut g);
+        g.set_shape_fill_rule(sid, FillRule::EvenOdd);
+        // Faces mode fills every face and reports no owners
+        assert_eq!(filled_at(&mut g, 0.0, 80.0), (true, vec![]));
+        assert!(g.get_regions().iter().all(|r| r.get("shapes").is_none()));
+        g.set_region_fill_mode(RegionFillMode::Shapes);
+        assert_eq!(filled_at(&mut g, 0.0, 0.0), (false, vec![]));
+        assert_eq!(filled_at(&mut g, 0.0, 80.0), (true, vec![sid]));
+        g.set_shape_fill_rule(sid, FillRule::NonZero);
+        assert_eq!(filled_at(&mut g, 0.0, 0.0), (true, vec![sid]));
+    }
+
+    #[test]
+    fn overlapping_shapes_share_faces() {
+        let mut g = Graph::new();
+        let a = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0).shape;
+        let b = g.add_rectangle(50.0, 50.0, 100.0, 100.0, 0.0).shape;
+        g.set_region_fill_mode(RegionFillMode::Shapes);
+        assert_eq!(filled_at(&mut g, 75.0, 75.0), (true, vec![a, b]));
+        assert_eq!(filled_at(&mut g, 25.0, 25.0), (true, vec![a]));
+        assert_eq!(filled_at(&mut g, 125.0, 125.0), (true, vec![b]));
+    }
+}
@@ -0,0 +1,571 @@
+//! Budgeted, resumable region computation.
+//!
+//! A job rebuilds the faces of the graph as of the geometry version it was
+//! started at, in the same stages as a full rebuild: flatten visible edges,
+//! find and test intersection candidates, cut segments into half-edges, walk
+//! faces and nest holes. Each `step` works through fixed-size chunks until its
+//! time budget runs out, so a large document never blocks for much longer
+//! than the budget. Any edit bumps `geom_ver`; the next step (or status query)
+//! sees the mismatch and discards the job.
+//!
+//! Faces are readable while the job runs and are reported as unstable until
+//! hole nesting has finished. A finished job replaces the region cache, so
+//! `get_regions` and the region queries pick its faces up without another
+//! rebuild.
+
+use serde::Serialize;
+
+use crate::algorithms::planarize::{
+    intersect_pairs, push_edge_segs, same_scope, CandidateSearch, HalfEdgeBuilder, Planarized, Seg,
+};
+use crate::algorithms::region_scope::EdgeScopes;
+use crate::algorithms::regions::{
+    flatten_points_for_edge, install_region_faces, region_to_cache_face, scope_cycles, FaceWalk,
+    HoleNesting, Region, SimpleCycles,
+};
+use crate::model::Vec2;
+use crate::Graph;
+
+/// Work items (edges, segments, grid cells, sweep events, pairs, start
+/// half-edges or rings) between clock checks.
+const STEP_CHUNK: usize = 512;
+
+/// Stage a region job is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionJobPhase {
+    /// Flattening visible edges into segments
+    Flatten,
+    /// Finding and testing intersection candidates
+    Intersect,
+    /// Cutting segments into half-edges at their intersections
+    Split,
+    /// Walking faces (or tracing simple cycles when the walk finds none)
+    Walk,
+    /// Attaching hole rings to their enclosing faces
+    Nest,
+    Done,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionJobState {
+    /// No job is running
+    Idle,
+    Running,
+    /// Faces are final for `geom_ver`
+    Done,
+    /// The graph changed since the job began; the job was discarded
+    Stale,
+}
+
+/// Progress report from `step_regions_job`; `done`/`total` count work items
+/// in the current phase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct RegionJobStatus {
+    pub state: RegionJobState,
+    pub phase: RegionJobPhase,
+    pub done: usize,
+    pub total: usize,
+    pub geom_ver: u64,
+}
+
+enum Stage {
+    Flatten,
+    Candidates,
+    Intersect,
+    Split,
+    Walk,
+    Nest,
+    Cycles,
+    Done,
+}
+
+pub(crate) struct RegionJob {
+    geom_ver: u64,
+    scopes: EdgeScopes,
+    stage: Stage,
+    next: usize,
+    segs: Vec<Seg>,
+    search: Option<CandidateSearch>,
+    pairs: Vec<(usize, usize)>,
+    splits: Vec<Vec<f64>>,
+    builder: HalfEdgeBuilder,
+    plan: Option<Planarized>,
+    walk: Option<FaceWalk>,
+    nesting: Option<HoleNesting>,
+    cycles: Option<SimpleCycles>,
+    /// Nesting the simple-cycle fallback rather than walked faces
+    fallback: bool,
+    regions: Vec<Region>,
+}
+
+impl RegionJob {
+    fn new(geom_ver: u64, scopes: EdgeScopes) -> Self {
+        RegionJob {
+            geom_ver,
+            builder: HalfEdgeBuilder::scoped(scopes.clone()),
+            scopes,
+            stage: Stage::Flatten,
+            next: 0,
+            segs: Vec::new(),
+            search: None,
+            pairs: Vec::new(),
+            splits: Vec::new(),
+            plan: None,
+            walk: None,
+            nesting: None,
+            cycles: None,
+            fallback: false,
+            regions: Vec::new(),
+        }
+    }
+
+    fn status(&self, g: &Graph) -> RegionJobStatus {
+        let (state, phase, done, total) = match self.stage {
+            Stage::Flatten => (
+                RegionJobState::Running,
+                RegionJobPhase::Flatten,
+                self.next,
+                g.edges.len(),
+            ),
+            Stage::Candidates => {
+                let (done, total) = self
+                    .search
+                    .as_ref()
+                    .map_or((0, 0), |s| s.progress(&self.segs));
+                (
+                    RegionJobState::Running,
+                    RegionJobPhase::Intersect,
+                    done,
+                    total,
+                )
+            }
+            Stage::Intersect => (
+                RegionJobState::Running,
+                RegionJobPhase::Intersect,
+                self.next,
+                self.pairs.len(),
+            ),
+            Stage::Split => (
+                RegionJobState::Running,
+                RegionJobPhase::Split,
+                self.next,
+                self.segs.len(),
+            ),
+            Stage::Walk => {
+                let (done, total) = self.walk.as_ref().map_or((0, 0), |w| (w.done(), w.total()));
+                (RegionJobState::Running, RegionJobPhase::Walk, done, total)
+            }
+            Stage::Nest => {
+                let (done, total) = self
+                    .nesting
+                    .as_ref()
+                    .map_or((0, 0), |n| (n.done(), n.total()));
+                (RegionJobState::Running, RegionJobPhase::Nest, done, total)
+            }
+            Stage::Cycles => {
+                let (done, total) = self
+                    .cycles
+                    .as_ref()
+                    .map_or((0, 0), |c| (c.done(), c.total()));
+                (RegionJobState::Running, RegionJobPhase::Walk, done, total)
+            }
+            Stage::Done => (
+                RegionJobState::Done,
+                RegionJobPhase::Done,
+                self.regions.len(),
+                self.regions.len(),
+            ),
+        };
+        RegionJobStatus {
+            state,
+            phase,
+            done,
+            total,
+            geom_ver: self.geom_ver,
+        }
+    }
+
+    /// Run one chunk of the current stage; true once the job is done.
+    fn advance(&mut self, g: &Graph) -> bool {
+        match self.stage {
+            Stage::Flatten => {
+                let end = (self.next + STEP_CHUNK).min(g.edges.len());
+                for eid in self.next as u32..end as u32 {
+                    // Skip hidden and construction edges
+                    if !g.edge_in_regions(eid) {
+                        continue;
+                    }
+                    if let Some(pts) = flatten_points_for_edge(g, eid) {
+                        push_edge_segs(&mut self.segs, eid, &pts);
+                    }
+                }
+                self.next = end;
+                if end == g.edges.len() {
+                    self.search = Some(CandidateSearch::new(g, &self.segs, g.intersect_backend));
+                    self.stage = Stage::Candidates;
+                }
+            }
+            Stage::Candidates => {
+                let Some(search) = self.search.as_mut() else {
+                    self.stage = Stage::Done;
+                    return true;
+                };
+                if search.step(&self.segs, STEP_CHUNK) {
+                    self.pairs = self
+                        .search
+                        .take()
+                        .map(CandidateSearch::finish)
+                        .unwrap_or_default();
+                    self.splits = vec![vec![0.0f64, 1.0f64]; self.segs.len()];
+                    self.next = 0;
+                    self.stage = Stage::Intersect;
+                }
+            }
+            Stage::Intersect => {
+                let end = (self.next + STEP_CHUNK).min(self.pairs.len());
+                let chunk = &self.pairs[self.next..end];
+                if self.scopes.is_scoped() {
+                    // Pairs across region scopes are dropped
+                    let same: Vec<(usize, usize)> = chunk
+                        .iter()
+                        .copied()
+                        .filter(|&pair| same_scope(&self.segs, &self.scopes, pair))
+                        .collect();
+                    intersect_pairs(&self.segs, &same, &mut self.splits);
+                } else {
+                    intersect_pairs(&self.segs, chunk, &mut self.splits);
+                }
+                self.next = end;
+                if end == self.pairs.len() {
+                    self.next = 0;
+                    self.stage = Stage::Split;
+                }
+            }
+            Stage::Split => {
+                let end = (self.next + STEP_CHUNK).min(self.segs.len());
+                for i in self.next..end {
+                    self.builder.add_seg(&self.segs[i], &self.splits[i]);
+                }
+                self.next = end;
+                if end == self.segs.len() {
+                    let plan = std::mem::take(&mut self.builder).finish();
+                    self.walk = Some(FaceWalk::new(&plan, self.scopes.clone()));
+                    self.plan = Some(plan);
+                    self.stage = Stage::Walk;
+                }
+            }
+            Stage::Walk => {
+                let (Some(walk), Some(plan)) = (self.walk.as_mut(), self.plan.as_ref()) else {
+                    self.stage = Stage::Done;
+                    return true;
+                };
+                if walk.walk(g, plan, STEP_CHUNK) {
+                    let faces = std::mem::take(&mut walk.faces);
+                    self.nesting = Some(HoleNesting::faces(faces));
+                    self.segs = Vec::new();
+                    self.pairs = Vec::new();
+                    self.splits = Vec::new();
+                    self.plan = None;
+                    self.walk = None;
+                    self.stage = Stage::Nest;
+                }
+            }
+            Stage::Nest => {
+                let Some(nesting) = self.nesting.as_mut() else {
+                    self.stage = Stage::Done;
+                    return true;
+                };
+                if nesting.step(STEP_CHUNK) {
+                    let regions = self
+                        .nesting
+                        .take()
+                        .map(HoleNesting::finish)
+                        .unwrap_or_default();
+                    if regions.is_empty() && !self.fallback {
+                        self.cycles = Some(SimpleCycles::new(g));
+                        self.stage = Stage::Cycles;
+                    } else {
+                        self.regions = regions;
+                        self.stage = Stage::Done;
+                    }
+                }
+            }
+            Stage::Cycles => {
+                let Some(cycles) = self.cycles.as_mut() else {
+                    self.stage = Stage::Done;
+                    return true;
+                };
+                if cycles.step(g, STEP_CHUNK) {
+                    let found = self
+                        .cycles
+                        .take()
+                        .map(SimpleCycles::finish)
+                        .unwrap_or_default();
+                    self.nesting = Some(HoleNesting::cycles(scope_cycles(found, &self.scopes)));
+                    self.fallback = true;
+                    self.stage = Stage::Nest;
+                }
+            }
+            Stage::Done => {}
+        }
+        matches!(self.stage, Stage::Done)
+    }
+
+    /// Faces found so far: the final regions once done, otherwise the faces
+    /// walked or being nested (holes not yet attached).
+    fn faces(&self) -> (&[Region], bool) {
+        match (&self.stage, &self.walk, &self.nesting) {
+            (Stage::Done, _, _) => (&self.regions, true),
+            (_, Some(walk), _) => (&walk.faces, false),
+            (_, _, Some(nesting)) => (nesting.pending_faces(), false),
+            _ => (&[], false),
+        }
+    }
+}
+
+fn idle_status(g: &Graph) -> RegionJobStatus {
+    RegionJobStatus {
+        state: RegionJobState::Idle,
+        phase: RegionJobPhase::Flatten,
+        done: 0,
+        total: 0,
+        geom_ver: g.geom_version(),
+    }
+}
+
+/// Drop the job if the graph changed since it began; returns the stale status.
+fn discard_stale(g: &mut Graph) -> Option<RegionJobStatus> {
+    let job = g.region_job.as_ref()?;
+    if job.geom_ver == g.geom_version() {
+        return None;
+    }
+    let mut status = job.status(g);
+    status.state = RegionJobState::Stale;
+    g.region_job = None;
+    Some(status)
+}
+
+pub(crate) fn begin_job(g: &mut Graph) -> u64 {
+    g.region_job = Some(RegionJob::new(g.geom_version(), EdgeScopes::capture(g)));
+    g.geom_version()
+}
+
+pub(crate) fn job_status(g: &mut Graph) -> RegionJobStatus {
+    if let Some(stale) = discard_stale(g) {
+        return stale;
+    }
+    match g.region_job.as_ref() {
+        Some(job) => job.status(g),
+        None => idle_status(g),
+    }
+}
+
+/// Advance the job until `budget_ms` (measured with `now_ms`) is spent; at
+/// least one chunk runs per call.
+pub(crate) fn step_job(
+    g: &mut Graph,
+    budget_ms: f64,
+    now_ms: &mut dyn FnMut() -> f64,
+) -> RegionJobStatus {
+    if let Some(stale) = discard_stale(g) {
+        return stale;
+    }
+    let Some(mut job) = g.region_job.take() else {
+        return idle_status(g);
+    };
+    let start = now_ms();
+    let mut finished = matches!(job.stage, Stage::Done);
+    while !finished {
+        finished = job.advance(g);
+        if now_ms() - start >= budget_ms {
+            break;
+        }
+    }
+    if finished {
+        publish(g, &job.regions);
+    }
+    let status = job.status(g);
+    g.region_job = Some(job);
+    status
+}
+
+/// Hand finished faces to the region cache. The flatten caches are brought up
+/// to date first (incrementally) because publishing clears the dirty flags
+/// they follow; after a full invalidation that would mean a full rebuild, so
+/// the faces stay with the job and `get_regions` rebuilds on its own.
+fn publish(g: &mut Graph, regions: &[Region]) {
+    let fresh = g
+        .region_cache
+        .borrow()
+        .as_ref()
+        .is_some_and(|c| c.built_ver == g.geom_version());
+    if fresh || g.dirty.full {
+        return;
+    }
+    install_region_faces(g, regions.iter().map(region_to_cache_face).collect());
+}
+
+pub(crate) fn cancel_job(g: &mut Graph) -> bool {
+    g.region_job.take().is_some()
+}
+
+/// Faces of the current job as `{ key, area, points, holes, stable }`.
+pub(crate) fn job_regions(g: &mut Graph) -> Vec<serde_json::Value> {
+    #[derive(Serialize)]
+    struct JobRegionSer {
+        key: u32,
+        area: f32,
+        points: Vec<f32>,
+        holes: Vec<Vec<f32>>,
+        stable: bool,
+    }
+    if discard_stale(g).is_some() {
+        return Vec::new();
+    }
+    let Some(job) = g.region_job.as_ref() else {
+        return Vec::new();
+    };
+    let flat = |ring: &[Vec2]| -> Vec<f32> { ring.iter().flat_map(|p| [p.x, p.y]).collect() };
+    let (faces, stable) = job.faces();
+    faces
+        .iter()
+        .map(|r| {
+            serde_json::to_value(JobRegionSer {
+                key: r.key,
+                area: r.area,
+                points: flat(&r.points),
+                holes: r.holes.iter().map(|h| flat(h)).collect(),
+                stable,
+            })
+            .unwrap()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::planarize::IntersectBackend;
+    use crate::test_util::{sorted_keys, square};
+
+    fn lattice(g: &mut Graph, n: usize) {
+        for i in 0..=n {
+            let t = i as f32 * 10.0;
+            let a = g.add_node(t, -5.0);
+            let b = g.add_node(t, n as f32 * 10.0 + 5.0);
+            g.add_edge(a, b);
+            let c = g.add_node(-5.0, t);
+            let d = g.add_node(n as f32 * 10.0 + 5.0, t);
+            g.add_edge(c, d);
+        }
+    }
+
+    #[test]
+    fn tiny_budget_matches_full_rebuild() {
+        let mut g = Graph::new();
+        lattice(&mut g, 30);
+        let ver = g.begin_regions_job();
+        // A clock that advances 1ms per read: every step runs a single chunk
+        let mut t = 0.0;
+        let mut clock = || {
+            t += 1.0;
+            t
+        };
+        let mut steps = 0;
+        let mut saw_partial = false;
+        loop {
+            let st = g.step_regions_job_with_clock(0.5, &mut clock);
+            assert_eq!(st.geom_ver, ver);
+            steps += 1;
+            if st.state == RegionJobState::Done {
+                break;
+            }
+            assert_eq!(st.state, RegionJobState::Running);
+            let partial = g.regions_job_result();
+            if !partial.is_empty() {
+                assert!(partial.iter().all(|r| r["stable"] == false));
+                saw_partial = true;
+            }
+        }
+        assert!(steps > 4 && saw_partial);
+        let done = g.regions_job_result();
+        assert_eq!(done.len(), 900);
+        assert!(done.iter().all(|r| r["stable"] == true));
+        // Published: get_regions serves the same faces from the cache
+        assert_eq!(g.region_cache.borrow().as_ref().unwrap().built_ver, ver);
+        let mut fresh = Graph::new();
+        lattice(&mut fresh, 30);
+        assert_eq!(sorted_keys(&done), sorted_keys(&fresh.get_regions()));
+        assert_eq!(sorted_keys(&done), sorted_keys(&g.get_regions()));
+    }
+
+    #[test]
+    fn candidates_and_nesting_span_steps() {
+        // A frame around 40x40 separate squares: every square's outer ring
+        // is a hole of the frame
+        let build = || {
+            let mut g = Graph::new();
+            g.set_intersect_backend(IntersectBackend::Sweep);
+            square(&mut g, -10.0, -10.0, 420.0);
+            for i in 0..40 {
+                for j in 0..40 {
+                    square(&mut g, i as f32 * 10.0, j as f32 * 10.0, 5.0);
+                }
+            }
+            g
+        };
+        let mut g = build();
+        g.begin_regions_job();
+        let mut t = 0.0;
+        let mut clock = || {
+            t += 1.0;
+            t
+        };
+        let (mut search_steps, mut nest_steps) = (0, 0);
+        loop {
+            let st = g.step_regions_job_with_clock(0.5, &mut clock);
+            if st.state == RegionJobState::Done {
+                break;
+            }
+            match st.phase {
+                RegionJobPhase::Intersect if st.total > 0 && st.done < st.total => {
+                    search_steps += 1
+                }
+                RegionJobPhase::Nest => nest_steps += 1,
+                _ => {}
+            }
+        }
+        assert!(search_steps > 1 && nest_steps > 1);
+        let done = g.regions_job_result();
+        assert_eq!(done.len(), 1601);
+        assert_eq!(sorted_keys(&done), sorted_keys(&build().get_regions()));
+    }
+
+    #[test]
+    fn edits_make_job_stale_and_cancel_clears_it() {
+        let mut g = Graph::new();
+        lattice(&mut g, 10);
+        g.begin_regions_job();
+        let mut t = 0.0;
+        let mut clock = || {
+            t += 1.0;
+            t
+        };
+        g.step_regions_job_with_clock(0.5, &mut clock);
+        g.move_node(0, 1.0, -5.0);
+        let st = g.step_regions_job_with_clock(0.5, &mut clock);
+        assert_eq!(st.state, RegionJobState::Stale);
+        assert_eq!(g.regions_job_status().state, RegionJobState::Idle);
+        assert!(g.regions_job_result().is_empty());
+
+        g.begin_regions_job();
+        assert!(g.cancel_regions_job());
+        assert!(!g.cancel_regions_job());
+        assert_eq!(
+            g.step_regions_job_with_clock(0.5, &mut clock).state,
+            RegionJobState::Idle
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Region hit testing, adjacency and metrics.
+//!
+//! Queries run against the cached faces in `RegionCache`, with a uniform grid
+//! over face bboxes so hover and bucket fills stay cheap with many faces.
+
+use crate::algorithms::regions::{point_in_face, polygon_area, polygon_centroid};
+use crate::model::Vec2;
+use crate::{Graph, RegionFaceCache};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Faces spanning more cells than this on either axis are kept in a flat list.
+const MAX_CELL_SPAN: i32 = 64;
+
+/// Parameter overlap under which two boundary spans are not considered shared.
+const SPAN_OVERLAP_EPS: f32 = 1e-4;
+
+/// Shared edge ids per (smaller, larger) region key pair.
+type SharedEdges = BTreeMap<(u32, u32), BTreeSet<u32>>;
+
+/// Two regions sharing boundary, with the edges between them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RegionAdjacency {
+    pub a: u32, // smaller key
+    pub b: u32,
+    pub edges: Vec<u32>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RegionMetrics {
+    pub key: u32,
+    pub area: f32,        // outer area minus holes
+    pub signed_area: f32, // shoelace sum over outer ring and holes, in walk orientation
+    pub perimeter: f32,   // outer ring plus hole rings
+    pub centroid: (f32, f32),
+    pub bbox: (f32, f32, f32, f32),
+    pub touches_outer: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RegionIndex {
+    pub cell: f32,
+    pub cells: HashMap<(i32, i32), Vec<usize>>, // indices into RegionCache::faces
+    pub large: Vec<usize>,                      // faces too big for the grid
+}
+
+fn cell_ix(cell: f32, x: f32) -> i32 {
+    (x / cell).floor() as i32
+}
+
+fn choose_cell_size(faces: &[RegionFaceCache]) -> f32 {
+    // Heuristic: about four faces per cell over the faces' extent.
+    let mut minx = f32::INFINITY;
+    let mut miny = f32::INFINITY;
+    let mut maxx = f32::NEG_INFINITY;
+    let mut maxy = f32::NEG_INFINITY;
+    for f in faces {
+        minx = minx.min(f.bbox.0);
+        miny = miny.min(f.bbox.1);
+        maxx = maxx.max(f.bbox.2);
+        maxy = maxy.max(f.bbox.3);
+    }
+    if faces.is_empty() || !(maxx > minx && maxy > miny) {
+        return 64.0;
+    }
+    let target_cells = (faces.len() as f32 / 4.0).max(16.0);
+    let cell_area = ((maxx - minx) * (maxy - miny) / target_cells).max(64.0);
+    cell_area.sqrt().clamp(8.0, 256.0)
+}
+
+pub fn build_region_index(faces: &[RegionFaceCache]) -> RegionIndex {
+    let cell = choose_cell_size(faces);
+    let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    let mut large = Vec::new();
+    for (i, f) in faces.iter().enumerate() {
+        let (ix0, iy0) = (cell_ix(cell, f.bbox.0), cell_ix(cell, f.bbox.1));
+        let (ix1, iy1) = (cell_ix(cell, f.bbox.2), cell_ix(cell, f.bbox.3));
+        if ix1 - ix0 > MAX_CELL_SPAN || iy1 - iy0 > MAX_CELL_SPAN {
+            large.push(i);
+            continue;
+        }
+        for ix in ix0..=ix1 {
+            for iy in iy0..=iy1 {
+                cells.entry((ix, iy)).or_default().push(i);
+            }
+        }
+    }
+    RegionIndex { cell, cells, large }
+}
+
+impl RegionIndex {
+    /// Face indices whose grid cells overlap the rect, without duplicates.
+    pub(crate) fn candidates(&self, minx: f32, miny: f32, maxx: f32, maxy: f32) -> Vec<usize> {
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut out = Vec::new();
+        for ix in cell_ix(self.cell, minx)..=cell_ix(self.cell, maxx) {
+            for iy in cell_ix(self.cell, miny)..=cell_ix(self.cell, maxy) {
+                if let Some(lst) = self.cells.get(&(ix, iy)) {
+                    out.extend(lst.iter().copied().filter(|i| seen.insert(*i)));
+                }
+            }
+        }
+        out.extend(self.large.iter().copied());
+        out
+    }
+}
+
+/// Bring regions up to date and rebuild the index when geometry changed.
+fn ensure_region_index(g: &mut Graph) {
+    let _ = g.compute_regions_incremental();
+    let ver = g.geom_version();
+    let stale = g
+        .region_index
+        .borrow()
+        .as_ref()
+        .is_none_or(|(built, _)| *built != ver);
+    if stale {
+        let idx = match g.region_cache.borrow().as_ref() {
+            Some(cache) => build_region_index(&cache.faces),
+            None => build_region_index(&[]),
+        };
+        *g.region_index.borrow_mut() = Some((ver, idx));
+    }
+}
+
+fn bbox_has_point(b: (f32, f32, f32, f32), x: f32, y: f32) -> bool {
+    x >= b.0 && x <= b.2 && y >= b.1 && y <= b.3
+}
+
+/// Liang-Barsky clip of segment `a`-`b` against the rect.
+fn segment_hits_rect(a: Vec2, b: Vec2, r: (f32, f32, f32, f32)) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+    for (p, q) in [
+        (-dx, a.x - r.0),
+        (dx, r.2 - a.x),
+        (-dy, a.y - r.1),
+        (dy, r.3 - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return false;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Whether the face's filled area (outside its holes) overlaps the rect.
+fn face_meets_rect(face: &RegionFaceCache, r: (f32, f32, f32, f32)) -> bool {
+    let rings = || std::iter::once(&face.points).chain(face.holes.iter());
+    // A ring crossing into the rect has face interior on one side of it
+    for ring in rings() {
+        for i in 0..ring.len() {
+            let a = ring[i];
+            let b = ring[(i + 1) % ring.len()];
+            if segment_hits_rect(a, b, r) {
+                return true;
+            }
+        }
+    }
+    // Otherwise the rect lies wholly inside or outside the face
+    let (cx, cy) = (0.5 * (r.0 + r.2), 0.5 * (r.1 + r.3));
+    point_in_face(cx, cy, &face.points, &face.holes)
+}
+
+/// Key of the innermost region containing the point, honoring holes.
+pub fn region_at_impl(g: &mut Graph, x: f32, y: f32) -> Option<u32> {
+    ensure_region_index(g);
+    let cache = g.region_cache.borrow();
+    let faces = &cache.as_ref()?.faces;
+    let idx_guard = g.region_index.borrow();
+    let (_, idx) = idx_guard.as_ref()?;
+    let mut best: Option<(u32, f32)> = None;
+    for i in idx.candidates(x, y, x, y) {
+        let Some(face) = faces.get(i) else {
+            continue;
+        };
+        if !bbox_has_point(face.bbox, x, y) || !point_in_face(x, y, &face.points, &face.holes) {
+            continue;
+        }
+        let area = face.area.abs();
+        if best.is_none_or(|(_, a)| area < a) {
+            best = Some((face.key, area));
+        }
+    }
+    best.map(|(key, _)| key)
+}
+
+/// Every region containing the point as `(key, boundary edges)`. Faces only
+/// overlap across region scopes, so there is at most one per scope.
+pub(crate) fn regions_at(g: &mut Graph, x: f32, y: f32) -> Vec<(u32, Vec<u32>)> {
+    ensure_region_index(g);
+    let cache = g.region_cache.borrow();
+    let Some(cache) = cache.as_ref() else {
+        return Vec::new();
+    };
+    let idx_guard = g.region_index.borrow();
+    let Some((_, idx)) = idx_guard.as_ref() else {
+        return Vec::new();
+    };
+    idx.candidates(x, y, x, y)
+        .into_iter()
+        .filter_map(|i| cache.faces.get(i))
+        .filter(|f| bbox_has_point(f.bbox, x, y) && point_in_face(x, y, &f.points, &f.holes))
+        .map(|f| (f.key, f.edges.clone()))
+        .collect()
+}
+
+/// Keys of all regions overlapping the rect, sorted ascending.
+pub fn regions_in_rect_impl(g: &mut Graph, x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<u32> {
+    ensure_region_index(g);
+    let r = (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1));
+    let cache = g.region_cache.borrow();
+    let Some(faces) = cache.as_ref().map(|c| &c.faces) else {
+        return Vec::new();
+    };
+    let idx_guard = g.region_index.borrow();
+    let Some((_, idx)) = idx_guard.as_ref() else {
+        return Vec::new();
+    };
+    let mut keys: Vec<u32> = idx
+        .candidates(r.0, r.1, r.2, r.3)
+        .into_iter()
+        .filter_map(|i| faces.get(i))
+        .filter(|f| {
+            !(f.bbox.2 < r.0 || f.bbox.0 > r.2 || f.bbox.3 < r.1 || f.bbox.1 > r.3)
+                && face_meets_rect(f, r)
+        })
+        .map(|f| f.key)
+        .collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Pair boundary spans that run in opposite directions over the same piece of
+/// an edge. Each such pair is a twin half-edge run from the face walk, so its
+/// faces share that edge. A span left (partly) unpaired borders the unbounded
+/// face.
+fn span_twins(faces: &[RegionFaceCache]) -> (SharedEdges, Vec<bool>) {
+    let mut by_edge: HashMap<u32, Vec<(usize, f32, f32, bool)>> = HashMap::new();
+    for (fi, f) in faces.iter().enumerate() {
+        for sp in f.spans.iter().chain(f.hole_spans.iter().flatten()) {
+            by_edge
+                .entry(sp.edge)
+                .or_default()
+                .push((fi, sp.t_start, sp.t_end, sp.forward));
+        }
+    }
+    let mut pairs: SharedEdges = BTreeMap::new();
+    let mut touches_outer = vec![false; faces.len()];
+    for (eid, list) in &by_edge {
+        for (i, &(fi, t0, t1, fwd)) in list.iter().enumerate() {
+            let mut covered = 0.0f32;
+            for (j, &(fj, u0, u1, ufwd)) in list.iter().enumerate() {
+                if i == j || fwd == ufwd {
+                    continue;
+                }
+                let overlap = t1.min(u1) - t0.max(u0);
+                if overlap <= SPAN_OVERLAP_EPS {
+                    continue;
+                }
+                covered += overlap;
+                if fi != fj {
+                    let (ka, kb) = (faces[fi].key, faces[fj].key);
+                    pairs
+                        .entry((ka.min(kb), ka.max(kb)))
+                        .or_default()
+                        .insert(*eid);
+                }
+            }
+            if covered < (t1 - t0) - SPAN_OVERLAP_EPS {
+                touches_outer[fi] = true;
+            }
+        }
+    }
+    (pairs, touches_outer)
+}
+
+/// All pairs of regions sharing boundary, ordered by keys.
+pub fn region_adjacency_impl(g: &mut Graph) -> Vec<RegionAdjacency> {
+    let _ = g.compute_regions_incremental();
+    let cache = g.region_cache.borrow();
+    let Some(cache) = cache.as_ref() else {
+        return Vec::new();
+    };
+    let (pairs, _) = span_twins(&cache.faces);
+    pairs
+        .into_iter()
+        .map(|((a, b), edges)| RegionAdjacency {
+            a,
+            b,
+            edges: edges.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Keys of regions sharing boundary with `key`, sorted ascending.
+pub fn region_neighbors_impl(g: &mut Graph, key: u32) -> Vec<u32> {
+    region_adjacency_impl(g)
+        .into_iter()
+        .filter_map(|adj| match (adj.a == key, adj.b == key) {
+            (true, _) => Some(adj.b),
+            (_, true) => Some(adj.a),
+            _ => None,
+        })
+        .collect()
+}
+
+fn ring_length(ring: &[Vec2]) -> f32 {
+    let mut len = 0.0f32;
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        len += ((b.x - a.x) * (b.x - a.x) + (b.y - a.y) * (b.y - a.y)).sqrt();
+    }
+    len
+}
+
+pub fn region_metrics_impl(g: &mut Graph, key: u32) -> Option<RegionMetrics> {
+    let _ = g.compute_regions_incremental();
+    let cache = g.region_cache.borrow();
+    let faces = &cache.as_ref()?.faces;
+    let fi = faces.iter().position(|f| f.key == key)?;
+    let face = &faces[fi];
+    let (_, touches_outer) = span_twins(faces);
+
+    let rings = || std::iter::once(&face.points).chain(face.holes.iter());
+    let signed_area: f32 = rings().map(|r| polygon_area(r)).sum();
+    let perimeter: f32 = rings().map(|r| ring_length(r)).sum();
+    // Area-weighted centroid; hole rings wind opposite to the outer ring
+    let (mut cx, mut cy) = (0.0f32, 0.0f32);
+    for ring in rings() {
+        let a = polygon_area(ring);
+        let (rx, ry) = polygon_centroid(ring);
+        cx += a * rx;
+        cy += a * ry;
+    }
+    let centroid = if signed_area.abs() > f32::EPSILON {
+        (cx / signed_area, cy / signed_area)
+    } else {
+        polygon_centroid(&face.points)
+    };
+    Some(RegionMetrics {
+        key,
+        area: face.area,
+        signed_area,
+        perimeter,
+        centroid,
+        bbox: face.bbox,
+        touches_outer: touches_outer[fi],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Graph;
+
+    #[test]
+    fn region_at_respects_holes() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(40.0, 40.0, 20.0, 20.0, 0.0);
+        let outer = g.region_at(10.0, 10.0).expect("outer face");
+        let inner = g.region_at(50.0, 50.0).expect("inner face");
+        assert_ne!(outer, inner);
+        assert_eq!(g.region_at(150.0, 50.0), None);
+    }
+
+    #[test]
+    fn rect_query_skips_faces_seen_only_through_holes() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(30.0, 30.0, 40.0, 40.0, 0.0);
+        let outer = g.region_at(5.0, 5.0).unwrap();
+        let inner = g.region_at(50.0, 50.0).unwrap();
+        assert_eq!(g.regions_in_rect(45.0, 45.0, 55.0, 55.0), vec![inner]);
+        let mut both = vec![outer, inner];
+        both.sort_unstable();
+        assert_eq!(g.regions_in_rect(20.0, 20.0, 40.0, 40.0), both);
+        assert!(g.regions_in_rect(200.0, 200.0, 300.0, 300.0).is_empty());
+    }
+
+    #[test]
+    fn adjacency_follows_shared_boundary() {
+        // Three stacked cells: top and bottom only meet the middle one
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 90.0, 0.0);
+        for y in [30.0, 60.0] {
+            let a = g.add_node(-10.0, y);
+            let b = g.add_node(110.0, y);
+            g.add_edge(a, b);
+        }
+        let top = g.region_at(50.0, 15.0).unwrap();
+        let mid = g.region_at(50.0, 45.0).unwrap();
+        let bot = g.region_at(50.0, 75.0).unwrap();
+        let mut expect = vec![top, bot];
+        expect.sort_unstable();
+        assert_eq!(g.region_neighbors(mid), expect);
+        assert_eq!(g.region_neighbors(top), vec![mid]);
+        let adj = g.region_adjacency();
+        assert_eq!(adj.len(), 2);
+        assert!(adj.iter().all(|p| p.edges.len() == 1));
+    }
+
+    #[test]
+    fn metrics_of_ring_face() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(40.0, 40.0, 20.0, 20.0, 0.0);
+        let ring = g.region_at(10.0, 10.0).unwrap();
+        let inner = g.region_at(50.0, 50.0).unwrap();
+        let m = g.region_metrics(ring).unwrap();
+        assert!((m.area - 9600.0).abs() < 1.0);
+        assert!((m.signed_area.abs() - 9600.0).abs() < 1.0);
+        assert!((m.perimeter - 480.0).abs() < 1.0);
+        assert!((m.centroid.0 - 50.0).abs() < 0.1 && (m.centroid.1 - 50.0).abs() < 0.1);
+        assert_eq!(m.bbox, (0.0, 0.0, 100.0, 100.0));
+        assert!(m.touches_outer);
+        let mi = g.region_metrics(inner).unwrap();
+        assert!(!mi.touches_outer);
+        assert_eq!(g.region_neighbors(inner), vec![ring]);
+        let missing = (0u32..).find(|k| *k != ring && *k != inner).unwrap();
+        assert!(g.region_metrics(missing).is_none());
+    }
+
+    #[test]
+    fn index_tracks_edits() {
+        let mut g = Graph::new();
+        let r = g.add_rectangle(0.0, 0.0, 10.0, 10.0, 0.0);
+        assert!(g.region_at(5.0, 5.0).is_some());
+        for &n in &r.nodes {
+            let (x, y) = g.get_node(n).unwrap();
+            g.move_node(n, x + 100.0, y);
+        }
+        assert_eq!(g.region_at(5.0, 5.0), None);
+        assert!(g.region_at(105.0, 5.0).is_some());
+    }
+}
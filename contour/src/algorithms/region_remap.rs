@@ -0,0 +1,390 @@
+//! Carrying per-region state across topology changes.
+//!
+//! Region keys hash boundary edge ids, so edits that split, merge or re-route
+//! a face rename it. After each recompute every new face takes its fill,
+//! region effects and region gradient from the nearest unclaimed old
+//! centroid. The report is built from overlap instead: each new face
+//! overlaps its own key if that survived, else the old face holding its
+//! interior probe (a split or re-keyed face). Each old key that changed is
+//! listed with the new keys it reaches: faces overlapping it, plus the new
+//! face now holding its own probe, which is how merges show up.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::algorithms::region_query::{build_region_index, RegionIndex};
+use crate::algorithms::regions::{
+    face_interior_point, point_in_face, polygon_centroid, region_to_cache_face, Region,
+};
+use crate::geometry::tolerance::QUANT_SCALE;
+use crate::model::{FillState, Vec2};
+use crate::{Graph, RegionFaceCache};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionRemapKind {
+    /// Same face under a new key
+    Renamed,
+    /// Old face now spans several faces
+    Split,
+    /// Old face shares its new face with other old faces
+    Merge,
+    /// Nothing covers the old face any more
+    Removed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RegionRemap {
+    pub old: u32,
+    /// New keys, ascending
+    pub new: Vec<u32>,
+    pub kind: RegionRemapKind,
+}
+
+/// Key changes between two region passes, by ascending old key.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RegionRemapReport {
+    pub from_ver: u64,
+    pub to_ver: u64,
+    pub remaps: Vec<RegionRemap>,
+}
+
+/// The face holding `p`, if any, limited to region scope `scope` when given.
+/// Faces exclude their holes, so at most one per scope does.
+fn face_containing(
+    faces: &[RegionFaceCache],
+    index: &RegionIndex,
+    p: Vec2,
+    scope: Option<u32>,
+) -> Option<usize> {
+    index.candidates(p.x, p.y, p.x, p.y).into_iter().find(|&i| {
+        let f = &faces[i];
+        let (minx, miny, maxx, maxy) = f.bbox;
+        scope.is_none_or(|s| s == f.scope)
+            && p.x >= minx
+            && p.x <= maxx
+            && p.y >= miny
+            && p.y <= maxy
+            && point_in_face(p.x, p.y, &f.points, &f.holes)
+    })
+}
+
+/// Fills loaded under keys the rebuilt regions no longer use, re-keyed by
+/// containment: each saved anchor point claims the face it falls in (closest
+/// area wins). Faces whose own key carries a fill are left alone.
+/// Returns new key -> loaded key.
+fn anchored_fill_keys(g: &Graph, faces: &[RegionFaceCache]) -> HashMap<u32, u32> {
+    let mut best: HashMap<u32, (u32, f32)> = HashMap::new();
+    let index = build_region_index(faces);
+    for &(old, x, y, area) in &g.fill_anchors {
+        let Some(i) = face_containing(faces, &index, Vec2 { x, y }, None) else {
+            continue;
+        };
+        let f = &faces[i];
+        if f.key == old || g.fills.contains_key(&f.key) {
+            continue;
+        }
+        let ad = (f.area - area).abs();
+        let better = best
+            .get(&f.key)
+            .is_none_or(|&(bk, ba)| ad < ba || (ad == ba && old < bk));
+        if better {
+            best.insert(f.key, (old, ad));
+        }
+    }
+    best.into_iter().map(|(k, (old, _))| (k, old)).collect()
+}
+
+/// `(key, qcx, qcy, area)` with the centroid on the quantization grid.
+fn quantized_centroid(f: &RegionFaceCache) -> (u32, i32, i32, f32) {
+    let (cx, cy) = polygon_centroid(&f.points);
+    let qx = (cx * QUANT_SCALE).round() as i32;
+    let qy = (cy * QUANT_SCALE).round() as i32;
+    (f.key, qx, qy, f.area)
+}
+
+/// Source face for each new face by nearest unclaimed old centroid in the
+/// same region scope, visiting new faces in centroid order so the result is
+/// deterministic.
+fn match_centroids(old: &[RegionFaceCache], new: &[RegionFaceCache]) -> Vec<Option<u32>> {
+    let old_prev: Vec<(u32, i32, i32, f32)> = old.iter().map(quantized_centroid).collect();
+    let new_prev: Vec<(u32, i32, i32, f32)> = new.iter().map(quantized_centroid).collect();
+    let mut source = vec![None; new.len()];
+    let mut claimed: HashSet<u32> = HashSet::new();
+    let mut order: Vec<usize> = (0..new_prev.len()).collect();
+    order.sort_by(|&i, &j| {
+        new_prev[i]
+            .1
+            .cmp(&new_prev[j].1)
+            .then(new_prev[i].2.cmp(&new_prev[j].2))
+            .then(new_prev[i].3.partial_cmp(&new_prev[j].3).unwrap())
+            .then(new_prev[i].0.cmp(&new_prev[j].0))
+    });
+    for idx in order {
+        let (_, qx, qy, area_new) = new_prev[idx];
+        let mut best: Option<(u32, i64, f32)> = None;
+        for ((k_old, oqx, oqy, area_old), of) in old_prev.iter().zip(old) {
+            if claimed.contains(k_old) || of.scope != new[idx].scope {
+                continue;
+            }
+            let dx = (qx as i64) - (*oqx as i64);
+            let dy = (qy as i64) - (*oqy as i64);
+            let d2 = dx * dx + dy * dy;
+            let ad = (area_new - *area_old).abs();
+            best = match best {
+                None => Some((*k_old, d2, ad)),
+                Some((bk, bd, ba)) => {
+                    if d2 < bd
+                        || (d2 == bd
+                            && (ad < ba || ((ad - ba).abs() <= f32::EPSILON && *k_old < bk)))
+                    {
+                        Some((*k_old, d2, ad))
+                    } else {
+                        Some((bk, bd, ba))
+                    }
+                }
+            };
+        }
+        if let Some((old_key, _, _)) = best {
+            claimed.insert(old_key);
+            source[idx] = Some(old_key);
+        }
+    }
+    source
+}
+
+/// Old face each new face overlaps: itself if its key survived, else the
+/// one holding its interior probe.
+fn match_overlaps(old: &[RegionFaceCache], new: &[RegionFaceCache]) -> Vec<Option<u32>> {
+    let old_keys: HashSet<u32> = old.iter().map(|f| f.key).collect();
+    let index = build_region_index(old);
+    new.iter()
+        .map(|f| {
+            if old_keys.contains(&f.key) {
+                return Some(f.key);
+            }
+            let probe = face_interior_point(&f.points, &f.holes);
+            face_containing(old, &index, probe, Some(f.scope)).map(|i| old[i].key)
+        })
+        .collect()
+}
+
+/// Old key -> new keys it reaches, classified; unchanged faces are left out.
+fn classify(
+    old: &[RegionFaceCache],
+    new: &[RegionFaceCache],
+    overlap: &[Option<u32>],
+) -> Vec<RegionRemap> {
+    let slot: HashMap<u32, usize> = old.iter().enumerate().map(|(i, f)| (f.key, i)).collect();
+    let mut reach: Vec<Vec<u32>> = vec![Vec::new(); old.len()];
+    for (f, s) in new.iter().zip(overlap) {
+        if let Some(i) = s.and_then(|k| slot.get(&k)) {
+            reach[*i].push(f.key);
+        }
+    }
+    let index = build_region_index(new);
+    for (i, f) in old.iter().enumerate() {
+        let probe = face_interior_point(&f.points, &f.holes);
+        if let Some(j) = face_containing(new, &index, probe, Some(f.scope)) {
+            reach[i].push(new[j].key);
+        }
+    }
+    let mut sources_of: HashMap<u32, usize> = HashMap::new();
+    for keys in reach.iter_mut() {
+        keys.sort_unstable();
+        keys.dedup();
+        for &k in keys.iter() {
+            *sources_of.entry(k).or_default() += 1;
+        }
+    }
+    let mut remaps: Vec<RegionRemap> = old
+        .iter()
+        .zip(reach)
+        .filter_map(|(f, new)| {
+            let kind = match new.as_slice() {
+                [] => RegionRemapKind::Removed,
+                [_, _, ..] => RegionRemapKind::Split,
+                [k] if sources_of[k] > 1 => RegionRemapKind::Merge,
+                [k] if *k != f.key => RegionRemapKind::Renamed,
+                _ => return None,
+            };
+            Some(RegionRemap {
+                old: f.key,
+                new,
+                kind,
+            })
+        })
+        .collect();
+    remaps.sort_by_key(|r| r.old);
+    remaps
+}
+
+/// Move fills, region effects and region gradients from the previous region
+/// pass onto `regions` and record what happened to the old keys.
+pub(crate) fn remap_region_state(g: &mut Graph, regions: &[Region]) {
+    let new_faces: Vec<RegionFaceCache> = regions.iter().map(region_to_cache_face).collect();
+    let old_faces = std::mem::take(&mut g.prev_faces);
+    let anchored = if g.fill_anchors.is_empty() {
+        HashMap::new()
+    } else {
+        anchored_fill_keys(g, &new_faces)
+    };
+    g.fill_anchors.clear();
+
+    let source = match_centroids(&old_faces, &new_faces);
+
+    let mut fills = HashMap::new();
+    let mut effects = HashMap::new();
+    let mut gradients = HashMap::new();
+    for (f, s) in new_faces.iter().zip(&source) {
+        let from: Vec<u32> = [*s, Some(f.key), anchored.get(&f.key).copied()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let state = from
+            .iter()
+            .find_map(|k| g.fills.get(k))
+            .copied()
+            .unwrap_or(FillState {
+                filled: true,
+                color: None,
+            });
+        fills.insert(f.key, state);
+        if let Some(stack) = from.iter().find_map(|k| g.region_effects.get(k)) {
+            effects.insert(f.key, stack.clone());
+        }
+        if let Some(&grad) = from.iter().find_map(|k| g.region_gradients.get(k)) {
+            gradients.insert(f.key, grad);
+        }
+    }
+    g.fills = fills;
+    g.region_effects = effects;
+    g.region_gradients = gradients;
+
+    let overlap = match_overlaps(&old_faces, &new_faces);
+    g.region_remap = RegionRemapReport {
+        from_ver: g.last_geom_ver,
+        to_ver: g.geom_ver,
+        remaps: classify(&old_faces, &new_faces, &overlap),
+    };
+    g.prev_faces = new_faces;
+    g.last_geom_ver = g.geom_ver;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Color, GradientUnits, SpreadMethod};
+    use crate::test_util::square;
+
+    fn key_at(g: &mut Graph, x: f32, y: f32) -> u32 {
+        g.region_at(x, y).unwrap()
+    }
+
+    #[test]
+    fn chord_split_then_merge_back() {
+        let mut g = Graph::new();
+        let (ids, _) = square(&mut g, 0.0, 0.0, 100.0);
+        let _ = g.get_regions();
+        let whole = key_at(&mut g, 50.0, 50.0);
+        g.set_region_color(whole, 0, 0, 255, 255);
+        let black = Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 128,
+        };
+        let fx = g.add_drop_shadow(2.0, 2.0, 4.0, 0.0, black);
+        assert!(g.add_effect_to_region(whole, fx));
+        let grad = g.add_linear_gradient(
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            Vec::new(),
+            GradientUnits::default(),
+            SpreadMethod::default(),
+        );
+        assert!(g.set_region_gradient(whole, grad));
+
+        let chord = g.add_edge(ids[0], ids[2]).unwrap();
+        let _ = g.get_regions();
+        let (a, b) = (key_at(&mut g, 80.0, 20.0), key_at(&mut g, 20.0, 80.0));
+        let mut halves = vec![a, b];
+        halves.sort_unstable();
+        assert_eq!(
+            g.region_remap_report().remaps,
+            vec![RegionRemap {
+                old: whole,
+                new: halves,
+                kind: RegionRemapKind::Split,
+            }]
+        );
+        // State stays with the nearest old centroid: one half inherits it
+        let (heir, other) = if g.get_region_effects(a).is_some() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        assert!(g.get_region_effects(heir).is_some());
+        assert!(g.get_region_effects(other).is_none());
+        assert_eq!(g.get_region_gradient(heir), Some(grad));
+        assert_eq!(g.get_region_gradient(other), None);
+        assert!(g.get_region_effects(whole).is_none());
+
+        assert!(g.remove_edge(chord));
+        let _ = g.get_regions();
+        let merged = key_at(&mut g, 50.0, 50.0);
+        let report = g.region_remap_report();
+        assert_eq!(report.to_ver, g.geom_version());
+        assert_eq!(report.remaps.len(), 2);
+        assert!(report
+            .remaps
+            .iter()
+            .all(|r| r.kind == RegionRemapKind::Merge && r.new == vec![merged]));
+    }
+
+    #[test]
+    fn rerouted_edge_renames_and_removal_reports() {
+        let mut g = Graph::new();
+        let (ids, edges) = square(&mut g, 0.0, 0.0, 100.0);
+        let (_, lone_edges) = square(&mut g, 300.0, 0.0, 50.0);
+        let _ = g.get_regions();
+        let old = key_at(&mut g, 50.0, 50.0);
+        let lone = key_at(&mut g, 325.0, 25.0);
+        g.set_region_color(old, 1, 2, 3, 255);
+
+        // Same face, new boundary edge ids
+        assert!(g.remove_edge(edges[0]));
+        let mid = g.add_node(50.0, 0.0);
+        g.add_edge(ids[0], mid).unwrap();
+        g.add_edge(mid, ids[1]).unwrap();
+        let regions = g.get_regions();
+        let new = key_at(&mut g, 50.0, 50.0);
+        assert_ne!(old, new);
+        assert_eq!(
+            g.region_remap_report().remaps,
+            vec![RegionRemap {
+                old,
+                new: vec![new],
+                kind: RegionRemapKind::Renamed,
+            }]
+        );
+        let face = regions.iter().find(|v| v["key"] == new).unwrap();
+        assert_eq!(face["color"], serde_json::json!([1, 2, 3, 255]));
+
+        for e in lone_edges {
+            assert!(g.remove_edge(e));
+        }
+        let _ = g.get_regions();
+        assert_eq!(
+            g.region_remap_report().remaps,
+            vec![RegionRemap {
+                old: lone,
+                new: vec![],
+                kind: RegionRemapKind::Removed,
+            }]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Region scopes: which edges may combine into a face.
+//!
+//! Under `RegionScope::Layer` or `RegionScope::Group` every visible edge is
+//! tagged with its layer or group id. Planarization only intersects segments
+//! of the same scope and keeps one vertex per scope at shared points, so the
+//! face walk never crosses scopes. Face keys fold the scope id in, which keeps
+//! fills on one layer independent of geometry on another.
+
+use std::collections::HashMap;
+
+use crate::layers::LayerSystem;
+use crate::model::{LayerId, RegionScope};
+use crate::Graph;
+
+/// Scope of edges without a layer or group.
+pub(crate) const NO_SCOPE: u32 = u32::MAX;
+
+/// Scope id per edge; empty when all edges share one planar map.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EdgeScopes {
+    by_edge: Option<HashMap<u32, u32>>,
+}
+
+impl EdgeScopes {
+    pub(crate) fn capture(g: &Graph) -> Self {
+        let scope_of: fn(&LayerSystem, u32) -> Option<LayerId> = match g.region_scope {
+            RegionScope::All => return EdgeScopes::default(),
+            RegionScope::Layer => LayerSystem::get_edge_layer,
+            RegionScope::Group => LayerSystem::get_edge_group,
+        };
+        let by_edge = g
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.is_some())
+            .map(|(eid, _)| {
+                let eid = eid as u32;
+                (eid, scope_of(&g.layer_system, eid).unwrap_or(NO_SCOPE))
+            })
+            .collect();
+        EdgeScopes {
+            by_edge: Some(by_edge),
+        }
+    }
+
+    /// Scopes from explicit `(edge, scope)` pairs.
+    pub(crate) fn from_pairs(pairs: impl IntoIterator<Item = (u32, u32)>) -> Self {
+        EdgeScopes {
+            by_edge: Some(pairs.into_iter().collect()),
+        }
+    }
+
+    pub(crate) fn is_scoped(&self) -> bool {
+        self.by_edge.is_some()
+    }
+
+    /// Scope of an edge; 0 when unscoped.
+    pub(crate) fn of(&self, eid: u32) -> u32 {
+        match &self.by_edge {
+            Some(map) => map.get(&eid).copied().unwrap_or(NO_SCOPE),
+            None => 0,
+        }
+    }
+
+    /// Face key for an edge-sequence key in `scope`: unchanged when unscoped,
+    /// otherwise the scope id is hashed in (FNV-1a, like the sequence key).
+    pub(crate) fn key(&self, key: u32, scope: u32) -> u32 {
+        if !self.is_scoped() {
+            return key;
+        }
+        let mut hash = key;
+        for b in scope.to_le_bytes() {
+            hash ^= b as u32;
+            hash = hash.wrapping_mul(0x0100_0193);
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::square;
+
+    /// New layer and its root group.
+    fn layer(g: &mut Graph, name: &str) -> (LayerId, LayerId) {
+        let id = g.create_layer(name.to_string());
+        (id, g.layer_system.get_layer(id).unwrap().root_group)
+    }
+
+    fn move_to(g: &mut Graph, edges: &[u32], group: LayerId) {
+        for &e in edges {
+            assert!(g.add_edge_to_group(e, group));
+        }
+    }
+
+    /// `(key, scope)` of every region, sorted.
+    fn faces(g: &mut Graph) -> Vec<(u32, Option<u32>)> {
+        let mut out: Vec<(u32, Option<u32>)> = g
+            .get_regions()
+            .iter()
+            .map(|r| {
+                (
+                    r["key"].as_u64().unwrap() as u32,
+                    r.get("scope").and_then(|s| s.as_u64()).map(|s| s as u32),
+                )
+            })
+            .collect();
+        out.sort_unstable();
+        out
+    }
+
+    #[test]
+    fn other_layer_chord_does_not_split() {
+        let mut g = Graph::new();
+        let (_, e1) = square(&mut g, 0.0, 0.0, 100.0);
+        let (lb, gb) = layer(&mut g, "B");
+        let a = g.add_node(-20.0, 50.0);
+        let b = g.add_node(120.0, 50.0);
+        let chord = g.add_edge(a, b).unwrap();
+        move_to(&mut g, &[chord], gb);
+        assert_eq!(faces(&mut g).len(), 2);
+
+        g.set_region_scope(RegionScope::Layer);
+        let scoped = faces(&mut g);
+        assert_eq!(scoped.len(), 1);
+        let layer_a = g.get_edge_layer(e1[0]).unwrap();
+        assert_ne!(layer_a, lb);
+        assert_eq!(scoped[0].1, Some(layer_a));
+
+        // Moving the chord onto the square's layer splits it again.
+        let home = g.default_group().unwrap();
+        move_to(&mut g, &[chord], home);
+        assert_eq!(faces(&mut g).len(), 2);
+    }
+
+    #[test]
+    fn same_geometry_on_two_layers_gets_two_keys() {
+        let mut g = Graph::new();
+        square(&mut g, 0.0, 0.0, 100.0);
+        let (_, e2) = square(&mut g, 0.0, 0.0, 100.0);
+        let (_, gb) = layer(&mut g, "B");
+        move_to(&mut g, &e2, gb);
+        g.set_region_scope(RegionScope::Layer);
+        let scoped = faces(&mut g);
+        assert_eq!(scoped.len(), 2);
+        assert_ne!(scoped[0].0, scoped[1].0);
+        assert_ne!(scoped[0].1, scoped[1].1);
+    }
+
+    #[test]
+    fn fill_survives_edit_on_other_layer() {
+        let mut g = Graph::new();
+        g.set_region_scope(RegionScope::Layer);
+        square(&mut g, 0.0, 0.0, 100.0);
+        let (n2, e2) = square(&mut g, 50.0, 50.0, 100.0);
+        let (lb, gb) = layer(&mut g, "B");
+        move_to(&mut g, &e2, gb);
+        let before = faces(&mut g);
+        assert_eq!(before.len(), 2);
+        let (key_a, _) = *before.iter().find(|f| f.1 != Some(lb)).unwrap();
+        g.set_region_color(key_a, 255, 0, 0, 255);
+
+        assert!(g.move_node(n2[0], 30.0, 60.0));
+        let after = faces(&mut g);
+        assert_eq!(after.len(), 2);
+        assert!(after.iter().any(|f| f.0 == key_a));
+        assert!(g.fills.get(&key_a).is_some_and(|f| f.filled));
+    }
+}
@@ -0,0 +1,476 @@
+//! Extract a region (a face of the planar map) as a standalone `Shape`.
+
+use std::collections::HashMap;
+
+use crate::algorithms::region_remap::RegionRemapReport;
+use crate::algorithms::regions::{face_interior_point, RegionSpan};
+use crate::geometry::cubic::CubicBezier;
+use crate::layers::LayerSystem;
+use crate::model::{
+    BlendMode, Color, Edge, EdgeKind, EffectStack, FillRule, FillState, GradientId, GroupItem,
+    HandleMode, Node, PrimitiveResult, Shape, TextElement, Vec2,
+};
+use crate::{Graph, RegionFaceCache};
+
+/// Parameter slack when deciding whether a span covers its whole edge.
+const WHOLE_EDGE_EPS: f32 = 1e-3;
+
+/// Point on an edge at parameter `t` (polylines use [0, segment count]).
+fn edge_point(g: &Graph, e: &Edge, t: f32) -> Option<Vec2> {
+    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    let (a, b) = (Vec2 { x: a.x, y: a.y }, Vec2 { x: b.x, y: b.y });
+    Some(match &e.kind {
+        EdgeKind::Line => Vec2 {
+            x: a.x + (b.x - a.x) * t,
+            y: a.y + (b.y - a.y) * t,
+        },
+        EdgeKind::Cubic { ha, hb, .. } => CubicBezier::new(
+            a,
+            Vec2 {
+                x: a.x + ha.x,
+                y: a.y + ha.y,
+            },
+            Vec2 {
+                x: b.x + hb.x,
+                y: b.y + hb.y,
+            },
+            b,
+        )
+        .eval(t),
+        EdgeKind::Polyline { points } => {
+            let mut pts = Vec::with_capacity(points.len() + 2);
+            pts.push(a);
+            pts.extend(points.iter().copied());
+            pts.push(b);
+            let i = (t.max(0.0).floor() as usize).min(pts.len() - 2);
+            let u = t - i as f32;
+            Vec2 {
+                x: pts[i].x + (pts[i + 1].x - pts[i].x) * u,
+                y: pts[i].y + (pts[i + 1].y - pts[i].y) * u,
+            }
+        }
+    })
+}
+
+/// Geometry of a span as an edge kind running from `start` to `end`.
+fn span_kind(g: &Graph, e: &Edge, span: &RegionSpan, start: Vec2, end: Vec2) -> Option<EdgeKind> {
+    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    Some(match &e.kind {
+        EdgeKind::Line => EdgeKind::Line,
+        EdgeKind::Cubic { ha, hb, .. } => {
+            let curve = CubicBezier::new(
+                Vec2 { x: a.x, y: a.y },
+                Vec2 {
+                    x: a.x + ha.x,
+                    y: a.y + ha.y,
+                },
+                Vec2 {
+                    x: b.x + hb.x,
+                    y: b.y + hb.y,
+                },
+                Vec2 { x: b.x, y: b.y },
+            );
+            let sub = curve.subcurve(span.t_start, span.t_end);
+            let (c1, c2) = if span.forward {
+                (sub.p1, sub.p2)
+            } else {
+                (sub.p2, sub.p1)
+            };
+            EdgeKind::Cubic {
+                ha: Vec2 {
+                    x: c1.x - start.x,
+                    y: c1.y - start.y,
+                },
+                hb: Vec2 {
+                    x: c2.x - end.x,
+                    y: c2.y - end.y,
+                },
+                mode: HandleMode::Free,
+            }
+        }
+        EdgeKind::Polyline { points } => {
+            // Interior vertices strictly between the span ends
+            let lo = span.t_start.floor() as usize + 1;
+            let hi = span.t_end.ceil() as usize;
+            let mut inner: Vec<Vec2> = (lo..hi)
+                .filter_map(|i| points.get(i - 1).copied())
+                .collect();
+            if !span.forward {
+                inner.reverse();
+            }
+            if inner.is_empty() {
+                EdgeKind::Line
+            } else {
+                EdgeKind::Polyline { points: inner }
+            }
+        }
+    })
+}
+
+fn span_is_whole(e: &Edge, span: &RegionSpan) -> bool {
+    let max = match &e.kind {
+        EdgeKind::Polyline { points } => (points.len() + 1) as f32,
+        _ => 1.0,
+    };
+    span.t_start <= WHOLE_EDGE_EPS && span.t_end >= max - WHOLE_EDGE_EPS
+}
+
+/// A boundary piece of a detached copy, between ring node indices.
+struct DetachedEdge {
+    a: usize,
+    b: usize,
+    kind: EdgeKind,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    source: u32,
+}
+
+/// Graph state an in-place extraction can touch, kept to undo a split whose
+/// face cannot be read back as whole edges.
+struct SavedEdit {
+    nodes: Vec<Option<Node>>,
+    edges: Vec<Option<Edge>>,
+    shapes: Vec<Option<Shape>>,
+    texts: Vec<Option<TextElement>>,
+    layer_system: LayerSystem,
+    fills: HashMap<u32, FillState>,
+    region_effects: HashMap<u32, EffectStack>,
+    region_gradients: HashMap<u32, GradientId>,
+    prev_faces: Vec<RegionFaceCache>,
+    fill_anchors: Vec<(u32, f32, f32, f32)>,
+    region_remap: RegionRemapReport,
+}
+
+impl SavedEdit {
+    fn capture(g: &Graph) -> Self {
+        SavedEdit {
+            nodes: g.nodes.clone(),
+            edges: g.edges.clone(),
+            shapes: g.shapes.clone(),
+            texts: g.texts.clone(),
+            layer_system: g.layer_system.clone(),
+            fills: g.fills.clone(),
+            region_effects: g.region_effects.clone(),
+            region_gradients: g.region_gradients.clone(),
+            prev_faces: g.prev_faces.clone(),
+            fill_anchors: g.fill_anchors.clone(),
+            region_remap: g.region_remap.clone(),
+        }
+    }
+
+    fn restore(self, g: &mut Graph) {
+        g.nodes = self.nodes;
+        g.edges = self.edges;
+        g.shapes = self.shapes;
+        g.texts = self.texts;
+        g.layer_system = self.layer_system;
+        g.fills = self.fills;
+        g.region_effects = self.region_effects;
+        g.region_gradients = self.region_gradients;
+        g.prev_faces = self.prev_faces;
+        g.fill_anchors = self.fill_anchors;
+        g.region_remap = self.region_remap;
+        g.mark_full_dirty();
+        g.bump();
+    }
+}
+
+impl Graph {
+    /// Turn the region `key` into a closed `Shape` tracing its boundary, holes
+    /// included.
+    ///
+    /// With `detach == false` the boundary edges are split in place at the
+    /// region's corners and the shape references those pieces, so neighbors
+    /// keep sharing them. With `detach == true` the shape gets its own nodes and
+    /// edges copied from the boundary spans (cubic pieces cut exactly), free to
+    /// move without tearing the source drawing. The region's fill state and
+    /// gradient are carried over to the face the shape encloses.
+    pub fn region_to_shape(&mut self, key: u32, detach: bool) -> Option<PrimitiveResult> {
+        let regions = self.compute_regions_incremental();
+        let region = regions.into_iter().find(|r| r.key == key)?;
+        let probe = face_interior_point(&region.points, &region.holes);
+        let fill = self.fills.get(&key).copied();
+        let gradient = self.region_gradients.get(&key).copied();
+
+        let mut result = PrimitiveResult {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            shape: 0,
+        };
+        let mut shape_edges: Vec<u32> = Vec::new();
+        let rings: Vec<&Vec<RegionSpan>> = std::iter::once(&region.spans)
+            .chain(region.hole_spans.iter())
+            .collect();
+
+        if detach {
+            // Resolve every ring before touching the graph so a bad span
+            // leaves it unchanged
+            let mut plans: Vec<(Vec<Vec2>, Vec<DetachedEdge>)> = Vec::with_capacity(rings.len());
+            for ring in rings {
+                // One node per span start; each span runs to the next start
+                let mut starts: Vec<Vec2> = Vec::with_capacity(ring.len());
+                for span in ring {
+                    let e = self
+                        .edges
+                        .get(span.edge as usize)
+                        .and_then(|e| e.as_ref())?;
+                    let t = if span.forward {
+                        span.t_start
+                    } else {
+                        span.t_end
+                    };
+                    starts.push(edge_point(self, e, t)?);
+                }
+                let mut pieces = Vec::with_capacity(ring.len());
+                for (i, span) in ring.iter().enumerate() {
+                    let j = (i + 1) % ring.len();
+                    if i == j {
+                        continue;
+                    }
+                    let src = self
+                        .edges
+                        .get(span.edge as usize)
+                        .and_then(|e| e.as_ref())?;
+                    pieces.push(DetachedEdge {
+                        a: i,
+                        b: j,
+                        kind: span_kind(self, src, span, starts[i], starts[j])?,
+                        stroke: src.stroke,
+                        stroke_width: src.stroke_width,
+                        source: span.edge,
+                    });
+                }
+                plans.push((starts, pieces));
+            }
+            for (starts, pieces) in plans {
+                let first_node = self.nodes.len() as u32;
+                for p in &starts {
+                    result.nodes.push(self.nodes.len() as u32);
+                    self.nodes.push(Some(Node { x: p.x, y: p.y }));
+                }
+                for piece in pieces {
+                    let id = self.edges.len() as u32;
+                    self.edges.push(Some(Edge {
+                        a: first_node + piece.a as u32,
+                        b: first_node + piece.b as u32,
+                        kind: piece.kind,
+                        stroke: piece.stroke,
+                        stroke_width: piece.stroke_width,
+                        construction: false,
+                    }));
+                    if let Some(gid) = self
+                        .layer_system
+                        .get_edge_group(piece.source)
+                        .or(self.layer_system.default_group())
+                    {
+                        self.layer_system.add_edge_to_group(id, gid);
+                    }
+                    result.edges.push(id);
+                    shape_edges.push(id);
+                }
+            }
+            self.mark_full_dirty();
+            self.bump();
+        } else {
+            // Split the boundary edges at the region's corners only, then
+            // read the now whole-edge spans back
+            let corners: Vec<(u32, f32)> = rings
+                .iter()
+                .flat_map(|ring| ring.iter())
+                .flat_map(|span| [(span.edge, span.t_start), (span.edge, span.t_end)])
+                .collect();
+            let saved = SavedEdit::capture(self);
+            let baked = self.split_edges_at_corners(&corners);
+            match self.traced_edges(probe) {
+                Some(edges) => shape_edges = edges,
+                None => {
+                    saved.restore(self);
+                    return None;
+                }
+            }
+            result.nodes = baked.nodes;
+            result.edges = baked.edges;
+        }
+
+        let fill_rule = if region.holes.is_empty() {
+            FillRule::NonZero
+        } else {
+            FillRule::EvenOdd
+        };
+        let id = self.shapes.len() as u32;
+        self.shapes.push(Some(Shape {
+            id,
+            edges: shape_edges,
+            closed: true,
+            fill_rule,
+            blend_mode: BlendMode::Normal,
+        }));
+        self.file_in_default_group(GroupItem::Shape(id));
+        self.touch_shape(id);
+        result.shape = id;
+
+        if fill.is_some() || gradient.is_some() {
+            if let Some(new_key) = self.region_at(probe.x, probe.y) {
+                if let Some(state) = fill {
+                    self.fills.insert(new_key, state);
+                }
+                if let Some(gradient) = gradient {
+                    self.region_gradients.insert(new_key, gradient);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Edges of the face around `probe`, None unless each span covers its
+    /// whole edge.
+    fn traced_edges(&mut self, probe: Vec2) -> Option<Vec<u32>> {
+        let regions = self.compute_regions_incremental();
+        let traced = regions.into_iter().find(|r| {
+            crate::algorithms::regions::point_in_face(probe.x, probe.y, &r.points, &r.holes)
+        })?;
+        let mut edges = Vec::new();
+        for span in std::iter::once(&traced.spans)
+            .chain(traced.hole_spans.iter())
+            .flatten()
+        {
+            let e = self
+                .edges
+                .get(span.edge as usize)
+                .and_then(|e| e.as_ref())?;
+            if !span_is_whole(e, span) {
+                return None;
+            }
+            edges.push(span.edge);
+        }
+        Some(edges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{EdgeKind, GradientUnits, GroupItem, SpreadMethod};
+    use crate::Graph;
+
+    #[test]
+    fn detached_copy_of_crossing_face() {
+        // Two overlapping squares; extract the lens where they overlap
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(50.0, 50.0, 100.0, 100.0, 0.0);
+        let key = g.region_at(75.0, 75.0).unwrap();
+        g.set_region_color(key, 10, 20, 30, 255);
+        let before = g.shape_count();
+        let res = g.region_to_shape(key, true).expect("shape");
+        assert_eq!(g.shape_count(), before + 1);
+        assert_eq!(res.edges.len(), 4);
+        assert_eq!(res.nodes.len(), 4);
+        let shape = g.get_shape(res.shape).unwrap();
+        assert!(shape.closed);
+        // Moving the copy away leaves the source faces intact
+        let n_regions = g.get_regions().len();
+        for &n in &res.nodes {
+            let (x, y) = g.get_node(n).unwrap();
+            g.move_node(n, x + 300.0, y);
+        }
+        assert_eq!(g.get_regions().len(), n_regions + 1);
+        assert!(g.region_at(375.0, 75.0).is_some());
+        assert!(g.region_at(75.0, 75.0).is_some());
+    }
+
+    #[test]
+    fn failed_in_place_split_leaves_graph_unchanged() {
+        // A locked square is never split, so the lens cannot be traced from
+        // whole edges and the split where the stray line crosses the other
+        // square is undone
+        let mut g = Graph::new();
+        let root = g.default_group().unwrap();
+        let locked = g.create_group("Locked".to_string(), root).unwrap();
+        let a = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(50.0, 50.0, 100.0, 100.0, 0.0);
+        let (p, q) = (g.add_node(95.0, 40.0), g.add_node(130.0, 60.0));
+        g.add_edge(p, q).unwrap();
+        let items: Vec<GroupItem> = a.edges.iter().map(|&e| GroupItem::Edge(e)).collect();
+        assert_eq!(g.move_items_to_group(&items, locked), items.len());
+        assert!(g.set_group_locked(locked, true));
+        let key = g.region_at(75.0, 75.0).unwrap();
+        let (nodes, edges, shapes) = (g.node_count(), g.edge_count(), g.shape_count());
+        let regions = g.get_regions().len();
+
+        assert!(g.region_to_shape(key, false).is_none());
+        assert_eq!(
+            (g.node_count(), g.edge_count(), g.shape_count()),
+            (nodes, edges, shapes)
+        );
+        assert_eq!(g.get_regions().len(), regions);
+        assert_eq!(g.region_at(75.0, 75.0), Some(key));
+    }
+
+    #[test]
+    fn in_place_shape_keeps_curves_and_holes() {
+        let mut g = Graph::new();
+        g.add_ellipse(0.0, 0.0, 50.0, 50.0);
+        g.add_ellipse(0.0, 0.0, 20.0, 20.0);
+        let a = g.add_node(-80.0, 35.0);
+        let b = g.add_node(80.0, 35.0);
+        g.add_edge(a, b);
+        // Ring face below the chord, with the inner disc as its hole
+        let key = g.region_at(0.0, -35.0).unwrap();
+        g.set_region_color(key, 200, 0, 0, 255);
+        let res = g.region_to_shape(key, false).expect("shape");
+        assert!(!res.nodes.is_empty(), "chord crossings become nodes");
+        let shape = g.get_shape(res.shape).unwrap().clone();
+        assert!(shape.edges.iter().any(|&e| matches!(
+            g.edges[e as usize].as_ref().map(|e| &e.kind),
+            Some(EdgeKind::Cubic { .. })
+        )));
+        let new_key = g.region_at(0.0, -35.0).unwrap();
+        assert_eq!(
+            g.fills.get(&new_key).and_then(|f| f.color).map(|c| c.r),
+            Some(200)
+        );
+        // The hole ring is part of the shape
+        let inner_shape_edges = g.get_shape(1).unwrap().edges.clone();
+        assert!(inner_shape_edges.iter().all(|e| shape.edges.contains(e)));
+    }
+
+    #[test]
+    fn in_place_split_leaves_other_edges_and_keeps_gradient() {
+        let mut g = Graph::new();
+        let t: Vec<u32> = [(0.0, 0.0), (100.0, 0.0), (0.0, 100.0)]
+            .iter()
+            .map(|&(x, y)| g.add_node(x, y))
+            .collect();
+        for i in 0..3 {
+            g.add_edge(t[i], t[(i + 1) % 3]).unwrap();
+        }
+        // An X inside the triangle's bbox but outside the triangle
+        let (p, q) = (g.add_node(70.0, 70.0), g.add_node(90.0, 90.0));
+        let (r, s) = (g.add_node(70.0, 90.0), g.add_node(90.0, 70.0));
+        let x1 = g.add_edge(p, q).unwrap();
+        let x2 = g.add_edge(r, s).unwrap();
+        let key = g.region_at(20.0, 20.0).unwrap();
+        let grad = g.add_linear_gradient(
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            Vec::new(),
+            GradientUnits::default(),
+            SpreadMethod::default(),
+        );
+        assert!(g.set_region_gradient(key, grad));
+        let edges = g.edge_count();
+
+        let res = g.region_to_shape(key, false).expect("shape");
+        assert!(res.edges.is_empty() && res.nodes.is_empty());
+        assert_eq!(g.edge_count(), edges);
+        assert_eq!(g.get_edge_nodes(x1), Some((p, q)));
+        assert_eq!(g.get_edge_nodes(x2), Some((r, s)));
+        assert_eq!(g.get_shape(res.shape).unwrap().edges.len(), 3);
+        let new_key = g.region_at(20.0, 20.0).unwrap();
+        assert_eq!(g.get_region_gradient(new_key), Some(grad));
+    }
+}
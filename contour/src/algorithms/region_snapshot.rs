@@ -0,0 +1,242 @@
+//! Off-thread region computation: snapshot in, faces out.
+//!
+//! A `RegionSnapshot` carries everything a full region rebuild reads: the
+//! visible edges (ids kept, since region keys hash edge ids) with their end
+//! nodes, `flatten_tol`, the intersection backend and the `geom_ver` it was
+//! taken at. `compute_region_snapshot` is a pure function of it, so it can run
+//! on a native thread or in a Web Worker holding its own wasm instance. The
+//! main graph takes the faces back with `apply_region_result`, which refuses
+//! results for any other geometry version. Under a layer or group region
+//! scope each edge carries its scope id, so the worker keeps scopes apart.
+
+use serde::{Deserialize, Serialize};
+
+use crate::algorithms::planarize::IntersectBackend;
+use crate::algorithms::region_scope::EdgeScopes;
+use crate::algorithms::regions::{faces_from_graph, install_region_faces, region_to_cache_face};
+use crate::geometry::limits;
+use crate::model::{Edge, EdgeKind, Node};
+use crate::{Graph, RegionFaceCache};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SnapshotEdge {
+    pub id: u32,
+    pub a: u32,
+    pub b: u32,
+    pub kind: EdgeKind,
+    /// Region scope id; only read when the snapshot is `scoped`
+    #[serde(default)]
+    pub scope: u32,
+}
+
+/// Geometry and settings a full region rebuild depends on.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegionSnapshot {
+    pub geom_ver: u64,
+    pub flatten_tol: f32,
+    pub backend: IntersectBackend,
+    /// Regions form per layer or group rather than across all edges
+    #[serde(default)]
+    pub scoped: bool,
+    /// `(id, x, y)` of every node an edge ends on
+    pub nodes: Vec<(u32, f32, f32)>,
+    pub edges: Vec<SnapshotEdge>,
+}
+
+/// Faces computed from a snapshot, tagged with its geometry version.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RegionSnapshotResult {
+    pub geom_ver: u64,
+    pub faces: Vec<RegionFaceCache>,
+}
+
+impl RegionSnapshot {
+    pub(crate) fn capture(g: &Graph) -> Self {
+        let scopes = EdgeScopes::capture(g);
+        let mut used = vec![false; g.nodes.len()];
+        let mut edges = Vec::new();
+        for (eid, e) in g.edges.iter().enumerate() {
+            let Some(e) = e else { continue };
+            // Skip hidden and construction edges
+            if !g.edge_in_regions(eid as u32) {
+                continue;
+            }
+            for n in [e.a, e.b] {
+                if let Some(u) = used.get_mut(n as usize) {
+                    *u = true;
+                }
+            }
+            edges.push(SnapshotEdge {
+                id: eid as u32,
+                a: e.a,
+                b: e.b,
+                kind: e.kind.clone(),
+                scope: scopes.of(eid as u32),
+            });
+        }
+        let nodes = g
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| used[*id])
+            .filter_map(|(id, n)| n.map(|n| (id as u32, n.x, n.y)))
+            .collect();
+        RegionSnapshot {
+            geom_ver: g.geom_version(),
+            flatten_tol: g.flatten_tol,
+            backend: g.intersect_backend,
+            scoped: scopes.is_scoped(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// A bare graph (no layers, styles or caches) with the snapshot's ids.
+    /// Ids past the graph caps are refused rather than allocated for.
+    fn to_graph(&self) -> Result<Graph, (&'static str, String)> {
+        if let Some(&(id, _, _)) = self
+            .nodes
+            .iter()
+            .find(|n| n.0 as usize >= limits::MAX_NODES)
+        {
+            return Err((
+                "caps_exceeded",
+                format!("node id {}>={}", id, limits::MAX_NODES),
+            ));
+        }
+        if let Some(e) = self
+            .edges
+            .iter()
+            .find(|e| e.id as usize >= limits::MAX_EDGES)
+        {
+            return Err((
+                "caps_exceeded",
+                format!("edge id {}>={}", e.id, limits::MAX_EDGES),
+            ));
+        }
+        let mut g = Graph::new();
+        g.flatten_tol = self.flatten_tol;
+        g.intersect_backend = self.backend;
+        for &(id, x, y) in &self.nodes {
+            let id = id as usize;
+            if g.nodes.len() <= id {
+                g.nodes.resize(id + 1, None);
+            }
+            g.nodes[id] = Some(Node { x, y });
+        }
+        for e in &self.edges {
+            let id = e.id as usize;
+            if g.edges.len() <= id {
+                g.edges.resize(id + 1, None);
+            }
+            g.edges[id] = Some(Edge {
+                a: e.a,
+                b: e.b,
+                kind: e.kind.clone(),
+                stroke: None,
+                stroke_width: 2.0,
+                construction: false,
+            });
+        }
+        Ok(g)
+    }
+}
+
+/// Compute the faces of a snapshot. Needs no graph, so it can run anywhere.
+/// Fails with `caps_exceeded` when a node or edge id is past the graph caps.
+pub fn compute_region_snapshot(
+    snapshot: &RegionSnapshot,
+) -> Result<RegionSnapshotResult, (&'static str, String)> {
+    let g = snapshot.to_graph()?;
+    let scopes = if snapshot.scoped {
+        EdgeScopes::from_pairs(snapshot.edges.iter().map(|e| (e.id, e.scope)))
+    } else {
+        EdgeScopes::default()
+    };
+    Ok(RegionSnapshotResult {
+        geom_ver: snapshot.geom_ver,
+        faces: faces_from_graph(&g, &scopes)
+            .iter()
+            .map(region_to_cache_face)
+            .collect(),
+    })
+}
+
+/// Install a snapshot result if the graph is still at its geometry version.
+pub(crate) fn apply_result(g: &mut Graph, result: RegionSnapshotResult) -> bool {
+    if result.geom_ver != g.geom_version() {
+        return false;
+    }
+    install_region_faces(g, result.faces);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted_keys;
+
+    fn scene(g: &mut Graph) {
+        let _ = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 20.0);
+        let _ = g.add_rectangle(50.0, 50.0, 100.0, 100.0, 0.0);
+        let _ = g.add_ellipse(100.0, 100.0, 30.0, 20.0);
+    }
+
+    #[test]
+    fn json_round_trip_matches_local_rebuild() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let json = serde_json::to_string(&g.region_snapshot()).unwrap();
+
+        // "Worker" side: nothing but the serialized snapshot
+        let snap: RegionSnapshot = serde_json::from_str(&json).unwrap();
+        let out = serde_json::to_string(&compute_region_snapshot(&snap).unwrap()).unwrap();
+
+        let result: RegionSnapshotResult = serde_json::from_str(&out).unwrap();
+        assert!(g.apply_region_result(result));
+        let cached = g.region_cache.borrow().as_ref().unwrap().built_ver;
+        assert_eq!(cached, g.geom_version());
+        let applied = g.get_regions();
+        assert!(applied.len() >= 5);
+
+        let mut fresh = Graph::new();
+        scene(&mut fresh);
+        assert_eq!(sorted_keys(&applied), sorted_keys(&fresh.get_regions()));
+    }
+
+    #[test]
+    fn stale_results_and_hidden_edges() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let snap = g.region_snapshot();
+        g.move_node(0, 1.0, 0.0);
+        assert!(!g.apply_region_result(compute_region_snapshot(&snap).unwrap()));
+
+        let a = g.add_node(300.0, 0.0);
+        let b = g.add_node(300.0, 10.0);
+        let e = g.add_edge(a, b).unwrap();
+        let parent = g.get_edge_group(e).unwrap();
+        let hidden = g.create_group("hidden".to_string(), parent).unwrap();
+        assert!(g.add_edge_to_group(e, hidden));
+        assert!(g.set_group_visibility(hidden, false));
+        let snap = g.region_snapshot();
+        assert!(snap.edges.iter().all(|s| s.id != e));
+        assert!(snap.nodes.iter().all(|n| n.0 != a && n.0 != b));
+    }
+
+    #[test]
+    fn ids_past_the_caps_are_refused() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let mut snap = g.region_snapshot();
+        snap.nodes[0].0 = limits::MAX_NODES as u32;
+        let err = compute_region_snapshot(&snap).unwrap_err();
+        assert_eq!(err.0, "caps_exceeded");
+
+        let mut snap = g.region_snapshot();
+        snap.edges[0].id = u32::MAX;
+        let err = compute_region_snapshot(&snap).unwrap_err();
+        assert_eq!(err.0, "caps_exceeded");
+        assert!(err.1.starts_with("edge id"));
+    }
+}
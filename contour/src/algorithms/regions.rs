@@ -1,19 +1,26 @@
 use std::collections::{HashMap, HashSet};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     algorithms::{
         incremental::{ensure_incr_plan, neighbor_edges_for_edges},
-        planarize::planarize_graph,
+        masking::region_unmasked_at,
+        parallel::par_map,
+        planarize::planarize_scoped,
         planarize::Planarized,
         planarize_subset::planarize_subset_with_bbox_guard,
+        region_fill::{closed_shape_fills, face_owners},
+        region_remap::remap_region_state,
+        region_scope::EdgeScopes,
+        winding::{point_in_polygon_nonzero, point_on_polygon_edge},
     },
     geometry::{
+        cubic::{flat_position_to_cubic_t, CubicBezier},
         flatten::flatten_cubic,
-        tolerance::{EPS_ANG, EPS_FACE_AREA, QUANT_SCALE},
+        tolerance::{EPS_ANG, EPS_FACE_AREA, EPS_POS},
     },
-    model::{EdgeKind, FillState, Vec2},
+    model::{Edge, EdgeKind, FillState, Node, PathCommand, RegionFillMode, RegionScope, Vec2},
     Graph, RegionFaceCache,
 };
 
@@ -23,12 +30,34 @@ use std::time::Instant;
 #[derive(Clone)]
 pub(crate) struct Region {
     pub key: u32,
+    pub scope: u32, // region scope id (0 when regions span all layers)
     pub points: Vec<Vec2>,
-    pub area: f32,
+    pub area: f32, // outer ring area minus holes
     pub edges: Vec<u32>,
+    pub holes: Vec<Vec<Vec2>>,
+    pub hole_edges: Vec<Vec<u32>>,
+    pub spans: Vec<RegionSpan>,
+    pub hole_spans: Vec<Vec<RegionSpan>>,
 }
 
-fn polygon_area(poly: &[Vec2]) -> f32 {
+/// A piece of an edge on a region boundary, listed in boundary order.
+///
+/// `t_start <= t_end` are edge parameters: [0, 1] for lines and cubics and
+/// [0, segment count] for polylines. `forward` is false when the boundary runs
+/// from `t_end` back to `t_start`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegionSpan {
+    pub edge: u32,
+    pub t_start: f32,
+    pub t_end: f32,
+    pub forward: bool,
+}
+
+/// Gap in flattened position under which consecutive half-edges of the same
+/// edge are joined into one span.
+const FLAT_JOIN_EPS: f32 = 1e-4;
+
+pub(crate) fn polygon_area(poly: &[Vec2]) -> f32 {
     let mut a = 0.0f32;
     for i in 0..poly.len() {
         let j = (i + 1) % poly.len();
@@ -88,7 +117,7 @@ fn region_key_from_edges(seq: &[u32]) -> u32 {
     hash
 }
 
-fn polygon_bbox(points: &[Vec2]) -> (f32, f32, f32, f32) {
+pub(crate) fn polygon_bbox(points: &[Vec2]) -> (f32, f32, f32, f32) {
     let mut minx = f32::INFINITY;
     let mut miny = f32::INFINITY;
     let mut maxx = f32::NEG_INFINITY;
@@ -123,6 +152,10 @@ fn bbox_union(
     }
 }
 
+fn bbox_contains(outer: (f32, f32, f32, f32), inner: (f32, f32, f32, f32)) -> bool {
+    outer.0 <= inner.0 && outer.1 <= inner.1 && outer.2 >= inner.2 && outer.3 >= inner.3
+}
+
 fn bbox_intersects(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> bool {
     let (ax0, ay0, ax1, ay1) = a;
     let (bx0, by0, bx1, by1) = b;
@@ -133,13 +166,18 @@ fn bbox_pad(b: (f32, f32, f32, f32), pad: f32) -> (f32, f32, f32, f32) {
     (b.0 - pad, b.1 - pad, b.2 + pad, b.3 + pad)
 }
 
-fn region_to_cache_face(region: &Region) -> RegionFaceCache {
+pub(crate) fn region_to_cache_face(region: &Region) -> RegionFaceCache {
     RegionFaceCache {
         key: region.key,
+        scope: region.scope,
         area: region.area,
         bbox: polygon_bbox(&region.points),
         points: region.points.clone(),
         edges: region.edges.clone(),
+        holes: region.holes.clone(),
+        hole_edges: region.hole_edges.clone(),
+        spans: region.spans.clone(),
+        hole_spans: region.hole_spans.clone(),
     }
 }
 
@@ -148,13 +186,440 @@ fn cache_faces_to_regions(faces: &[RegionFaceCache]) -> Vec<Region> {
         .iter()
         .map(|f| Region {
             key: f.key,
+            scope: f.scope,
             area: f.area,
             points: f.points.clone(),
             edges: f.edges.clone(),
+            holes: f.holes.clone(),
+            hole_edges: f.hole_edges.clone(),
+            spans: f.spans.clone(),
+            hole_spans: f.hole_spans.clone(),
         })
         .collect()
 }
 
+/// Whether a point lies inside a face's outer ring and outside all of its holes.
+pub(crate) fn point_in_face(x: f32, y: f32, points: &[Vec2], holes: &[Vec<Vec2>]) -> bool {
+    point_in_polygon_nonzero(x, y, points)
+        && !holes.iter().any(|h| point_in_polygon_nonzero(x, y, h))
+}
+
+/// A point strictly inside a face (outside its holes), used as a containment
+/// probe. Scans a few horizontal lines and takes the middle of the widest
+/// interior span; falls back to the centroid.
+pub(crate) fn face_interior_point(points: &[Vec2], holes: &[Vec<Vec2>]) -> Vec2 {
+    let (_, miny, _, maxy) = polygon_bbox(points);
+    let h = maxy - miny;
+    let mut best: Option<(f32, Vec2)> = None;
+    for frac in [0.5f32, 0.25, 0.75, 0.125, 0.875] {
+        // Nudge off the exact fraction so the scanline rarely hits a vertex
+        let y = miny + h * (frac + 0.0137);
+        let mut xs: Vec<f32> = Vec::new();
+        for ring in std::iter::once(points).chain(holes.iter().map(|r| r.as_slice())) {
+            for i in 0..ring.len() {
+                let a = ring[i];
+                let b = ring[(i + 1) % ring.len()];
+                if (a.y <= y) != (b.y <= y) {
+                    xs.push(a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y));
+                }
+            }
+        }
+        xs.sort_by(|a, b| a.total_cmp(b));
+        for w in xs.windows(2) {
+            let width = w[1] - w[0];
+            let mid = Vec2 {
+                x: 0.5 * (w[0] + w[1]),
+                y,
+            };
+            if best.is_none_or(|(bw, _)| width > bw) && point_in_face(mid.x, mid.y, points, holes) {
+                best = Some((width, mid));
+            }
+        }
+        if best.is_some() {
+            break;
+        }
+    }
+    best.map(|(_, p)| p).unwrap_or_else(|| {
+        let (cx, cy) = polygon_centroid(points);
+        Vec2 { x: cx, y: cy }
+    })
+}
+
+/// Bounding box and area of a bounded face.
+type FaceBounds = ((f32, f32, f32, f32), f32);
+
+/// Index of the smallest bounded face strictly enclosing `ring`.
+fn enclosing_face(ring: &Region, bounded: &[Region], outer: &[FaceBounds]) -> Option<usize> {
+    let ring_bbox = polygon_bbox(&ring.points);
+    let mut best: Option<usize> = None;
+    for (i, face) in bounded.iter().enumerate() {
+        let (bbox, area) = outer[i];
+        if face.scope != ring.scope || area <= ring.area || !bbox_contains(bbox, ring_bbox) {
+            continue;
+        }
+        if best.is_some_and(|b| outer[b].1 <= area) {
+            continue;
+        }
+        // Rings of the face's own component only touch its boundary
+        let probe = ring
+            .points
+            .iter()
+            .find(|p| !point_on_polygon_edge(p.x, p.y, &face.points, EPS_POS));
+        if probe.is_some_and(|p| point_in_polygon_nonzero(p.x, p.y, &face.points)) {
+            best = Some(i);
+        }
+    }
+    best
+}
+
+/// Hole nesting state between calls to `step`, so region jobs can find
+/// enclosing faces a chunk of rings at a time. `nest_faces` and
+/// `nest_cycles` run it in one go.
+pub(crate) struct HoleNesting {
+    faces: Vec<Region>,
+    outer: Vec<FaceBounds>,
+    /// Rings to place; empty when nesting simple cycles into each other
+    rings: Vec<Region>,
+    cycles: bool,
+    parents: Vec<Option<usize>>,
+}
+
+impl HoleNesting {
+    /// Nest the output of a face walk (see `nest_faces`).
+    pub(crate) fn faces(walked: Vec<Region>) -> Self {
+        let (rings, mut bounded): (Vec<Region>, Vec<Region>) =
+            walked.into_iter().partition(|f| f.area > 0.0);
+        for face in &mut bounded {
+            face.area = -face.area;
+        }
+        Self::with_rings(bounded, rings, false)
+    }
+
+    /// Nest simple-cycle fallback faces (see `nest_cycles`).
+    pub(crate) fn cycles(mut cycles: Vec<Region>) -> Self {
+        for c in &mut cycles {
+            c.area = c.area.abs();
+        }
+        Self::with_rings(cycles, Vec::new(), true)
+    }
+
+    fn with_rings(faces: Vec<Region>, rings: Vec<Region>, cycles: bool) -> Self {
+        let outer = faces
+            .iter()
+            .map(|f| (polygon_bbox(&f.points), f.area))
+            .collect();
+        HoleNesting {
+            faces,
+            outer,
+            rings,
+            cycles,
+            parents: Vec::new(),
+        }
+    }
+
+    fn probes(&self) -> &[Region] {
+        if self.cycles {
+            &self.faces
+        } else {
+            &self.rings
+        }
+    }
+
+    /// Faces with positive areas; holes are attached only by `finish`.
+    pub(crate) fn pending_faces(&self) -> &[Region] {
+        &self.faces
+    }
+
+    pub(crate) fn done(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.probes().len()
+    }
+
+    /// Place up to `max_rings` more rings; true once every ring is placed.
+    pub(crate) fn step(&mut self, max_rings: usize) -> bool {
+        let start = self.parents.len();
+        let end = start.saturating_add(max_rings).min(self.total());
+        // Each ring's enclosing face is found independently
+        let found = par_map(&self.probes()[start..end], |ring| {
+            enclosing_face(ring, &self.faces, &self.outer)
+        });
+        self.parents.extend(found);
+        end == self.total()
+    }
+
+    /// Attach each placed ring to its enclosing face, in ring order.
+    pub(crate) fn finish(self) -> Vec<Region> {
+        let HoleNesting {
+            mut faces,
+            outer,
+            rings,
+            cycles,
+            parents,
+        } = self;
+        if cycles {
+            for (i, parent) in parents.into_iter().enumerate() {
+                let Some(p) = parent else { continue };
+                let (points, edges, spans) = (
+                    faces[i].points.clone(),
+                    faces[i].edges.clone(),
+                    faces[i].spans.clone(),
+                );
+                let face = &mut faces[p];
+                face.area -= outer[i].1;
+                face.holes.push(points);
+                face.hole_edges.push(edges);
+                face.hole_spans.push(spans);
+            }
+        } else {
+            for (ring, parent) in rings.into_iter().zip(parents) {
+                let Some(p) = parent else { continue };
+                let face = &mut faces[p];
+                face.area -= ring.area;
+                face.holes.push(ring.points);
+                face.hole_edges.push(ring.edges);
+                face.hole_spans.push(ring.spans);
+            }
+        }
+        faces
+    }
+
+    fn run(mut self) -> Vec<Region> {
+        self.step(usize::MAX);
+        self.finish()
+    }
+}
+
+/// Build the face containment hierarchy after a face walk. Rings only nest
+/// into faces of their own region scope.
+///
+/// Bounded faces come out of the walk clockwise (negative area), while each
+/// connected component also yields its outer boundary counter-clockwise. Those
+/// outer boundaries become hole rings of the smallest bounded face enclosing
+/// them and are dropped otherwise, since they only bound the unbounded face.
+/// Areas of the returned faces are positive.
+pub(crate) fn nest_faces(faces: Vec<Region>) -> Vec<Region> {
+    HoleNesting::faces(faces).run()
+}
+
+/// Nest simple-cycle fallback faces: each cycle stays a face and also
+/// becomes a hole ring of the smallest cycle of its scope strictly enclosing
+/// it. Areas of the returned faces are positive.
+pub(crate) fn nest_cycles(cycles: Vec<Region>) -> Vec<Region> {
+    HoleNesting::cycles(cycles).run()
+}
+
+/// Join consecutive half-edges of a face cycle into runs of
+/// `(edge, flat_start, flat_end)` over each edge's flattened polyline.
+fn merge_flat_runs(cycle: &[(u32, f32, f32)]) -> Vec<(u32, f32, f32)> {
+    let joins = |a: &(u32, f32, f32), b: &(u32, f32, f32)| {
+        a.0 == b.0 && (a.2 - b.1).abs() <= FLAT_JOIN_EPS && (a.2 - a.1) * (b.2 - b.1) > 0.0
+    };
+    let mut runs: Vec<(u32, f32, f32)> = Vec::new();
+    for h in cycle {
+        match runs.last_mut() {
+            Some(last) if joins(last, h) => last.2 = h.2,
+            _ => runs.push(*h),
+        }
+    }
+    // The walk may start mid-edge
+    if runs.len() >= 2 && joins(&runs[runs.len() - 1], &runs[0]) {
+        let last = runs.pop().unwrap();
+        runs[0].1 = last.1;
+    }
+    runs
+}
+
+fn edge_max_param(kind: &EdgeKind) -> f32 {
+    match kind {
+        EdgeKind::Polyline { points } => (points.len() + 1) as f32,
+        _ => 1.0,
+    }
+}
+
+fn edge_cubic(g: &Graph, eid: u32) -> Option<CubicBezier> {
+    let e = g.edges.get(eid as usize).and_then(|e| e.as_ref())?;
+    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    match &e.kind {
+        EdgeKind::Cubic { ha, hb, .. } => Some(CubicBezier::new(
+            Vec2 { x: a.x, y: a.y },
+            Vec2 {
+                x: a.x + ha.x,
+                y: a.y + ha.y,
+            },
+            Vec2 {
+                x: b.x + hb.x,
+                y: b.y + hb.y,
+            },
+            Vec2 { x: b.x, y: b.y },
+        )),
+        _ => None,
+    }
+}
+
+/// Map flattened runs back to edge parameters.
+fn resolve_spans(g: &Graph, runs: &[(u32, f32, f32)]) -> Vec<RegionSpan> {
+    let cache = g.flatten_cache.borrow();
+    let mut spans = Vec::with_capacity(runs.len());
+    for &(eid, f0, f1) in runs {
+        if !matches!(g.edges.get(eid as usize), Some(Some(_))) {
+            continue;
+        }
+        // Lines and polylines are flattened vertex-for-vertex, so the flat
+        // position already is the parameter
+        let (mut t0, mut t1) = (f0, f1);
+        if let Some(curve) = edge_cubic(g, eid) {
+            let pts = match cache.as_ref().and_then(|fc| fc.per_edge.get(&eid)) {
+                Some(pts) => pts.clone(),
+                None => flatten_points_for_edge(g, eid).unwrap_or_default(),
+            };
+            let segs: Vec<(Vec2, Vec2)> = pts.windows(2).map(|w| (w[0], w[1])).collect();
+            let n = segs.len();
+            let to_t = |f: f32| {
+                if n == 0 {
+                    f
+                } else if f <= FLAT_JOIN_EPS {
+                    0.0
+                } else if f >= n as f32 - FLAT_JOIN_EPS {
+                    1.0
+                } else {
+                    let seg = (f.floor() as usize).min(n - 1);
+                    flat_position_to_cubic_t(
+                        &curve,
+                        &segs,
+                        seg,
+                        f - seg as f32,
+                        g.flatten_tol * 0.1,
+                    )
+                }
+            };
+            t0 = to_t(f0);
+            t1 = to_t(f1);
+        }
+        spans.push(RegionSpan {
+            edge: eid,
+            t_start: t0.min(t1),
+            t_end: t0.max(t1),
+            forward: f1 > f0,
+        });
+    }
+    spans
+}
+
+/// Append one closed ring of spans as path commands, cutting cubic pieces
+/// from the source curves instead of using the flattened points.
+fn push_ring_commands(g: &Graph, spans: &[RegionSpan], out: &mut Vec<PathCommand>) {
+    let mut started = false;
+    for span in spans {
+        let Some(e) = g.edges.get(span.edge as usize).and_then(|e| e.as_ref()) else {
+            continue;
+        };
+        let (Some(a), Some(b)) = (
+            g.nodes.get(e.a as usize).and_then(|n| *n),
+            g.nodes.get(e.b as usize).and_then(|n| *n),
+        ) else {
+            continue;
+        };
+        let (ts, te) = if span.forward {
+            (span.t_start, span.t_end)
+        } else {
+            (span.t_end, span.t_start)
+        };
+        match &e.kind {
+            EdgeKind::Line => {
+                let at = |t: f32| (a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t);
+                if !started {
+                    let (x, y) = at(ts);
+                    out.push(PathCommand::MoveTo(x, y));
+                    started = true;
+                }
+                let (x, y) = at(te);
+                out.push(PathCommand::LineTo(x, y));
+            }
+            EdgeKind::Cubic { .. } => {
+                let Some(curve) = edge_cubic(g, span.edge) else {
+                    continue;
+                };
+                let mut sub = curve.subcurve(span.t_start, span.t_end);
+                if !span.forward {
+                    sub = CubicBezier::new(sub.p3, sub.p2, sub.p1, sub.p0);
+                }
+                if !started {
+                    out.push(PathCommand::MoveTo(sub.p0.x, sub.p0.y));
+                    started = true;
+                }
+                out.push(PathCommand::CubicTo(
+                    sub.p1.x, sub.p1.y, sub.p2.x, sub.p2.y, sub.p3.x, sub.p3.y,
+                ));
+            }
+            EdgeKind::Polyline { points } => {
+                let mut pts = Vec::with_capacity(points.len() + 2);
+                pts.push(Vec2 { x: a.x, y: a.y });
+                pts.extend(points.iter().copied());
+                pts.push(Vec2 { x: b.x, y: b.y });
+                let at = |t: f32| {
+                    let i = (t.floor() as usize).min(pts.len() - 2);
+                    let u = t - i as f32;
+                    (
+                        pts[i].x + (pts[i + 1].x - pts[i].x) * u,
+                        pts[i].y + (pts[i + 1].y - pts[i].y) * u,
+                    )
+                };
+                if !started {
+                    let (x, y) = at(ts);
+                    out.push(PathCommand::MoveTo(x, y));
+                    started = true;
+                }
+                // Interior vertices strictly between the span ends
+                let lo = span.t_start.floor() as usize + 1;
+                let hi = span.t_end.ceil() as usize;
+                let mut inner: Vec<usize> = (lo..hi).collect();
+                if !span.forward {
+                    inner.reverse();
+                }
+                for i in inner {
+                    out.push(PathCommand::LineTo(pts[i].x, pts[i].y));
+                }
+                let (x, y) = at(te);
+                out.push(PathCommand::LineTo(x, y));
+            }
+        }
+    }
+    if started {
+        out.push(PathCommand::Close);
+    }
+}
+
+/// Exact boundary path of a region (outer ring, then one subpath per hole).
+pub(crate) fn region_path_commands(g: &Graph, region: &Region) -> Vec<PathCommand> {
+    let mut out = Vec::new();
+    push_ring_commands(g, &region.spans, &mut out);
+    for ring in &region.hole_spans {
+        push_ring_commands(g, ring, &mut out);
+    }
+    out
+}
+
+/// Format path commands as an SVG `d` attribute.
+pub fn path_commands_to_d(cmds: &[PathCommand]) -> String {
+    let mut parts: Vec<String> = Vec::with_capacity(cmds.len());
+    for c in cmds {
+        parts.push(match *c {
+            PathCommand::MoveTo(x, y) => format!("M {} {}", x, y),
+            PathCommand::LineTo(x, y) => format!("L {} {}", x, y),
+            PathCommand::QuadTo(cx, cy, x, y) => format!("Q {} {} {} {}", cx, cy, x, y),
+            PathCommand::CubicTo(c1x, c1y, c2x, c2y, x, y) => {
+                format!("C {} {} {} {} {} {}", c1x, c1y, c2x, c2y, x, y)
+            }
+            PathCommand::Close => "Z".to_string(),
+        });
+    }
+    parts.join(" ")
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct FlattenIndex {
     pub tol: f32,
@@ -175,10 +640,15 @@ fn choose_cell_size_for_regions(flatten_tol: f32) -> f32 {
     (flatten_tol * 8.0).clamp(4.0, 64.0)
 }
 
-fn flatten_points_for_edge(g: &Graph, eid: u32) -> Option<Vec<Vec2>> {
+pub(crate) fn flatten_points_for_edge(g: &Graph, eid: u32) -> Option<Vec<Vec2>> {
     let e = g.edges.get(eid as usize).and_then(|e| e.as_ref())?;
-    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
-    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    flatten_edge(&g.nodes, e, g.flatten_tol)
+}
+
+/// Flatten one edge from plain node data (shareable across worker threads).
+fn flatten_edge(nodes: &[Option<Node>], e: &Edge, tol: f32) -> Option<Vec<Vec2>> {
+    let a = nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = nodes.get(e.b as usize).and_then(|n| *n)?;
     match &e.kind {
         EdgeKind::Line => Some(vec![Vec2 { x: a.x, y: a.y }, Vec2 { x: b.x, y: b.y }]),
         EdgeKind::Cubic { ha, hb, .. } => {
@@ -188,19 +658,7 @@ fn flatten_points_for_edge(g: &Graph, eid: u32) -> Option<Vec<Vec2>> {
             let p2y = b.y + hb.y;
             let mut pts = Vec::new();
             pts.push(Vec2 { x: a.x, y: a.y });
-            flatten_cubic(
-                &mut pts,
-                a.x,
-                a.y,
-                p1x,
-                p1y,
-                p2x,
-                p2y,
-                b.x,
-                b.y,
-                g.flatten_tol,
-                0,
-            );
+            flatten_cubic(&mut pts, a.x, a.y, p1x, p1y, p2x, p2y, b.x, b.y, tol, 0);
             Some(pts)
         }
         EdgeKind::Polyline { points } => {
@@ -213,6 +671,19 @@ fn flatten_points_for_edge(g: &Graph, eid: u32) -> Option<Vec<Vec2>> {
     }
 }
 
+/// Flattened polyline of an edge (endpoints included), cached when possible.
+pub(crate) fn edge_polyline(g: &Graph, eid: u32) -> Option<Vec<Vec2>> {
+    if let Some(pts) = g
+        .flatten_cache
+        .borrow()
+        .as_ref()
+        .and_then(|fc| fc.per_edge.get(&eid))
+    {
+        return Some(pts.clone());
+    }
+    flatten_points_for_edge(g, eid)
+}
+
 pub(crate) fn ensure_flatten_cache(g: &mut Graph) {
     let mut guard = g.flatten_cache.borrow_mut();
     let rebuild = guard
@@ -224,13 +695,17 @@ pub(crate) fn ensure_flatten_cache(g: &mut Graph) {
             built_ver: g.geom_version(),
             per_edge: HashMap::new(),
         };
-        for (eid, e_opt) in g.edges.iter().enumerate() {
-            if e_opt.is_none() {
-                continue;
-            }
-            let eid = eid as u32;
-            if let Some(poly) = flatten_points_for_edge(g, eid) {
-                fc.per_edge.insert(eid, poly);
+        let live: Vec<(u32, &Edge)> = g
+            .edges
+            .iter()
+            .enumerate()
+            .filter_map(|(eid, e)| e.as_ref().map(|e| (eid as u32, e)))
+            .collect();
+        let (nodes, tol) = (&g.nodes, g.flatten_tol);
+        let polys = par_map(&live, |(_, e)| flatten_edge(nodes, e, tol));
+        for ((eid, _), poly) in live.iter().zip(polys) {
+            if let Some(poly) = poly {
+                fc.per_edge.insert(*eid, poly);
             }
         }
         *guard = Some(fc);
@@ -362,143 +837,289 @@ fn ensure_flatten_index(g: &mut Graph) {
     }
 }
 
-fn regions_from_plan(plan: &Planarized) -> Vec<Region> {
-    #[derive(Clone, Copy)]
-    struct Pt {
-        x: f32,
-        y: f32,
-    }
-    let verts: Vec<Pt> = plan
-        .verts
+fn regions_from_plan(g: &Graph, plan: &Planarized, scopes: &EdgeScopes) -> Vec<Region> {
+    let mut walk = FaceWalk::new(plan, scopes.clone());
+    walk.walk(g, plan, usize::MAX);
+    nest_faces(walk.faces)
+}
+
+/// Which half-edges the face walk uses: the first one per directed vertex
+/// pair.
+///
+/// Edges drawn over each other (a shape duplicated in place, two shapes
+/// sharing a side) flatten onto the same vertex pairs. Walking every copy
+/// turns each overlap into a zero-area face between the copies and repeats
+/// the faces on either side, so later copies are marked used up front. Only
+/// exact overlaps collapse; partial ones were already split onto shared
+/// vertices by planarization.
+fn first_half_edges(plan: &Planarized) -> Vec<bool> {
+    let mut seen: HashSet<(usize, usize)> = HashSet::new();
+    plan.half_from
         .iter()
-        .map(|(x, y)| Pt { x: *x, y: *y })
-        .collect();
-    let half_from = &plan.half_from;
-    let half_to = &plan.half_to;
-    let half_eid = &plan.half_eid;
-
-    let m = half_from.len();
-    let mut adj: Vec<Vec<(usize, f32, usize)>> = vec![Vec::new(); verts.len()];
-    for i in 0..m {
-        let u = half_from[i];
-        let v = half_to[i];
-        let ang = (verts[v].y - verts[u].y).atan2(verts[v].x - verts[u].x);
-        adj[u].push((v, ang, i));
-    }
-    for lst in &mut adj {
-        lst.sort_by(|a, b| {
-            a.1.partial_cmp(&b.1)
-                .unwrap()
-                .then(a.0.cmp(&b.0))
-                .then(a.2.cmp(&b.2))
-        });
-    }
-    let mut idx_map: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
-    for i in 0..m {
-        idx_map
-            .entry((half_from[i], half_to[i]))
-            .or_default()
-            .push(i);
-    }
-    let mut used = vec![false; m];
-    let mut regions = Vec::new();
-    for i_start in 0..m {
-        if used[i_start] {
-            continue;
-        }
-        let mut i_he = i_start;
-        let mut cycle: Vec<usize> = Vec::new();
-        let mut cycle_eids: Vec<u32> = Vec::new();
-        let mut guard = 0usize;
-        loop {
-            used[i_he] = true;
-            let v = half_to[i_he];
-            let u = half_from[i_he];
-            cycle.push(u);
-            cycle_eids.push(half_eid[i_he]);
-            let lst = &adj[v];
-            if lst.is_empty() {
-                break;
+        .zip(&plan.half_to)
+        .map(|(&u, &v)| seen.insert((u, v)))
+        .collect()
+}
+
+/// Left-hand face walk over a `Planarized`, resumable between start half-edges.
+///
+/// The turn taken after each half-edge depends only on the plan, so it is
+/// found for all of them up front (in parallel under `threads`). Tracing the
+/// cycles stays serial and cheap; the faces found in each call are then
+/// built in parallel, except for their spans, which read the flatten cache.
+pub(crate) struct FaceWalk {
+    used: Vec<bool>,
+    // Half-edge the walk turns onto after each one, if any
+    succ: Vec<Option<usize>>,
+    next: usize,
+    scopes: EdgeScopes,
+    /// Faces found so far, before hole nesting.
+    pub faces: Vec<Region>,
+}
+
+impl FaceWalk {
+    pub(crate) fn new(plan: &Planarized, scopes: EdgeScopes) -> Self {
+        let verts = &plan.verts;
+        let half_from = &plan.half_from;
+        let half_to = &plan.half_to;
+        let m = half_from.len();
+        let keep = first_half_edges(plan);
+        let mut adj: Vec<Vec<(usize, f32, usize)>> = vec![Vec::new(); verts.len()];
+        for i in 0..m {
+            if !keep[i] {
+                continue;
             }
-            let rev_idx = idx_map.get(&(v, u)).and_then(|cands| {
-                cands
-                    .iter()
-                    .copied()
-                    .find(|&c| half_from[c] == v && half_to[c] == u)
+            let u = half_from[i];
+            let v = half_to[i];
+            let ang = (verts[v].1 - verts[u].1).atan2(verts[v].0 - verts[u].0);
+            adj[u].push((v, ang, i));
+        }
+        for lst in &mut adj {
+            lst.sort_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap()
+                    .then(a.0.cmp(&b.0))
+                    .then(a.2.cmp(&b.2))
             });
-            if rev_idx.is_none() {
-                break;
+        }
+        let reverse: HashSet<(usize, usize)> = (0..m)
+            .filter(|&i| keep[i])
+            .map(|i| (half_from[i], half_to[i]))
+            .collect();
+        // Kept half-edges are one per vertex pair, so the adjacency entry
+        // names the half-edge to continue on
+        let halves: Vec<usize> = (0..m).collect();
+        let succ = par_map(&halves, |&i| {
+            let (u, v) = (half_from[i], half_to[i]);
+            let lst = &adj[v];
+            if !keep[i] || lst.is_empty() || !reverse.contains(&(v, u)) {
+                return None;
             }
-            let ang = (verts[u].y - verts[v].y).atan2(verts[u].x - verts[v].x);
+            let ang = (verts[u].1 - verts[v].1).atan2(verts[u].0 - verts[v].0);
             let mut idx = 0usize;
             while idx < lst.len() && lst[idx].1 <= ang + EPS_ANG {
                 idx += 1;
             }
-            let next = if idx == lst.len() { 0 } else { idx };
-            let (w, _, _) = lst[next];
-            if let Some(list) = idx_map.get(&(v, w)) {
-                if let Some(nhe) = list.iter().copied().find(|cand| !used[*cand]) {
-                    i_he = nhe;
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-            guard += 1;
-            if guard > 100_000 {
-                break;
-            }
-            if i_he == i_start {
-                break;
-            }
+            Some(lst[if idx == lst.len() { 0 } else { idx }].2)
+        });
+        let used: Vec<bool> = keep.iter().map(|k| !k).collect();
+        FaceWalk {
+            used,
+            succ,
+            next: 0,
+            scopes,
+            faces: Vec::new(),
         }
-        if cycle.len() >= 3 {
-            let mut poly = Vec::new();
-            for &idx in &cycle {
-                poly.push(Vec2 {
-                    x: verts[idx].x,
-                    y: verts[idx].y,
-                });
-            }
-            let area = polygon_area(&poly);
-            if area.abs() < EPS_FACE_AREA {
+    }
+
+    /// Start half-edges visited so far, out of `total()`.
+    pub(crate) fn done(&self) -> usize {
+        self.next
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.used.len()
+    }
+
+    /// Walk faces from up to `starts` more start half-edges; true once every
+    /// half-edge has been tried.
+    pub(crate) fn walk(&mut self, g: &Graph, plan: &Planarized, starts: usize) -> bool {
+        let m = plan.half_from.len();
+        let stop = self.next.saturating_add(starts).min(m);
+        let mut cycles: Vec<Vec<usize>> = Vec::new();
+        while self.next < stop {
+            let i_start = self.next;
+            self.next += 1;
+            if self.used[i_start] {
                 continue;
             }
-            let mut seq = Vec::new();
-            for &e in &cycle_eids {
-                if seq.last().copied() != Some(e) {
-                    seq.push(e);
+            let mut i_he = i_start;
+            let mut cycle: Vec<usize> = Vec::new();
+            let mut guard = 0usize;
+            loop {
+                self.used[i_he] = true;
+                cycle.push(i_he);
+                match self.succ[i_he] {
+                    Some(nhe) if !self.used[nhe] => i_he = nhe,
+                    _ => break,
+                }
+                guard += 1;
+                if guard > 100_000 {
+                    break;
+                }
+                if i_he == i_start {
+                    break;
                 }
             }
-            if seq.len() >= 2 && seq.first() == seq.last() {
-                seq.pop();
+            if cycle.len() >= 3 {
+                cycles.push(cycle);
             }
-            let key = region_key_from_edges(&seq);
-            regions.push(Region {
-                key,
-                points: poly,
-                area,
-                edges: seq,
+        }
+        let scopes = &self.scopes;
+        let built = par_map(&cycles, |cycle| face_from_cycle(plan, scopes, cycle));
+        for (face, runs) in built.into_iter().flatten() {
+            self.faces.push(Region {
+                spans: resolve_spans(g, &runs),
+                ..face
             });
         }
+        self.next >= m
     }
-    regions
+}
+
+/// Flattened piece of an edge: `(edge, t_start, t_end)`.
+type FlatRun = (u32, f32, f32);
+
+/// A walked cycle of half-edges as a face without spans, and its flattened
+/// runs to resolve them from; None for a degenerate cycle.
+fn face_from_cycle(
+    plan: &Planarized,
+    scopes: &EdgeScopes,
+    cycle: &[usize],
+) -> Option<(Region, Vec<FlatRun>)> {
+    let poly: Vec<Vec2> = cycle
+        .iter()
+        .map(|&he| {
+            let (x, y) = plan.verts[plan.half_from[he]];
+            Vec2 { x, y }
+        })
+        .collect();
+    let area = polygon_area(&poly);
+    if area.abs() < EPS_FACE_AREA {
+        return None;
+    }
+    let mut seq = Vec::new();
+    for &he in cycle {
+        let e = plan.half_eid[he];
+        if seq.last().copied() != Some(e) {
+            seq.push(e);
+        }
+    }
+    if seq.len() >= 2 && seq.first() == seq.last() {
+        seq.pop();
+    }
+    let flat: Vec<(u32, f32, f32)> = cycle
+        .iter()
+        .map(|&he| {
+            let (f0, f1) = plan.half_flat[he];
+            (plan.half_eid[he], f0, f1)
+        })
+        .collect();
+    // Half-edges never join across scopes, so any edge tells it
+    let scope = scopes.of(seq[0]);
+    let key = scopes.key(region_key_from_edges(&seq), scope);
+    let face = Region {
+        key,
+        scope,
+        points: poly,
+        area,
+        edges: seq,
+        holes: Vec::new(),
+        hole_edges: Vec::new(),
+        spans: Vec::new(),
+        hole_spans: Vec::new(),
+    };
+    Some((face, merge_flat_runs(&flat)))
 }
 
 fn rebuild_regions_full(g: &mut Graph) -> Vec<Region> {
     let mut regs = g.compute_regions();
     if regs.is_empty() {
-        regs = g.find_simple_cycles();
+        regs = scope_simple_cycles(g.find_simple_cycles(), &EdgeScopes::capture(g));
     }
+    store_region_cache(g, &regs);
+    regs
+}
+
+/// Replace the region cache with `regs` as of the current geometry version.
+fn store_region_cache(g: &mut Graph, regs: &[Region]) {
     let faces: Vec<RegionFaceCache> = regs.iter().map(region_to_cache_face).collect();
+    store_region_faces(g, faces);
+}
+
+fn store_region_faces(g: &mut Graph, faces: Vec<RegionFaceCache>) {
     g.region_cache.borrow_mut().replace(crate::RegionCache {
         faces,
         built_ver: g.geom_version(),
         tol: g.flatten_tol,
     });
     g.clear_dirty_flags();
-    regs
+}
+
+/// Install faces computed outside `compute_regions_incremental` (a region job
+/// or an off-thread snapshot) for the current geometry version. The flatten
+/// caches follow the dirty flags that installing clears, so they are brought
+/// up to date first.
+pub(crate) fn install_region_faces(g: &mut Graph, faces: Vec<RegionFaceCache>) {
+    ensure_flatten_cache(g);
+    ensure_flatten_index(g);
+    crate::algorithms::incremental::ensure_incr_plan(g);
+    store_region_faces(g, faces);
+    g.region_index.borrow_mut().take();
+}
+
+/// Faces of the visible edges, from scratch, kept apart by `scopes`. Reads
+/// only edge geometry and settings, so it also runs on the bare graph rebuilt
+/// from a `RegionSnapshot`.
+pub(crate) fn faces_from_graph(g: &Graph, scopes: &EdgeScopes) -> Vec<Region> {
+    let plan = planarize_scoped(g, g.intersect_backend, scopes);
+    let regions = regions_from_plan(g, &plan, scopes);
+    if regions.is_empty() {
+        return scope_simple_cycles(g.find_simple_cycles(), scopes);
+    }
+    regions
+}
+
+/// Simple-cycle fallback faces under `scopes`, nested like walked faces;
+/// cycles mixing scopes are dropped.
+pub(crate) fn scope_simple_cycles(cycles: Vec<Region>, scopes: &EdgeScopes) -> Vec<Region> {
+    nest_cycles(scope_cycles(cycles, scopes))
+}
+
+/// Tag simple cycles with their region scope, dropping cycles that mix scopes.
+pub(crate) fn scope_cycles(cycles: Vec<Region>, scopes: &EdgeScopes) -> Vec<Region> {
+    if !scopes.is_scoped() {
+        return cycles;
+    }
+    cycles
+        .into_iter()
+        .filter_map(|mut r| {
+            let scope = scopes.of(*r.edges.first()?);
+            if r.edges.iter().any(|&e| scopes.of(e) != scope) {
+                return None;
+            }
+            r.key = scopes.key(r.key, scope);
+            r.scope = scope;
+            Some(r)
+        })
+        .collect()
+}
+
+/// Whether `cache` holds the faces of the current geometry and tolerance.
+pub(crate) fn region_cache_fresh(g: &Graph, cache: &crate::RegionCache) -> bool {
+    cache.built_ver == g.geom_version()
+        && (cache.tol - g.flatten_tol).abs() <= f32::EPSILON
+        && !g.dirty.full
 }
 
 fn compute_regions_full(g: &mut Graph) -> Vec<Region> {
@@ -511,25 +1132,15 @@ fn compute_regions_full(g: &mut Graph) -> Vec<Region> {
     #[cfg(feature = "region_prof")]
     let cache_ms = t_cache.elapsed().as_secs_f64() * 1000.0;
 
-    #[cfg(feature = "region_prof")]
-    let t_plan = std::time::Instant::now();
-    let plan = planarize_graph(g);
-    #[cfg(feature = "region_prof")]
-    let plan_ms = t_plan.elapsed().as_secs_f64() * 1000.0;
-
     #[cfg(feature = "region_prof")]
     let t_faces = std::time::Instant::now();
-    let mut regions = regions_from_plan(&plan);
-    if regions.is_empty() {
-        regions = g.find_simple_cycles();
-    }
+    let regions = faces_from_graph(g, &EdgeScopes::capture(g));
     #[cfg(feature = "region_prof")]
     let faces_ms = t_faces.elapsed().as_secs_f64() * 1000.0;
     #[cfg(feature = "region_prof")]
     eprintln!(
-        "regions_full cache_ms={:.3} planarize_ms={:.3} faces_ms={:.3} total_ms={:.3}",
+        "regions_full cache_ms={:.3} faces_ms={:.3} total_ms={:.3}",
         cache_ms,
-        plan_ms,
         faces_ms,
         t_all.elapsed().as_secs_f64() * 1000.0
     );
@@ -577,6 +1188,10 @@ pub(crate) fn compute_regions_incremental(g: &mut Graph) -> Vec<Region> {
         }
         return rebuild_regions_full(g);
     }
+    // Local patching assumes a single planar map; scoped maps rebuild
+    if g.region_scope != RegionScope::All {
+        return rebuild_regions_full(g);
+    }
 
     let mut seed_edges: HashSet<u32> = HashSet::new();
     let mut removed_edges: HashSet<u32> = HashSet::new();
@@ -657,7 +1272,7 @@ pub(crate) fn compute_regions_incremental(g: &mut Graph) -> Vec<Region> {
     let mut candidate_edges: Vec<u32> = candidate_set
         .iter()
         .copied()
-        .filter(|eid| matches!(g.edges.get(*eid as usize), Some(Some(_))))
+        .filter(|&eid| g.edge_in_regions(eid))
         .collect();
     candidate_edges.sort_unstable();
 
@@ -688,9 +1303,9 @@ pub(crate) fn compute_regions_incremental(g: &mut Graph) -> Vec<Region> {
                     return regs;
                 }
             };
-        new_faces = regions_from_plan(&plan);
+        new_faces = regions_from_plan(g, &plan, &EdgeScopes::default());
         if new_faces.is_empty() {
-            new_faces = g.find_simple_cycles();
+            new_faces = scope_simple_cycles(g.find_simple_cycles(), &EdgeScopes::default());
         }
     }
 
@@ -708,12 +1323,45 @@ pub(crate) fn compute_regions_incremental(g: &mut Graph) -> Vec<Region> {
     for face in &new_faces {
         new_face_keys.insert(face.key);
     }
-    cache.faces.retain(|face| {
-        let edge_hit = face.edges.iter().any(|eid| removal_edges.contains(eid));
-        let bbox_hit = clip_bbox.map_or(false, |clip| bbox_intersects(face.bbox, clip));
-        let replaced = new_face_keys.contains(&face.key);
-        !(edge_hit || bbox_hit || replaced)
+    let (kept, dropped): (Vec<RegionFaceCache>, Vec<RegionFaceCache>) =
+        std::mem::take(&mut cache.faces)
+            .into_iter()
+            .partition(|face| {
+                let edge_hit = face
+                    .edges
+                    .iter()
+                    .chain(face.hole_edges.iter().flatten())
+                    .any(|eid| removal_edges.contains(eid));
+                let bbox_hit = clip_bbox.is_some_and(|clip| bbox_intersects(face.bbox, clip));
+                let replaced = new_face_keys.contains(&face.key);
+                !(edge_hit || bbox_hit || replaced)
+            });
+    cache.faces = kept;
+    // The local patch cannot see containment across its boundary: rebuild when a
+    // dropped face had holes or enclosed new faces, or a new face encloses a kept one
+    let nesting_changed = dropped.iter().any(|face| {
+        !face.holes.is_empty()
+            || (!new_face_keys.contains(&face.key)
+                && new_faces.iter().any(|n| {
+                    let nb = polygon_bbox(&n.points);
+                    bbox_contains(face.bbox, nb) && {
+                        let p = face_interior_point(&n.points, &n.holes);
+                        point_in_face(p.x, p.y, &face.points, &face.holes)
+                    }
+                }))
+    }) || new_faces.iter().any(|n| {
+        let nb = polygon_bbox(&n.points);
+        cache.faces.iter().any(|face| {
+            bbox_contains(nb, face.bbox) && {
+                let p = face_interior_point(&face.points, &face.holes);
+                point_in_face(p.x, p.y, &n.points, &n.holes)
+            }
+        })
     });
+    if nesting_changed {
+        drop(cache_guard);
+        return rebuild_regions_full(g);
+    }
     for face in &new_faces {
         cache.faces.push(region_to_cache_face(face));
     }
@@ -745,75 +1393,31 @@ pub fn get_regions_with_fill(g: &mut Graph) -> Vec<serde_json::Value> {
         filled: bool,
         color: Option<[u8; 4]>,
         points: Vec<f32>,
+        holes: Vec<Vec<f32>>,
+        spans: Vec<RegionSpan>,
+        hole_spans: Vec<Vec<RegionSpan>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        shapes: Option<Vec<u32>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scope: Option<u32>,
     }
 
     let mut regions = g.compute_regions_incremental();
     regions.sort_by(|a, b| a.key.cmp(&b.key));
 
     if g.last_geom_ver != g.geom_ver {
-        let mut new_prev: Vec<(u32, i32, i32, f32)> = Vec::with_capacity(regions.len());
-        for r in &regions {
-            let (cx, cy) = polygon_centroid(&r.points);
-            let qx = (cx * QUANT_SCALE).round() as i32;
-            let qy = (cy * QUANT_SCALE).round() as i32;
-            new_prev.push((r.key, qx, qy, r.area));
-        }
-        let mut new_fills = HashMap::new();
-        let old_prev = g.prev_regions.clone();
-        let mut claimed: HashMap<u32, bool> = HashMap::new();
-        let mut order: Vec<usize> = (0..new_prev.len()).collect();
-        order.sort_by(|&i, &j| {
-            new_prev[i]
-                .1
-                .cmp(&new_prev[j].1)
-                .then(new_prev[i].2.cmp(&new_prev[j].2))
-                .then(new_prev[i].3.partial_cmp(&new_prev[j].3).unwrap())
-                .then(new_prev[i].0.cmp(&new_prev[j].0))
-        });
-        for idx in order {
-            let (k_new, qx, qy, area_new) = new_prev[idx];
-            let mut best: Option<(u32, i64, f32)> = None;
-            for (k_old, oqx, oqy, area_old) in &old_prev {
-                if claimed.get(k_old).copied().unwrap_or(false) {
-                    continue;
-                }
-                let dx = (qx as i64) - (*oqx as i64);
-                let dy = (qy as i64) - (*oqy as i64);
-                let d2 = dx * dx + dy * dy;
-                let ad = (area_new - *area_old).abs();
-                best = match best {
-                    None => Some((*k_old, d2, ad)),
-                    Some((bk, bd, ba)) => {
-                        if d2 < bd
-                            || (d2 == bd
-                                && (ad < ba || ((ad - ba).abs() <= f32::EPSILON && *k_old < bk)))
-                        {
-                            Some((*k_old, d2, ad))
-                        } else {
-                            Some((bk, bd, ba))
-                        }
-                    }
-                };
-            }
-            let state = if let Some((old_key, _, _)) = best {
-                claimed.insert(old_key, true);
-                g.fills.get(&old_key).copied().unwrap_or(FillState {
-                    filled: true,
-                    color: None,
-                })
-            } else {
-                g.fills.get(&k_new).copied().unwrap_or(FillState {
-                    filled: true,
-                    color: None,
-                })
-            };
-            new_fills.insert(k_new, state);
-        }
-        g.fills = new_fills;
-        g.prev_regions = new_prev;
-        g.last_geom_ver = g.geom_ver;
+        remap_region_state(g, &regions);
     }
 
+    // Owners only matter (and are only reported) in shape mode
+    let by_shapes = g.region_fill_mode == RegionFillMode::Shapes;
+    let shape_fills = if by_shapes {
+        closed_shape_fills(g)
+    } else {
+        Vec::new()
+    };
+    let scoped = g.region_scope != RegionScope::All;
+    let masked = g.layer_system.has_masks();
     regions
         .into_iter()
         .map(|r| {
@@ -821,24 +1425,58 @@ pub fn get_regions_with_fill(g: &mut Graph) -> Vec<serde_json::Value> {
                 filled: true,
                 color: None,
             });
+            let shapes = by_shapes.then(|| face_owners(&shape_fills, &r));
+            // In shape mode a face needs an owning shape; its own fill state can
+            // still switch it off
+            let filled = st.filled && shapes.as_ref().is_none_or(|s| !s.is_empty());
+            // A group mask over the whole boundary can hide the face
+            let filled = filled && (!masked || region_shows(g, &r));
             let color = st.color.map(|c| [c.r, c.g, c.b, c.a]);
-            let mut pts = Vec::with_capacity(r.points.len() * 2);
-            for p in &r.points {
-                pts.push(p.x);
-                pts.push(p.y);
-            }
+            let flat = |ring: &[Vec2]| -> Vec<f32> {
+                let mut pts = Vec::with_capacity(ring.len() * 2);
+                for p in ring {
+                    pts.push(p.x);
+                    pts.push(p.y);
+                }
+                pts
+            };
             serde_json::to_value(RegionSer {
                 key: r.key,
                 area: r.area,
-                filled: st.filled,
+                filled,
                 color,
-                points: pts,
+                points: flat(&r.points),
+                holes: r.holes.iter().map(|h| flat(h)).collect(),
+                spans: r.spans,
+                hole_spans: r.hole_spans,
+                shapes,
+                scope: scoped.then_some(r.scope),
             })
             .unwrap()
         })
         .collect()
 }
 
+/// Whether the masks shared by a region's boundary edges let its interior
+/// through
+fn region_shows(g: &Graph, r: &Region) -> bool {
+    let edges: Vec<u32> = r
+        .spans
+        .iter()
+        .chain(r.hole_spans.iter().flatten())
+        .map(|s| s.edge)
+        .collect();
+    let p = face_interior_point(&r.points, &r.holes);
+    region_unmasked_at(g, &edges, p.x, p.y)
+}
+
+/// Exact boundary path of the region with `key`, if it exists.
+pub fn region_path(g: &mut Graph, key: u32) -> Option<Vec<PathCommand>> {
+    let regions = g.compute_regions_incremental();
+    let region = regions.iter().find(|r| r.key == key)?;
+    Some(region_path_commands(g, region))
+}
+
 impl Graph {
     pub(crate) fn compute_regions(&mut self) -> Vec<Region> {
         compute_regions_full(self)
@@ -849,173 +1487,201 @@ impl Graph {
     }
 
     pub(crate) fn find_simple_cycles(&self) -> Vec<Region> {
+        let mut cycles = SimpleCycles::new(self);
+        cycles.step(self, usize::MAX);
+        cycles.finish()
+    }
+}
+
+/// Simple-cycle fallback state between calls to `step`: cycles through
+/// degree-2 nodes of the visible edges, traced from a chunk of start nodes
+/// at a time so region jobs can spread the fallback over several steps.
+pub(crate) struct SimpleCycles {
+    adj: HashMap<u32, Vec<u32>>,
+    /// First live edge (by id) joining each unordered node pair
+    between: HashMap<(u32, u32), u32>,
+    starts: Vec<u32>,
+    next: usize,
+    visited: HashSet<u32>,
+    regions: Vec<Region>,
+}
+
+impl SimpleCycles {
+    pub(crate) fn new(g: &Graph) -> Self {
         let mut adj: HashMap<u32, Vec<u32>> = HashMap::new();
-        for (eid, e) in self.edges.iter().enumerate() {
-            if let Some(e) = e {
-                // Skip hidden edges (from invisible layers/groups)
-                if !self.layer_system.is_edge_visible(eid as u32) {
-                    continue;
-                }
-                if self.nodes.get(e.a as usize).and_then(|n| *n).is_none() {
-                    continue;
-                }
-                if self.nodes.get(e.b as usize).and_then(|n| *n).is_none() {
-                    continue;
-                }
-                adj.entry(e.a).or_default().push(e.b);
-                adj.entry(e.b).or_default().push(e.a);
+        let mut between: HashMap<(u32, u32), u32> = HashMap::new();
+        let live = |n: u32| g.nodes.get(n as usize).and_then(|n| *n).is_some();
+        for (eid, e) in g.edges.iter().enumerate() {
+            let Some(e) = e else { continue };
+            between
+                .entry((e.a.min(e.b), e.a.max(e.b)))
+                .or_insert(eid as u32);
+            // Skip hidden and construction edges
+            if !g.edge_in_regions(eid as u32) || !live(e.a) || !live(e.b) {
+                continue;
             }
+            adj.entry(e.a).or_default().push(e.b);
+            adj.entry(e.b).or_default().push(e.a);
         }
-        let mut visited: HashMap<u32, bool> = HashMap::new();
-        let mut regions = Vec::new();
-        for (&start, neigh) in adj.iter() {
-            if neigh.len() != 2 {
+        let mut starts: Vec<u32> = adj
+            .iter()
+            .filter(|(_, neigh)| neigh.len() == 2)
+            .map(|(&n, _)| n)
+            .collect();
+        starts.sort_unstable();
+        SimpleCycles {
+            adj,
+            between,
+            starts,
+            next: 0,
+            visited: HashSet::new(),
+            regions: Vec::new(),
+        }
+    }
+
+    pub(crate) fn done(&self) -> usize {
+        self.next
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Trace from up to `max_starts` more start nodes; true once all are done.
+    pub(crate) fn step(&mut self, g: &Graph, max_starts: usize) -> bool {
+        let end = self.next.saturating_add(max_starts).min(self.starts.len());
+        for k in self.next..end {
+            let start = self.starts[k];
+            if self.visited.contains(&start) {
                 continue;
             }
-            if visited.get(&start).copied().unwrap_or(false) {
-                continue;
+            if let Some(region) = self.trace(g, start) {
+                self.regions.push(region);
             }
-            let mut cycle_ids = Vec::new();
-            let mut prev = start;
-            let mut cur = start;
-            let mut guard = 0usize;
-            loop {
-                cycle_ids.push(cur);
-                visited.insert(cur, true);
-                let ns = adj.get(&cur).cloned().unwrap_or_default();
-                let mut found = None;
-                for n in ns {
-                    if n != prev {
-                        found = Some(n);
-                        break;
+        }
+        self.next = end;
+        end == self.starts.len()
+    }
+
+    pub(crate) fn finish(self) -> Vec<Region> {
+        self.regions
+    }
+
+    /// Follow degree-2 nodes from `start`; the face if the walk closes.
+    fn trace(&mut self, g: &Graph, start: u32) -> Option<Region> {
+        let mut cycle_ids = Vec::new();
+        let mut prev = start;
+        let mut cur = start;
+        let mut guard = 0usize;
+        loop {
+            cycle_ids.push(cur);
+            self.visited.insert(cur);
+            let found = self
+                .adj
+                .get(&cur)
+                .and_then(|ns| ns.iter().copied().find(|&n| n != prev));
+            let Some(nxt) = found else { break };
+            prev = cur;
+            cur = nxt;
+            guard += 1;
+            if guard > 10_000 || cur == start {
+                break;
+            }
+        }
+        if cycle_ids.len() < 3 || cur != start {
+            return None;
+        }
+        let mut poly = Vec::new();
+        let mut edge_seq = Vec::new();
+        let mut spans = Vec::new();
+        for i in 0..cycle_ids.len() {
+            let u = cycle_ids[i];
+            let v = cycle_ids[(i + 1) % cycle_ids.len()];
+            let nu = g.nodes.get(u as usize).and_then(|n| *n)?;
+            let nv = g.nodes.get(v as usize).and_then(|n| *n)?;
+            let eid = *self.between.get(&(u.min(v), u.max(v)))?;
+            let e = g.edges[eid as usize].as_ref()?;
+            match &e.kind {
+                EdgeKind::Line => {
+                    if poly.is_empty() {
+                        poly.push(Vec2 { x: nu.x, y: nu.y });
                     }
+                    poly.push(Vec2 { x: nv.x, y: nv.y });
                 }
-                if let Some(nxt) = found {
-                    prev = cur;
-                    cur = nxt;
-                } else {
-                    break;
-                }
-                guard += 1;
-                if guard > 10_000 {
-                    break;
-                }
-                if cur == start {
-                    break;
-                }
-            }
-            if cycle_ids.len() >= 3 && cur == start {
-                let mut poly = Vec::new();
-                let mut edge_seq = Vec::new();
-                for i in 0..cycle_ids.len() {
-                    let u = cycle_ids[i];
-                    let v = cycle_ids[(i + 1) % cycle_ids.len()];
-                    let nu = match self.nodes.get(u as usize).and_then(|n| *n) {
-                        Some(n) => n,
-                        None => {
-                            poly.clear();
-                            break;
-                        }
-                    };
-                    let nv = match self.nodes.get(v as usize).and_then(|n| *n) {
-                        Some(n) => n,
-                        None => {
-                            poly.clear();
-                            break;
-                        }
+                EdgeKind::Cubic { ha, hb, .. } => {
+                    let (ax, ay, bx, by, p1x, p1y, p2x, p2y) = if e.a == u {
+                        (
+                            nu.x,
+                            nu.y,
+                            nv.x,
+                            nv.y,
+                            nu.x + ha.x,
+                            nu.y + ha.y,
+                            nv.x + hb.x,
+                            nv.y + hb.y,
+                        )
+                    } else {
+                        (
+                            nv.x,
+                            nv.y,
+                            nu.x,
+                            nu.y,
+                            nv.x + hb.x,
+                            nv.y + hb.y,
+                            nu.x + ha.x,
+                            nu.y + ha.y,
+                        )
                     };
-                    let mut added = false;
-                    for (eid_idx, e) in self.edges.iter().enumerate() {
-                        if let Some(e) = e {
-                            if (e.a == u && e.b == v) || (e.a == v && e.b == u) {
-                                match &e.kind {
-                                    EdgeKind::Line => {
-                                        if poly.is_empty() {
-                                            poly.push(Vec2 { x: nu.x, y: nu.y });
-                                        }
-                                        poly.push(Vec2 { x: nv.x, y: nv.y });
-                                    }
-                                    EdgeKind::Cubic { ha, hb, .. } => {
-                                        let (ax, ay, bx, by, p1x, p1y, p2x, p2y) = if e.a == u {
-                                            (
-                                                nu.x,
-                                                nu.y,
-                                                nv.x,
-                                                nv.y,
-                                                nu.x + ha.x,
-                                                nu.y + ha.y,
-                                                nv.x + hb.x,
-                                                nv.y + hb.y,
-                                            )
-                                        } else {
-                                            (
-                                                nv.x,
-                                                nv.y,
-                                                nu.x,
-                                                nu.y,
-                                                nv.x + hb.x,
-                                                nv.y + hb.y,
-                                                nu.x + ha.x,
-                                                nu.y + ha.y,
-                                            )
-                                        };
-                                        if poly.is_empty() {
-                                            poly.push(Vec2 { x: ax, y: ay });
-                                        }
-                                        let mut pts = Vec::new();
-                                        flatten_cubic(
-                                            &mut pts,
-                                            ax,
-                                            ay,
-                                            p1x,
-                                            p1y,
-                                            p2x,
-                                            p2y,
-                                            bx,
-                                            by,
-                                            self.flatten_tol,
-                                            0,
-                                        );
-                                        for w in pts.into_iter().skip(1) {
-                                            poly.push(w);
-                                        }
-                                    }
-                                    EdgeKind::Polyline { points } => {
-                                        if poly.is_empty() {
-                                            poly.push(Vec2 { x: nu.x, y: nu.y });
-                                        }
-                                        for p in points {
-                                            poly.push(*p);
-                                        }
-                                        poly.push(Vec2 { x: nv.x, y: nv.y });
-                                    }
-                                }
-                                edge_seq.push(eid_idx as u32);
-                                added = true;
-                                break;
-                            }
-                        }
-                    }
-                    if !added {
-                        poly.clear();
-                        break;
+                    if poly.is_empty() {
+                        poly.push(Vec2 { x: ax, y: ay });
                     }
+                    let mut pts = Vec::new();
+                    flatten_cubic(
+                        &mut pts,
+                        ax,
+                        ay,
+                        p1x,
+                        p1y,
+                        p2x,
+                        p2y,
+                        bx,
+                        by,
+                        g.flatten_tol,
+                        0,
+                    );
+                    poly.extend(pts.into_iter().skip(1));
                 }
-                if !poly.is_empty() {
-                    let area = polygon_area(&poly);
-                    if area.abs() >= EPS_FACE_AREA {
-                        let key = region_key_from_edges(&edge_seq);
-                        regions.push(Region {
-                            key,
-                            points: poly,
-                            area,
-                            edges: edge_seq,
-                        });
+                EdgeKind::Polyline { points } => {
+                    if poly.is_empty() {
+                        poly.push(Vec2 { x: nu.x, y: nu.y });
                     }
+                    poly.extend(points.iter().copied());
+                    poly.push(Vec2 { x: nv.x, y: nv.y });
                 }
             }
+            edge_seq.push(eid);
+            spans.push(RegionSpan {
+                edge: eid,
+                t_start: 0.0,
+                t_end: edge_max_param(&e.kind),
+                forward: e.a == u,
+            });
         }
-        regions
+        let area = polygon_area(&poly);
+        if area.abs() < EPS_FACE_AREA {
+            return None;
+        }
+        Some(Region {
+            key: region_key_from_edges(&edge_seq),
+            scope: 0,
+            points: poly,
+            area,
+            edges: edge_seq,
+            holes: Vec::new(),
+            hole_edges: Vec::new(),
+            spans,
+            hole_spans: Vec::new(),
+        })
     }
 }
 
@@ -1079,6 +1745,232 @@ mod tests {
         );
     }
 
+    #[test]
+    fn incremental_patch_follows_intersect_backend() {
+        use crate::algorithms::planarize::IntersectBackend;
+        let keys = |rs: Vec<Region>| {
+            let mut k: Vec<u32> = rs.iter().map(|r| r.key).collect();
+            k.sort_unstable();
+            k
+        };
+        for backend in [IntersectBackend::Grid, IntersectBackend::Sweep] {
+            let mut g = Graph::new();
+            g.set_intersect_backend(backend);
+            let n: Vec<u32> = [(0.0, 0.0), (12.0, 0.0), (12.0, 10.0), (0.0, 10.0)]
+                .iter()
+                .map(|&(x, y)| g.add_node(x, y))
+                .collect();
+            for i in 0..4 {
+                g.add_edge(n[i], n[(i + 1) % 4]);
+            }
+            // A long chord splits the square in two
+            let (a, b) = (g.add_node(-200.0, 5.0), g.add_node(200.0, 5.0));
+            g.add_edge(a, b);
+            let _ = g.compute_regions_incremental();
+            // Moving a corner is patched locally, through the backend
+            assert!(g.move_node(n[2], 12.0, 13.0));
+            let inc = g.compute_regions_incremental();
+            assert_eq!(inc.len(), 2);
+            assert_eq!(keys(inc), keys(g.compute_regions()));
+        }
+    }
+
+    #[test]
+    fn concentric_ellipses_form_ring_with_hole() {
+        let mut g = Graph::new();
+        g.add_ellipse(0.0, 0.0, 50.0, 50.0);
+        g.add_ellipse(0.0, 0.0, 20.0, 20.0);
+        let rs = g.compute_regions();
+        assert_eq!(rs.len(), 2, "outer ring and inner disc");
+        let ring = rs.iter().find(|r| !r.holes.is_empty()).expect("ring face");
+        let disc = rs.iter().find(|r| r.holes.is_empty()).expect("disc face");
+        assert_eq!(ring.holes.len(), 1);
+        let expected = std::f32::consts::PI * (50.0 * 50.0 - 20.0 * 20.0);
+        assert!(
+            (ring.area - expected).abs() / expected < 0.02,
+            "ring area {}",
+            ring.area
+        );
+        assert!(disc.area > 0.0);
+        let p = face_interior_point(&ring.points, &ring.holes);
+        assert!(point_in_face(p.x, p.y, &ring.points, &ring.holes));
+        assert!(!point_in_face(0.0, 0.0, &ring.points, &ring.holes));
+    }
+
+    #[test]
+    fn chord_splits_face_in_two() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let a = g.add_node(-20.0, 50.0);
+        let b = g.add_node(120.0, 50.0);
+        g.add_edge(a, b);
+        let rs = g.compute_regions();
+        assert_eq!(rs.len(), 2);
+        for r in &rs {
+            assert!((r.area - 5000.0).abs() < 1.0, "half area {}", r.area);
+            assert!(r.holes.is_empty());
+        }
+    }
+
+    #[test]
+    fn dense_lattice_with_nested_squares() {
+        // Large enough to cross the parallel thresholds when `threads` is on
+        let n = 40;
+        let build = || {
+            let mut g = Graph::new();
+            for i in 0..n {
+                let p = i as f32 * 10.0;
+                let (a, b) = (g.add_node(p, -5.0), g.add_node(p, 395.0));
+                g.add_edge(a, b);
+                let (a, b) = (g.add_node(-5.0, p), g.add_node(395.0, p));
+                g.add_edge(a, b);
+            }
+            for i in 0..600 {
+                let x = 1000.0 + (i % 30) as f32 * 20.0;
+                let y = (i / 30) as f32 * 20.0;
+                g.add_rectangle(x, y, 10.0, 10.0, 0.0);
+            }
+            g.add_rectangle(990.0, -10.0, 620.0, 420.0, 0.0);
+            g
+        };
+        let rs = build().compute_regions();
+        assert_eq!(rs.len(), (n - 1) * (n - 1) + 600 + 1);
+        let frame = rs.iter().find(|r| r.holes.len() == 600).expect("frame");
+        assert!((frame.area - (620.0 * 420.0 - 600.0 * 100.0)).abs() < 1.0);
+        // Threaded chunks are merged in input order, so the faces match the
+        // serial pass. Face order and ring start points follow hash maps even
+        // serially, so faces are compared by key with their rings as sets.
+        let summary = |rs: &[Region]| {
+            let mut faces: Vec<_> = rs
+                .iter()
+                .map(|r| {
+                    let mut edges = r.edges.clone();
+                    edges.sort_unstable();
+                    let mut holes: Vec<Vec<u32>> = r.hole_edges.clone();
+                    holes.iter_mut().for_each(|h| h.sort_unstable());
+                    holes.sort();
+                    let mut pts: Vec<(u32, u32)> = std::iter::once(&r.points)
+                        .chain(&r.holes)
+                        .flatten()
+                        .map(|p| (p.x.to_bits(), p.y.to_bits()))
+                        .collect();
+                    pts.sort_unstable();
+                    (r.key, (r.area * 100.0).round() as i64, edges, holes, pts)
+                })
+                .collect();
+            faces.sort_unstable_by_key(|f| f.0);
+            faces
+        };
+        let serial = crate::algorithms::parallel::serially(|| build().compute_regions());
+        assert_eq!(summary(&rs), summary(&serial));
+    }
+
+    #[test]
+    fn spans_map_cut_ellipse_back_to_curves() {
+        let mut g = Graph::new();
+        g.add_ellipse(0.0, 0.0, 50.0, 30.0);
+        let a = g.add_node(-80.0, 10.0);
+        let b = g.add_node(80.0, 10.0);
+        g.add_edge(a, b);
+        let rs = g.compute_regions();
+        assert_eq!(rs.len(), 2);
+        let at = |sp: &RegionSpan, t: f32| -> Vec2 {
+            match edge_cubic(&g, sp.edge) {
+                Some(c) => c.eval(t),
+                None => {
+                    let (ax, ay) = (-80.0 + 160.0 * t, 10.0);
+                    Vec2 { x: ax, y: ay }
+                }
+            }
+        };
+        for r in &rs {
+            assert!(r.spans.iter().any(|sp| sp.t_start > 0.0 || sp.t_end < 1.0));
+            for (i, sp) in r.spans.iter().enumerate() {
+                assert!(sp.t_start <= sp.t_end);
+                let next = &r.spans[(i + 1) % r.spans.len()];
+                let end = at(sp, if sp.forward { sp.t_end } else { sp.t_start });
+                let start = at(
+                    next,
+                    if next.forward {
+                        next.t_start
+                    } else {
+                        next.t_end
+                    },
+                );
+                let d = ((end.x - start.x).powi(2) + (end.y - start.y).powi(2)).sqrt();
+                assert!(d < 0.5, "gap {} between spans", d);
+            }
+            let cmds = region_path_commands(&g, r);
+            assert!(matches!(cmds.first(), Some(PathCommand::MoveTo(..))));
+            assert!(matches!(cmds.last(), Some(PathCommand::Close)));
+            assert!(cmds.iter().any(|c| matches!(c, PathCommand::CubicTo(..))));
+        }
+    }
+
+    #[test]
+    fn ring_path_has_hole_subpath() {
+        let mut g = Graph::new();
+        g.add_ellipse(0.0, 0.0, 50.0, 50.0);
+        g.add_ellipse(0.0, 0.0, 20.0, 20.0);
+        let rs = g.compute_regions();
+        let ring = rs.iter().find(|r| !r.holes.is_empty()).expect("ring face");
+        assert_eq!(ring.hole_spans.len(), 1);
+        let d = g.get_region_path_d(ring.key).expect("path");
+        assert!(d.starts_with("M "));
+        assert_eq!(d.matches('Z').count(), 2);
+        assert_eq!(d.matches('C').count(), 8);
+    }
+
+    #[test]
+    fn coincident_edges_walk_as_one() {
+        // Two squares drawn over each other, and a third sharing one side:
+        // each overlap is walked once, so no duplicate or zero-area faces
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 50.0, 50.0, 0.0);
+        g.add_rectangle(0.0, 0.0, 50.0, 50.0, 0.0);
+        g.add_rectangle(50.0, 0.0, 50.0, 50.0, 0.0);
+        let regions = g.compute_regions();
+        assert_eq!(regions.len(), 2);
+        for r in &regions {
+            assert!((r.area.abs() - 2500.0).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn simple_cycle_fallback_nests_holes() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        g.add_rectangle(40.0, 40.0, 20.0, 20.0, 0.0);
+        let rs = scope_simple_cycles(g.find_simple_cycles(), &EdgeScopes::default());
+        assert_eq!(rs.len(), 2);
+        let outer = rs.iter().find(|r| !r.holes.is_empty()).expect("ring face");
+        assert!((outer.area - 9600.0).abs() < 1.0, "area {}", outer.area);
+        assert_eq!((outer.hole_edges.len(), outer.hole_spans.len()), (1, 1));
+        let inner = rs.iter().find(|r| r.holes.is_empty()).unwrap();
+        assert!((inner.area - 400.0).abs() < 1.0, "area {}", inner.area);
+        assert!(!point_in_face(50.0, 50.0, &outer.points, &outer.holes));
+    }
+
+    #[test]
+    fn incremental_keeps_holes_after_inner_move() {
+        let mut g = Graph::new();
+        g.add_rectangle(0.0, 0.0, 200.0, 200.0, 0.0);
+        let inner = g.add_rectangle(80.0, 80.0, 20.0, 20.0, 0.0);
+        let _ = g.compute_regions_incremental();
+        let n = inner.nodes[0];
+        let (x, y) = g.get_node(n).unwrap();
+        assert!(g.move_node(n, x - 3.0, y - 2.0));
+        let inc = g.compute_regions_incremental();
+        let full = g.compute_regions();
+        let holes = |rs: &[Region]| -> Vec<(u32, usize)> {
+            let mut v: Vec<(u32, usize)> = rs.iter().map(|r| (r.key, r.holes.len())).collect();
+            v.sort_unstable();
+            v
+        };
+        assert_eq!(holes(&inc), holes(&full));
+        assert!(inc.iter().any(|r| r.holes.len() == 1));
+    }
+
     #[test]
     fn self_touch_no_crash() {
         let mut g = Graph::new();
@@ -1139,4 +2031,40 @@ mod tests {
             "region keys must be stable under small jitter"
         );
     }
+
+    #[test]
+    fn construction_edges_skip_regions_and_export() {
+        let mut g = Graph::new();
+        let n: Vec<u32> = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+            .iter()
+            .map(|&(x, y)| g.add_node(x, y))
+            .collect();
+        for i in 0..4 {
+            g.add_edge(n[i], n[(i + 1) % 4]);
+        }
+        let a = g.add_node(-5.0, 5.0);
+        let b = g.add_node(15.0, 5.0);
+        let guide = g.add_edge(a, b).unwrap();
+        assert_eq!(g.get_regions().len(), 2);
+
+        assert!(g.set_edge_construction(guide, true));
+        assert_eq!(g.get_regions().len(), 1);
+        // Incremental edits near the guide still leave it out
+        assert!(g.move_node(n[2], 11.0, 10.0));
+        assert_eq!(g.get_regions().len(), 1);
+
+        assert!(matches!(
+            g.pick(12.0, 5.0, 1.0),
+            Some(crate::Pick::Edge { id, .. }) if id == guide
+        ));
+        assert_eq!(g.to_svg_paths().len(), 4);
+        assert!(!g.to_svg_document().contains("M -5 5"));
+        let arrays = g.get_edge_arrays();
+        let at = arrays.ids.iter().position(|&id| id == guide).unwrap();
+        assert_eq!(arrays.construction[at], 1);
+        assert_eq!(arrays.construction.iter().filter(|&&c| c == 1).count(), 1);
+
+        assert!(g.set_edge_construction(guide, false));
+        assert_eq!(g.get_regions().len(), 2);
+    }
 }
@@ -0,0 +1,492 @@
+//! Marquee and lasso selection.
+//!
+//! Node and edge candidates come from the `PickIndex` grid cells under the
+//! query's bounding box; exact tests then decide each one. Rectangles test
+//! cubics exactly (tight bounds for containment, control-hull subdivision for
+//! crossings); lassos test the flattened edge. Hidden edges, shapes and texts
+//! are skipped, and so are locked ones when `Graph::set_pick_skip_locked`
+//! asks for it; nodes whose every edge is skipped and shapes with any skipped
+//! edge go with them. Texts use their estimated box.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::algorithms::picking::{current_pick_index, query_rect};
+use crate::algorithms::regions::flatten_points_for_edge;
+use crate::algorithms::winding::point_in_polygon_evenodd;
+use crate::geometry::intersect::{intersect_segments, SegIntersection};
+use crate::geometry::tolerance::{EPS_DENOM, EPS_POS};
+use crate::model::{EdgeKind, GroupItem, SelectMode, Vec2};
+use crate::Graph;
+
+type Rect = (f32, f32, f32, f32);
+
+/// Subdivision stops once a cubic's control hull is this small (px).
+const HULL_EPS: f32 = 1e-3;
+const MAX_HULL_DEPTH: u32 = 32;
+
+/// Ids hit by a selection query, each list ascending.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SelectionResult {
+    pub nodes: Vec<u32>,
+    pub edges: Vec<u32>,
+    pub texts: Vec<u32>,
+    pub shapes: Vec<u32>,
+}
+
+enum Area {
+    Rect(Rect),
+    Polygon { points: Vec<Vec2>, bbox: Rect },
+}
+
+impl Area {
+    fn bbox(&self) -> Rect {
+        match self {
+            Area::Rect(r) => *r,
+            Area::Polygon { bbox, .. } => *bbox,
+        }
+    }
+
+    fn contains(&self, p: Vec2) -> bool {
+        match self {
+            Area::Rect(r) => rect_contains(*r, p),
+            Area::Polygon { points, bbox } => {
+                rect_contains(*bbox, p) && point_in_polygon_evenodd(p.x, p.y, points)
+            }
+        }
+    }
+
+    fn ring(&self) -> Vec<Vec2> {
+        match self {
+            Area::Rect(r) => rect_ring(*r),
+            Area::Polygon { points, .. } => points.clone(),
+        }
+    }
+
+    /// Whether the polyline `pts` lies inside (`Contained`) or touches the area.
+    fn hits_polyline(&self, pts: &[Vec2], mode: SelectMode) -> bool {
+        match mode {
+            SelectMode::Contained => {
+                if !pts.iter().all(|&p| self.contains(p)) {
+                    return false;
+                }
+                // A rectangle is convex; a lasso may bulge back between points
+                let Area::Polygon { points, .. } = self else {
+                    return true;
+                };
+                !pts.windows(2)
+                    .any(|w| ring_crosses(points, w[0], w[1], true))
+            }
+            SelectMode::Intersecting => {
+                if pts.iter().any(|&p| self.contains(p)) {
+                    return true;
+                }
+                let ring = self.ring();
+                pts.windows(2)
+                    .any(|w| ring_crosses(&ring, w[0], w[1], false))
+            }
+        }
+    }
+}
+
+fn rect_contains(r: Rect, p: Vec2) -> bool {
+    p.x >= r.0 && p.x <= r.2 && p.y >= r.1 && p.y <= r.3
+}
+
+fn rect_inside(inner: Rect, outer: Rect) -> bool {
+    inner.0 >= outer.0 && inner.1 >= outer.1 && inner.2 <= outer.2 && inner.3 <= outer.3
+}
+
+fn rects_overlap(a: Rect, b: Rect) -> bool {
+    a.0 <= b.2 && b.0 <= a.2 && a.1 <= b.3 && b.1 <= a.3
+}
+
+fn rect_ring(r: Rect) -> Vec<Vec2> {
+    vec![
+        Vec2 { x: r.0, y: r.1 },
+        Vec2 { x: r.2, y: r.1 },
+        Vec2 { x: r.2, y: r.3 },
+        Vec2 { x: r.0, y: r.3 },
+    ]
+}
+
+/// Whether segment `a`-`b` meets the closed ring; `proper_only` ignores touches.
+fn ring_crosses(ring: &[Vec2], a: Vec2, b: Vec2, proper_only: bool) -> bool {
+    (0..ring.len()).any(|i| {
+        let (c, d) = (ring[i], ring[(i + 1) % ring.len()]);
+        match intersect_segments(a.x, a.y, b.x, b.y, c.x, c.y, d.x, d.y, EPS_POS, EPS_DENOM) {
+            SegIntersection::None => false,
+            SegIntersection::Proper { .. } => true,
+            _ => !proper_only,
+        }
+    })
+}
+
+fn points_bbox(points: &[Vec2]) -> Rect {
+    points.iter().fold(
+        (
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |b, p| (b.0.min(p.x), b.1.min(p.y), b.2.max(p.x), b.3.max(p.y)),
+    )
+}
+
+/// Roots in (0, 1) of the derivative of one cubic coordinate.
+fn extrema_params(p0: f32, p1: f32, p2: f32, p3: f32) -> Vec<f32> {
+    // B'(t)/3 = a t^2 + b t + c
+    let (p0, p1, p2, p3) = (p0 as f64, p1 as f64, p2 as f64, p3 as f64);
+    let a = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let b = 2.0 * (p0 - 2.0 * p1 + p2);
+    let c = p1 - p0;
+    let mut ts = Vec::new();
+    if a.abs() < 1e-12 {
+        if b.abs() > 1e-12 {
+            ts.push(-c / b);
+        }
+    } else {
+        let disc = b * b - 4.0 * a * c;
+        if disc >= 0.0 {
+            let sq = disc.sqrt();
+            ts.push((-b + sq) / (2.0 * a));
+            ts.push((-b - sq) / (2.0 * a));
+        }
+    }
+    ts.into_iter()
+        .filter(|t| *t > 0.0 && *t < 1.0)
+        .map(|t| t as f32)
+        .collect()
+}
+
+fn cubic_eval(c: &[Vec2; 4], t: f32) -> Vec2 {
+    let mt = 1.0 - t;
+    let (w0, w1, w2, w3) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+    Vec2 {
+        x: w0 * c[0].x + w1 * c[1].x + w2 * c[2].x + w3 * c[3].x,
+        y: w0 * c[0].y + w1 * c[1].y + w2 * c[2].y + w3 * c[3].y,
+    }
+}
+
+/// Exact bounds of the curve itself (endpoints plus axis extrema).
+fn cubic_tight_bbox(c: &[Vec2; 4]) -> Rect {
+    let mut ts = vec![0.0, 1.0];
+    ts.extend(extrema_params(c[0].x, c[1].x, c[2].x, c[3].x));
+    ts.extend(extrema_params(c[0].y, c[1].y, c[2].y, c[3].y));
+    let pts: Vec<Vec2> = ts.into_iter().map(|t| cubic_eval(c, t)).collect();
+    points_bbox(&pts)
+}
+
+fn split_half(c: &[Vec2; 4]) -> ([Vec2; 4], [Vec2; 4]) {
+    let mid = |a: Vec2, b: Vec2| Vec2 {
+        x: (a.x + b.x) * 0.5,
+        y: (a.y + b.y) * 0.5,
+    };
+    let (p01, p12, p23) = (mid(c[0], c[1]), mid(c[1], c[2]), mid(c[2], c[3]));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let m = mid(p012, p123);
+    ([c[0], p01, p012, m], [m, p123, p23, c[3]])
+}
+
+/// Whether the cubic with control points `c` meets rectangle `r`: a piece
+/// whose control hull misses `r` is dropped, an endpoint inside hits, and
+/// anything else is halved until the hull shrinks below `HULL_EPS`.
+fn cubic_hits_rect(c: &[Vec2; 4], r: Rect, depth: u32) -> bool {
+    let hull = points_bbox(c);
+    if !rects_overlap(hull, r) {
+        return false;
+    }
+    if rect_contains(r, c[0]) || rect_contains(r, c[3]) {
+        return true;
+    }
+    if depth >= MAX_HULL_DEPTH || (hull.2 - hull.0).max(hull.3 - hull.1) <= HULL_EPS {
+        return true;
+    }
+    let (lo, hi) = split_half(c);
+    cubic_hits_rect(&lo, r, depth + 1) || cubic_hits_rect(&hi, r, depth + 1)
+}
+
+fn edge_selectable(g: &Graph, eid: u32) -> bool {
+    g.item_hittable(GroupItem::Edge(eid))
+}
+
+fn edge_hit(g: &Graph, eid: u32, area: &Area, mode: SelectMode) -> bool {
+    let Some(e) = g.edges.get(eid as usize).and_then(|e| e.as_ref()) else {
+        return false;
+    };
+    if let (Area::Rect(r), EdgeKind::Cubic { ha, hb, .. }) = (area, &e.kind) {
+        let (Some(a), Some(b)) = (
+            g.nodes.get(e.a as usize).and_then(|n| *n),
+            g.nodes.get(e.b as usize).and_then(|n| *n),
+        ) else {
+            return false;
+        };
+        let c = [
+            Vec2 { x: a.x, y: a.y },
+            Vec2 {
+                x: a.x + ha.x,
+                y: a.y + ha.y,
+            },
+            Vec2 {
+                x: b.x + hb.x,
+                y: b.y + hb.y,
+            },
+            Vec2 { x: b.x, y: b.y },
+        ];
+        return match mode {
+            SelectMode::Contained => rect_inside(cubic_tight_bbox(&c), *r),
+            SelectMode::Intersecting => cubic_hits_rect(&c, *r, 0),
+        };
+    }
+    flatten_points_for_edge(g, eid).is_some_and(|pts| area.hits_polyline(&pts, mode))
+}
+
+fn text_hit(tb: Rect, area: &Area, mode: SelectMode) -> bool {
+    match (area, mode) {
+        (Area::Rect(r), SelectMode::Contained) => rect_inside(tb, *r),
+        (Area::Rect(r), SelectMode::Intersecting) => rects_overlap(tb, *r),
+        (Area::Polygon { points, .. }, _) => {
+            let mut ring = rect_ring(tb);
+            ring.push(ring[0]);
+            area.hits_polyline(&ring, mode)
+                || (mode == SelectMode::Intersecting && rect_contains(tb, points[0]))
+        }
+    }
+}
+
+fn select_in_area(g: &Graph, area: &Area, mode: SelectMode) -> SelectionResult {
+    let bbox = area.bbox();
+    let (node_cands, edge_cands) = {
+        let idx = current_pick_index(g);
+        (
+            query_rect(&idx.nodes, idx.cell, bbox),
+            query_rect(&idx.edges, idx.cell, bbox),
+        )
+    };
+    let edge_cands: HashSet<u32> = edge_cands.into_iter().collect();
+
+    // An edge incident to a node in the area covers the node's cell, so the
+    // candidates list every edge that can make a node (un)selectable.
+    let mut node_access: HashMap<u32, bool> = HashMap::new();
+    let mut edges = Vec::new();
+    for &eid in &edge_cands {
+        let Some(e) = g.edges.get(eid as usize).and_then(|e| e.as_ref()) else {
+            continue;
+        };
+        let selectable = edge_selectable(g, eid);
+        for n in [e.a, e.b] {
+            *node_access.entry(n).or_insert(false) |= selectable;
+        }
+        if selectable && edge_hit(g, eid, area, mode) {
+            edges.push(eid);
+        }
+    }
+    edges.sort_unstable();
+
+    let mut nodes: Vec<u32> = node_cands
+        .into_iter()
+        .collect::<HashSet<u32>>()
+        .into_iter()
+        .filter(|&id| {
+            g.nodes
+                .get(id as usize)
+                .and_then(|n| *n)
+                .is_some_and(|n| area.contains(Vec2 { x: n.x, y: n.y }))
+                && node_access.get(&id).copied().unwrap_or(true)
+        })
+        .collect();
+    nodes.sort_unstable();
+
+    let texts = g
+        .texts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            let t = t.as_ref()?;
+            if !g.item_hittable(GroupItem::Text(i as u32)) {
+                return None;
+            }
+            text_hit(Graph::text_aabb(t), area, mode).then_some(i as u32)
+        })
+        .collect();
+
+    let hit: HashSet<u32> = edges.iter().copied().collect();
+    let shapes = g
+        .shapes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| {
+            let s = s.as_ref()?;
+            if s.edges.is_empty()
+                || !g.item_hittable(GroupItem::Shape(i as u32))
+                || !s.edges.iter().all(|&e| edge_selectable(g, e))
+            {
+                return None;
+            }
+            let selected = match mode {
+                SelectMode::Contained => s.edges.iter().all(|e| hit.contains(e)),
+                SelectMode::Intersecting => s.edges.iter().any(|e| hit.contains(e)),
+            };
+            selected.then_some(i as u32)
+        })
+        .collect();
+
+    SelectionResult {
+        nodes,
+        edges,
+        texts,
+        shapes,
+    }
+}
+
+pub fn select_in_rect_impl(g: &Graph, rect: Rect, mode: SelectMode) -> SelectionResult {
+    let (x0, y0, x1, y1) = rect;
+    if ![x0, y0, x1, y1].iter().all(|v| v.is_finite()) {
+        return SelectionResult::default();
+    }
+    let r = (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1));
+    select_in_area(g, &Area::Rect(r), mode)
+}
+
+pub fn select_in_polygon_impl(
+    g: &Graph,
+    points: &[(f32, f32)],
+    mode: SelectMode,
+) -> SelectionResult {
+    if points.len() < 3 || !points.iter().all(|(x, y)| x.is_finite() && y.is_finite()) {
+        return SelectionResult::default();
+    }
+    let points: Vec<Vec2> = points.iter().map(|&(x, y)| Vec2 { x, y }).collect();
+    let bbox = points_bbox(&points);
+    select_in_area(g, &Area::Polygon { points, bbox }, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(g: &mut Graph, a: (f32, f32), b: (f32, f32)) -> (u32, u32, u32) {
+        let na = g.add_node(a.0, a.1);
+        let nb = g.add_node(b.0, b.1);
+        (na, nb, g.add_edge(na, nb).unwrap())
+    }
+
+    #[test]
+    fn rect_tests_cubics_exactly() {
+        let mut g = Graph::new();
+        let (_, _, e) = line(&mut g, (0.0, 0.0), (100.0, 0.0));
+        // Peaks at y = 60 while its control points reach y = 80
+        assert!(g.set_edge_cubic(e, 0.0, 80.0, 100.0, 80.0));
+        let hit = |g: &Graph, r: Rect, mode| g.select_in_rect(r.0, r.1, r.2, r.3, mode).edges;
+
+        assert!(hit(&g, (-1.0, -1.0, 101.0, 50.0), SelectMode::Contained).is_empty());
+        assert_eq!(
+            hit(&g, (-1.0, -1.0, 101.0, 50.0), SelectMode::Intersecting),
+            vec![e]
+        );
+        assert_eq!(
+            hit(&g, (-1.0, -1.0, 101.0, 61.0), SelectMode::Contained),
+            vec![e]
+        );
+        // Inside the control hull, above the curve
+        assert!(hit(&g, (40.0, 65.0, 60.0, 75.0), SelectMode::Intersecting).is_empty());
+        // Straddles the apex with both endpoints outside
+        assert_eq!(
+            hit(&g, (40.0, 55.0, 60.0, 65.0), SelectMode::Intersecting),
+            vec![e]
+        );
+        // Corners may be given in any order
+        assert_eq!(
+            hit(&g, (60.0, 65.0, 40.0, 55.0), SelectMode::Intersecting),
+            vec![e]
+        );
+    }
+
+    #[test]
+    fn lasso_respects_concavity() {
+        let mut g = Graph::new();
+        let (a, b, across) = line(&mut g, (10.0, 80.0), (90.0, 80.0));
+        let (_, _, inside) = line(&mut g, (10.0, 10.0), (90.0, 10.0));
+        let u = [
+            (0.0, 0.0),
+            (100.0, 0.0),
+            (100.0, 100.0),
+            (70.0, 100.0),
+            (70.0, 30.0),
+            (30.0, 30.0),
+            (30.0, 100.0),
+            (0.0, 100.0),
+        ];
+        let contained = g.select_in_polygon(&u, SelectMode::Contained);
+        assert_eq!(contained.edges, vec![inside]);
+        assert!(contained.nodes.contains(&a) && contained.nodes.contains(&b));
+        let touching = g.select_in_polygon(&u, SelectMode::Intersecting);
+        assert_eq!(touching.edges, vec![across, inside]);
+        assert!(g
+            .select_in_polygon(&u[..2], SelectMode::Intersecting)
+            .edges
+            .is_empty());
+    }
+
+    #[test]
+    fn hidden_and_locked_edges_are_skipped() {
+        let mut g = Graph::new();
+        let root = g.default_group().unwrap();
+        let (na, _, shown) = line(&mut g, (0.0, 0.0), (10.0, 0.0));
+        let (nh, _, hidden) = line(&mut g, (0.0, 5.0), (10.0, 5.0));
+        let (nl, _, locked) = line(&mut g, (0.0, 8.0), (10.0, 8.0));
+        let lone = g.add_node(5.0, 9.0);
+        let gh = g.create_group("hidden".to_string(), root).unwrap();
+        let gl = g.create_group("locked".to_string(), root).unwrap();
+        g.add_edge_to_group(hidden, gh);
+        g.add_edge_to_group(locked, gl);
+        g.set_group_visibility(gh, false);
+        g.set_group_locked(gl, true);
+
+        let sel = g.select_in_rect(-1.0, -1.0, 11.0, 10.0, SelectMode::Contained);
+        assert_eq!(sel.edges, vec![shown, locked]);
+
+        g.set_pick_skip_locked(true);
+        let sel = g.select_in_rect(-1.0, -1.0, 11.0, 10.0, SelectMode::Contained);
+        assert_eq!(sel.edges, vec![shown]);
+        assert!(sel.nodes.contains(&na) && sel.nodes.contains(&lone));
+        assert!(!sel.nodes.contains(&nh) && !sel.nodes.contains(&nl));
+    }
+
+    #[test]
+    fn shapes_and_texts() {
+        let mut g = Graph::new();
+        let rect = g.add_rectangle(0.0, 0.0, 50.0, 50.0, 0.0);
+        let t = g.add_text("hi", 200.0, 200.0);
+
+        let all = g.select_in_rect(-1.0, -1.0, 51.0, 51.0, SelectMode::Contained);
+        assert_eq!(all.shapes, vec![rect.shape]);
+        assert!(all.texts.is_empty());
+        let part = g.select_in_rect(40.0, 40.0, 60.0, 60.0, SelectMode::Contained);
+        assert!(part.shapes.is_empty());
+        let part = g.select_in_rect(40.0, 40.0, 60.0, 60.0, SelectMode::Intersecting);
+        assert_eq!(part.shapes, vec![rect.shape]);
+
+        let around = [
+            (190.0, 190.0),
+            (300.0, 190.0),
+            (300.0, 260.0),
+            (190.0, 260.0),
+        ];
+        assert_eq!(
+            g.select_in_polygon(&around, SelectMode::Contained).texts,
+            vec![t]
+        );
+        let inner = [(201.0, 201.0), (202.0, 201.0), (202.0, 202.0)];
+        assert!(g
+            .select_in_polygon(&inner, SelectMode::Contained)
+            .texts
+            .is_empty());
+        assert_eq!(
+            g.select_in_polygon(&inner, SelectMode::Intersecting).texts,
+            vec![t]
+        );
+    }
+}
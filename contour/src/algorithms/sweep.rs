@@ -0,0 +1,371 @@
+//! Bentley–Ottmann sweep for intersection candidates in planarization.
+//!
+//! Sweeps in x over the flattened segments with an event queue (the insert,
+//! end and remove events sorted up front, merged with a binary heap of the
+//! crossings found on the way) and a status of the active non-vertical
+//! segments ordered bottom to top by their y at the sweep position (f64),
+//! kept as a sorted vector with binary-search insertion. Only segments that
+//! become neighbours in the status are tested for a crossing to the right of
+//! the sweep; a crossing is queued as an event that swaps the pair and tests
+//! their new neighbours.
+//!
+//! Touches within `EPS_POS` need not cross, so each end point also collects
+//! everything within a window around it: a binary search into the status
+//! plus the vertical segments and the segments that ended within `ep`, which
+//! are tracked on the side. Queued crossings are checked against the status
+//! when they come up and dropped if the pair is no longer adjacent, so stale
+//! events need no deletion.
+//!
+//! The sweep runs in O((n + k) log n) for k crossings plus the window hits,
+//! independent of segment length; status insertions and removals also move
+//! the vector tail. The result is a superset of the intersecting pairs;
+//! `planarize_graph` runs the same exact pair test on it as on the grid
+//! candidates. `Sweep` keeps its state between calls so region jobs can run
+//! it in chunks of events.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+struct SweepSeg {
+    lx: f64,
+    ly: f64,
+    rx: f64,
+    ry: f64,
+    slope: f64,
+    vertical: bool,
+}
+
+impl SweepSeg {
+    fn new(ends: [f32; 4], ep: f64) -> Self {
+        let [ax, ay, bx, by] = ends.map(|v| v as f64);
+        let (lx, ly, rx, ry) = if (ax, ay) <= (bx, by) {
+            (ax, ay, bx, by)
+        } else {
+            (bx, by, ax, ay)
+        };
+        let vertical = rx - lx <= ep;
+        SweepSeg {
+            lx,
+            ly,
+            rx,
+            ry,
+            slope: if vertical { 0.0 } else { (ry - ly) / (rx - lx) },
+            vertical,
+        }
+    }
+
+    /// y on the segment at `x`, held constant past either end.
+    fn y_at(&self, x: f64) -> f64 {
+        if self.vertical || x <= self.lx {
+            return self.ly;
+        }
+        if x >= self.rx {
+            return self.ry;
+        }
+        self.ly + self.slope * (x - self.lx)
+    }
+
+    /// y extent covered at `x` (the whole span for verticals).
+    fn y_range(&self, x: f64) -> (f64, f64) {
+        if self.vertical {
+            (self.ly.min(self.ry), self.ly.max(self.ry))
+        } else {
+            let y = self.y_at(x);
+            (y, y)
+        }
+    }
+}
+
+/// Crossings come first at a shared x so the status is ordered at x before
+/// anything is inserted there.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    Cross,
+    Insert,
+    End,
+    Remove,
+}
+
+/// A queued event; `b` is the upper segment of a crossing pair, else unused.
+#[derive(Clone, Copy)]
+struct Event {
+    x: f64,
+    kind: EventKind,
+    a: usize,
+    b: usize,
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.x
+            .total_cmp(&other.x)
+            .then(self.kind.cmp(&other.kind))
+            .then(self.a.cmp(&other.a))
+            .then(self.b.cmp(&other.b))
+    }
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Event {}
+
+/// Sweep state between calls to `step`, so region jobs can spread the
+/// search over several steps.
+pub(crate) struct Sweep {
+    ss: Vec<SweepSeg>,
+    win: f64,
+    // End point events (insert, end, remove), sorted up front, and the
+    // crossings found so far; the next event is the lesser head
+    ends: Vec<Event>,
+    next: usize,
+    crossings: BinaryHeap<Reverse<Event>>,
+    x: f64,
+    // Non-vertical segments bottom to top at `x`
+    status: Vec<usize>,
+    // Vertical segments, and non-vertical ones past their right end, until
+    // `ep` past their x
+    verts: Vec<usize>,
+    ended: Vec<usize>,
+    seen: HashSet<(usize, usize)>,
+    pairs: Vec<(usize, usize)>,
+    // Scratch buffer for `scan`
+    hits: Vec<usize>,
+}
+
+impl Sweep {
+    pub(crate) fn new(segs: impl Iterator<Item = [f32; 4]>, ep: f32) -> Self {
+        let ep = ep as f64;
+        let ss: Vec<SweepSeg> = segs.map(|s| SweepSeg::new(s, ep)).collect();
+
+        // A point within `ep` of a segment sits within `ep * sqrt(1 + m^2)` of
+        // it vertically; scan windows use the steepest non-vertical slope
+        let mut steep = 1.0f64;
+        for s in ss.iter().filter(|s| !s.vertical) {
+            steep = steep.max((1.0 + s.slope * s.slope).sqrt());
+        }
+
+        let mut events = Vec::with_capacity(ss.len() * 3);
+        for (i, s) in ss.iter().enumerate() {
+            let at = |x: f64, kind: EventKind| Event {
+                x,
+                kind,
+                a: i,
+                b: i,
+            };
+            events.push(at(s.lx, EventKind::Insert));
+            if !s.vertical {
+                events.push(at(s.rx, EventKind::End));
+            }
+            events.push(at(s.rx + ep, EventKind::Remove));
+        }
+        events.sort_unstable();
+        Sweep {
+            ss,
+            win: 2.0 * ep * steep,
+            ends: events,
+            next: 0,
+            crossings: BinaryHeap::new(),
+            x: f64::NEG_INFINITY,
+            status: Vec::new(),
+            verts: Vec::new(),
+            ended: Vec::new(),
+            seen: HashSet::new(),
+            pairs: Vec::new(),
+            hits: Vec::new(),
+        }
+    }
+
+    /// End point events handled so far; crossings are not counted.
+    pub(crate) fn done(&self) -> usize {
+        self.next
+    }
+
+    pub(crate) fn total(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Handle up to `max_events` events, crossings included; true once both
+    /// queues are empty.
+    pub(crate) fn step(&mut self, max_events: usize) -> bool {
+        for _ in 0..max_events {
+            let end = self.ends.get(self.next).copied();
+            let ev = match (end, self.crossings.peek()) {
+                (Some(e), Some(Reverse(c))) if e < *c => e,
+                (_, Some(_)) => self.crossings.pop().unwrap().0,
+                (Some(e), None) => e,
+                (None, None) => break,
+            };
+            if ev.kind != EventKind::Cross {
+                self.next += 1;
+            }
+            self.x = ev.x;
+            match ev.kind {
+                EventKind::Cross => self.cross(ev.a, ev.b),
+                EventKind::Insert => self.insert(ev.a),
+                EventKind::End => self.end(ev.a),
+                EventKind::Remove => {
+                    let side = if self.ss[ev.a].vertical {
+                        &mut self.verts
+                    } else {
+                        &mut self.ended
+                    };
+                    side.retain(|&v| v != ev.a);
+                }
+            }
+        }
+        self.next == self.ends.len() && self.crossings.is_empty()
+    }
+
+    /// Unique candidate pairs in discovery order.
+    pub(crate) fn finish(self) -> Vec<(usize, usize)> {
+        self.pairs
+    }
+
+    fn push(&mut self, a: usize, b: usize) {
+        if a != b && self.seen.insert((a.min(b), a.max(b))) {
+            self.pairs.push((a.min(b), a.max(b)));
+        }
+    }
+
+    /// Status order at `x`: by y, then by slope for segments meeting there.
+    fn below(&self, a: usize, b: usize) -> Ordering {
+        let (sa, sb) = (&self.ss[a], &self.ss[b]);
+        sa.y_at(self.x)
+            .total_cmp(&sb.y_at(self.x))
+            .then(sa.slope.total_cmp(&sb.slope))
+    }
+
+    /// First status index whose y at `x` is not below `y`.
+    fn lower_bound(&self, y: f64) -> usize {
+        self.status
+            .partition_point(|&k| self.ss[k].y_at(self.x) < y)
+    }
+
+    /// Where `i` sits in the status: searched near its y, then everywhere in
+    /// case rounding put it out of place.
+    fn position(&self, i: usize) -> Option<usize> {
+        let y = self.ss[i].y_at(self.x);
+        let start = self.lower_bound(y - self.win);
+        self.status[start..]
+            .iter()
+            .take_while(|&&k| self.ss[k].y_at(self.x) <= y + self.win)
+            .position(|&k| k == i)
+            .map(|p| start + p)
+            .or_else(|| self.status.iter().position(|&k| k == i))
+    }
+
+    /// Everything within the scan window of `i` at `x`.
+    fn scan(&mut self, i: usize) {
+        let x = self.x;
+        let (lo, hi) = self.ss[i].y_range(x);
+        let (lo, hi) = (lo - self.win, hi + self.win);
+        let mut hits = std::mem::take(&mut self.hits);
+        for &k in &self.status[self.lower_bound(lo)..] {
+            if self.ss[k].y_at(x) > hi {
+                break;
+            }
+            hits.push(k);
+        }
+        for &k in self.verts.iter().chain(&self.ended) {
+            let (klo, khi) = self.ss[k].y_range(x);
+            if klo <= hi && khi >= lo {
+                hits.push(k);
+            }
+        }
+        for &k in &hits {
+            self.push(i, k);
+        }
+        hits.clear();
+        self.hits = hits;
+    }
+
+    /// Queue the crossing of the status neighbours at `lo` and `lo + 1`, if
+    /// the lower one rises above the upper one before either ends.
+    fn check(&mut self, lo: usize) {
+        let (Some(&u), Some(&v)) = (self.status.get(lo), self.status.get(lo + 1)) else {
+            return;
+        };
+        let (su, sv) = (&self.ss[u], &self.ss[v]);
+        if su.slope <= sv.slope {
+            return;
+        }
+        let xc = (sv.ly - su.ly + su.slope * su.lx - sv.slope * sv.lx) / (su.slope - sv.slope);
+        if !xc.is_finite() || xc > su.rx.min(sv.rx) {
+            return;
+        }
+        self.push(u, v);
+        // Rounding may place the crossing just behind the sweep
+        self.crossings.push(Reverse(Event {
+            x: xc.max(self.x),
+            kind: EventKind::Cross,
+            a: u,
+            b: v,
+        }));
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.scan(i);
+        if self.ss[i].vertical {
+            self.verts.push(i);
+            return;
+        }
+        let at = self
+            .status
+            .partition_point(|&k| self.below(k, i) == Ordering::Less);
+        self.status.insert(at, i);
+        if at > 0 {
+            self.check(at - 1);
+        }
+        self.check(at);
+    }
+
+    fn end(&mut self, i: usize) {
+        self.scan(i);
+        if let Some(at) = self.position(i) {
+            self.status.remove(at);
+            if at > 0 {
+                self.check(at - 1);
+            }
+        }
+        self.ended.push(i);
+    }
+
+    /// Swap a crossing pair that is still adjacent, lower `a` below upper `b`,
+    /// and test the new neighbours; otherwise the event is stale.
+    fn cross(&mut self, a: usize, b: usize) {
+        let Some(at) = self.position(a) else {
+            return;
+        };
+        if self.status.get(at + 1) != Some(&b) {
+            return;
+        }
+        self.status.swap(at, at + 1);
+        if at > 0 {
+            self.check(at - 1);
+        }
+        self.check(at + 1);
+    }
+}
+
+/// Candidate `(lo, hi)` index pairs over segments given as
+/// `[ax, ay, bx, by]`, sorted and unique.
+pub(crate) fn sweep_candidate_pairs(
+    segs: impl Iterator<Item = [f32; 4]>,
+    ep: f32,
+) -> Vec<(usize, usize)> {
+    let mut sweep = Sweep::new(segs, ep);
+    sweep.step(usize::MAX);
+    let mut pairs = sweep.finish();
+    pairs.sort_unstable();
+    pairs
+}
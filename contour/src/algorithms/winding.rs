@@ -3,7 +3,7 @@
 //! Uses horizontal ray casting with signed crossing count to determine
 //! the winding number of a point relative to a polygon.
 
-use crate::model::Vec2;
+use crate::model::{FillRule, Vec2};
 
 /// Compute the winding number of a point relative to a polygon.
 ///
@@ -108,6 +108,21 @@ pub fn winding_numbers_dual(
     )
 }
 
+/// Winding number of a point against several rings forming one path, such as
+/// a shape's outer contour and its holes.
+pub fn winding_number_rings(px: f32, py: f32, rings: &[Vec<Vec2>]) -> i32 {
+    rings.iter().map(|r| winding_number(px, py, r)).sum()
+}
+
+/// Check if a multi-ring path covers a point under the given fill rule.
+pub fn point_in_rings(rule: FillRule, px: f32, py: f32, rings: &[Vec<Vec2>]) -> bool {
+    let w = winding_number_rings(px, py, rings);
+    match rule {
+        FillRule::NonZero => w != 0,
+        FillRule::EvenOdd => w % 2 != 0,
+    }
+}
+
 /// Check if a point lies on a polygon edge within tolerance.
 pub fn point_on_polygon_edge(px: f32, py: f32, polygon: &[Vec2], tol: f32) -> bool {
     if polygon.is_empty() {
@@ -41,6 +41,7 @@ fn main() {
     let mut picks = 10000usize;
     let mut tol = 3.0f32;
     let mut assert_ms: Option<f64> = None;
+    let mut drag_frames = 2000usize;
     for a in &args[1..] {
         if let Some(val) = a.strip_prefix("--edges=") {
             if let Ok(v) = val.parse() {
@@ -54,6 +55,10 @@ fn main() {
             if let Ok(v) = val.parse() {
                 tol = v;
             }
+        } else if let Some(val) = a.strip_prefix("--drag-frames=") {
+            if let Ok(v) = val.parse() {
+                drag_frames = v;
+            }
         } else if let Some(val) = a.strip_prefix("--assert-ms=") {
             if let Ok(v) = val.parse() {
                 assert_ms = Some(v);
@@ -61,8 +66,10 @@ fn main() {
         }
     }
 
-    let g = build_grid_graph(edges);
-    // Warm-up build of index
+    let mut g = build_grid_graph(edges);
+    // Warm-up build of index; the first region pass settles the dirty state
+    // left by construction, as an editor's first render would
+    let _ = g.get_regions();
     let _ = g.pick(1.0, 1.0, tol);
 
     // Generate pick points cycling across the grid
@@ -88,10 +95,47 @@ fn main() {
         "edges={} picks={} tol={} hits={} total_ms={:.3} median_ms={:.4} p90_ms={:.4} p99_ms={:.4}",
         edges, picks, tol, hits, dur_all, med, p90, p99
     );
+
+    // Continuous edits: drag one node per frame (each move bumps geom_ver)
+    // and hover-pick next to it, as an editor does during a drag
+    let mut drag_ms: Vec<f64> = Vec::with_capacity(drag_frames);
+    let start_drag = Instant::now();
+    let mut drag_hits = 0usize;
+    for k in 0..drag_frames {
+        let id = (k % 100) as u32;
+        let x = (k % 100) as f32 * 8.0 + (k % 7) as f32 * 0.5;
+        let y = 2.0 + (k % 5) as f32 * 0.3;
+        g.move_node(id, x, y);
+        let t0 = Instant::now();
+        if g.pick(x + 0.5, y + 0.5, tol).is_some() {
+            drag_hits += 1;
+        }
+        drag_ms.push(t0.elapsed().as_secs_f64() * 1000.0);
+    }
+    let drag_all = start_drag.elapsed().as_secs_f64() * 1000.0;
+    drag_ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let drag_med = percentile(&drag_ms, 0.5);
+    println!(
+        "drag frames={} hits={} total_ms={:.3} median_ms={:.4} p90_ms={:.4} p99_ms={:.4}",
+        drag_frames,
+        drag_hits,
+        drag_all,
+        drag_med,
+        percentile(&drag_ms, 0.9),
+        percentile(&drag_ms, 0.99)
+    );
+
     if let Some(th) = assert_ms {
         if med > th {
             eprintln!("FAIL: median {:.4} ms > threshold {:.3} ms", med, th);
             std::process::exit(1);
         }
+        if drag_med > th {
+            eprintln!(
+                "FAIL: drag median {:.4} ms > threshold {:.3} ms",
+                drag_med, th
+            );
+            std::process::exit(1);
+        }
     }
 }
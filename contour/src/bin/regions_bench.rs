@@ -1,4 +1,6 @@
 #[cfg(feature = "bench_regions")]
+use contour::algorithms::planarize::{planarize_graph_with, IntersectBackend};
+#[cfg(feature = "bench_regions")]
 use contour::Graph;
 #[cfg(feature = "bench_regions")]
 use std::time::Instant;
@@ -31,12 +33,62 @@ fn build_grid_graph(w: usize, h: usize) -> Graph {
     g
 }
 
+/// Dense detail (a "logo" of small ellipses and a hatch) next to a giant frame
+/// crossed by long diagonals: uneven density that strains a uniform grid.
+#[cfg(feature = "bench_regions")]
+fn build_uneven_graph(detail: usize) -> Graph {
+    let mut g = Graph::new();
+    for i in 0..detail {
+        for j in 0..detail {
+            g.add_ellipse(i as f32 * 7.0, j as f32 * 7.0, 5.0, 4.0);
+        }
+    }
+    let span = detail as f32 * 7.0;
+    for k in 0..detail {
+        let y = k as f32 * 7.0 + 2.0;
+        let a = g.add_node(-3.0, y);
+        let b = g.add_node(span + 3.0, y + 5.0);
+        g.add_edge(a, b);
+    }
+    g.add_rectangle(-4_000.0, -4_000.0, 8_000.0, 8_000.0, 0.0);
+    for k in 0..8 {
+        let o = k as f32 * 400.0;
+        let a = g.add_node(-3_900.0 + o, -3_900.0);
+        let b = g.add_node(3_900.0, 3_900.0 - o);
+        g.add_edge(a, b);
+    }
+    g
+}
+
+/// Average full-planarization time per intersection backend, with the
+/// resulting half-edge count (differs when the grid drops long segments).
+#[cfg(feature = "bench_regions")]
+fn time_backends(g: &mut Graph, repeats: usize) -> Vec<(&'static str, f64, usize)> {
+    // Build flatten caches and the incremental plan once
+    let _ = g.bench_recompute_regions_full();
+    let mut out = Vec::new();
+    for (name, backend) in [
+        ("grid", IntersectBackend::Grid),
+        ("sweep", IntersectBackend::Sweep),
+    ] {
+        let halves = planarize_graph_with(g, backend).half_from.len();
+        let t = Instant::now();
+        for _ in 0..repeats.max(1) {
+            let _ = planarize_graph_with(g, backend);
+        }
+        let ms = t.elapsed().as_secs_f64() * 1000.0 / repeats.max(1) as f64;
+        out.push((name, ms, halves));
+    }
+    out
+}
+
 #[cfg(feature = "bench_regions")]
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     let mut w = 70usize; // ~ (w+1)*h + (h+1)*w edges; choose for ~5k edges by default
     let mut h = 70usize;
     let mut repeats = 10usize;
+    let mut detail = 30usize;
     for a in &args[1..] {
         if let Some(val) = a.strip_prefix("--w=") {
             if let Ok(v) = val.parse() {
@@ -46,6 +98,10 @@ fn main() {
             if let Ok(v) = val.parse() {
                 h = v;
             }
+        } else if let Some(val) = a.strip_prefix("--detail=") {
+            if let Ok(v) = val.parse() {
+                detail = v;
+            }
         } else if let Some(val) = a.strip_prefix("--repeats=") {
             if let Ok(v) = val.parse() {
                 repeats = v;
@@ -90,4 +146,16 @@ fn main() {
         full_geo_ms,
         avg_inc_geo
     );
+
+    // Intersection backends on the uniform grid and on an uneven scene
+    let backend_repeats = (repeats / 2).max(1);
+    let mut uneven = build_uneven_graph(detail);
+    for (scene, g) in [("grid", &mut g), ("uneven", &mut uneven)] {
+        for (name, ms, halves) in time_backends(g, backend_repeats) {
+            println!(
+                "backend scene={} backend={} planarize_ms={:.3} half_edges={}",
+                scene, name, ms, halves
+            );
+        }
+    }
 }
@@ -15,6 +15,16 @@ pub fn flatten_cubic(
     tol: f32,
     depth: u32,
 ) {
+    #[cfg(feature = "simd")]
+    crate::geometry::simd::flatten_cubic(points, [x0, y0, x1, y1, x2, y2, x3, y3], tol, depth);
+    #[cfg(not(feature = "simd"))]
+    flatten_cubic_scalar(points, [x0, y0, x1, y1, x2, y2, x3, y3], tol, depth);
+}
+
+/// Scalar subdivision over control points `[x0, y0, x1, y1, x2, y2, x3, y3]`.
+#[cfg_attr(feature = "simd", allow(dead_code))]
+pub(crate) fn flatten_cubic_scalar(points: &mut Vec<Vec2>, c: [f32; 8], tol: f32, depth: u32) {
+    let [x0, y0, x1, y1, x2, y2, x3, y3] = c;
     if depth > MAX_FLATTEN_DEPTH {
         points.push(Vec2 { x: x3, y: y3 });
         return;
@@ -38,29 +48,15 @@ pub fn flatten_cubic(
     let y123 = 0.5 * (y12 + y23);
     let x0123 = 0.5 * (x012 + x123);
     let y0123 = 0.5 * (y012 + y123);
-    flatten_cubic(
+    flatten_cubic_scalar(
         points,
-        x0,
-        y0,
-        x01,
-        y01,
-        x012,
-        y012,
-        x0123,
-        y0123,
+        [x0, y0, x01, y01, x012, y012, x0123, y0123],
         tol,
         depth + 1,
     );
-    flatten_cubic(
+    flatten_cubic_scalar(
         points,
-        x0123,
-        y0123,
-        x123,
-        y123,
-        x23,
-        y23,
-        x3,
-        y3,
+        [x0123, y0123, x123, y123, x23, y23, x3, y3],
         tol,
         depth + 1,
     );
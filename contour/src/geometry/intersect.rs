@@ -12,6 +12,7 @@ pub enum SegIntersection {
     CollinearOverlap { t0: f64, t1: f64, u0: f64, u1: f64 },
 }
 
+#[cfg(not(feature = "simd"))]
 #[inline]
 fn orient(ax: f64, ay: f64, bx: f64, by: f64, cx: f64, cy: f64) -> f64 {
     (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
@@ -122,10 +123,18 @@ pub fn intersect_segments(
     let eps = eps_pos as f64;
     let denom_eps = eps_denom as f64;
 
-    let o1 = orient(ax, ay, bx, by, cx, cy);
-    let o2 = orient(ax, ay, bx, by, dx, dy);
-    let o3 = orient(cx, cy, dx, dy, ax, ay);
-    let o4 = orient(cx, cy, dx, dy, bx, by);
+    #[cfg(feature = "simd")]
+    let ((o1, o2), (o3, o4)) = (
+        crate::geometry::simd::orient_pair([ax, ay, bx, by], [cx, cy, dx, dy]),
+        crate::geometry::simd::orient_pair([cx, cy, dx, dy], [ax, ay, bx, by]),
+    );
+    #[cfg(not(feature = "simd"))]
+    let (o1, o2, o3, o4) = (
+        orient(ax, ay, bx, by, cx, cy),
+        orient(ax, ay, bx, by, dx, dy),
+        orient(cx, cy, dx, dy, ax, ay),
+        orient(cx, cy, dx, dy, bx, by),
+    );
 
     // Collinear cases: all orientations ~ 0
     if within_eps(o1, eps) && within_eps(o2, eps) && within_eps(o3, eps) && within_eps(o4, eps) {
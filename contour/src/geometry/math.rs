@@ -24,6 +24,23 @@ pub fn seg_distance_sq(px: f32, py: f32, x1: f32, y1: f32, x2: f32, y2: f32) ->
     (dx * dx + dy * dy, t)
 }
 
+/// `seg_distance_sq` from one point to many segments `(x1, y1, x2, y2)`,
+/// appending `(d2, t)` per segment to `out` (vectorized with `simd`).
+pub fn seg_distance_sq_many(
+    px: f32,
+    py: f32,
+    segs: &[(f32, f32, f32, f32)],
+    out: &mut Vec<(f32, f32)>,
+) {
+    #[cfg(feature = "simd")]
+    crate::geometry::simd::seg_distance_sq_many(px, py, segs, out);
+    #[cfg(not(feature = "simd"))]
+    out.extend(
+        segs.iter()
+            .map(|&(x1, y1, x2, y2)| seg_distance_sq(px, py, x1, y1, x2, y2)),
+    );
+}
+
 pub fn cubic_point(
     t: f32,
     x0: f32,
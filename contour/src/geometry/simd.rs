@@ -0,0 +1,480 @@
+//! Vectorized geometry kernels behind the `simd` feature.
+//!
+//! Lanes use wasm32 simd128 when the target enables it, SSE2 on x86_64 and a
+//! plain array fallback elsewhere. Every kernel performs the same IEEE
+//! operations in the same order as its scalar twin, so results agree with the
+//! scalar path (bit for bit on finite inputs, within the tolerance.rs epsilons
+//! in general).
+
+use crate::geometry::tolerance::MAX_FLATTEN_DEPTH;
+use crate::model::Vec2;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+mod lanes {
+    use core::arch::wasm32::*;
+
+    #[derive(Clone, Copy)]
+    pub struct F32x4(v128);
+
+    impl F32x4 {
+        #[inline]
+        pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+            F32x4(f32x4(a, b, c, d))
+        }
+        #[inline]
+        pub fn splat(v: f32) -> Self {
+            F32x4(f32x4_splat(v))
+        }
+        #[inline]
+        pub fn add(self, o: Self) -> Self {
+            F32x4(f32x4_add(self.0, o.0))
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            F32x4(f32x4_sub(self.0, o.0))
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            F32x4(f32x4_mul(self.0, o.0))
+        }
+        #[inline]
+        pub fn div(self, o: Self) -> Self {
+            F32x4(f32x4_div(self.0, o.0))
+        }
+        /// `self` clamped into [lo, hi].
+        #[inline]
+        pub fn clamp(self, lo: Self, hi: Self) -> Self {
+            F32x4(f32x4_pmin(f32x4_pmax(self.0, lo.0), hi.0))
+        }
+        /// Lanes of `a` where `self > 0`, lanes of `b` elsewhere.
+        #[inline]
+        pub fn select_pos(self, a: Self, b: Self) -> Self {
+            F32x4(v128_bitselect(a.0, b.0, f32x4_gt(self.0, f32x4_splat(0.0))))
+        }
+        #[inline]
+        pub fn to_array(self) -> [f32; 4] {
+            [
+                f32x4_extract_lane::<0>(self.0),
+                f32x4_extract_lane::<1>(self.0),
+                f32x4_extract_lane::<2>(self.0),
+                f32x4_extract_lane::<3>(self.0),
+            ]
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct F64x2(v128);
+
+    impl F64x2 {
+        #[inline]
+        pub fn new(a: f64, b: f64) -> Self {
+            F64x2(f64x2(a, b))
+        }
+        #[inline]
+        pub fn splat(v: f64) -> Self {
+            F64x2(f64x2_splat(v))
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            F64x2(f64x2_sub(self.0, o.0))
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            F64x2(f64x2_mul(self.0, o.0))
+        }
+        #[inline]
+        pub fn to_array(self) -> [f64; 2] {
+            [
+                f64x2_extract_lane::<0>(self.0),
+                f64x2_extract_lane::<1>(self.0),
+            ]
+        }
+    }
+}
+
+#[cfg(all(
+    target_arch = "x86_64",
+    not(all(target_arch = "wasm32", target_feature = "simd128"))
+))]
+mod lanes {
+    use core::arch::x86_64::*;
+
+    // SAFETY (all blocks below): SSE2 is part of the x86_64 baseline, so the
+    // intrinsics are always available on this target.
+
+    #[derive(Clone, Copy)]
+    pub struct F32x4(__m128);
+
+    impl F32x4 {
+        #[inline]
+        pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+            F32x4(unsafe { _mm_set_ps(d, c, b, a) })
+        }
+        #[inline]
+        pub fn splat(v: f32) -> Self {
+            F32x4(unsafe { _mm_set1_ps(v) })
+        }
+        #[inline]
+        pub fn add(self, o: Self) -> Self {
+            F32x4(unsafe { _mm_add_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            F32x4(unsafe { _mm_sub_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            F32x4(unsafe { _mm_mul_ps(self.0, o.0) })
+        }
+        #[inline]
+        pub fn div(self, o: Self) -> Self {
+            F32x4(unsafe { _mm_div_ps(self.0, o.0) })
+        }
+        /// `self` clamped into [lo, hi].
+        #[inline]
+        pub fn clamp(self, lo: Self, hi: Self) -> Self {
+            F32x4(unsafe { _mm_min_ps(_mm_max_ps(self.0, lo.0), hi.0) })
+        }
+        /// Lanes of `a` where `self > 0`, lanes of `b` elsewhere.
+        #[inline]
+        pub fn select_pos(self, a: Self, b: Self) -> Self {
+            F32x4(unsafe {
+                let m = _mm_cmpgt_ps(self.0, _mm_setzero_ps());
+                _mm_or_ps(_mm_and_ps(m, a.0), _mm_andnot_ps(m, b.0))
+            })
+        }
+        #[inline]
+        pub fn to_array(self) -> [f32; 4] {
+            let mut out = [0.0f32; 4];
+            // `out` holds exactly four f32s; storeu has no alignment needs
+            unsafe { _mm_storeu_ps(out.as_mut_ptr(), self.0) };
+            out
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct F64x2(__m128d);
+
+    impl F64x2 {
+        #[inline]
+        pub fn new(a: f64, b: f64) -> Self {
+            F64x2(unsafe { _mm_set_pd(b, a) })
+        }
+        #[inline]
+        pub fn splat(v: f64) -> Self {
+            F64x2(unsafe { _mm_set1_pd(v) })
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            F64x2(unsafe { _mm_sub_pd(self.0, o.0) })
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            F64x2(unsafe { _mm_mul_pd(self.0, o.0) })
+        }
+        #[inline]
+        pub fn to_array(self) -> [f64; 2] {
+            let mut out = [0.0f64; 2];
+            // `out` holds exactly two f64s; storeu has no alignment needs
+            unsafe { _mm_storeu_pd(out.as_mut_ptr(), self.0) };
+            out
+        }
+    }
+}
+
+#[cfg(not(any(
+    target_arch = "x86_64",
+    all(target_arch = "wasm32", target_feature = "simd128")
+)))]
+mod lanes {
+    // Portable fallback; the optimizer usually vectorizes these fixed-size loops.
+
+    #[derive(Clone, Copy)]
+    pub struct F32x4([f32; 4]);
+
+    impl F32x4 {
+        #[inline]
+        pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+            F32x4([a, b, c, d])
+        }
+        #[inline]
+        pub fn splat(v: f32) -> Self {
+            F32x4([v; 4])
+        }
+        #[inline]
+        fn zip(self, o: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+            F32x4(std::array::from_fn(|i| f(self.0[i], o.0[i])))
+        }
+        #[inline]
+        pub fn add(self, o: Self) -> Self {
+            self.zip(o, |a, b| a + b)
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            self.zip(o, |a, b| a - b)
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            self.zip(o, |a, b| a * b)
+        }
+        #[inline]
+        pub fn div(self, o: Self) -> Self {
+            self.zip(o, |a, b| a / b)
+        }
+        /// `self` clamped into [lo, hi].
+        #[inline]
+        pub fn clamp(self, lo: Self, hi: Self) -> Self {
+            self.zip(lo, f32::max).zip(hi, f32::min)
+        }
+        /// Lanes of `a` where `self > 0`, lanes of `b` elsewhere.
+        #[inline]
+        pub fn select_pos(self, a: Self, b: Self) -> Self {
+            F32x4(std::array::from_fn(|i| {
+                if self.0[i] > 0.0 {
+                    a.0[i]
+                } else {
+                    b.0[i]
+                }
+            }))
+        }
+        #[inline]
+        pub fn to_array(self) -> [f32; 4] {
+            self.0
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    pub struct F64x2([f64; 2]);
+
+    impl F64x2 {
+        #[inline]
+        pub fn new(a: f64, b: f64) -> Self {
+            F64x2([a, b])
+        }
+        #[inline]
+        pub fn splat(v: f64) -> Self {
+            F64x2([v; 2])
+        }
+        #[inline]
+        pub fn sub(self, o: Self) -> Self {
+            F64x2([self.0[0] - o.0[0], self.0[1] - o.0[1]])
+        }
+        #[inline]
+        pub fn mul(self, o: Self) -> Self {
+            F64x2([self.0[0] * o.0[0], self.0[1] * o.0[1]])
+        }
+        #[inline]
+        pub fn to_array(self) -> [f64; 2] {
+            self.0
+        }
+    }
+}
+
+use lanes::{F32x4, F64x2};
+
+/// Four point-to-segment distances: `(d2, t)` lanes for points `px/py`
+/// against segments `x1,y1 -> x2,y2`, matching `math::seg_distance_sq`.
+#[inline]
+fn seg_distance_sq_x4(
+    px: F32x4,
+    py: F32x4,
+    x1: F32x4,
+    y1: F32x4,
+    x2: F32x4,
+    y2: F32x4,
+) -> (F32x4, F32x4) {
+    let zero = F32x4::splat(0.0);
+    let vx = x2.sub(x1);
+    let vy = y2.sub(y1);
+    let wx = px.sub(x1);
+    let wy = py.sub(y1);
+    let vv = vx.mul(vx).add(vy.mul(vy));
+    let t = vv
+        .select_pos(wx.mul(vx).add(wy.mul(vy)).div(vv), zero)
+        .clamp(zero, F32x4::splat(1.0));
+    let dx = px.sub(x1.add(t.mul(vx)));
+    let dy = py.sub(y1.add(t.mul(vy)));
+    (dx.mul(dx).add(dy.mul(dy)), t)
+}
+
+/// Squared distance and clamped parameter from `(px, py)` to each segment
+/// `(x1, y1, x2, y2)`, four segments per step.
+pub fn seg_distance_sq_many(
+    px: f32,
+    py: f32,
+    segs: &[(f32, f32, f32, f32)],
+    out: &mut Vec<(f32, f32)>,
+) {
+    let (spx, spy) = (F32x4::splat(px), F32x4::splat(py));
+    for chunk in segs.chunks(4) {
+        // Pad short tails by repeating the first segment
+        let s = |i: usize| chunk.get(i).copied().unwrap_or(chunk[0]);
+        let (a, b, c, d) = (s(0), s(1), s(2), s(3));
+        let (d2, t) = seg_distance_sq_x4(
+            spx,
+            spy,
+            F32x4::new(a.0, b.0, c.0, d.0),
+            F32x4::new(a.1, b.1, c.1, d.1),
+            F32x4::new(a.2, b.2, c.2, d.2),
+            F32x4::new(a.3, b.3, c.3, d.3),
+        );
+        let (d2, t) = (d2.to_array(), t.to_array());
+        out.extend((0..chunk.len()).map(|i| (d2[i], t[i])));
+    }
+}
+
+/// Halves of the cubic `[x0, y0, x1, y1, x2, y2, x3, y3]` at t = 0.5, with the
+/// de Casteljau steps run on (x, y) lane pairs.
+fn split_cubic(c: [f32; 8]) -> ([f32; 8], [f32; 8]) {
+    let [x0, y0, x1, y1, x2, y2, x3, y3] = c;
+    let half = F32x4::splat(0.5);
+    let a = F32x4::new(x0, y0, x1, y1);
+    let b = F32x4::new(x1, y1, x2, y2);
+    let c = F32x4::new(x2, y2, x3, y3);
+    // (x01, y01, x12, y12) and (x12, y12, x23, y23)
+    let l1 = half.mul(a.add(b));
+    let l2 = half.mul(b.add(c));
+    // (x012, y012, x123, y123)
+    let l3 = half.mul(l1.add(l2));
+    let [x01, y01, _, _] = l1.to_array();
+    let [_, _, x23, y23] = l2.to_array();
+    let [x012, y012, x123, y123] = l3.to_array();
+    let x0123 = 0.5 * (x012 + x123);
+    let y0123 = 0.5 * (y012 + y123);
+    (
+        [x0, y0, x01, y01, x012, y012, x0123, y0123],
+        [x0123, y0123, x123, y123, x23, y23, x3, y3],
+    )
+}
+
+/// Vectorized `flatten::flatten_cubic_scalar`, one subdivision level at a
+/// time: the pieces still pending are flatness-tested two per step (both
+/// control points of each in one lane set), then the curved ones are split.
+/// Pieces stay in curve order, so points come out as in the depth-first
+/// scalar recursion.
+pub fn flatten_cubic(points: &mut Vec<Vec2>, c: [f32; 8], tol: f32, depth: u32) {
+    let tol2 = tol * tol;
+    // (control points, flat enough) in curve order
+    let mut pieces: Vec<([f32; 8], bool)> = vec![(c, false)];
+    let mut depth = depth;
+    while depth <= MAX_FLATTEN_DEPTH && pieces.iter().any(|p| !p.1) {
+        let pending: Vec<[f32; 8]> = pieces.iter().filter(|p| !p.1).map(|p| p.0).collect();
+        let mut flat: Vec<bool> = Vec::with_capacity(pending.len());
+        for pair in pending.chunks(2) {
+            // Pad a lone piece by testing it twice
+            let (a, b) = (pair[0], pair.get(1).copied().unwrap_or(pair[0]));
+            let (d2, _) = seg_distance_sq_x4(
+                F32x4::new(a[2], a[4], b[2], b[4]),
+                F32x4::new(a[3], a[5], b[3], b[5]),
+                F32x4::new(a[0], a[0], b[0], b[0]),
+                F32x4::new(a[1], a[1], b[1], b[1]),
+                F32x4::new(a[6], a[6], b[6], b[6]),
+                F32x4::new(a[7], a[7], b[7], b[7]),
+            );
+            let d2 = d2.to_array();
+            flat.push(d2[0].max(d2[1]) <= tol2);
+            if pair.len() == 2 {
+                flat.push(d2[2].max(d2[3]) <= tol2);
+            }
+        }
+        let mut flat = flat.into_iter();
+        let mut next = Vec::with_capacity(pieces.len() * 2);
+        for (c, done) in pieces {
+            if done || flat.next() == Some(true) {
+                next.push((c, true));
+            } else {
+                let (l, r) = split_cubic(c);
+                next.push((l, false));
+                next.push((r, false));
+            }
+        }
+        pieces = next;
+        depth += 1;
+    }
+    // Pieces still pending past the depth limit end where they are
+    points.extend(pieces.iter().map(|(c, _)| Vec2 { x: c[6], y: c[7] }));
+}
+
+/// Orientations of `c` and `d` relative to the directed line `a -> b`, i.e.
+/// `(orient(a, b, c), orient(a, b, d))` from `intersect.rs`, for
+/// `line = [ax, ay, bx, by]` and `pts = [cx, cy, dx, dy]`.
+#[inline]
+pub fn orient_pair(line: [f64; 4], pts: [f64; 4]) -> (f64, f64) {
+    let [ax, ay, bx, by] = line;
+    let [cx, cy, dx, dy] = pts;
+    let px = F64x2::new(cx, dx).sub(F64x2::splat(ax));
+    let py = F64x2::new(cy, dy).sub(F64x2::splat(ay));
+    let o = F64x2::splat(bx - ax)
+        .mul(py)
+        .sub(F64x2::splat(by - ay).mul(px))
+        .to_array();
+    (o[0], o[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::math::seg_distance_sq;
+    use crate::geometry::tolerance::{EPS_LEN, EPS_POS};
+
+    // Small deterministic LCG so the comparisons cover varied inputs
+    fn coords(n: usize) -> Vec<f32> {
+        let mut s: u32 = 0x2545_F491;
+        (0..n)
+            .map(|_| {
+                s = s.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (s >> 8) as f32 / (1u32 << 24) as f32 * 200.0 - 100.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn seg_distances_match_scalar() {
+        let c = coords(4 * 37 + 2);
+        let mut segs: Vec<(f32, f32, f32, f32)> = c
+            .chunks(4)
+            .take(37)
+            .map(|w| (w[0], w[1], w[2], w[3]))
+            .collect();
+        segs.push((5.0, 5.0, 5.0, 5.0)); // degenerate
+        let (px, py) = (c[c.len() - 2], c[c.len() - 1]);
+        let mut out = Vec::new();
+        seg_distance_sq_many(px, py, &segs, &mut out);
+        assert_eq!(out.len(), segs.len());
+        for (s, (d2, t)) in segs.iter().zip(out) {
+            let (sd2, st) = seg_distance_sq(px, py, s.0, s.1, s.2, s.3);
+            assert!((d2 - sd2).abs() <= EPS_POS * sd2.max(1.0));
+            assert!((t - st).abs() <= EPS_LEN);
+        }
+    }
+
+    #[test]
+    fn flatten_matches_scalar() {
+        let c = coords(8 * 20);
+        for w in c.chunks(8) {
+            for tol in [0.05f32, 0.25, 2.0] {
+                let mut simd = Vec::new();
+                let mut scalar = Vec::new();
+                let ctrl = [w[0], w[1], w[2], w[3], w[4], w[5], w[6], w[7]];
+                flatten_cubic(&mut simd, ctrl, tol, 0);
+                crate::geometry::flatten::flatten_cubic_scalar(&mut scalar, ctrl, tol, 0);
+                assert_eq!(simd.len(), scalar.len());
+                for (a, b) in simd.iter().zip(&scalar) {
+                    assert!((a.x - b.x).abs() <= EPS_POS && (a.y - b.y).abs() <= EPS_POS);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn orientations_match_scalar() {
+        let c = coords(8 * 25);
+        for w in c.chunks(8) {
+            let p: Vec<f64> = w.iter().map(|&v| v as f64).collect();
+            let (o1, o2) = orient_pair([p[0], p[1], p[2], p[3]], [p[4], p[5], p[6], p[7]]);
+            let s1 = (p[2] - p[0]) * (p[5] - p[1]) - (p[3] - p[1]) * (p[4] - p[0]);
+            let s2 = (p[2] - p[0]) * (p[7] - p[1]) - (p[3] - p[1]) * (p[6] - p[0]);
+            assert_eq!((o1, o2), (s1, s2));
+        }
+    }
+}
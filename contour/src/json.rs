@@ -1,16 +1,134 @@
+use crate::algorithms::regions::{face_interior_point, install_region_faces, region_cache_fresh};
 use crate::geometry::limits;
 use crate::layers::LayerSystem;
 use crate::{
     model::{
-        Color, Effect, EffectId, EffectStack, FillState, Gradient, GradientId, Group, HandleMode,
-        Layer, LayerId, TextElement, Vec2,
+        BlendMode, Color, Effect, EffectId, EffectStack, FillState, Gradient, GradientId, Group,
+        GroupItem, GroupMask, HandleMode, Layer, LayerId, RegionFillMode, RegionScope, Shape,
+        TextElement, Vec2,
     },
-    Graph,
+    Graph, RegionFaceCache,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
+
+/// Computed regions embedded in a document; only valid at flatten tolerance
+/// `tol` and region scope `scope`.
+#[derive(Serialize, Deserialize)]
+struct SavedRegions {
+    tol: f32,
+    #[serde(default)]
+    scope: RegionScope,
+    faces: Vec<RegionFaceCache>,
+}
+
+/// Saved fill as a containment anchor: `(key, x, y, area)`, if it has one.
+fn fill_anchor(key: u32, point: Option<Vec2>, area: Option<f32>) -> Option<(u32, f32, f32, f32)> {
+    let (p, area) = (point?, area?);
+    if !limits::in_coord_bounds(p.x) || !limits::in_coord_bounds(p.y) || !area.is_finite() {
+        return None;
+    }
+    Some((key, p.x, p.y, area))
+}
+
+/// Region scope and fill mode of a loaded document. Documents before v9 only
+/// record the scope with embedded regions; otherwise both fall back to their
+/// defaults.
+fn load_region_settings(
+    g: &mut Graph,
+    scope: Option<RegionScope>,
+    fill_mode: Option<RegionFillMode>,
+    saved: &Option<SavedRegions>,
+) {
+    g.region_scope = scope
+        .or(saved.as_ref().map(|r| r.scope))
+        .unwrap_or_default();
+    g.region_fill_mode = fill_mode.unwrap_or_default();
+}
+
+/// Geometry was replaced wholesale: drop derived region state, keep the fill
+/// anchors for remapping, and reuse the saved regions if they were built at
+/// this graph's flatten tolerance and region scope, only reference loaded
+/// edges and have coordinates within bounds.
+fn finish_region_load(
+    g: &mut Graph,
+    anchors: Vec<(u32, f32, f32, f32)>,
+    saved: Option<SavedRegions>,
+) {
+    g.prev_faces.clear();
+    g.region_gradients.clear();
+    g.fill_anchors = anchors;
+    g.region_cache.borrow_mut().take();
+    g.region_index.borrow_mut().take();
+    g.flatten_index.borrow_mut().take();
+    g.flatten_cache.borrow_mut().take();
+    g.incr_plan.borrow_mut().take();
+    g.mark_full_dirty();
+    let Some(saved) = saved else { return };
+    if (saved.tol - g.flatten_tol).abs() > f32::EPSILON
+        || saved.scope != g.region_scope
+        || saved.faces.len() > limits::MAX_EDGES
+    {
+        return;
+    }
+    let edge_ok = |e: u32| g.edges.get(e as usize).is_some_and(|e| e.is_some());
+    let in_bounds = |p: &Vec2| limits::in_coord_bounds(p.x) && limits::in_coord_bounds(p.y);
+    let valid = saved.faces.iter().all(|f| {
+        let (x0, y0, x1, y1) = f.bbox;
+        f.points.len() >= 3
+            && f.area.is_finite()
+            && [x0, y0, x1, y1].into_iter().all(limits::in_coord_bounds)
+            && f.points
+                .iter()
+                .chain(f.holes.iter().flatten())
+                .all(in_bounds)
+            && f.edges
+                .iter()
+                .chain(f.hole_edges.iter().flatten())
+                .all(|&e| edge_ok(e))
+            && f.spans
+                .iter()
+                .chain(f.hole_spans.iter().flatten())
+                .all(|s| edge_ok(s.edge) && s.t_start.is_finite() && s.t_end.is_finite())
+    });
+    if valid {
+        install_region_faces(g, saved.faces);
+    }
+}
+
+/// Member list of a group saved before v6: edges, then subgroups
+fn legacy_group_items(edges: Vec<u32>, children: Vec<LayerId>) -> Vec<GroupItem> {
+    edges
+        .into_iter()
+        .map(GroupItem::Edge)
+        .chain(children.into_iter().map(GroupItem::Group))
+        .collect()
+}
+
+/// Restore saved shapes, dropping any whose edges did not load. Documents
+/// before v6 carry no shapes.
+fn load_shapes(g: &mut Graph, shapes: Option<Vec<Shape>>) {
+    g.shapes.clear();
+    let edge_ok = |g: &Graph, e: u32| g.edges.get(e as usize).is_some_and(|e| e.is_some());
+    for shape in shapes.unwrap_or_default() {
+        let idx = shape.id as usize;
+        if idx >= limits::MAX_EDGES || !shape.edges.iter().all(|&e| edge_ok(g, e)) {
+            continue;
+        }
+        if g.shapes.len() <= idx {
+            g.shapes.resize(idx + 1, None);
+        }
+        g.shapes[idx] = Some(shape);
+    }
+}
 
 pub fn to_json_impl(g: &Graph) -> Value {
+    to_json_impl_with(g, false)
+}
+
+/// Serialize the graph; `include_regions` also embeds the computed regions.
+pub fn to_json_impl_with(g: &Graph, include_regions: bool) -> Value {
     #[derive(Serialize)]
     struct NodeSer {
         id: u32,
@@ -39,12 +157,20 @@ pub fn to_json_impl(g: &Graph) -> Value {
         kind: EdgeSerKind,
         stroke: Option<Color>,
         width: f32,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        construction: bool,
     }
     #[derive(Serialize)]
     struct FillSer {
         key: u32,
         filled: bool,
         color: Option<Color>,
+        // Interior point and area of the region, to re-key the fill by
+        // containment if the key changes on load
+        #[serde(skip_serializing_if = "Option::is_none")]
+        point: Option<Vec2>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        area: Option<f32>,
     }
     #[derive(Serialize)]
     struct LayerSer {
@@ -54,6 +180,7 @@ pub fn to_json_impl(g: &Graph) -> Value {
         visible: bool,
         locked: bool,
         opacity: f32,
+        blend_mode: BlendMode,
         root_group: LayerId,
     }
     #[derive(Serialize)]
@@ -61,11 +188,12 @@ pub fn to_json_impl(g: &Graph) -> Value {
         id: LayerId,
         name: String,
         parent: Option<LayerId>,
-        children: Vec<LayerId>,
-        edges: Vec<u32>,
+        items: Vec<GroupItem>,
         visible: bool,
         locked: bool,
         opacity: f32,
+        blend_mode: BlendMode,
+        mask: Option<GroupMask>,
     }
     #[derive(Serialize)]
     struct GradientSer {
@@ -96,8 +224,13 @@ pub fn to_json_impl(g: &Graph) -> Value {
         groups: Vec<GroupSer>,
         gradients: Vec<GradientSer>,
         texts: Vec<TextElement>,
+        shapes: Vec<Shape>,
         effects: Vec<EffectSer>,
         effect_bindings: Vec<EffectBindingSer>,
+        region_scope: RegionScope,
+        region_fill_mode: RegionFillMode,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        regions: Option<SavedRegions>,
     }
     let mut nodes = Vec::new();
     for (i, n) in g.nodes.iter().enumerate() {
@@ -130,15 +263,43 @@ pub fn to_json_impl(g: &Graph) -> Value {
                 kind,
                 stroke: e.stroke,
                 width: e.stroke_width,
+                construction: e.construction,
             });
         }
     }
+    // Saving never rebuilds regions. Fill keys refer to the faces as last
+    // built, so anchors come from the cache even when it is stale, or from
+    // the anchors loaded with the document; regions are embedded only when
+    // the cache is current.
+    let cache_guard = g.region_cache.borrow();
+    let cache = cache_guard.as_ref();
+    let mut anchors: HashMap<u32, (Vec2, f32)> = g
+        .fill_anchors
+        .iter()
+        .map(|&(key, x, y, area)| (key, (Vec2 { x, y }, area)))
+        .collect();
+    for f in cache.map_or(&[][..], |c| &c.faces) {
+        if g.fills.contains_key(&f.key) {
+            let point = face_interior_point(&f.points, &f.holes);
+            anchors.insert(f.key, (point, f.area));
+        }
+    }
+    let regions = cache
+        .filter(|c| include_regions && region_cache_fresh(g, c))
+        .map(|c| SavedRegions {
+            tol: g.flatten_tol,
+            scope: g.region_scope,
+            faces: c.faces.clone(),
+        });
     let mut fills = Vec::new();
     for (k, v) in g.fills.iter() {
+        let anchor = anchors.get(k);
         fills.push(FillSer {
             key: *k,
             filled: v.filled,
             color: v.color,
+            point: anchor.map(|a| a.0),
+            area: anchor.map(|a| a.1),
         });
     }
     // Serialize layers
@@ -153,6 +314,7 @@ pub fn to_json_impl(g: &Graph) -> Value {
             visible: l.visible,
             locked: l.locked,
             opacity: l.opacity,
+            blend_mode: l.blend_mode,
             root_group: l.root_group,
         })
         .collect();
@@ -165,11 +327,12 @@ pub fn to_json_impl(g: &Graph) -> Value {
             id: gr.id,
             name: gr.name.clone(),
             parent: gr.parent,
-            children: gr.children.clone(),
-            edges: gr.edges.clone(),
+            items: gr.items.clone(),
             visible: gr.visible,
             locked: gr.locked,
             opacity: gr.opacity,
+            blend_mode: gr.blend_mode,
+            mask: gr.mask,
         })
         .collect();
     // Serialize gradients
@@ -183,6 +346,7 @@ pub fn to_json_impl(g: &Graph) -> Value {
         .collect();
     // Serialize texts
     let texts: Vec<TextElement> = g.texts.iter().filter_map(|t| t.clone()).collect();
+    let shapes: Vec<Shape> = g.shapes.iter().filter_map(|s| s.clone()).collect();
     // Serialize effects
     let effects: Vec<EffectSer> = g
         .effects
@@ -235,7 +399,7 @@ pub fn to_json_impl(g: &Graph) -> Value {
         }
     }
     serde_json::to_value(Doc {
-        version: 4,
+        version: 9,
         nodes,
         edges,
         fills,
@@ -243,8 +407,12 @@ pub fn to_json_impl(g: &Graph) -> Value {
         groups,
         gradients,
         texts,
+        shapes,
         effects,
         effect_bindings,
+        region_scope: g.region_scope,
+        region_fill_mode: g.region_fill_mode,
+        regions,
     })
     .unwrap()
 }
@@ -278,12 +446,16 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
         kind: Option<EdgeDeKind>,
         stroke: Option<Color>,
         width: Option<f32>,
+        #[serde(default)]
+        construction: bool,
     }
     #[derive(Deserialize)]
     struct FillDe {
         key: u32,
         filled: bool,
         color: Option<Color>,
+        point: Option<Vec2>,
+        area: Option<f32>,
     }
     #[derive(Deserialize)]
     struct LayerDe {
@@ -293,6 +465,8 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
         visible: bool,
         locked: bool,
         opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
         root_group: LayerId,
     }
     #[derive(Deserialize)]
@@ -300,11 +474,20 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
         id: LayerId,
         name: String,
         parent: Option<LayerId>,
+        // Before v6 groups listed subgroups and edges separately
+        #[serde(default)]
         children: Vec<LayerId>,
+        #[serde(default)]
         edges: Vec<u32>,
+        #[serde(default)]
+        items: Option<Vec<GroupItem>>,
         visible: bool,
         locked: bool,
         opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
+        #[serde(default)]
+        mask: Option<GroupMask>,
     }
     #[derive(Deserialize)]
     struct GradientDe {
@@ -336,8 +519,12 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
         groups: Option<Vec<GroupDe>>,
         gradients: Option<Vec<GradientDe>>,
         texts: Option<Vec<TextElement>>,
+        shapes: Option<Vec<Shape>>,
         effects: Option<Vec<EffectDe>>,
         effect_bindings: Option<Vec<EffectBindingDe>>,
+        region_scope: Option<RegionScope>,
+        region_fill_mode: Option<RegionFillMode>,
+        regions: Option<SavedRegions>,
     }
     let parsed: Result<DocDe, _> = serde_json::from_value(v);
     if let Ok(doc) = parsed {
@@ -394,6 +581,64 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
                 let _ = (c.r, c.g, c.b, c.a);
             }
         }
+        // Restore layers and groups if present (v2 format), checking the
+        // hierarchy before the graph is touched
+        let layer_system = if let (Some(layers), Some(groups)) = (doc.layers, doc.groups) {
+            // V2 format: restore layer system
+            let mut layer_system = LayerSystem::default();
+
+            // Find the max ID to set next_id properly
+            let max_layer_id = layers.iter().map(|l| l.id).max().unwrap_or(0);
+            let max_group_id = groups.iter().map(|gr| gr.id).max().unwrap_or(0);
+            layer_system.next_id = max_layer_id.max(max_group_id) + 1;
+
+            // Restore layers
+            for l in layers {
+                layer_system.layers.push(Layer {
+                    id: l.id,
+                    name: l.name,
+                    z_index: l.z_index,
+                    visible: l.visible,
+                    locked: l.locked,
+                    opacity: l.opacity,
+                    blend_mode: l.blend_mode,
+                    root_group: l.root_group,
+                });
+            }
+
+            // Restore groups
+            for gr in groups {
+                let items = gr
+                    .items
+                    .unwrap_or_else(|| legacy_group_items(gr.edges, gr.children));
+                let dup = layer_system.groups.insert(
+                    gr.id,
+                    Group {
+                        id: gr.id,
+                        name: gr.name,
+                        parent: gr.parent,
+                        items,
+                        visible: gr.visible,
+                        locked: gr.locked,
+                        opacity: gr.opacity,
+                        blend_mode: gr.blend_mode,
+                        mask: gr.mask,
+                    },
+                );
+                if dup.is_some() {
+                    return false;
+                }
+            }
+            // Rebuild the member lookups
+            layer_system.reindex();
+            if layer_system.check_tree().is_err() {
+                return false;
+            }
+            layer_system.drop_invalid_masks();
+            Some(layer_system)
+        } else {
+            None
+        };
         let max_node = doc.nodes.iter().map(|n| n.id).max().unwrap_or(0);
         let max_edge = doc.edges.iter().map(|e| e.id).max().unwrap_or(0);
         g.nodes = vec![None; (max_node as usize) + 1];
@@ -434,11 +679,14 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
                 kind,
                 stroke: e.stroke,
                 stroke_width: width,
+                construction: e.construction,
             });
             loaded_edge_ids.push(e.id);
         }
+        let mut anchors = Vec::new();
         if let Some(fills) = doc.fills {
             for f in fills {
+                anchors.extend(fill_anchor(f.key, f.point, f.area));
                 g.fills.insert(
                     f.key,
                     FillState {
@@ -449,50 +697,8 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
             }
         }
 
-        // Load layers and groups if present (v2 format), otherwise migrate v1
-        if let (Some(layers), Some(groups)) = (doc.layers, doc.groups) {
-            // V2 format: restore layer system
-            let mut layer_system = LayerSystem::default();
-
-            // Find the max ID to set next_id properly
-            let max_layer_id = layers.iter().map(|l| l.id).max().unwrap_or(0);
-            let max_group_id = groups.iter().map(|gr| gr.id).max().unwrap_or(0);
-            layer_system.next_id = max_layer_id.max(max_group_id) + 1;
-
-            // Restore layers
-            for l in layers {
-                layer_system.layers.push(Layer {
-                    id: l.id,
-                    name: l.name,
-                    z_index: l.z_index,
-                    visible: l.visible,
-                    locked: l.locked,
-                    opacity: l.opacity,
-                    root_group: l.root_group,
-                });
-            }
-
-            // Restore groups
-            for gr in groups {
-                layer_system.groups.insert(
-                    gr.id,
-                    Group {
-                        id: gr.id,
-                        name: gr.name,
-                        parent: gr.parent,
-                        children: gr.children,
-                        edges: gr.edges.clone(),
-                        visible: gr.visible,
-                        locked: gr.locked,
-                        opacity: gr.opacity,
-                    },
-                );
-                // Rebuild edge_to_group mapping
-                for eid in gr.edges {
-                    layer_system.edge_to_group.insert(eid, gr.id);
-                }
-            }
-
+        // Layers and groups from a v2 document, otherwise migrate v1
+        if let Some(layer_system) = layer_system {
             g.layer_system = layer_system;
         } else {
             // V1 format: create default layer and assign all edges to it
@@ -529,6 +735,9 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
             }
         }
 
+        load_shapes(g, doc.shapes);
+        g.file_unassigned_members();
+
         // Load effects if present (v4 format)
         g.effects.clear();
         g.shape_effects.clear();
@@ -569,6 +778,8 @@ pub fn from_json_impl(g: &mut Graph, v: Value) -> bool {
         }
 
         g.geom_ver = g.geom_ver.wrapping_add(1);
+        load_region_settings(g, doc.region_scope, doc.region_fill_mode, &doc.regions);
+        finish_region_load(g, anchors, doc.regions);
         true
     } else {
         false
@@ -605,12 +816,16 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         kind: Option<EdgeDeKind>,
         stroke: Option<Color>,
         width: Option<f32>,
+        #[serde(default)]
+        construction: bool,
     }
     #[derive(Deserialize)]
     struct FillDe {
         key: u32,
         filled: bool,
         color: Option<Color>,
+        point: Option<Vec2>,
+        area: Option<f32>,
     }
     #[derive(Deserialize)]
     struct LayerDe {
@@ -620,6 +835,8 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         visible: bool,
         locked: bool,
         opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
         root_group: LayerId,
     }
     #[derive(Deserialize)]
@@ -627,11 +844,20 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         id: LayerId,
         name: String,
         parent: Option<LayerId>,
+        // Before v6 groups listed subgroups and edges separately
+        #[serde(default)]
         children: Vec<LayerId>,
+        #[serde(default)]
         edges: Vec<u32>,
+        #[serde(default)]
+        items: Option<Vec<GroupItem>>,
         visible: bool,
         locked: bool,
         opacity: f32,
+        #[serde(default)]
+        blend_mode: BlendMode,
+        #[serde(default)]
+        mask: Option<GroupMask>,
     }
     #[derive(Deserialize)]
     struct GradientDe {
@@ -663,8 +889,12 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         groups: Option<Vec<GroupDe>>,
         gradients: Option<Vec<GradientDe>>,
         texts: Option<Vec<TextElement>>,
+        shapes: Option<Vec<Shape>>,
         effects: Option<Vec<EffectDe>>,
         effect_bindings: Option<Vec<EffectBindingDe>>,
+        region_scope: Option<RegionScope>,
+        region_fill_mode: Option<RegionFillMode>,
+        regions: Option<SavedRegions>,
     }
     let doc: DocDe = serde_json::from_value(v).map_err(|e| ("json_parse", format!("{}", e)))?;
     if doc.nodes.len() > limits::MAX_NODES {
@@ -728,6 +958,64 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
             }
         }
     }
+    // Restore layers and groups if present (v2 format), checking the
+    // hierarchy before the graph is touched
+    let layer_system = if let (Some(layers), Some(groups)) = (doc.layers, doc.groups) {
+        // V2 format: restore layer system
+        let mut layer_system = LayerSystem::default();
+
+        // Find the max ID to set next_id properly
+        let max_layer_id = layers.iter().map(|l| l.id).max().unwrap_or(0);
+        let max_group_id = groups.iter().map(|gr| gr.id).max().unwrap_or(0);
+        layer_system.next_id = max_layer_id.max(max_group_id) + 1;
+
+        // Restore layers
+        for l in layers {
+            layer_system.layers.push(Layer {
+                id: l.id,
+                name: l.name,
+                z_index: l.z_index,
+                visible: l.visible,
+                locked: l.locked,
+                opacity: l.opacity,
+                blend_mode: l.blend_mode,
+                root_group: l.root_group,
+            });
+        }
+
+        // Restore groups
+        for gr in groups {
+            let items = gr
+                .items
+                .unwrap_or_else(|| legacy_group_items(gr.edges, gr.children));
+            let dup = layer_system.groups.insert(
+                gr.id,
+                Group {
+                    id: gr.id,
+                    name: gr.name,
+                    parent: gr.parent,
+                    items,
+                    visible: gr.visible,
+                    locked: gr.locked,
+                    opacity: gr.opacity,
+                    blend_mode: gr.blend_mode,
+                    mask: gr.mask,
+                },
+            );
+            if dup.is_some() {
+                return Err(("invalid_structure", format!("duplicate group id {}", gr.id)));
+            }
+        }
+        // Rebuild the member lookups
+        layer_system.reindex();
+        if let Err(msg) = layer_system.check_tree() {
+            return Err(("invalid_structure", msg));
+        }
+        layer_system.drop_invalid_masks();
+        Some(layer_system)
+    } else {
+        None
+    };
     let max_node = doc.nodes.iter().map(|n| n.id).max().unwrap_or(0);
     let max_edge = doc.edges.iter().map(|e| e.id).max().unwrap_or(0);
     g.nodes = vec![None; (max_node as usize) + 1];
@@ -768,11 +1056,14 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
             kind,
             stroke: e.stroke,
             stroke_width: width,
+            construction: e.construction,
         });
         loaded_edge_ids.push(e.id);
     }
+    let mut anchors = Vec::new();
     if let Some(fills) = doc.fills {
         for f in fills {
+            anchors.extend(fill_anchor(f.key, f.point, f.area));
             g.fills.insert(
                 f.key,
                 FillState {
@@ -783,50 +1074,8 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         }
     }
 
-    // Load layers and groups if present (v2 format), otherwise migrate v1
-    if let (Some(layers), Some(groups)) = (doc.layers, doc.groups) {
-        // V2 format: restore layer system
-        let mut layer_system = LayerSystem::default();
-
-        // Find the max ID to set next_id properly
-        let max_layer_id = layers.iter().map(|l| l.id).max().unwrap_or(0);
-        let max_group_id = groups.iter().map(|gr| gr.id).max().unwrap_or(0);
-        layer_system.next_id = max_layer_id.max(max_group_id) + 1;
-
-        // Restore layers
-        for l in layers {
-            layer_system.layers.push(Layer {
-                id: l.id,
-                name: l.name,
-                z_index: l.z_index,
-                visible: l.visible,
-                locked: l.locked,
-                opacity: l.opacity,
-                root_group: l.root_group,
-            });
-        }
-
-        // Restore groups
-        for gr in groups {
-            layer_system.groups.insert(
-                gr.id,
-                Group {
-                    id: gr.id,
-                    name: gr.name,
-                    parent: gr.parent,
-                    children: gr.children,
-                    edges: gr.edges.clone(),
-                    visible: gr.visible,
-                    locked: gr.locked,
-                    opacity: gr.opacity,
-                },
-            );
-            // Rebuild edge_to_group mapping
-            for eid in gr.edges {
-                layer_system.edge_to_group.insert(eid, gr.id);
-            }
-        }
-
+    // Layers and groups from a v2 document, otherwise migrate v1
+    if let Some(layer_system) = layer_system {
         g.layer_system = layer_system;
     } else {
         // V1 format: create default layer and assign all edges to it
@@ -863,6 +1112,9 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
         }
     }
 
+    load_shapes(g, doc.shapes);
+    g.file_unassigned_members();
+
     // Load effects if present (v4 format)
     g.effects.clear();
     g.shape_effects.clear();
@@ -903,5 +1155,230 @@ pub fn from_json_impl_strict(g: &mut Graph, v: Value) -> Result<bool, (&'static
     }
 
     g.geom_ver = g.geom_ver.wrapping_add(1);
+    load_region_settings(g, doc.region_scope, doc.region_fill_mode, &doc.regions);
+    finish_region_load(g, anchors, doc.regions);
     Ok(true)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::sorted_keys;
+
+    fn scene(g: &mut Graph) {
+        let _ = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let _ = g.add_ellipse(50.0, 50.0, 20.0, 20.0);
+    }
+
+    fn color_at(g: &mut Graph, x: f32, y: f32) -> Value {
+        let key = g.region_at(x, y).unwrap() as u64;
+        let r = g.get_regions();
+        r.into_iter()
+            .find(|r| r["key"].as_u64() == Some(key))
+            .unwrap()["color"]
+            .clone()
+    }
+
+    #[test]
+    fn renamed_fill_keys_remap_by_containment() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let key = g.region_at(10.0, 10.0).unwrap();
+        g.set_region_color(key, 255, 0, 0, 255);
+        let mut doc = g.to_json_value();
+        assert_eq!(doc["version"], 9);
+
+        // Simulate a key scheme change between versions
+        for f in doc["fills"].as_array_mut().unwrap() {
+            let k = f["key"].as_u64().unwrap();
+            f["key"] = Value::from(k ^ 0x5a5a_5a5a);
+        }
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(doc.clone()));
+        assert_eq!(
+            color_at(&mut loaded, 10.0, 10.0),
+            serde_json::json!([255, 0, 0, 255])
+        );
+        assert_eq!(color_at(&mut loaded, 50.0, 50.0), Value::Null);
+
+        // Without anchors the fill is lost
+        for f in doc["fills"].as_array_mut().unwrap() {
+            f.as_object_mut().unwrap().remove("point");
+        }
+        let mut bare = Graph::new();
+        assert_eq!(bare.from_json_value_strict(doc), Ok(true));
+        assert_eq!(color_at(&mut bare, 10.0, 10.0), Value::Null);
+    }
+
+    #[test]
+    fn saved_regions_load_without_rebuild() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let expect = sorted_keys(&g.get_regions());
+        let doc = g.to_json_value_with_regions();
+        assert!(g.to_json_value().get("regions").is_none());
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(doc.clone()));
+        let built = loaded.region_cache.borrow().as_ref().map(|c| c.built_ver);
+        assert_eq!(built, Some(loaded.geom_version()));
+        assert!(!loaded.dirty.full);
+        assert_eq!(sorted_keys(&loaded.get_regions()), expect);
+
+        // A cache built at another tolerance is ignored
+        let mut other = Graph::new();
+        other.set_flatten_tolerance(0.9);
+        assert!(other.from_json_value(doc.clone()));
+        assert!(other.region_cache.borrow().is_none());
+        assert!(!other.get_regions().is_empty());
+
+        // So are faces with coordinates out of bounds
+        for (field, value) in [
+            ("points", serde_json::json!({ "x": 1e30, "y": 0.0 })),
+            ("bbox", 1e30.into()),
+        ] {
+            let mut bad = doc.clone();
+            bad["regions"]["faces"][0][field][0] = value;
+            let mut loaded = Graph::new();
+            assert!(loaded.from_json_value(bad));
+            assert!(loaded.region_cache.borrow().is_none());
+        }
+
+        // Saving after an edit embeds no regions rather than rebuilding them
+        g.move_node(0, 5.0, 0.0);
+        let built = g.region_cache.borrow().as_ref().map(|c| c.built_ver);
+        assert!(g.to_json_value_with_regions().get("regions").is_none());
+        assert_eq!(g.region_cache.borrow().as_ref().map(|c| c.built_ver), built);
+    }
+
+    #[test]
+    fn region_settings_round_trip() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        g.set_region_scope(RegionScope::Layer);
+        g.set_region_fill_mode(RegionFillMode::Shapes);
+        let mut doc = g.to_json_value();
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(doc.clone()));
+        assert_eq!(loaded.get_region_scope(), RegionScope::Layer);
+        assert_eq!(loaded.get_region_fill_mode(), RegionFillMode::Shapes);
+
+        // Older documents carry neither setting and load with the defaults
+        let obj = doc.as_object_mut().unwrap();
+        obj.remove("region_scope");
+        obj.remove("region_fill_mode");
+        assert_eq!(loaded.from_json_value_strict(doc), Ok(true));
+        assert_eq!(loaded.get_region_scope(), RegionScope::All);
+        assert_eq!(loaded.get_region_fill_mode(), RegionFillMode::Faces);
+    }
+
+    #[test]
+    fn construction_flag_round_trips() {
+        let mut g = Graph::new();
+        scene(&mut g);
+        let a = g.add_node(-50.0, 50.0);
+        let b = g.add_node(150.0, 50.0);
+        let guide = g.add_edge(a, b).unwrap();
+        assert!(g.set_edge_construction(guide, true));
+        let count = g.get_regions().len();
+        let doc = g.to_json_value();
+        assert_eq!(doc["edges"][guide as usize]["construction"], true);
+        assert!(doc["edges"][0].get("construction").is_none());
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(doc));
+        assert!(loaded.is_edge_construction(guide));
+        assert_eq!(loaded.get_regions().len(), count);
+    }
+
+    #[test]
+    fn shapes_round_trip_with_their_groups() {
+        let mut g = Graph::new();
+        let rect = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let root = g.default_group().unwrap();
+        let grp = g.create_group("G".to_string(), root).unwrap();
+        assert!(g.add_item_to_group(GroupItem::Shape(rect.shape), grp));
+        let doc = g.to_json_value();
+        assert_eq!(doc["shapes"][0]["edges"], serde_json::json!(rect.edges));
+
+        for strict in [false, true] {
+            let mut loaded = Graph::new();
+            if strict {
+                assert!(loaded.from_json_value_strict(doc.clone()).is_ok());
+            } else {
+                assert!(loaded.from_json_value(doc.clone()));
+            }
+            assert_eq!(loaded.get_shape(rect.shape).unwrap().edges, rect.edges);
+            assert_eq!(
+                loaded.get_item_group(GroupItem::Shape(rect.shape)),
+                Some(grp)
+            );
+        }
+    }
+
+    #[test]
+    fn blend_modes_and_shapes_round_trip() {
+        let mut g = Graph::new();
+        let rect = g.add_rectangle(0.0, 0.0, 100.0, 100.0, 0.0);
+        let layer = g.get_layers()[0].0;
+        let root = g.default_group().unwrap();
+        let grp = g.create_group("G".to_string(), root).unwrap();
+        assert!(g.add_item_to_group(GroupItem::Shape(rect.shape), grp));
+        assert!(g.set_layer_blend_mode(layer, BlendMode::Multiply));
+        assert!(g.set_group_blend_mode(grp, BlendMode::ColorDodge));
+        assert!(g.set_shape_blend_mode(rect.shape, BlendMode::Luminosity));
+
+        let svg = g.to_svg_document();
+        assert!(
+            svg.contains(r#"id="layer-0" data-name="Layer 1" style="mix-blend-mode:multiply">"#)
+        );
+        assert!(svg.contains(r#"data-name="G" style="mix-blend-mode:color-dodge">"#));
+        // The shape paints through its edges, which carry its blend mode
+        assert!(!svg.contains(r#"fill-rule="nonzero" style="#));
+        let blended = r#"stroke-linejoin="round" style="mix-blend-mode:luminosity"/>"#;
+        assert_eq!(svg.matches(blended).count(), rect.edges.len());
+
+        let doc = g.to_json_value();
+        assert_eq!(doc["layers"][0]["blend_mode"], "multiply");
+        assert_eq!(doc["shapes"][0]["blend_mode"], "luminosity");
+        for strict in [false, true] {
+            let mut loaded = Graph::new();
+            if strict {
+                assert!(loaded.from_json_value_strict(doc.clone()).is_ok());
+            } else {
+                assert!(loaded.from_json_value(doc.clone()));
+            }
+            assert_eq!(
+                loaded.get_layer_blend_mode(layer),
+                Some(BlendMode::Multiply)
+            );
+            assert_eq!(
+                loaded.get_group_blend_mode(grp),
+                Some(BlendMode::ColorDodge)
+            );
+            let shape = loaded.get_shape(rect.shape).unwrap();
+            assert_eq!(shape.blend_mode, BlendMode::Luminosity);
+            assert_eq!(shape.edges, rect.edges);
+            assert_eq!(
+                loaded.get_item_group(GroupItem::Shape(rect.shape)),
+                Some(grp)
+            );
+            assert_eq!(loaded.to_svg_document(), svg);
+        }
+
+        // Older documents carry neither; everything blends normally
+        let mut old = doc;
+        old.as_object_mut().unwrap().remove("shapes");
+        old["layers"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("blend_mode");
+        // Loading over a graph drops its shapes too
+        let mut loaded = Graph::new();
+        loaded.create_shape(&[], true);
+        assert!(loaded.from_json_value(old));
+        assert_eq!(loaded.get_layer_blend_mode(layer), Some(BlendMode::Normal));
+        assert!(loaded.get_shape(rect.shape).is_none());
+    }
+}
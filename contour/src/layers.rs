@@ -1,13 +1,68 @@
-use crate::model::{Group, Layer, LayerId};
+use crate::model::{BlendMode, Group, GroupItem, GroupMask, Layer, LayerId};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// A layer in the tree returned by [`LayerSystem::get_tree`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LayerNode {
+    pub id: LayerId,
+    pub name: String,
+    pub z_index: i32,
+    pub visible: bool,
+    pub locked: bool,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub root: GroupNode,
+}
+
+/// A group in the tree returned by [`LayerSystem::get_tree`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GroupNode {
+    pub id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    pub locked: bool,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub mask: Option<GroupMask>,
+    /// Members, bottom to top
+    pub items: Vec<TreeItem>,
+}
+
+/// A member of a [`GroupNode`]
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TreeItem {
+    Group(GroupNode),
+    Edge { id: u32 },
+    Shape { id: u32 },
+    Text { id: u32 },
+}
 
-/// Manages layers and groups for organizing edges hierarchically
+/// Where [`LayerSystem::reorder_item`] moves an item among its siblings
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZOrder {
+    /// On top of every sibling
+    Front,
+    /// Below every sibling
+    Back,
+    /// One step up
+    Forward,
+    /// One step down
+    Backward,
+}
+
+/// Manages layers and groups for organizing edges, shapes and texts
+/// hierarchically
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LayerSystem {
     pub layers: Vec<Layer>,
     pub groups: HashMap<LayerId, Group>,
     pub edge_to_group: HashMap<u32, LayerId>,
+    #[serde(default)]
+    pub shape_to_group: HashMap<u32, LayerId>,
+    #[serde(default)]
+    pub text_to_group: HashMap<u32, LayerId>,
     pub(crate) next_id: LayerId,
 }
 
@@ -33,11 +88,12 @@ impl LayerSystem {
             id: root_group_id,
             name: format!("{} (root)", name),
             parent: None,
-            children: Vec::new(),
-            edges: Vec::new(),
+            items: Vec::new(),
             visible: true,
             locked: false,
             opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            mask: None,
         };
 
         let z = self.layers.len() as i32;
@@ -48,6 +104,7 @@ impl LayerSystem {
             visible: true,
             locked: false,
             opacity: 1.0,
+            blend_mode: BlendMode::Normal,
             root_group: root_group_id,
         };
 
@@ -56,26 +113,32 @@ impl LayerSystem {
         layer_id
     }
 
-    /// Remove a layer and all its groups, returns removed edge IDs
-    pub fn remove_layer(&mut self, id: LayerId) -> Option<Vec<u32>> {
+    /// Remove a layer and all its groups, returns the removed edges, shapes
+    /// and texts
+    pub fn remove_layer(&mut self, id: LayerId) -> Option<Vec<GroupItem>> {
         let idx = self.layers.iter().position(|l| l.id == id)?;
         let layer = self.layers.remove(idx);
 
-        let mut removed_edges = Vec::new();
+        let mut removed = Vec::new();
         let mut groups_to_remove = vec![layer.root_group];
 
         while let Some(gid) = groups_to_remove.pop() {
             if let Some(group) = self.groups.remove(&gid) {
-                removed_edges.extend(group.edges.iter().copied());
-                groups_to_remove.extend(group.children.iter().copied());
-
-                for eid in &group.edges {
-                    self.edge_to_group.remove(eid);
+                for item in group.items {
+                    match item {
+                        GroupItem::Group(child) => groups_to_remove.push(child),
+                        item => {
+                            if let Some((map, key)) = self.member_map_mut(item) {
+                                map.remove(&key);
+                            }
+                            removed.push(item);
+                        }
+                    }
                 }
             }
         }
 
-        Some(removed_edges)
+        Some(removed)
     }
 
     /// Get layer by ID
@@ -104,16 +167,17 @@ impl LayerSystem {
             id: group_id,
             name,
             parent: Some(parent_id),
-            children: Vec::new(),
-            edges: Vec::new(),
+            items: Vec::new(),
             visible: true,
             locked: false,
             opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            mask: None,
         };
 
         self.groups.insert(group_id, group);
         if let Some(parent) = self.groups.get_mut(&parent_id) {
-            parent.children.push(group_id);
+            parent.items.push(GroupItem::Group(group_id));
         }
 
         Some(group_id)
@@ -121,51 +185,169 @@ impl LayerSystem {
 
     /// Remove a group and reassign its edges/children to parent
     pub fn remove_group(&mut self, id: LayerId) -> bool {
-        let group = match self.groups.remove(&id) {
-            Some(g) => g,
-            None => return false,
-        };
-
-        // Can't remove root groups (those without parents)
-        let parent_id = match group.parent {
-            Some(p) => p,
-            None => {
-                // Put it back
-                self.groups.insert(id, group);
-                return false;
-            }
-        };
+        self.ungroup(id).is_some()
+    }
 
-        // Collect children to reparent
-        let children_to_reparent: Vec<LayerId> = group.children.clone();
+    /// Dissolve a group into its parent, keeping its content: its members
+    /// take the group's place in the parent's order. Returns the promoted
+    /// child group IDs, or None for unknown and root groups.
+    pub fn ungroup(&mut self, id: LayerId) -> Option<Vec<LayerId>> {
+        let parent_id = self.groups.get(&id)?.parent?;
+        let group = self.groups.remove(&id)?;
 
-        // Move edges to parent and update parent's children list
         if let Some(parent) = self.groups.get_mut(&parent_id) {
-            parent.children.retain(|&c| c != id);
-            for eid in &group.edges {
-                parent.edges.push(*eid);
+            let slot = GroupItem::Group(id);
+            let at = parent.items.iter().position(|&i| i == slot);
+            parent.items.retain(|&i| i != slot);
+            let at = at.unwrap_or(parent.items.len());
+            parent.items.splice(at..at, group.items.iter().copied());
+        }
+
+        for &item in &group.items {
+            self.set_parent(item, parent_id);
+        }
+
+        Some(group.child_groups().collect())
+    }
+
+    /// Pull every member below a group up into it and drop its subgroups.
+    /// Each subgroup is replaced by its members in place, so drawing order
+    /// is kept. Subgroup visibility, locks and opacity are discarded.
+    /// Returns the removed group IDs.
+    pub fn flatten_group(&mut self, id: LayerId) -> Option<Vec<LayerId>> {
+        let items = std::mem::take(&mut self.groups.get_mut(&id)?.items);
+
+        let mut removed = Vec::new();
+        let mut flat = Vec::new();
+        // Depth-first, preorder: push reversed so the first member pops first
+        let mut stack: Vec<GroupItem> = items.into_iter().rev().collect();
+        while let Some(item) = stack.pop() {
+            match item {
+                GroupItem::Group(gid) => {
+                    if let Some(group) = self.groups.remove(&gid) {
+                        stack.extend(group.items.into_iter().rev());
+                        removed.push(gid);
+                    }
+                }
+                item => flat.push(item),
             }
-            // Add removed group's children to parent
-            for child_id in &children_to_reparent {
-                parent.children.push(*child_id);
+        }
+
+        for &item in &flat {
+            self.set_parent(item, id);
+        }
+        if let Some(group) = self.groups.get_mut(&id) {
+            group.items = flat;
+        }
+
+        Some(removed)
+    }
+
+    /// Whether `group_id` is `ancestor_id` or sits somewhere below it
+    pub fn is_within(&self, group_id: LayerId, ancestor_id: LayerId) -> bool {
+        let mut current = Some(group_id);
+        while let Some(gid) = current {
+            if gid == ancestor_id {
+                return true;
             }
+            current = self.groups.get(&gid).and_then(|g| g.parent);
+        }
+        false
+    }
+
+    /// Move a group (with its subtree) under a new parent, possibly in
+    /// another layer. `index` is the position among the new parent's
+    /// members after the move, bottom to top; None or past the end puts it
+    /// on top. Refused for root groups, unknown IDs and moves that would
+    /// place a group inside its own subtree.
+    pub fn move_group(&mut self, id: LayerId, new_parent: LayerId, index: Option<usize>) -> bool {
+        let Some(old_parent) = self.groups.get(&id).and_then(|g| g.parent) else {
+            return false;
+        };
+        if !self.groups.contains_key(&new_parent) || self.is_within(new_parent, id) {
+            return false;
         }
 
-        // Update edge-to-group mappings
-        for eid in &group.edges {
-            self.edge_to_group.insert(*eid, parent_id);
+        let slot = GroupItem::Group(id);
+        if let Some(parent) = self.groups.get_mut(&old_parent) {
+            parent.items.retain(|&i| i != slot);
         }
+        if let Some(parent) = self.groups.get_mut(&new_parent) {
+            let at = index.map_or(parent.items.len(), |i| i.min(parent.items.len()));
+            parent.items.insert(at, slot);
+        }
+        self.set_parent(slot, new_parent);
+        true
+    }
 
-        // Reparent children (separate borrow)
-        for child_id in &children_to_reparent {
-            if let Some(child) = self.groups.get_mut(child_id) {
-                child.parent = Some(parent_id);
+    /// Move a group to the top level of a layer
+    pub fn move_group_to_layer(
+        &mut self,
+        id: LayerId,
+        layer_id: LayerId,
+        index: Option<usize>,
+    ) -> bool {
+        match self.get_layer(layer_id) {
+            Some(layer) => {
+                let root = layer.root_group;
+                self.move_group(id, root, index)
             }
+            None => false,
         }
+    }
 
+    /// Move a group to a new position among its siblings (bottom to top)
+    pub fn reorder_group(&mut self, id: LayerId, index: usize) -> bool {
+        match self.groups.get(&id).and_then(|g| g.parent) {
+            Some(parent) => self.move_group(id, parent, Some(index)),
+            None => false,
+        }
+    }
+
+    /// Move an edge, shape, text or group among its siblings. Returns false
+    /// if the item is not in a group (root groups included).
+    pub fn reorder_item(&mut self, item: GroupItem, to: ZOrder) -> bool {
+        let Some(parent) = self.item_group(item) else {
+            return false;
+        };
+        let Some(items) = self.groups.get_mut(&parent).map(|g| &mut g.items) else {
+            return false;
+        };
+        let Some(from) = items.iter().position(|&i| i == item) else {
+            return false;
+        };
+        let last = items.len() - 1;
+        let at = match to {
+            ZOrder::Front => last,
+            ZOrder::Back => 0,
+            ZOrder::Forward => (from + 1).min(last),
+            ZOrder::Backward => from.saturating_sub(1),
+        };
+        items.remove(from);
+        items.insert(at, item);
         true
     }
 
+    /// Move edges into a group in the given order, on top of its existing
+    /// members. Returns how many were moved (0 if the group doesn't exist).
+    pub fn move_edges_to_group(&mut self, edge_ids: &[u32], group_id: LayerId) -> usize {
+        let items: Vec<GroupItem> = edge_ids.iter().map(|&e| GroupItem::Edge(e)).collect();
+        self.move_items_to_group(&items, group_id)
+    }
+
+    /// Move edges, shapes and texts into a group in the given order, on top
+    /// of its existing members. Groups in the list are skipped (use
+    /// [`Self::move_group`]). Returns how many were moved.
+    pub fn move_items_to_group(&mut self, items: &[GroupItem], group_id: LayerId) -> usize {
+        if !self.groups.contains_key(&group_id) {
+            return 0;
+        }
+        items
+            .iter()
+            .filter(|&&item| self.add_item_to_group(item, group_id))
+            .count()
+    }
+
     /// Get group by ID
     pub fn get_group(&self, id: LayerId) -> Option<&Group> {
         self.groups.get(&id)
@@ -176,31 +358,186 @@ impl LayerSystem {
         self.groups.get_mut(&id)
     }
 
-    /// Add an edge to a group
-    pub fn add_edge_to_group(&mut self, edge_id: u32, group_id: LayerId) -> bool {
-        // Remove from previous group if any
-        if let Some(old_group_id) = self.edge_to_group.remove(&edge_id) {
-            if let Some(old_group) = self.groups.get_mut(&old_group_id) {
-                old_group.edges.retain(|&e| e != edge_id);
+    /// Lookup map and key for an edge, shape or text; None for groups
+    fn member_map(&self, item: GroupItem) -> Option<(&HashMap<u32, LayerId>, u32)> {
+        match item {
+            GroupItem::Group(_) => None,
+            GroupItem::Edge(id) => Some((&self.edge_to_group, id)),
+            GroupItem::Shape(id) => Some((&self.shape_to_group, id)),
+            GroupItem::Text(id) => Some((&self.text_to_group, id)),
+        }
+    }
+
+    fn member_map_mut(&mut self, item: GroupItem) -> Option<(&mut HashMap<u32, LayerId>, u32)> {
+        match item {
+            GroupItem::Group(_) => None,
+            GroupItem::Edge(id) => Some((&mut self.edge_to_group, id)),
+            GroupItem::Shape(id) => Some((&mut self.shape_to_group, id)),
+            GroupItem::Text(id) => Some((&mut self.text_to_group, id)),
+        }
+    }
+
+    /// Point a member's back-reference at `group_id`
+    fn set_parent(&mut self, item: GroupItem, group_id: LayerId) {
+        match item {
+            GroupItem::Group(gid) => {
+                if let Some(group) = self.groups.get_mut(&gid) {
+                    group.parent = Some(group_id);
+                }
+            }
+            item => {
+                if let Some((map, key)) = self.member_map_mut(item) {
+                    map.insert(key, group_id);
+                }
             }
         }
+    }
 
+    /// Put an edge, shape or text on top of a group, leaving any group it
+    /// was in. Returns false for unknown groups and for group items.
+    pub fn add_item_to_group(&mut self, item: GroupItem, group_id: LayerId) -> bool {
+        if matches!(item, GroupItem::Group(_)) || !self.groups.contains_key(&group_id) {
+            return false;
+        }
+        self.remove_item(item);
         if let Some(group) = self.groups.get_mut(&group_id) {
-            group.edges.push(edge_id);
-            self.edge_to_group.insert(edge_id, group_id);
-            true
-        } else {
-            false
+            group.items.push(item);
         }
+        self.set_parent(item, group_id);
+        true
     }
 
-    /// Remove an edge from its group
-    pub fn remove_edge(&mut self, edge_id: u32) {
-        if let Some(group_id) = self.edge_to_group.remove(&edge_id) {
+    /// Take an edge, shape or text out of its group
+    pub fn remove_item(&mut self, item: GroupItem) {
+        let Some((map, key)) = self.member_map_mut(item) else {
+            return;
+        };
+        if let Some(group_id) = map.remove(&key) {
             if let Some(group) = self.groups.get_mut(&group_id) {
-                group.edges.retain(|&e| e != edge_id);
+                group.items.retain(|&i| i != item);
+            }
+        }
+    }
+
+    /// Put the edges, shapes or texts `with` in place of `item`, at its
+    /// position in its group, and take `item` out. Does nothing if `item`
+    /// has no group.
+    pub(crate) fn replace_item(&mut self, item: GroupItem, with: &[GroupItem]) {
+        let Some(group_id) = self.item_group(item) else {
+            return;
+        };
+        for &new in with {
+            self.remove_item(new);
+        }
+        let Some(items) = self.groups.get_mut(&group_id).map(|g| &mut g.items) else {
+            return;
+        };
+        let Some(at) = items.iter().position(|&i| i == item) else {
+            return;
+        };
+        items.splice(at..=at, with.iter().copied());
+        if let Some((map, key)) = self.member_map_mut(item) {
+            map.remove(&key);
+        }
+        for &new in with {
+            self.set_parent(new, group_id);
+        }
+    }
+
+    /// Get the group holding an edge, shape or text
+    pub fn item_group(&self, item: GroupItem) -> Option<LayerId> {
+        match item {
+            GroupItem::Group(gid) => self.groups.get(&gid)?.parent,
+            item => {
+                let (map, key) = self.member_map(item)?;
+                map.get(&key).copied()
+            }
+        }
+    }
+
+    /// Rebuild the member lookups from the groups' item lists
+    pub(crate) fn reindex(&mut self) {
+        self.edge_to_group.clear();
+        self.shape_to_group.clear();
+        self.text_to_group.clear();
+        let memberships: Vec<(GroupItem, LayerId)> = self
+            .groups
+            .values()
+            .flat_map(|g| g.items.iter().map(move |&i| (i, g.id)))
+            .filter(|(i, _)| !matches!(i, GroupItem::Group(_)))
+            .collect();
+        for (item, gid) in memberships {
+            self.set_parent(item, gid);
+        }
+    }
+
+    /// Structural check for a hierarchy read from a document: every layer
+    /// has its own root group without a parent, every other group is listed
+    /// exactly once, in the items of its declared parent, parent chains end
+    /// at a layer's root, and no item is listed twice.
+    pub(crate) fn check_tree(&self) -> Result<(), String> {
+        let roots: HashSet<LayerId> = self.layers.iter().map(|l| l.root_group).collect();
+        if roots.len() != self.layers.len() {
+            return Err("layers share a root group".into());
+        }
+        for &root in &roots {
+            match self.groups.get(&root) {
+                None => return Err(format!("root group {} missing", root)),
+                Some(g) if g.parent.is_some() => {
+                    return Err(format!("root group {} has a parent", root))
+                }
+                Some(_) => {}
+            }
+        }
+        let mut ids: Vec<LayerId> = self.groups.keys().copied().collect();
+        ids.sort_unstable();
+        let mut listed: HashSet<GroupItem> = HashSet::new();
+        for &gid in &ids {
+            for &item in &self.groups[&gid].items {
+                if !listed.insert(item) {
+                    return Err(format!("{:?} listed twice", item));
+                }
+                let GroupItem::Group(child) = item else {
+                    continue;
+                };
+                if self.groups.get(&child).map(|c| c.parent) != Some(Some(gid)) {
+                    return Err(format!(
+                        "group {} listed under {}, not its parent",
+                        child, gid
+                    ));
+                }
+            }
+        }
+        for &gid in &ids {
+            if !roots.contains(&gid) && !listed.contains(&GroupItem::Group(gid)) {
+                return Err(format!("group {} not listed by a parent", gid));
+            }
+            // Each step moves to the group listing the current one
+            let mut current = gid;
+            for _ in 0..=ids.len() {
+                match self.groups[&current].parent {
+                    Some(parent) => current = parent,
+                    None => break,
+                }
+            }
+            if !roots.contains(&current) {
+                return Err(format!(
+                    "group {} is in a cycle or outside every layer",
+                    gid
+                ));
             }
         }
+        Ok(())
+    }
+
+    /// Add an edge to a group
+    pub fn add_edge_to_group(&mut self, edge_id: u32, group_id: LayerId) -> bool {
+        self.add_item_to_group(GroupItem::Edge(edge_id), group_id)
+    }
+
+    /// Remove an edge from its group
+    pub fn remove_edge(&mut self, edge_id: u32) {
+        self.remove_item(GroupItem::Edge(edge_id))
     }
 
     /// Get the group containing an edge
@@ -227,10 +564,32 @@ impl LayerSystem {
         }
     }
 
+    /// Get the layer containing an edge, shape, text or group
+    pub fn item_layer(&self, item: GroupItem) -> Option<LayerId> {
+        match item {
+            GroupItem::Group(gid) => self.find_layer_for_group(gid),
+            item => self.find_layer_for_group(self.item_group(item)?),
+        }
+    }
+
     /// Get the layer containing an edge
     pub fn get_edge_layer(&self, edge_id: u32) -> Option<LayerId> {
-        let group_id = self.edge_to_group.get(&edge_id)?;
-        self.find_layer_for_group(*group_id)
+        self.item_layer(GroupItem::Edge(edge_id))
+    }
+
+    /// Z-index of the layer holding an item; items outside any layer sit
+    /// with the default layer
+    pub fn item_z_index(&self, item: GroupItem) -> i32 {
+        self.item_layer(item)
+            .and_then(|id| self.get_layer(id))
+            .or(self.layers.first())
+            .map_or(0, |l| l.z_index)
+    }
+
+    /// Z-index of the layer holding an edge; edges outside any layer sit with
+    /// the default layer
+    pub fn edge_z_index(&self, edge_id: u32) -> i32 {
+        self.item_z_index(GroupItem::Edge(edge_id))
     }
 
     /// Get layers in z-order (bottom to top)
@@ -240,13 +599,71 @@ impl LayerSystem {
         layers
     }
 
+    /// Edges, shapes and texts in drawing order, bottom to top: layers by
+    /// z-index, then each group's items depth-first. Hidden items included.
+    pub fn draw_order(&self) -> Vec<GroupItem> {
+        let mut out = Vec::new();
+        let mut seen = HashSet::new();
+        for layer in self.layers_ordered() {
+            self.collect_drawn(layer.root_group, &mut out, &mut seen);
+        }
+        out
+    }
+
+    /// Members of `id` in drawing order; `seen` keeps each group to one visit.
+    fn collect_drawn(&self, id: LayerId, out: &mut Vec<GroupItem>, seen: &mut HashSet<LayerId>) {
+        let Some(group) = self.groups.get(&id) else {
+            return;
+        };
+        if !seen.insert(id) {
+            return;
+        }
+        for &item in &group.items {
+            match item {
+                GroupItem::Group(child) => self.collect_drawn(child, out, seen),
+                item => out.push(item),
+            }
+        }
+    }
+
+    /// Position of an item in [`Self::draw_order`] as a sortable key: the
+    /// layer's rank by z-index, then the sibling index at each level down
+    /// to the item. Greater keys draw on top; None outside any layer.
+    pub fn paint_key(&self, item: GroupItem) -> Option<Vec<usize>> {
+        let mut key = Vec::new();
+        let mut current = item;
+        while let Some(parent) = self.item_group(current) {
+            let siblings = &self.groups.get(&parent)?.items;
+            key.push(siblings.iter().position(|&i| i == current)?);
+            current = GroupItem::Group(parent);
+        }
+        let GroupItem::Group(root) = current else {
+            return None;
+        };
+        let layers = self.layers_ordered();
+        key.push(layers.iter().position(|l| l.root_group == root)?);
+        key.reverse();
+        Some(key)
+    }
+
+    /// Check if an item is visible (considering layer and group visibility
+    /// chain). Items without a group are visible.
+    pub fn is_item_visible(&self, item: GroupItem) -> bool {
+        match item {
+            GroupItem::Group(gid) => self.is_group_chain_visible(gid),
+            item => self
+                .item_group(item)
+                .is_none_or(|gid| self.is_group_chain_visible(gid)),
+        }
+    }
+
     /// Check if an edge is visible (considering layer and group visibility chain)
     pub fn is_edge_visible(&self, edge_id: u32) -> bool {
-        let group_id = match self.edge_to_group.get(&edge_id) {
-            Some(gid) => *gid,
-            None => return true, // Edges without group are visible by default
-        };
+        self.is_item_visible(GroupItem::Edge(edge_id))
+    }
 
+    /// Check if a group, its ancestors and its layer are all visible
+    pub fn is_group_chain_visible(&self, group_id: LayerId) -> bool {
         let mut current = group_id;
         loop {
             let group = match self.groups.get(&current) {
@@ -271,11 +688,57 @@ impl LayerSystem {
         }
     }
 
-    /// Compute effective opacity for an edge (accumulates through chain)
-    pub fn edge_opacity(&self, edge_id: u32) -> f32 {
-        let group_id = match self.edge_to_group.get(&edge_id) {
-            Some(gid) => *gid,
-            None => return 1.0,
+    /// Check if an item is locked (any group in its chain, or its layer)
+    pub fn is_item_locked(&self, item: GroupItem) -> bool {
+        match item {
+            GroupItem::Group(gid) => self.is_group_chain_locked(gid),
+            item => self
+                .item_group(item)
+                .is_some_and(|gid| self.is_group_chain_locked(gid)),
+        }
+    }
+
+    /// Check if an edge is locked (any group in its chain, or its layer)
+    pub fn is_edge_locked(&self, edge_id: u32) -> bool {
+        self.is_item_locked(GroupItem::Edge(edge_id))
+    }
+
+    /// Whether any layer or group is locked; lets edit paths skip lock checks
+    pub fn has_locks(&self) -> bool {
+        self.layers.iter().any(|l| l.locked) || self.groups.values().any(|g| g.locked)
+    }
+
+    /// Check if a group is locked (itself, an ancestor, or its layer)
+    pub fn is_group_chain_locked(&self, group_id: LayerId) -> bool {
+        let mut current = group_id;
+        loop {
+            let Some(group) = self.groups.get(&current) else {
+                return false;
+            };
+            if group.locked {
+                return true;
+            }
+            match group.parent {
+                Some(parent) => current = parent,
+                None => {
+                    return self
+                        .layers
+                        .iter()
+                        .find(|l| l.root_group == current)
+                        .is_some_and(|l| l.locked);
+                }
+            }
+        }
+    }
+
+    /// Compute effective opacity for an item (accumulates through chain)
+    pub fn item_opacity(&self, item: GroupItem) -> f32 {
+        let group_id = match item {
+            GroupItem::Group(gid) => gid,
+            item => match self.item_group(item) {
+                Some(gid) => gid,
+                None => return 1.0,
+            },
         };
 
         let mut opacity = 1.0f32;
@@ -303,6 +766,11 @@ impl LayerSystem {
         opacity.clamp(0.0, 1.0)
     }
 
+    /// Compute effective opacity for an edge (accumulates through chain)
+    pub fn edge_opacity(&self, edge_id: u32) -> f32 {
+        self.item_opacity(GroupItem::Edge(edge_id))
+    }
+
     /// Set layer visibility
     pub fn set_layer_visibility(&mut self, id: LayerId, visible: bool) -> bool {
         if let Some(layer) = self.layers.iter_mut().find(|l| l.id == id) {
@@ -313,6 +781,16 @@ impl LayerSystem {
         }
     }
 
+    /// Set layer locked state
+    pub fn set_layer_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        if let Some(layer) = self.layers.iter_mut().find(|l| l.id == id) {
+            layer.locked = locked;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Set layer opacity
     pub fn set_layer_opacity(&mut self, id: LayerId, opacity: f32) -> bool {
         if let Some(layer) = self.layers.iter_mut().find(|l| l.id == id) {
@@ -323,6 +801,16 @@ impl LayerSystem {
         }
     }
 
+    /// Set layer blend mode
+    pub fn set_layer_blend_mode(&mut self, id: LayerId, mode: BlendMode) -> bool {
+        if let Some(layer) = self.layers.iter_mut().find(|l| l.id == id) {
+            layer.blend_mode = mode;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Set layer z-index
     pub fn set_layer_z_index(&mut self, id: LayerId, z: i32) -> bool {
         if let Some(layer) = self.layers.iter_mut().find(|l| l.id == id) {
@@ -353,6 +841,30 @@ impl LayerSystem {
         }
     }
 
+    /// Set group locked state
+    pub fn set_group_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        if let Some(group) = self.groups.get_mut(&id) {
+            group.locked = locked;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Whether a group or any group below it is locked
+    pub fn is_subtree_locked(&self, group_id: LayerId) -> bool {
+        let mut stack = vec![group_id];
+        while let Some(gid) = stack.pop() {
+            if let Some(group) = self.groups.get(&gid) {
+                if group.locked {
+                    return true;
+                }
+                stack.extend(group.child_groups());
+            }
+        }
+        false
+    }
+
     /// Set group opacity
     pub fn set_group_opacity(&mut self, id: LayerId, opacity: f32) -> bool {
         if let Some(group) = self.groups.get_mut(&id) {
@@ -363,6 +875,135 @@ impl LayerSystem {
         }
     }
 
+    /// Set group blend mode
+    pub fn set_group_blend_mode(&mut self, id: LayerId, mode: BlendMode) -> bool {
+        if let Some(group) = self.groups.get_mut(&id) {
+            group.blend_mode = mode;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Set or clear a group's mask. A source group must be a non-root group
+    /// that neither contains nor sits inside the masked group, and whose own
+    /// masks do not lead back to it.
+    pub fn set_group_mask(&mut self, id: LayerId, mask: Option<GroupMask>) -> bool {
+        if !self.groups.contains_key(&id) {
+            return false;
+        }
+        if let Some(source) = mask.and_then(GroupMask::source_group) {
+            if !self.valid_mask_source(id, source) {
+                return false;
+            }
+        }
+        if let Some(group) = self.groups.get_mut(&id) {
+            group.mask = mask;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Clear masks read from a document whose source group
+    /// `set_group_mask` would refuse; where masks lead back to each other,
+    /// the lowest group id loses its mask.
+    pub(crate) fn drop_invalid_masks(&mut self) {
+        let mut ids: Vec<LayerId> = self.groups.keys().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            let source = self.groups[&id].mask.and_then(GroupMask::source_group);
+            if source.is_some_and(|source| !self.valid_mask_source(id, source)) {
+                if let Some(group) = self.groups.get_mut(&id) {
+                    group.mask = None;
+                }
+            }
+        }
+    }
+
+    fn valid_mask_source(&self, id: LayerId, source: LayerId) -> bool {
+        if self.groups.get(&source).is_none_or(|g| g.parent.is_none())
+            || self.is_within(source, id)
+            || self.is_within(id, source)
+        {
+            return false;
+        }
+        let mut seen = HashSet::new();
+        let mut current = Some(source);
+        while let Some(gid) = current {
+            if gid == id {
+                return false;
+            }
+            if !seen.insert(gid) {
+                break;
+            }
+            current = self
+                .groups
+                .get(&gid)
+                .and_then(|g| g.mask)
+                .and_then(GroupMask::source_group);
+        }
+        true
+    }
+
+    /// A group's mask, if it has one
+    pub fn group_mask(&self, id: LayerId) -> Option<GroupMask> {
+        self.groups.get(&id).and_then(|g| g.mask)
+    }
+
+    /// Whether any group is masked; lets hit tests skip mask checks
+    pub fn has_masks(&self) -> bool {
+        self.groups.values().any(|g| g.mask.is_some())
+    }
+
+    /// Groups serving as alpha or luminance masks for some existing group
+    pub(crate) fn mask_sources(&self) -> HashSet<LayerId> {
+        self.groups
+            .values()
+            .filter_map(|g| g.mask.and_then(GroupMask::source_group))
+            .collect()
+    }
+
+    /// Whether an item is, or sits inside, a group used as a mask source.
+    /// Such content only shapes the mask and is not drawn on its own.
+    pub fn is_in_mask_source(&self, item: GroupItem) -> bool {
+        if !self.has_masks() {
+            return false;
+        }
+        let sources = self.mask_sources();
+        let mut current = match item {
+            GroupItem::Group(gid) => Some(gid),
+            item => self.item_group(item),
+        };
+        while let Some(gid) = current {
+            if sources.contains(&gid) {
+                return true;
+            }
+            current = self.groups.get(&gid).and_then(|g| g.parent);
+        }
+        false
+    }
+
+    /// Masks limiting an item as (masked group, mask), innermost first. A
+    /// group's own mask is included.
+    pub fn item_masks(&self, item: GroupItem) -> Vec<(LayerId, GroupMask)> {
+        let mut masks = Vec::new();
+        let mut current = match item {
+            GroupItem::Group(gid) => Some(gid),
+            item => self.item_group(item),
+        };
+        while let Some(gid) = current {
+            let Some(group) = self.groups.get(&gid) else {
+                break;
+            };
+            if let Some(mask) = group.mask {
+                masks.push((gid, mask));
+            }
+            current = group.parent;
+        }
+        masks
+    }
+
     /// Rename a group
     pub fn rename_group(&mut self, id: LayerId, name: String) -> bool {
         if let Some(group) = self.groups.get_mut(&id) {
@@ -372,12 +1013,67 @@ impl LayerSystem {
             false
         }
     }
+
+    /// Snapshot of the whole hierarchy: layers bottom to top by z-index, each
+    /// with its group tree in sibling order
+    pub fn get_tree(&self) -> Vec<LayerNode> {
+        self.layers_ordered()
+            .into_iter()
+            .filter_map(|layer| {
+                Some(LayerNode {
+                    id: layer.id,
+                    name: layer.name.clone(),
+                    z_index: layer.z_index,
+                    visible: layer.visible,
+                    locked: layer.locked,
+                    opacity: layer.opacity,
+                    blend_mode: layer.blend_mode,
+                    root: self.group_node(layer.root_group, &mut HashSet::new())?,
+                })
+            })
+            .collect()
+    }
+
+    /// Tree node for `id`; a group already in `seen` is left out.
+    fn group_node(&self, id: LayerId, seen: &mut HashSet<LayerId>) -> Option<GroupNode> {
+        let group = self.groups.get(&id)?;
+        if !seen.insert(id) {
+            return None;
+        }
+        Some(GroupNode {
+            id,
+            name: group.name.clone(),
+            visible: group.visible,
+            locked: group.locked,
+            opacity: group.opacity,
+            blend_mode: group.blend_mode,
+            mask: group.mask,
+            items: group
+                .items
+                .iter()
+                .filter_map(|&item| match item {
+                    GroupItem::Group(c) => self.group_node(c, seen).map(TreeItem::Group),
+                    GroupItem::Edge(id) => Some(TreeItem::Edge { id }),
+                    GroupItem::Shape(id) => Some(TreeItem::Shape { id }),
+                    GroupItem::Text(id) => Some(TreeItem::Text { id }),
+                })
+                .collect(),
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn groups_of(sys: &LayerSystem, id: LayerId) -> Vec<LayerId> {
+        sys.get_group(id).unwrap().child_groups().collect()
+    }
+
+    fn edges_of(sys: &LayerSystem, id: LayerId) -> Vec<u32> {
+        sys.get_group(id).unwrap().edge_ids().collect()
+    }
+
     #[test]
     fn test_create_layer() {
         let mut sys = LayerSystem::new();
@@ -435,6 +1131,30 @@ mod tests {
         assert!(!sys.is_edge_visible(0));
     }
 
+    #[test]
+    fn test_lock_chain() {
+        let mut sys = LayerSystem::new();
+        let root = sys.default_group().unwrap();
+        let g1 = sys.create_group("G1".to_string(), root).unwrap();
+        sys.add_edge_to_group(0, g1);
+        assert!(!sys.is_edge_locked(0));
+        assert!(!sys.is_edge_locked(7));
+
+        assert!(!sys.has_locks());
+
+        assert!(sys.set_group_locked(g1, true));
+        assert!(sys.is_edge_locked(0));
+        assert!(sys.is_subtree_locked(root));
+        assert!(!sys.is_group_chain_locked(root));
+
+        assert!(sys.set_group_locked(g1, false));
+        let layer = sys.layers[0].id;
+        assert!(sys.set_layer_locked(layer, true));
+        assert!(sys.is_edge_locked(0));
+        assert!(sys.is_group_chain_locked(g1));
+        assert!(!sys.set_layer_locked(999, true));
+    }
+
     #[test]
     fn test_opacity_chain() {
         let mut sys = LayerSystem::new();
@@ -450,4 +1170,439 @@ mod tests {
         let opacity = sys.edge_opacity(0);
         assert!((opacity - 0.4).abs() < 0.001);
     }
+
+    #[test]
+    fn test_move_group_order_and_cycles() {
+        let mut sys = LayerSystem::new();
+        let root = sys.default_group().unwrap();
+        let a = sys.create_group("A".to_string(), root).unwrap();
+        let b = sys.create_group("B".to_string(), root).unwrap();
+        let c = sys.create_group("C".to_string(), root).unwrap();
+        let a1 = sys.create_group("A1".to_string(), a).unwrap();
+
+        // No moving into self or own subtree, no moving roots
+        assert!(!sys.move_group(a, a, None));
+        assert!(!sys.move_group(a, a1, None));
+        assert!(!sys.move_group(root, a, None));
+        assert!(!sys.move_group(a, 999, None));
+
+        assert!(sys.reorder_group(c, 0));
+        assert_eq!(groups_of(&sys, root), vec![c, a, b]);
+        assert!(sys.reorder_group(c, 99));
+        assert_eq!(groups_of(&sys, root), vec![a, b, c]);
+
+        // Move across layers with the subtree and its edges
+        sys.add_edge_to_group(5, a1);
+        let layer2 = sys.create_layer("Layer 2".to_string());
+        assert!(sys.move_group_to_layer(a, layer2, None));
+        assert_eq!(sys.get_edge_layer(5), Some(layer2));
+        assert_eq!(groups_of(&sys, root), vec![b, c]);
+        assert!(sys.move_group(b, a1, Some(0)));
+        assert_eq!(sys.find_layer_for_group(b), Some(layer2));
+    }
+
+    #[test]
+    fn test_ungroup_and_flatten() {
+        let mut sys = LayerSystem::new();
+        let root = sys.default_group().unwrap();
+        let a = sys.create_group("A".to_string(), root).unwrap();
+        let g = sys.create_group("G".to_string(), root).unwrap();
+        let b = sys.create_group("B".to_string(), root).unwrap();
+        let g1 = sys.create_group("G1".to_string(), g).unwrap();
+        let g2 = sys.create_group("G2".to_string(), g).unwrap();
+        let g11 = sys.create_group("G11".to_string(), g1).unwrap();
+        assert_eq!(sys.move_edges_to_group(&[3, 1, 2], g), 3);
+        assert_eq!(edges_of(&sys, g), vec![3, 1, 2]);
+        sys.add_edge_to_group(4, g11);
+        sys.add_edge_to_group(5, g1);
+        sys.add_edge_to_group(6, g2);
+        assert_eq!(sys.move_edges_to_group(&[7], 999), 0);
+
+        // Flatten replaces each subgroup by its members, depth-first
+        let mut flat = sys.clone();
+        assert_eq!(flat.flatten_group(g), Some(vec![g1, g11, g2]));
+        assert_eq!(edges_of(&flat, g), vec![4, 5, 6, 3, 1, 2]);
+        assert!(groups_of(&flat, g).is_empty());
+        assert_eq!(flat.get_edge_group(4), Some(g));
+
+        // Ungroup splices the members into the group's slot
+        assert_eq!(sys.ungroup(g), Some(vec![g1, g2]));
+        assert_eq!(groups_of(&sys, root), vec![a, g1, g2, b]);
+        assert_eq!(
+            sys.get_group(root).unwrap().items[3..6],
+            [GroupItem::Edge(3), GroupItem::Edge(1), GroupItem::Edge(2)]
+        );
+        assert_eq!(sys.get_group(g1).unwrap().parent, Some(root));
+        assert_eq!(sys.get_edge_group(1), Some(root));
+        assert_eq!(sys.ungroup(root), None);
+    }
+
+    #[test]
+    fn test_get_tree() {
+        let mut sys = LayerSystem::new();
+        let root = sys.default_group().unwrap();
+        let top = sys.create_layer("Top".to_string());
+        let g = sys.create_group("G".to_string(), root).unwrap();
+        let h = sys.create_group("H".to_string(), g).unwrap();
+        sys.add_edge_to_group(0, h);
+        sys.set_layer_z_index(top, -1);
+
+        let tree = sys.get_tree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].id, top);
+        let TreeItem::Group(g_node) = &tree[1].root.items[0] else {
+            panic!("expected a group");
+        };
+        assert_eq!((g_node.id, g_node.name.as_str()), (g, "G"));
+        let TreeItem::Group(h_node) = &g_node.items[0] else {
+            panic!("expected a group");
+        };
+        assert_eq!(h_node.id, h);
+        assert_eq!(h_node.items, vec![TreeItem::Edge { id: 0 }]);
+    }
+
+    #[test]
+    fn test_shapes_and_texts_are_members() {
+        let mut sys = LayerSystem::new();
+        let layer = sys.layers[0].id;
+        let root = sys.default_group().unwrap();
+        let g = sys.create_group("G".to_string(), root).unwrap();
+        let (text, shape) = (GroupItem::Text(2), GroupItem::Shape(0));
+        assert!(sys.add_item_to_group(text, g));
+        assert!(sys.add_item_to_group(shape, root));
+        assert!(!sys.add_item_to_group(GroupItem::Group(g), root));
+        assert_eq!(sys.item_group(text), Some(g));
+
+        sys.set_group_visibility(g, false);
+        sys.set_group_opacity(root, 0.5);
+        assert!(!sys.is_item_visible(text));
+        assert!(sys.is_item_visible(shape));
+        assert!((sys.item_opacity(text) - 0.5).abs() < 1e-6);
+        sys.set_group_locked(g, true);
+        assert!(sys.is_item_locked(text) && !sys.is_item_locked(shape));
+
+        // Moving keeps one membership per item
+        assert_eq!(sys.move_items_to_group(&[text], root), 1);
+        assert_eq!(sys.get_group(root).unwrap().items.last(), Some(&text));
+        assert!(!sys.get_group(g).unwrap().items.contains(&text));
+
+        let mut removed = sys.remove_layer(layer).unwrap();
+        removed.sort_by_key(|i| format!("{:?}", i));
+        assert_eq!(removed, vec![shape, text]);
+        assert_eq!(sys.item_group(text), None);
+    }
+
+    #[test]
+    fn test_reorder_items_and_draw_order() {
+        let mut sys = LayerSystem::new();
+        let root = sys.default_group().unwrap();
+        for e in 0..3 {
+            sys.add_edge_to_group(e, root);
+        }
+        let gid = sys.create_group("G".to_string(), root).unwrap();
+        sys.add_item_to_group(GroupItem::Text(0), gid);
+        let top = sys.create_layer("Top".to_string());
+        let top_root = sys.get_layer(top).unwrap().root_group;
+        sys.add_item_to_group(GroupItem::Shape(0), top_root);
+
+        let edge = GroupItem::Edge;
+        assert!(sys.reorder_item(edge(0), ZOrder::Front));
+        assert_eq!(edges_of(&sys, root), vec![1, 2, 0]);
+        assert!(sys.reorder_item(edge(0), ZOrder::Backward));
+        assert!(sys.reorder_item(GroupItem::Group(gid), ZOrder::Back));
+        assert_eq!(
+            sys.get_group(root).unwrap().items,
+            vec![GroupItem::Group(gid), edge(1), edge(2), edge(0)]
+        );
+        // Steps clamp at either end
+        assert!(sys.reorder_item(edge(2), ZOrder::Forward));
+        assert!(sys.reorder_item(GroupItem::Group(gid), ZOrder::Backward));
+        assert_eq!(edges_of(&sys, root), vec![1, 0, 2]);
+        // Root groups and unfiled items have no siblings to move among
+        assert!(!sys.reorder_item(GroupItem::Group(root), ZOrder::Front));
+        assert!(!sys.reorder_item(edge(9), ZOrder::Front));
+
+        let order = vec![
+            GroupItem::Text(0),
+            edge(1),
+            edge(0),
+            edge(2),
+            GroupItem::Shape(0),
+        ];
+        assert_eq!(sys.draw_order(), order);
+        let keys: Vec<_> = order.iter().map(|&i| sys.paint_key(i).unwrap()).collect();
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(sys.paint_key(edge(9)), None);
+
+        // Layers draw by z-index
+        assert!(sys.set_layer_z_index(top, -1));
+        assert_eq!(sys.draw_order()[0], GroupItem::Shape(0));
+    }
+
+    #[test]
+    fn test_z_order_drives_export_and_picking() {
+        use crate::Graph;
+        use crate::Pick;
+
+        let mut g = Graph::new();
+        let lower = g.add_rectangle(0.0, 0.0, 20.0, 20.0, 0.0);
+        let upper = g.add_rectangle(10.0, 10.0, 20.0, 20.0, 0.0);
+        let shape = |r: &crate::PrimitiveResult| GroupItem::Shape(r.shape);
+        let hit = |g: &mut Graph| match g.pick(15.0, 15.0, 0.1) {
+            Some(Pick::Shape { id, .. }) => Some(id),
+            _ => None,
+        };
+        assert_eq!(hit(&mut g), Some(upper.shape));
+        let svg = g.to_svg_document();
+        let at = |svg: &str, id: u32| svg.find(&format!(r#"data-shape="{}""#, id)).unwrap();
+        assert!(at(&svg, lower.shape) < at(&svg, upper.shape));
+
+        // The shape alone is reordered; its outline comes along
+        assert!(g.reorder_item(shape(&lower), ZOrder::Front));
+        assert_eq!(hit(&mut g), Some(lower.shape));
+        let svg = g.to_svg_document();
+        assert!(at(&svg, upper.shape) < at(&svg, lower.shape));
+        let ids = g.get_edge_arrays().ids;
+        assert_eq!(ids[ids.len() - 4..], lower.edges[..]);
+        assert_eq!(ids.len(), 8);
+        assert_eq!(
+            g.draw_order().last(),
+            Some(&GroupItem::Edge(lower.edges[3]))
+        );
+
+        // A shape's own outline still picks as the edge
+        assert!(matches!(g.pick(0.0, 5.0, 0.5), Some(Pick::Edge { .. })));
+
+        // Stepping moves the outline by one sibling too, keeping its order
+        assert!(g.reorder_item(shape(&lower), ZOrder::Backward));
+        assert_eq!(hit(&mut g), Some(lower.shape));
+        assert!(g.reorder_item(shape(&lower), ZOrder::Back));
+        assert_eq!(hit(&mut g), Some(upper.shape));
+        let ids = g.get_edge_arrays().ids;
+        assert_eq!(ids[..4], lower.edges[..]);
+        assert!(g.reorder_item(shape(&lower), ZOrder::Forward));
+        let ids = g.get_edge_arrays().ids;
+        assert_eq!(ids[1..5], lower.edges[..]);
+
+        let root = g.default_group().unwrap();
+        assert!(g.set_group_locked(root, true));
+        assert!(!g.reorder_item(shape(&upper), ZOrder::Front));
+    }
+
+    #[test]
+    fn test_texts_and_shapes_follow_their_layer() {
+        use crate::model::Color;
+        use crate::{Graph, Pick};
+
+        let mut g = Graph::new();
+        let rect = g.add_rectangle(0.0, 0.0, 10.0, 10.0, 0.0);
+        let label = g.add_text("label", 50.0, 50.0);
+        let root = g.default_group().unwrap();
+        let items = &g.layer_system.get_group(root).unwrap().items;
+        assert!(items.contains(&GroupItem::Shape(rect.shape)));
+        assert!(items.contains(&GroupItem::Text(label)));
+
+        // A text on a hidden layer is neither picked nor exported
+        let top = g.create_layer("Top".to_string());
+        let top_root = g.layer_system.get_layer(top).unwrap().root_group;
+        let grp = g.create_group("labels".to_string(), top_root).unwrap();
+        assert!(g.add_item_to_group(GroupItem::Text(label), grp));
+        assert!(matches!(g.pick(51.0, 51.0, 1.0), Some(Pick::Text { .. })));
+        let svg = g.to_svg_document();
+        assert!(svg.find("data-shape").unwrap() < svg.find(">label</text>").unwrap());
+        assert!(svg.contains(&format!(r#"<g id="group-{}" data-name="labels">"#, grp)));
+        g.set_layer_visibility(top, false);
+        assert!(g.pick(51.0, 51.0, 1.0).is_none());
+        assert!(!g.to_svg_document().contains(">label</text>"));
+
+        // Shapes take visibility and opacity from their own group
+        let shapes = g.create_group("shapes".to_string(), root).unwrap();
+        assert!(g.add_item_to_group(GroupItem::Shape(rect.shape), shapes));
+        assert!(matches!(g.pick(5.0, 5.0, 0.1), Some(Pick::Shape { .. })));
+        g.set_group_opacity(shapes, 0.5);
+        assert!((g.get_item_opacity(GroupItem::Shape(rect.shape)) - 0.5).abs() < 1e-6);
+        assert!(g.to_svg_document().contains(r#" opacity="0.5">"#));
+        g.set_group_visibility(shapes, false);
+        assert!(!matches!(g.pick(5.0, 5.0, 0.1), Some(Pick::Shape { .. })));
+
+        // Group effects apply below the item's own
+        let black = Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        let own = g.add_drop_shadow(1.0, 1.0, 0.0, 0.0, black);
+        let inherited = g.add_drop_shadow(2.0, 2.0, 0.0, 0.0, black);
+        assert!(g.add_effect_to_text(label, own));
+        assert!(g.add_effect_to_group(grp, inherited));
+        assert_eq!(
+            g.get_effective_effects(GroupItem::Text(label)),
+            vec![own, inherited]
+        );
+
+        // Locks cover texts too
+        assert!(g.set_group_locked(grp, true));
+        assert!(!g.set_text_content(label, "changed") && !g.remove_text(label));
+        assert!(g.set_group_locked(grp, false));
+
+        // Membership survives a save/load; removed texts leave their group
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(g.to_json_value()));
+        assert_eq!(loaded.get_item_group(GroupItem::Text(label)), Some(grp));
+        assert!(g.remove_text(label));
+        assert_eq!(g.get_item_group(GroupItem::Text(label)), None);
+    }
+
+    #[test]
+    fn test_legacy_groups_load_as_items() {
+        use crate::Graph;
+
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(5.0, 0.0);
+        let e = g.add_edge(a, b).unwrap();
+        let root = g.default_group().unwrap();
+        let grp = g.create_group("G".to_string(), root).unwrap();
+        assert!(g.add_edge_to_group(e, grp));
+        let text = g.add_text("t", 0.0, 0.0);
+
+        // Rewrite groups in the pre-v6 shape: separate children and edges
+        let mut doc = g.to_json_value();
+        doc["version"] = serde_json::json!(5);
+        for group in doc["groups"].as_array_mut().unwrap() {
+            let id = group["id"].as_u64().unwrap() as u32;
+            let (children, edges) = if id == root {
+                (vec![grp], vec![])
+            } else if id == grp {
+                (vec![], vec![e])
+            } else {
+                (vec![], vec![])
+            };
+            let group = group.as_object_mut().unwrap();
+            group.remove("items");
+            group.insert("children".to_string(), serde_json::json!(children));
+            group.insert("edges".to_string(), serde_json::json!(edges));
+        }
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value(doc));
+        assert_eq!(loaded.get_edge_group(e), Some(grp));
+        // Texts had no membership before v6 and land in the default group
+        assert_eq!(loaded.get_item_group(GroupItem::Text(text)), Some(root));
+        assert_eq!(
+            loaded.layer_system.get_group(root).unwrap().items,
+            vec![GroupItem::Group(grp), GroupItem::Text(text)]
+        );
+    }
+
+    #[test]
+    fn test_malformed_group_trees_are_rejected() {
+        use crate::Graph;
+
+        let mut g = Graph::new();
+        let a = g.add_node(0.0, 0.0);
+        let b = g.add_node(5.0, 0.0);
+        let e = g.add_edge(a, b).unwrap();
+        let root = g.default_group().unwrap();
+        let grp = g.create_group("G".to_string(), root).unwrap();
+        assert!(g.add_edge_to_group(e, grp));
+        let saved = g.to_json_value();
+
+        // Push an extra item onto the listed group's items
+        let with_item = |gid: LayerId, item: GroupItem| {
+            let mut doc = saved.clone();
+            for group in doc["groups"].as_array_mut().unwrap() {
+                if group["id"].as_u64() == Some(gid as u64) {
+                    let items = group["items"].as_array_mut().unwrap();
+                    items.push(serde_json::to_value(item).unwrap());
+                }
+            }
+            doc
+        };
+        let bad = [
+            // A group listing itself would recurse forever when drawn
+            with_item(grp, GroupItem::Group(grp)),
+            // The root listed under its own child
+            with_item(grp, GroupItem::Group(root)),
+            // An edge in two groups
+            with_item(root, GroupItem::Edge(e)),
+        ];
+        for doc in bad {
+            let mut loaded = Graph::new();
+            let before = loaded.to_json_value();
+            assert!(!loaded.from_json_value(doc.clone()));
+            assert!(matches!(
+                loaded.from_json_value_strict(doc),
+                Err(("invalid_structure", _))
+            ));
+            assert_eq!(loaded.to_json_value(), before);
+        }
+
+        // A group whose parent does not list it
+        let mut doc = saved.clone();
+        for group in doc["groups"].as_array_mut().unwrap() {
+            if group["id"].as_u64() == Some(root as u64) {
+                group["items"] = serde_json::json!([]);
+            }
+        }
+        assert!(!Graph::new().from_json_value(doc));
+
+        let mut loaded = Graph::new();
+        assert!(loaded.from_json_value_strict(saved).unwrap());
+        assert_eq!(loaded.get_edge_group(e), Some(grp));
+    }
+
+    #[test]
+    fn test_locked_content_refuses_edits() {
+        use crate::algorithms::boolean::{BoolError, BoolOp};
+        use crate::Graph;
+
+        let mut g = Graph::new();
+        let rect = g.add_rectangle(0.0, 0.0, 10.0, 10.0, 0.0);
+        let other = g.add_rectangle(5.0, 5.0, 10.0, 10.0, 0.0);
+        let root = g.default_group().unwrap();
+        let grp = g.create_group("locked".to_string(), root).unwrap();
+        for &e in &rect.edges {
+            assert!(g.add_edge_to_group(e, grp));
+        }
+        assert!(g.set_group_locked(grp, true));
+        let layer2 = g.create_layer("L2".to_string());
+        let (e, n) = (rect.edges[0], rect.nodes[0]);
+        let ver = g.geom_version();
+
+        assert!(g.is_edge_locked(e) && g.is_node_locked(n) && g.is_shape_locked(rect.shape));
+        assert!(!g.move_node(n, 3.0, 3.0));
+        assert!(!g.set_edge_cubic(e, 1.0, 1.0, 2.0, 2.0));
+        assert!(!g.set_edge_style(e, 1, 2, 3, 255, 1.0));
+        assert!(!g.remove_edge(e));
+        assert!(!g.remove_node(n));
+        assert_eq!(g.translate_edges(&[e], 1.0, 1.0, false), 0);
+        assert_eq!(g.translate_nodes(&[other.nodes[1], n], 1.0, 1.0), 0);
+        assert_eq!(g.rotate_selection(&[n], &[], 0.0, 0.0, 1.0), 0);
+        assert!(!g.transform_all(2.0, 0.0, 0.0, false));
+        assert!(!g.remove_group(grp));
+        assert!(!g.add_edge_to_group(e, root));
+        assert!(!g.move_group_to_layer(grp, layer2, None));
+        assert_eq!(g.ungroup(grp), None);
+        assert!(!g.flatten_group(grp));
+        assert_eq!(g.move_edges_to_group(&[e], root), 0);
+        assert_eq!(g.move_edges_to_group(&[other.edges[0]], grp), 0);
+        assert!(!g.delete_shape(rect.shape));
+        assert!(matches!(
+            g.boolean_op(rect.shape, other.shape, BoolOp::Union),
+            Err(BoolError::Locked(id)) if id == rect.shape
+        ));
+        assert_eq!(g.geom_version(), ver);
+
+        // Unlocked content next door still edits, and unlocking restores edits
+        assert!(g.move_node(other.nodes[0], 6.0, 6.0));
+        let mut locked: Vec<u32> = g.locked_nodes().into_iter().collect();
+        locked.sort_unstable();
+        assert_eq!(locked, rect.nodes);
+        assert_eq!(g.translate_nodes(&other.nodes, 1.0, 1.0), 4);
+        assert!(g.set_group_locked(grp, false));
+        assert!(g.move_node(n, 3.0, 3.0));
+        assert!(g.transform_all(2.0, 0.0, 0.0, false));
+    }
 }
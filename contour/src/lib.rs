@@ -7,28 +7,48 @@ pub mod geometry {
     pub mod limits;
     pub mod math;
     pub mod path_length;
+    #[cfg(feature = "simd")]
+    pub mod simd;
     pub mod tolerance;
 }
 pub mod algorithms {
     pub mod boolean;
     pub mod incremental;
+    pub(crate) mod masking;
+    pub(crate) mod parallel;
+    #[cfg(all(feature = "threads", target_arch = "wasm32"))]
+    pub use parallel::{init_thread_pool, ThreadBuilder};
     pub mod picking;
     pub mod planarize;
+    pub mod planarize_into;
     pub mod planarize_subset;
+    pub(crate) mod region_fill;
+    pub mod region_job;
+    pub mod region_snapshot;
+    pub mod region_query;
+    pub mod region_remap;
+    pub(crate) mod region_scope;
+    pub mod region_shape;
     pub mod regions;
+    pub mod selection;
+    pub(crate) mod sweep;
     pub mod text_layout;
     pub mod text_outline;
     pub mod winding;
 }
 mod json;
 mod svg;
+#[cfg(test)]
+mod test_util;
 
 use layers::LayerSystem;
 use model::{
-    Color, ColorStop, DropShadow, Edge, EdgeKind, Effect, EffectId, EffectStack, FillRule,
-    FillState, FontStyle, Gradient, GradientId, GradientUnits, HandleMode, LayerId, LinearGradient,
-    Node, PrimitiveResult, RadialGradient, Shape, SpreadMethod, TextAlign, TextElement, TextId,
-    TextOverflow, TextStyle, TextType, Vec2, VerticalAlign,
+    BlendMode, Color, ColorStop, DropShadow, Edge, EdgeKind, Effect, EffectId, EffectStack,
+    FillRule, FillState, FontStyle, Gradient, GradientId, GradientUnits, GroupItem, GroupMask,
+    HandleMode, LayerId, LinearGradient,
+    Node, PathCommand, PrimitiveResult, RadialGradient, RegionFillMode, RegionScope, SelectMode,
+    Shape, SpreadMethod, TextAlign, TextElement, TextId, TextOverflow, TextStyle, TextType, Vec2,
+    VerticalAlign,
 };
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
@@ -43,17 +63,27 @@ pub struct DirtyState {
     pub edges_added: HashSet<u32>,
     pub edges_removed: HashSet<u32>,
     pub edges_modified: HashSet<u32>,
+    // Shapes and texts added, removed or edited; only the pick index reads
+    // these, tracked by `objects_ver` rather than geom_ver
+    pub shapes_touched: HashSet<u32>,
+    pub texts_touched: HashSet<u32>,
     pub bbox: Option<(f32, f32, f32, f32)>, // minx,miny,maxx,maxy
     pub full: bool,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RegionFaceCache {
     pub key: u32,
+    #[serde(default)]
+    pub scope: u32,
     pub area: f32,
     pub bbox: (f32, f32, f32, f32),
     pub points: Vec<Vec2>,
     pub edges: Vec<u32>,
+    pub holes: Vec<Vec<Vec2>>,
+    pub hole_edges: Vec<Vec<u32>>,
+    pub spans: Vec<algorithms::regions::RegionSpan>,
+    pub hole_spans: Vec<Vec<algorithms::regions::RegionSpan>>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -77,31 +107,45 @@ pub struct Graph {
     pub(crate) next_effect_id: EffectId,
     pub(crate) shape_effects: HashMap<u32, EffectStack>,
     pub(crate) region_effects: HashMap<u32, EffectStack>,
+    pub(crate) region_gradients: HashMap<u32, GradientId>, // region key -> gradient
     pub(crate) text_effects: HashMap<TextId, EffectStack>,
     pub(crate) group_effects: HashMap<LayerId, EffectStack>,
     pub(crate) geom_ver: u64,
     pub(crate) last_geom_ver: u64,
-    pub(crate) prev_regions: Vec<(u32, i32, i32, f32)>, // (key, qcx, qcy, area)
+    pub(crate) objects_ver: u64,
+    pub(crate) prev_faces: Vec<RegionFaceCache>, // faces of the last fill remap pass
+    pub(crate) fill_anchors: Vec<(u32, f32, f32, f32)>, // loaded fills: (key, x, y, area)
+    pub(crate) region_remap: crate::algorithms::region_remap::RegionRemapReport,
     pub(crate) flatten_tol: f32,
+    pub(crate) intersect_backend: crate::algorithms::planarize::IntersectBackend,
+    pub(crate) region_fill_mode: RegionFillMode,
+    pub(crate) region_scope: RegionScope,
+    pub(crate) pick_skip_locked: bool,
     // Picking spatial index: (built_geom_ver, index)
     pub(crate) pick_index: RefCell<Option<(u64, crate::algorithms::picking::PickIndex)>>,
     // Incremental regions bookkeeping
     pub(crate) dirty: DirtyState,
     pub(crate) region_cache: RefCell<Option<RegionCache>>,
+    // Region hit-test index: (built_geom_ver, index)
+    pub(crate) region_index: RefCell<Option<(u64, crate::algorithms::region_query::RegionIndex)>>,
     pub(crate) flatten_index: RefCell<Option<crate::algorithms::regions::FlattenIndex>>,
     pub(crate) flatten_cache: RefCell<Option<crate::algorithms::regions::FlattenCache>>,
     pub(crate) incr_plan: RefCell<Option<crate::algorithms::incremental::IncrPlan>>,
+    // Budgeted region computation in progress, if any
+    pub(crate) region_job: Option<crate::algorithms::region_job::RegionJob>,
 }
 
+/// Edge data for rendering, in drawing order (bottom to top)
 pub struct EdgeArrays {
     pub ids: Vec<u32>,
     pub endpoints: Vec<u32>,
     pub kinds: Vec<u8>,
     pub stroke_rgba: Vec<u8>,
     pub stroke_widths: Vec<f32>,
+    pub construction: Vec<u8>, // 1 for construction/guide edges
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Pick {
     #[serde(rename = "node")]
     Node { id: u32, dist: f32 },
@@ -109,6 +153,12 @@ pub enum Pick {
     Edge { id: u32, t: f32, dist: f32 },
     #[serde(rename = "handle")]
     Handle { edge: u32, end: u8, dist: f32 },
+    #[serde(rename = "text")]
+    Text { id: u32, dist: f32 },
+    #[serde(rename = "shape")]
+    Shape { id: u32, dist: f32 },
+    #[serde(rename = "region")]
+    Region { key: u32, dist: f32 },
 }
 
 impl Graph {
@@ -219,13 +269,22 @@ impl Graph {
             next_effect_id: 0,
             shape_effects: HashMap::new(),
             region_effects: HashMap::new(),
+            region_gradients: HashMap::new(),
             text_effects: HashMap::new(),
             group_effects: HashMap::new(),
             geom_ver: 1,
             last_geom_ver: 0,
-            prev_regions: Vec::new(),
+            objects_ver: 0,
+            prev_faces: Vec::new(),
+            fill_anchors: Vec::new(),
+            region_remap: Default::default(),
             flatten_tol: 0.25,
+            intersect_backend: Default::default(),
+            region_fill_mode: RegionFillMode::Faces,
+            region_scope: RegionScope::All,
+            pick_skip_locked: false,
             pick_index: RefCell::new(None),
+            region_index: RefCell::new(None),
             dirty: DirtyState {
                 since_ver: 1,
                 ..Default::default()
@@ -234,6 +293,7 @@ impl Graph {
             flatten_index: RefCell::new(None),
             flatten_cache: RefCell::new(None),
             incr_plan: RefCell::new(None),
+            region_job: None,
         }
     }
     pub fn geom_version(&self) -> u64 {
@@ -315,6 +375,7 @@ impl Graph {
     }
 
     pub fn dirty_reset(&mut self) {
+        algorithms::picking::sync_pick_index(self);
         self.dirty = DirtyState {
             since_ver: self.geom_ver,
             ..Default::default()
@@ -322,6 +383,8 @@ impl Graph {
     }
 
     pub(crate) fn clear_dirty_flags(&mut self) {
+        // The pick index patches itself from the dirty sets; catch it up first
+        algorithms::picking::sync_pick_index(self);
         self.dirty.full = false;
         self.dirty.bbox = None;
         self.dirty.nodes_added.clear();
@@ -330,6 +393,8 @@ impl Graph {
         self.dirty.edges_added.clear();
         self.dirty.edges_removed.clear();
         self.dirty.edges_modified.clear();
+        self.dirty.shapes_touched.clear();
+        self.dirty.texts_touched.clear();
         self.dirty.since_ver = self.geom_ver;
     }
 
@@ -348,6 +413,13 @@ impl Graph {
         id
     }
     pub fn move_node(&mut self, id: u32, x: f32, y: f32) -> bool {
+        if self.is_node_locked(id) {
+            return false;
+        }
+        self.move_node_unlocked(id, x, y)
+    }
+    /// `move_node` without the lock check, for batches checked once up front
+    fn move_node_unlocked(&mut self, id: u32, x: f32, y: f32) -> bool {
         if !x.is_finite() || !y.is_finite() {
             return false;
         }
@@ -388,6 +460,9 @@ impl Graph {
             .map(|n| (n.x, n.y))
     }
     pub fn remove_node(&mut self, id: u32) -> bool {
+        if self.is_node_locked(id) {
+            return false;
+        }
         let (nx, ny) = match self.nodes.get(id as usize).and_then(|n| *n) {
             Some(n) => (n.x, n.y),
             None => return false,
@@ -451,6 +526,7 @@ impl Graph {
             kind: EdgeKind::Line,
             stroke: None,
             stroke_width: 2.0,
+            construction: false,
         }));
         // Assign to default layer's root group
         if let Some(default_group) = self.layer_system.default_group() {
@@ -471,6 +547,9 @@ impl Graph {
         Some(id)
     }
     pub fn remove_edge(&mut self, id: u32) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         let old_bb = if let Some(Some(edge)) = self.edges.get(id as usize) {
             self.edge_aabb_of(edge)
         } else {
@@ -494,6 +573,13 @@ impl Graph {
     pub fn edge_count(&self) -> u32 {
         self.edges.iter().filter(|e| e.is_some()).count() as u32
     }
+    /// Endpoint node ids `(a, b)` of an edge
+    pub fn get_edge_nodes(&self, id: u32) -> Option<(u32, u32)> {
+        self.edges
+            .get(id as usize)
+            .and_then(|e| e.as_ref())
+            .map(|e| (e.a, e.b))
+    }
 
     pub fn get_node_arrays(&self) -> (Vec<u32>, Vec<f32>) {
         let mut ids = Vec::new();
@@ -513,9 +599,28 @@ impl Graph {
         let mut kinds = Vec::new();
         let mut rgba = Vec::new();
         let mut widths = Vec::new();
-        for (i, e) in self.edges.iter().enumerate() {
-            if let Some(e) = e {
-                ids.push(i as u32);
+        let mut construction = Vec::new();
+        // Drawing order, then any edges outside the layers by id
+        let mut drawn = vec![false; self.edges.len()];
+        let order: Vec<u32> = self
+            .layer_system
+            .draw_order()
+            .into_iter()
+            .filter_map(|item| match item {
+                GroupItem::Edge(id) => Some(id),
+                _ => None,
+            })
+            .chain(0..self.edges.len() as u32)
+            .filter(|&id| {
+                drawn
+                    .get_mut(id as usize)
+                    .is_some_and(|d| !std::mem::replace(d, true))
+            })
+            .collect();
+        for i in order {
+            if let Some(e) = &self.edges[i as usize] {
+                ids.push(i);
+                construction.push(e.construction as u8);
                 ep.push(e.a);
                 ep.push(e.b);
                 kinds.push(match e.kind {
@@ -538,18 +643,42 @@ impl Graph {
             kinds,
             stroke_rgba: rgba,
             stroke_widths: widths,
+            construction,
         }
     }
 
     // Picking return
+    /// Topmost visible node, handle, edge, text or shape at the point, ranked
+    /// as in [`Graph::pick_all`]. Regions are left out; see
+    /// [`Graph::pick_with_regions`].
     pub fn pick(&self, x: f32, y: f32, tol: f32) -> Option<Pick> {
         algorithms::picking::pick_impl(self, x, y, tol)
     }
 
+    /// Like [`Graph::pick`], but falls back to the region under the point.
+    /// May rebuild the region cache, hence `&mut self`.
+    pub fn pick_with_regions(&mut self, x: f32, y: f32, tol: f32) -> Option<Pick> {
+        algorithms::picking::pick_with_regions_impl(self, x, y, tol)
+    }
+
+    /// Every visible object at the point, topmost first by layer z-index,
+    /// then nodes and handles, edges, texts and shapes in drawing order
+    /// (see [`Graph::draw_order`]), and regions last; then kind, opacity and
+    /// distance. Takes `&mut self` because region hits use the region cache.
+    pub fn pick_all(&mut self, x: f32, y: f32, tol: f32) -> Vec<Pick> {
+        algorithms::picking::pick_all_impl(self, x, y, tol)
+    }
+
     // JSON
     pub fn to_json_value(&self) -> serde_json::Value {
         json::to_json_impl(self)
     }
+    /// Like `to_json_value`, but also embeds the computed regions so loading
+    /// can skip the rebuild. Only a current region cache is embedded; saving
+    /// never rebuilds regions.
+    pub fn to_json_value_with_regions(&self) -> serde_json::Value {
+        json::to_json_impl_with(self, true)
+    }
     pub fn from_json_value(&mut self, v: serde_json::Value) -> bool {
         json::from_json_impl(self, v)
     }
@@ -571,9 +700,11 @@ impl Graph {
         self.next_effect_id = 0;
         self.shape_effects.clear();
         self.region_effects.clear();
+        self.region_gradients.clear();
         self.text_effects.clear();
         self.group_effects.clear();
-        self.prev_regions.clear();
+        self.prev_faces.clear();
+        self.fill_anchors.clear();
         self.region_cache.borrow_mut().take();
         self.flatten_index.borrow_mut().take();
         self.flatten_cache.borrow_mut().take();
@@ -584,6 +715,19 @@ impl Graph {
 
     // Styles and handles
     pub fn set_edge_style(&mut self, id: u32, r: u8, g: u8, b: u8, a: u8, width: f32) -> bool {
+        !self.is_edge_locked(id) && self.apply_edge_style(id, r, g, b, a, width)
+    }
+    /// `set_edge_style` without the lock check, for importers styling the
+    /// edges they just created
+    pub(crate) fn apply_edge_style(
+        &mut self,
+        id: u32,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+        width: f32,
+    ) -> bool {
         if let Some(Some(e)) = self.edges.get_mut(id as usize) {
             e.stroke = Some(Color { r, g, b, a });
             e.stroke_width = if width > 0.0 { width } else { 2.0 };
@@ -591,6 +735,26 @@ impl Graph {
         }
         false
     }
+    /// Flag an edge as construction/guide geometry. Construction edges stay
+    /// pickable and snappable but are left out of regions and SVG export.
+    pub fn set_edge_construction(&mut self, id: u32, construction: bool) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
+        let Some(Some(e)) = self.edges.get_mut(id as usize) else {
+            return false;
+        };
+        if e.construction != construction {
+            e.construction = construction;
+            // Construction edges leave (or rejoin) the planar map
+            self.mark_full_dirty();
+            self.bump();
+        }
+        true
+    }
+    pub fn is_edge_construction(&self, id: u32) -> bool {
+        matches!(self.edges.get(id as usize), Some(Some(e)) if e.construction)
+    }
     pub fn get_edge_style(&self, id: u32) -> Option<(u8, u8, u8, u8, f32)> {
         if let Some(Some(e)) = self.edges.get(id as usize) {
             if let Some(c) = e.stroke {
@@ -601,6 +765,9 @@ impl Graph {
     }
     // set_edge_cubic defined below with guards
     pub fn set_edge_line(&mut self, id: u32) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         let changed = if let Some(Some(edge)) = self.edges.get_mut(id as usize) {
             if matches!(edge.kind, EdgeKind::Line) {
                 false
@@ -646,6 +813,9 @@ impl Graph {
         None
     }
     pub fn set_handle_pos(&mut self, id: u32, end: u8, x: f32, y: f32) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         if end != 0 && end != 1 {
             return false;
         }
@@ -703,6 +873,9 @@ impl Graph {
         true
     }
     pub fn set_handle_mode(&mut self, id: u32, mode: u8) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         let changed = {
             let edge = match self.edges.get_mut(id as usize) {
                 Some(Some(edge)) => edge,
@@ -731,6 +904,9 @@ impl Graph {
         true
     }
     pub fn set_edge_cubic(&mut self, id: u32, p1x: f32, p1y: f32, p2x: f32, p2y: f32) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         if !p1x.is_finite() || !p1y.is_finite() || !p2x.is_finite() || !p2y.is_finite() {
             return false;
         }
@@ -797,6 +973,9 @@ impl Graph {
         true
     }
     pub fn bend_edge_to(&mut self, id: u32, t: f32, tx: f32, ty: f32, stiffness: f32) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         let did_change = {
             let edge = match self.edges.get_mut(id as usize) {
                 Some(Some(edge)) => edge,
@@ -1521,9 +1700,124 @@ impl Graph {
         self.mark_full_dirty();
         self.bump();
     }
+    /// Choose how full planarization finds segment intersections.
+    pub fn set_intersect_backend(&mut self, backend: crate::algorithms::planarize::IntersectBackend) {
+        self.intersect_backend = backend;
+    }
+    pub fn get_intersect_backend(&self) -> crate::algorithms::planarize::IntersectBackend {
+        self.intersect_backend
+    }
+    /// Choose whether region fills come from per-face state only or from the
+    /// fill rules of the closed shapes covering each face.
+    pub fn set_region_fill_mode(&mut self, mode: RegionFillMode) {
+        self.region_fill_mode = mode;
+    }
+    pub fn get_region_fill_mode(&self) -> RegionFillMode {
+        self.region_fill_mode
+    }
+    /// Choose whether regions span all visible edges or form separately per
+    /// layer or per group. Scoped region keys are namespaced by layer/group id.
+    pub fn set_region_scope(&mut self, scope: RegionScope) {
+        if self.region_scope != scope {
+            self.region_scope = scope;
+            self.mark_full_dirty();
+            self.bump();
+        }
+    }
+    pub fn get_region_scope(&self) -> RegionScope {
+        self.region_scope
+    }
+    /// Layer/group membership changed: scoped regions must rebuild, as must
+    /// any regions when content can move in or out of a mask source.
+    fn membership_changed(&mut self) {
+        if self.region_scope != RegionScope::All || self.layer_system.has_masks() {
+            self.mark_full_dirty();
+            self.bump();
+        }
+    }
+    /// Start a budgeted region computation for the current geometry, replacing
+    /// any job in progress. Returns the geometry version the job is keyed by.
+    pub fn begin_regions_job(&mut self) -> u64 {
+        algorithms::region_job::begin_job(self)
+    }
+    /// Advance the region job for about `budget_ms` milliseconds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn step_regions_job(&mut self, budget_ms: f64) -> algorithms::region_job::RegionJobStatus {
+        let start = std::time::Instant::now();
+        let mut now_ms = || start.elapsed().as_secs_f64() * 1000.0;
+        algorithms::region_job::step_job(self, budget_ms, &mut now_ms)
+    }
+    /// Advance the region job for `budget_ms` as measured by `now_ms` (any
+    /// monotonic millisecond clock, e.g. `performance.now()` on the web).
+    pub fn step_regions_job_with_clock(
+        &mut self,
+        budget_ms: f64,
+        mut now_ms: impl FnMut() -> f64,
+    ) -> algorithms::region_job::RegionJobStatus {
+        algorithms::region_job::step_job(self, budget_ms, &mut now_ms)
+    }
+    pub fn regions_job_status(&mut self) -> algorithms::region_job::RegionJobStatus {
+        algorithms::region_job::job_status(self)
+    }
+    /// Faces of the region job so far; `stable` is false until the job is done.
+    pub fn regions_job_result(&mut self) -> Vec<serde_json::Value> {
+        algorithms::region_job::job_regions(self)
+    }
+    /// Drop the region job; false if none was running.
+    pub fn cancel_regions_job(&mut self) -> bool {
+        algorithms::region_job::cancel_job(self)
+    }
+    /// Serializable copy of what a full region rebuild reads, for computing
+    /// regions off the main thread with `compute_region_snapshot`.
+    pub fn region_snapshot(&self) -> algorithms::region_snapshot::RegionSnapshot {
+        algorithms::region_snapshot::RegionSnapshot::capture(self)
+    }
+    /// Install faces computed from a snapshot; false (and nothing changes) if
+    /// the geometry moved on since the snapshot was taken.
+    pub fn apply_region_result(
+        &mut self,
+        result: algorithms::region_snapshot::RegionSnapshotResult,
+    ) -> bool {
+        algorithms::region_snapshot::apply_result(self, result)
+    }
     pub fn get_regions(&mut self) -> Vec<serde_json::Value> {
         algorithms::regions::get_regions_with_fill(self)
     }
+    /// How region keys changed in the last `get_regions` pass that saw new
+    /// geometry (splits, merges, renames, removals). Fills, region effects and
+    /// region gradients have already been moved accordingly.
+    pub fn region_remap_report(&self) -> algorithms::region_remap::RegionRemapReport {
+        self.region_remap.clone()
+    }
+    /// Key of the region containing the point (holes excluded), if any.
+    pub fn region_at(&mut self, x: f32, y: f32) -> Option<u32> {
+        algorithms::region_query::region_at_impl(self, x, y)
+    }
+    /// Keys of regions whose filled area overlaps the rect, sorted ascending.
+    pub fn regions_in_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<u32> {
+        algorithms::region_query::regions_in_rect_impl(self, x0, y0, x1, y1)
+    }
+    /// Pairs of regions sharing boundary, with the shared edge ids.
+    pub fn region_adjacency(&mut self) -> Vec<algorithms::region_query::RegionAdjacency> {
+        algorithms::region_query::region_adjacency_impl(self)
+    }
+    /// Keys of regions sharing boundary with `key`.
+    pub fn region_neighbors(&mut self, key: u32) -> Vec<u32> {
+        algorithms::region_query::region_neighbors_impl(self, key)
+    }
+    /// Perimeter, centroid, bbox, areas and outer-face contact of a region.
+    pub fn region_metrics(&mut self, key: u32) -> Option<algorithms::region_query::RegionMetrics> {
+        algorithms::region_query::region_metrics_impl(self, key)
+    }
+    /// Exact boundary of a region as path commands (outer ring, then holes).
+    pub fn get_region_path(&mut self, key: u32) -> Option<Vec<PathCommand>> {
+        algorithms::regions::region_path(self, key)
+    }
+    /// Exact boundary of a region as an SVG path `d` string.
+    pub fn get_region_path_d(&mut self, key: u32) -> Option<String> {
+        self.get_region_path(key)
+            .map(|cmds| algorithms::regions::path_commands_to_d(&cmds))
+    }
     pub fn toggle_region(&mut self, key: u32) -> bool {
         let cur = self.fills.get(&key).copied().unwrap_or(FillState {
             filled: true,
@@ -1545,6 +1839,7 @@ impl Graph {
     }
     pub fn set_region_color(&mut self, key: u32, r: u8, g: u8, b: u8, a: u8) {
         let filled = self.fills.get(&key).map(|st| st.filled).unwrap_or(true);
+        self.region_gradients.remove(&key);
         self.fills.insert(
             key,
             FillState {
@@ -1567,6 +1862,9 @@ impl Graph {
 
     // Polyline
     pub fn set_edge_polyline(&mut self, id: u32, points: &[(f32, f32)]) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         let changed = {
             let edge = match self.edges.get_mut(id as usize) {
                 Some(Some(edge)) => edge,
@@ -1631,6 +1929,7 @@ impl Graph {
             kind: EdgeKind::Polyline { points: pts },
             stroke: None,
             stroke_width: 2.0,
+            construction: false,
         }));
         self.dirty.edges_added.insert(id);
         if let (Some(na), Some(nb)) = (
@@ -1655,7 +1954,8 @@ impl Graph {
         svg::to_svg_paths_impl(self)
     }
 
-    /// Generate a complete SVG document string with paths, styles, and text
+    /// Generate a complete SVG document string with paths, styles, and text,
+    /// nested as one `<g>` per visible layer and group in z-order
     pub fn to_svg_document(&self) -> String {
         svg::to_svg_document_impl(self)
     }
@@ -1663,6 +1963,19 @@ impl Graph {
     fn bump(&mut self) {
         self.geom_ver = self.geom_ver.wrapping_add(1);
     }
+
+    /// Note a shape whose edge list was set or that was added or deleted,
+    /// so the pick index refiles it
+    pub(crate) fn touch_shape(&mut self, id: u32) {
+        self.dirty.shapes_touched.insert(id);
+        self.objects_ver = self.objects_ver.wrapping_add(1);
+    }
+
+    /// Like [`Self::touch_shape`] for texts
+    pub(crate) fn touch_text(&mut self, id: TextId) {
+        self.dirty.texts_touched.insert(id);
+        self.objects_ver = self.objects_ver.wrapping_add(1);
+    }
 }
 
 // Layer and group management
@@ -1672,14 +1985,37 @@ impl Graph {
         self.layer_system.create_layer(name)
     }
 
-    /// Remove a layer and optionally its edges
-    pub fn remove_layer(&mut self, id: LayerId, remove_edges: bool) -> bool {
-        if let Some(removed_edges) = self.layer_system.remove_layer(id) {
-            if remove_edges {
-                for eid in removed_edges {
-                    self.remove_edge(eid);
+    /// Remove a layer and optionally its edges, shapes and texts
+    pub fn remove_layer(&mut self, id: LayerId, remove_content: bool) -> bool {
+        let locked = self.layer_system.get_layer(id).is_some_and(|l| {
+            l.locked || self.layer_system.is_subtree_locked(l.root_group)
+        });
+        if locked {
+            return false;
+        }
+        if let Some(removed) = self.layer_system.remove_layer(id) {
+            if remove_content {
+                for item in removed {
+                    match item {
+                        GroupItem::Edge(eid) => {
+                            self.remove_edge(eid);
+                        }
+                        GroupItem::Shape(sid) => {
+                            self.delete_shape(sid);
+                        }
+                        GroupItem::Text(tid) => {
+                            self.remove_text(tid);
+                        }
+                        GroupItem::Group(_) => {}
+                    }
+                }
+            } else {
+                // Orphaned content moves to the default layer
+                for item in removed {
+                    self.file_in_default_group(item);
                 }
             }
+            self.membership_changed();
             true
         } else {
             false
@@ -1717,6 +2053,16 @@ impl Graph {
         self.layer_system.set_layer_opacity(id, opacity)
     }
 
+    /// Set layer blend mode
+    pub fn set_layer_blend_mode(&mut self, id: LayerId, mode: BlendMode) -> bool {
+        self.layer_system.set_layer_blend_mode(id, mode)
+    }
+
+    /// Get layer blend mode
+    pub fn get_layer_blend_mode(&self, id: LayerId) -> Option<BlendMode> {
+        self.layer_system.get_layer(id).map(|l| l.blend_mode)
+    }
+
     /// Set layer z-index
     pub fn set_layer_z_index(&mut self, id: LayerId, z: i32) -> bool {
         self.layer_system.set_layer_z_index(id, z)
@@ -1729,7 +2075,261 @@ impl Graph {
 
     /// Remove a group (edges/children move to parent)
     pub fn remove_group(&mut self, id: LayerId) -> bool {
-        self.layer_system.remove_group(id)
+        if self.group_tree_locked(id) {
+            return false;
+        }
+        let removed = self.layer_system.remove_group(id);
+        if removed {
+            self.group_effects.remove(&id);
+            self.membership_changed();
+        }
+        removed
+    }
+
+    /// Dissolve a group into its parent; its members take its place in the
+    /// parent's order. Returns the promoted child group IDs.
+    pub fn ungroup(&mut self, id: LayerId) -> Option<Vec<LayerId>> {
+        if self.group_tree_locked(id) {
+            return None;
+        }
+        let children = self.layer_system.ungroup(id)?;
+        self.group_effects.remove(&id);
+        self.membership_changed();
+        Some(children)
+    }
+
+    /// Pull all edges below a group up into it and remove its subgroups
+    pub fn flatten_group(&mut self, id: LayerId) -> bool {
+        if self.group_tree_locked(id) {
+            return false;
+        }
+        let Some(removed) = self.layer_system.flatten_group(id) else {
+            return false;
+        };
+        if !removed.is_empty() {
+            for gid in &removed {
+                self.group_effects.remove(gid);
+            }
+            self.membership_changed();
+        }
+        true
+    }
+
+    /// Move a group and its subtree under another group, possibly in another
+    /// layer. `index` is the position among the new siblings, bottom to top;
+    /// None puts it on top. Refused for cycles, root groups and locked
+    /// content on either side.
+    pub fn move_group(&mut self, id: LayerId, new_parent: LayerId, index: Option<usize>) -> bool {
+        if self.group_tree_locked(id) || self.layer_system.is_group_chain_locked(new_parent) {
+            return false;
+        }
+        let moved = self.layer_system.move_group(id, new_parent, index);
+        if moved {
+            self.membership_changed();
+        }
+        moved
+    }
+
+    /// Move a group to the top level of a layer
+    pub fn move_group_to_layer(
+        &mut self,
+        id: LayerId,
+        layer_id: LayerId,
+        index: Option<usize>,
+    ) -> bool {
+        match self.layer_system.get_layer(layer_id) {
+            Some(layer) => self.move_group(id, layer.root_group, index),
+            None => false,
+        }
+    }
+
+    /// Move a group to a new position among its siblings (bottom to top)
+    pub fn reorder_group(&mut self, id: LayerId, index: usize) -> bool {
+        !self.group_tree_locked(id) && self.layer_system.reorder_group(id, index)
+    }
+
+    /// Bring an edge, shape, text or group to the front or back of its
+    /// siblings, or one step forward or backward. A shape takes its outline
+    /// edges along, keeping their relative order. Refused for locked items,
+    /// shapes with a locked outline edge and groups with locked content.
+    pub fn reorder_item(&mut self, item: GroupItem, to: layers::ZOrder) -> bool {
+        let outline = match item {
+            GroupItem::Shape(id) => self.get_shape(id).map(|s| s.edges.clone()),
+            _ => None,
+        };
+        let outline = outline.unwrap_or_default();
+        let locked = match item {
+            GroupItem::Group(id) => self.group_tree_locked(id),
+            item => {
+                self.layer_system.is_item_locked(item)
+                    || outline.iter().any(|&e| self.layer_system.is_edge_locked(e))
+            }
+        };
+        if locked || !self.layer_system.reorder_item(item, to) {
+            return false;
+        }
+        // Move the edges bottom-most first when raising and top-most first
+        // when lowering, so they don't leapfrog each other
+        let mut edges: Vec<(Vec<usize>, u32)> = outline
+            .into_iter()
+            .collect::<HashSet<u32>>()
+            .into_iter()
+            .filter_map(|e| Some((self.layer_system.paint_key(GroupItem::Edge(e))?, e)))
+            .collect();
+        edges.sort_unstable();
+        if matches!(to, layers::ZOrder::Back | layers::ZOrder::Forward) {
+            edges.reverse();
+        }
+        for (_, e) in edges {
+            self.layer_system.reorder_item(GroupItem::Edge(e), to);
+        }
+        true
+    }
+
+    /// Edges, shapes and texts bottom to top, as drawn by the SVG export;
+    /// hidden items included
+    pub fn draw_order(&self) -> Vec<GroupItem> {
+        self.layer_system.draw_order()
+    }
+
+    /// Move edges into a group, keeping the given order. All-or-nothing:
+    /// returns 0 if the group is unknown or locked, or any edge is missing
+    /// or locked; otherwise the number of edges moved.
+    pub fn move_edges_to_group(&mut self, edge_ids: &[u32], group_id: LayerId) -> usize {
+        let items: Vec<GroupItem> = edge_ids.iter().map(|&e| GroupItem::Edge(e)).collect();
+        self.move_items_to_group(&items, group_id)
+    }
+
+    /// Move edges, shapes and texts into a group, keeping the given order.
+    /// All-or-nothing like [`Self::move_edges_to_group`]; group items are
+    /// refused (use [`Self::move_group`]).
+    pub fn move_items_to_group(&mut self, items: &[GroupItem], group_id: LayerId) -> usize {
+        if self.layer_system.is_group_chain_locked(group_id)
+            || items.iter().any(|&item| {
+                matches!(item, GroupItem::Group(_))
+                    || !self.item_exists(item)
+                    || self.layer_system.is_item_locked(item)
+            })
+        {
+            return 0;
+        }
+        let moved = self.layer_system.move_items_to_group(items, group_id);
+        if moved > 0 {
+            self.membership_changed();
+        }
+        moved
+    }
+
+    /// Put an edge, shape or text on top of a group
+    pub fn add_item_to_group(&mut self, item: GroupItem, group_id: LayerId) -> bool {
+        self.move_items_to_group(&[item], group_id) == 1
+    }
+
+    /// Get the group holding an edge, shape, text or (for a group) its parent
+    pub fn get_item_group(&self, item: GroupItem) -> Option<LayerId> {
+        self.layer_system.item_group(item)
+    }
+
+    /// Get the layer holding an edge, shape, text or group
+    pub fn get_item_layer(&self, item: GroupItem) -> Option<LayerId> {
+        self.layer_system.item_layer(item)
+    }
+
+    /// Check if an item is visible (considering layer/group visibility)
+    pub fn is_item_visible(&self, item: GroupItem) -> bool {
+        self.layer_system.is_item_visible(item)
+    }
+
+    /// Check if an item is locked (considering layer/group lock chain)
+    pub fn is_item_locked(&self, item: GroupItem) -> bool {
+        self.layer_system.is_item_locked(item)
+    }
+
+    /// Effective opacity of an item from its layer and group chain
+    pub fn get_item_opacity(&self, item: GroupItem) -> f32 {
+        self.layer_system.item_opacity(item)
+    }
+
+    /// Effects applying to an item, innermost first: its own stack, then the
+    /// stacks of each enclosing group up to the layer root. Disabled stacks
+    /// are skipped.
+    pub fn get_effective_effects(&self, item: GroupItem) -> Vec<EffectId> {
+        let own = match item {
+            GroupItem::Shape(id) => self.shape_effects.get(&id),
+            GroupItem::Text(id) => self.text_effects.get(&id),
+            GroupItem::Group(id) => self.group_effects.get(&id),
+            GroupItem::Edge(_) => None,
+        };
+        let mut out: Vec<EffectId> = own
+            .filter(|s| s.enabled)
+            .map(|s| s.effects.clone())
+            .unwrap_or_default();
+        let mut group = self.layer_system.item_group(item);
+        while let Some(gid) = group {
+            if let Some(stack) = self.group_effects.get(&gid).filter(|s| s.enabled) {
+                out.extend(stack.effects.iter().copied());
+            }
+            group = self.layer_system.get_group(gid).and_then(|g| g.parent);
+        }
+        out
+    }
+
+    /// Check if an edge, shape, text or group exists
+    pub fn item_exists(&self, item: GroupItem) -> bool {
+        match item {
+            GroupItem::Group(id) => self.layer_system.groups.contains_key(&id),
+            GroupItem::Edge(id) => self.get_edge_nodes(id).is_some(),
+            GroupItem::Shape(id) => self.get_shape(id).is_some(),
+            GroupItem::Text(id) => self.get_text(id).is_some(),
+        }
+    }
+
+    /// Put a new edge, shape or text on the default layer
+    pub(crate) fn file_in_default_group(&mut self, item: GroupItem) {
+        if let Some(default_group) = self.layer_system.default_group() {
+            self.layer_system.add_item_to_group(item, default_group);
+        }
+    }
+
+    /// After loading: drop memberships of objects that don't exist and file
+    /// objects without a group on the default layer
+    pub(crate) fn file_unassigned_members(&mut self) {
+        let dead: Vec<GroupItem> = self
+            .layer_system
+            .groups
+            .values()
+            .flat_map(|g| g.items.iter().copied())
+            .filter(|&i| !matches!(i, GroupItem::Group(_)) && !self.item_exists(i))
+            .collect();
+        for item in dead {
+            self.layer_system.remove_item(item);
+        }
+        let live = (0..self.edges.len() as u32)
+            .map(GroupItem::Edge)
+            .chain((0..self.shapes.len() as u32).map(GroupItem::Shape))
+            .chain((0..self.texts.len() as u32).map(GroupItem::Text));
+        let unfiled: Vec<GroupItem> = live
+            .filter(|&i| self.item_exists(i) && self.layer_system.item_group(i).is_none())
+            .collect();
+        for item in unfiled {
+            self.file_in_default_group(item);
+        }
+    }
+
+    /// Whether a group is `ancestor_id` or nested somewhere below it
+    pub fn is_group_within(&self, id: LayerId, ancestor_id: LayerId) -> bool {
+        self.layer_system.is_within(id, ancestor_id)
+    }
+
+    /// The layer hierarchy in drawing order, for layer panels
+    pub fn get_tree(&self) -> Vec<layers::LayerNode> {
+        self.layer_system.get_tree()
+    }
+
+    /// A group is locked itself, through an ancestor or layer, or somewhere
+    /// below it
+    fn group_tree_locked(&self, id: LayerId) -> bool {
+        self.layer_system.is_group_chain_locked(id) || self.layer_system.is_subtree_locked(id)
     }
 
     /// Get all groups as (id, name, parent, visible, opacity)
@@ -1763,9 +2363,53 @@ impl Graph {
         self.layer_system.set_group_opacity(id, opacity)
     }
 
+    /// Set group blend mode
+    pub fn set_group_blend_mode(&mut self, id: LayerId, mode: BlendMode) -> bool {
+        self.layer_system.set_group_blend_mode(id, mode)
+    }
+
+    /// Get group blend mode
+    pub fn get_group_blend_mode(&self, id: LayerId) -> Option<BlendMode> {
+        self.layer_system.get_group(id).map(|g| g.blend_mode)
+    }
+
+    /// Set or clear a group's mask. A clip needs an existing closed shape; a
+    /// mask group must pass [`LayerSystem::set_group_mask`]. Refused for
+    /// locked groups. Mask source content leaves regions, so this rebuilds
+    /// them.
+    pub fn set_group_mask(&mut self, id: LayerId, mask: Option<GroupMask>) -> bool {
+        if self.layer_system.is_group_chain_locked(id) {
+            return false;
+        }
+        if let Some(GroupMask::Clip { shape }) = mask {
+            if !self.get_shape(shape).is_some_and(|s| s.closed) {
+                return false;
+            }
+        }
+        if self.layer_system.set_group_mask(id, mask) {
+            self.mark_full_dirty();
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Get a group's mask
+    pub fn get_group_mask(&self, id: LayerId) -> Option<GroupMask> {
+        self.layer_system.group_mask(id)
+    }
+
     /// Add an edge to a specific group
     pub fn add_edge_to_group(&mut self, edge_id: u32, group_id: LayerId) -> bool {
-        self.layer_system.add_edge_to_group(edge_id, group_id)
+        if self.is_edge_locked(edge_id) || self.layer_system.is_group_chain_locked(group_id) {
+            return false;
+        }
+        let moved = self.layer_system.add_edge_to_group(edge_id, group_id);
+        if moved {
+            self.membership_changed();
+        }
+        moved
     }
 
     /// Get the group containing an edge
@@ -1783,6 +2427,115 @@ impl Graph {
         self.layer_system.is_edge_visible(edge_id)
     }
 
+    /// Lock or unlock a layer. Edits to edges in a locked layer are refused.
+    pub fn set_layer_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        self.layer_system.set_layer_locked(id, locked)
+    }
+
+    /// Lock or unlock a group. Locking covers its subgroups.
+    pub fn set_group_locked(&mut self, id: LayerId, locked: bool) -> bool {
+        self.layer_system.set_group_locked(id, locked)
+    }
+
+    /// The layer's own locked flag
+    pub fn is_layer_locked(&self, id: LayerId) -> bool {
+        self.layer_system.get_layer(id).is_some_and(|l| l.locked)
+    }
+
+    /// The group's own locked flag
+    pub fn is_group_locked(&self, id: LayerId) -> bool {
+        self.layer_system.get_group(id).is_some_and(|g| g.locked)
+    }
+
+    /// Check if an edge is locked (considering layer/group lock chain)
+    pub fn is_edge_locked(&self, edge_id: u32) -> bool {
+        self.layer_system.is_edge_locked(edge_id)
+    }
+
+    /// A node is locked when any edge at it is; moving it would edit that edge
+    pub fn is_node_locked(&self, id: u32) -> bool {
+        self.selection_locked(&[id], &[], false)
+    }
+
+    /// A shape is locked when its group or any of its edges is
+    pub fn is_shape_locked(&self, id: u32) -> bool {
+        self.get_shape(id).is_some_and(|s| {
+            self.layer_system.is_item_locked(GroupItem::Shape(id))
+                || s.edges.iter().any(|&e| self.is_edge_locked(e))
+        })
+    }
+
+    /// A text is locked when its group or layer is
+    pub fn is_text_locked(&self, id: TextId) -> bool {
+        self.layer_system.is_item_locked(GroupItem::Text(id))
+    }
+
+    /// Whether editing these nodes and edges would touch locked content.
+    /// With `with_ends`, the edges' endpoints count as edited too.
+    pub(crate) fn selection_locked(
+        &self,
+        node_ids: &[u32],
+        edge_ids: &[u32],
+        with_ends: bool,
+    ) -> bool {
+        if !self.layer_system.has_locks() {
+            return false;
+        }
+        if edge_ids.iter().any(|&e| self.is_edge_locked(e)) {
+            return true;
+        }
+        let mut nodes: HashSet<u32> = node_ids.iter().copied().collect();
+        if with_ends {
+            for e in edge_ids.iter().filter_map(|&e| self.edges.get(e as usize)?.as_ref()) {
+                nodes.insert(e.a);
+                nodes.insert(e.b);
+            }
+        }
+        !nodes.is_empty() && self.locked_nodes().iter().any(|n| nodes.contains(n))
+    }
+
+    /// Ends of locked edges, in one pass over the edges: the nodes
+    /// `is_node_locked` reports. Checks several nodes without a pass each.
+    pub fn locked_nodes(&self) -> HashSet<u32> {
+        let mut out = HashSet::new();
+        if !self.layer_system.has_locks() {
+            return out;
+        }
+        for (i, e) in self.edges.iter().enumerate() {
+            let Some(e) = e else { continue };
+            if self.is_edge_locked(i as u32) {
+                out.insert(e.a);
+                out.insert(e.b);
+            }
+        }
+        out
+    }
+
+    /// Skip locked edges (and nodes, shapes and regions made only of them)
+    /// in `pick`, `pick_all` and the marquee/lasso selection queries
+    pub fn set_pick_skip_locked(&mut self, skip: bool) {
+        self.pick_skip_locked = skip;
+    }
+
+    pub fn pick_skips_locked(&self) -> bool {
+        self.pick_skip_locked
+    }
+
+    /// Whether picking and selection can reach an item: visible, not mask
+    /// source content, and not locked when the graph skips locked content
+    pub(crate) fn item_hittable(&self, item: GroupItem) -> bool {
+        self.layer_system.is_item_visible(item)
+            && !self.layer_system.is_in_mask_source(item)
+            && !(self.pick_skip_locked && self.layer_system.is_item_locked(item))
+    }
+
+    /// Whether an edge feeds region planarization: visible and not construction
+    pub(crate) fn edge_in_regions(&self, edge_id: u32) -> bool {
+        !self.is_edge_construction(edge_id)
+            && self.layer_system.is_edge_visible(edge_id)
+            && !self.layer_system.is_in_mask_source(GroupItem::Edge(edge_id))
+    }
+
     /// Get all visible edge IDs
     pub fn get_visible_edges(&self) -> Vec<u32> {
         self.edges
@@ -1881,6 +2634,7 @@ impl Graph {
 
     /// Remove a gradient
     pub fn remove_gradient(&mut self, id: GradientId) -> bool {
+        self.region_gradients.retain(|_, g| *g != id);
         self.gradients.remove(&id).is_some()
     }
 
@@ -1914,9 +2668,15 @@ impl Graph {
                 color: None, // Gradient reference stored separately
             },
         );
+        self.region_gradients.insert(key, gradient_id);
         true
     }
 
+    /// Gradient bound to a region fill, if any
+    pub fn get_region_gradient(&self, key: u32) -> Option<GradientId> {
+        self.region_gradients.get(&key).copied()
+    }
+
     /// Set edge stroke to use a gradient
     pub fn set_edge_stroke_gradient(
         &mut self,
@@ -1924,6 +2684,9 @@ impl Graph {
         gradient_id: GradientId,
         width: f32,
     ) -> bool {
+        if self.is_edge_locked(id) {
+            return false;
+        }
         if !self.gradients.contains_key(&gradient_id) {
             return false;
         }
@@ -2205,7 +2968,12 @@ impl Graph {
 
 // Transforms and grouping moves
 impl Graph {
-    pub fn transform_all(&mut self, s: f32, tx: f32, ty: f32, scale_stroke: bool) {
+    /// Scale and translate the whole drawing. Refused (false) while any layer
+    /// or group is locked.
+    pub fn transform_all(&mut self, s: f32, tx: f32, ty: f32, scale_stroke: bool) -> bool {
+        if self.layer_system.has_locks() {
+            return false;
+        }
         for n in self.nodes.iter_mut() {
             if let Some(n) = n {
                 n.x = n.x * s + tx;
@@ -2240,12 +3008,16 @@ impl Graph {
         self.incr_plan.borrow_mut().take();
         self.mark_full_dirty();
         self.bump();
+        true
     }
     pub fn translate_nodes(&mut self, ids: &[u32], dx: f32, dy: f32) -> u32 {
+        if self.selection_locked(ids, &[], false) {
+            return 0;
+        }
         let mut moved = 0;
         for &id in ids {
             if let Some((x, y)) = self.get_node(id) {
-                if self.move_node(id, x + dx, y + dy) {
+                if self.move_node_unlocked(id, x + dx, y + dy) {
                     moved += 1;
                 }
             }
@@ -2259,6 +3031,9 @@ impl Graph {
         dy: f32,
         split_shared: bool,
     ) -> u32 {
+        if self.selection_locked(&[], edge_ids, !split_shared) {
+            return 0;
+        }
         let mut nodes_to_move: HashSet<u32> = HashSet::new();
         for &eid in edge_ids {
             if let Some(e) = self.edges.get(eid as usize).and_then(|e| e.as_ref()) {
@@ -2305,7 +3080,7 @@ impl Graph {
         let mut moved = 0;
         for nid in nodes_to_move {
             if let Some((x, y)) = self.get_node(nid) {
-                if self.move_node(nid, x + dx, y + dy) {
+                if self.move_node_unlocked(nid, x + dx, y + dy) {
                     moved += 1;
                 }
             }
@@ -2318,6 +3093,8 @@ impl Graph {
 impl Graph {
     /// Compute the combined AABB for a selection of elements.
     /// Returns (minx, miny, maxx, maxy) or None if the selection is empty.
+    /// Edges, shapes and texts are cut to the bounds of their group masks;
+    /// fully masked ones add nothing.
     pub fn get_selection_bbox(
         &self,
         node_ids: &[u32],
@@ -2326,6 +3103,7 @@ impl Graph {
         text_ids: &[u32],
     ) -> Option<(f32, f32, f32, f32)> {
         let mut bbox: Option<(f32, f32, f32, f32)> = None;
+        let clip = |item, b| algorithms::masking::clip_to_masks(self, item, b);
 
         // Nodes contribute as single points
         for &nid in node_ids {
@@ -2338,7 +3116,7 @@ impl Graph {
         for &eid in edge_ids {
             if let Some(Some(e)) = self.edges.get(eid as usize) {
                 if let Some(eb) = self.edge_aabb_of(e) {
-                    bbox = Self::union_bbox(bbox, Some(eb));
+                    bbox = Self::union_bbox(bbox, clip(GroupItem::Edge(eid), eb));
                 }
             }
         }
@@ -2346,37 +3124,71 @@ impl Graph {
         // Shapes are unions of their edges
         for &sid in shape_ids {
             if let Some(Some(shape)) = self.shapes.get(sid as usize) {
+                let mut sb = None;
                 for &eid in &shape.edges {
                     if let Some(Some(e)) = self.edges.get(eid as usize) {
                         if let Some(eb) = self.edge_aabb_of(e) {
-                            bbox = Self::union_bbox(bbox, Some(eb));
+                            sb = Self::union_bbox(sb, Some(eb));
                         }
                     }
                 }
+                let sb = sb.and_then(|b| clip(GroupItem::Shape(sid), b));
+                bbox = Self::union_bbox(bbox, sb);
             }
         }
 
         // Text elements use position and estimated dimensions
         for &tid in text_ids {
             if let Some(Some(text)) = self.texts.get(tid as usize) {
-                let x = text.position.x;
-                let y = text.position.y;
-                let fs = text.style.font_size;
-                // Estimate width based on content length and font size
-                let est_width = text.content.len() as f32 * fs * 0.6;
-                let est_height = fs * text.style.line_height;
-                // For TextType::Box, use the actual dimensions
-                let (w, h) = match &text.text_type {
-                    TextType::Box { width, height, .. } => (*width, *height),
-                    _ => (est_width, est_height),
-                };
-                bbox = Self::union_bbox(bbox, Some((x, y, x + w, y + h)));
+                let tb = clip(GroupItem::Text(tid), Self::text_aabb(text));
+                bbox = Self::union_bbox(bbox, tb);
             }
         }
 
         bbox
     }
 
+    /// Estimated AABB of a text element: box texts use their box, others
+    /// assume 0.6 em per character and one line.
+    pub(crate) fn text_aabb(text: &TextElement) -> (f32, f32, f32, f32) {
+        let x = text.position.x;
+        let y = text.position.y;
+        let fs = text.style.font_size;
+        // Estimate width based on content length and font size
+        let est_width = text.content.len() as f32 * fs * 0.6;
+        let est_height = fs * text.style.line_height;
+        // For TextType::Box, use the actual dimensions
+        let (w, h) = match &text.text_type {
+            TextType::Box { width, height, .. } => (*width, *height),
+            _ => (est_width, est_height),
+        };
+        (x, y, x + w, y + h)
+    }
+
+    /// Nodes, edges, texts and shapes inside the rectangle (`Contained`) or
+    /// touching it (`Intersecting`). Hidden edges are skipped, as are locked
+    /// ones under [`Graph::set_pick_skip_locked`].
+    pub fn select_in_rect(
+        &self,
+        minx: f32,
+        miny: f32,
+        maxx: f32,
+        maxy: f32,
+        mode: SelectMode,
+    ) -> algorithms::selection::SelectionResult {
+        algorithms::selection::select_in_rect_impl(self, (minx, miny, maxx, maxy), mode)
+    }
+
+    /// Like `select_in_rect` for a lasso polygon (even-odd inside, implicitly
+    /// closed). Fewer than three points select nothing.
+    pub fn select_in_polygon(
+        &self,
+        points: &[(f32, f32)],
+        mode: SelectMode,
+    ) -> algorithms::selection::SelectionResult {
+        algorithms::selection::select_in_polygon_impl(self, points, mode)
+    }
+
     /// Rotate selected elements around a pivot point by angle (radians).
     /// Returns the number of nodes modified.
     pub fn rotate_selection(
@@ -2387,6 +3199,9 @@ impl Graph {
         cy: f32,
         angle: f32,
     ) -> u32 {
+        if self.selection_locked(node_ids, edge_ids, true) {
+            return 0;
+        }
         let cos_a = angle.cos();
         let sin_a = angle.sin();
         let mut modified = 0u32;
@@ -2407,7 +3222,7 @@ impl Graph {
                 let dy = oy - cy;
                 let nx = cx + dx * cos_a - dy * sin_a;
                 let ny = cy + dx * sin_a + dy * cos_a;
-                if self.move_node(nid, nx, ny) {
+                if self.move_node_unlocked(nid, nx, ny) {
                     modified += 1;
                 }
             }
@@ -2452,6 +3267,9 @@ impl Graph {
         sy: f32,
         scale_stroke: bool,
     ) -> u32 {
+        if self.selection_locked(node_ids, edge_ids, true) {
+            return 0;
+        }
         let mut modified = 0u32;
 
         // Collect unique nodes from both direct selection and edges
@@ -2468,7 +3286,7 @@ impl Graph {
             if let Some((ox, oy)) = self.get_node(nid) {
                 let nx = cx + (ox - cx) * sx;
                 let ny = cy + (oy - cy) * sy;
-                if self.move_node(nid, nx, ny) {
+                if self.move_node_unlocked(nid, nx, ny) {
                     modified += 1;
                 }
             }
@@ -2534,7 +3352,10 @@ impl Graph {
             edges: edge_ids.to_vec(),
             closed,
             fill_rule: FillRule::NonZero,
+            blend_mode: BlendMode::Normal,
         }));
+        self.file_in_default_group(GroupItem::Shape(id));
+        self.touch_shape(id);
         Some(id)
     }
 
@@ -2563,15 +3384,23 @@ impl Graph {
             edges: edge_ids.to_vec(),
             closed,
             fill_rule,
+            blend_mode: BlendMode::Normal,
         }));
+        self.file_in_default_group(GroupItem::Shape(id));
+        self.touch_shape(id);
         Some(id)
     }
 
     /// Delete a shape by ID. Returns true if the shape existed.
     pub fn delete_shape(&mut self, id: u32) -> bool {
+        if self.is_shape_locked(id) {
+            return false;
+        }
         if let Some(slot) = self.shapes.get_mut(id as usize) {
             if slot.is_some() {
                 *slot = None;
+                self.layer_system.remove_item(GroupItem::Shape(id));
+                self.touch_shape(id);
                 return true;
             }
         }
@@ -2604,6 +3433,9 @@ impl Graph {
 
     /// Set the fill rule for a shape.
     pub fn set_shape_fill_rule(&mut self, id: u32, fill_rule: FillRule) -> bool {
+        if self.is_shape_locked(id) {
+            return false;
+        }
         if let Some(Some(shape)) = self.shapes.get_mut(id as usize) {
             shape.fill_rule = fill_rule;
             return true;
@@ -2611,6 +3443,18 @@ impl Graph {
         false
     }
 
+    /// Set the blend mode a shape composites with.
+    pub fn set_shape_blend_mode(&mut self, id: u32, mode: BlendMode) -> bool {
+        if self.is_shape_locked(id) {
+            return false;
+        }
+        if let Some(Some(shape)) = self.shapes.get_mut(id as usize) {
+            shape.blend_mode = mode;
+            return true;
+        }
+        false
+    }
+
     /// Infer shapes from closed loops in the graph.
     ///
     /// This finds cycles of connected edges and creates shapes for each.
@@ -2747,6 +3591,8 @@ impl Graph {
         let id = self.texts.len() as TextId;
         self.texts
             .push(Some(TextElement::new_label(id, content.to_string(), x, y)));
+        self.file_in_default_group(GroupItem::Text(id));
+        self.touch_text(id);
         id
     }
 
@@ -2769,6 +3615,8 @@ impl Graph {
             width,
             height,
         )));
+        self.file_in_default_group(GroupItem::Text(id));
+        self.touch_text(id);
         id
     }
 
@@ -2781,14 +3629,21 @@ impl Graph {
             content.to_string(),
             edge_ids,
         )));
+        self.file_in_default_group(GroupItem::Text(id));
+        self.touch_text(id);
         id
     }
 
     /// Remove a text element by ID. Returns true if it existed.
     pub fn remove_text(&mut self, id: TextId) -> bool {
+        if self.is_text_locked(id) {
+            return false;
+        }
         if let Some(slot) = self.texts.get_mut(id as usize) {
             if slot.is_some() {
                 *slot = None;
+                self.layer_system.remove_item(GroupItem::Text(id));
+                self.touch_text(id);
                 return true;
             }
         }
@@ -2802,9 +3657,20 @@ impl Graph {
 
     /// Get a mutable reference to a text element by ID.
     pub fn get_text_mut(&mut self, id: TextId) -> Option<&mut TextElement> {
+        if self.get_text(id).is_some() {
+            self.touch_text(id);
+        }
         self.texts.get_mut(id as usize).and_then(|t| t.as_mut())
     }
 
+    /// The text for an edit, or None if it is missing or locked
+    fn editable_text(&mut self, id: TextId) -> Option<&mut TextElement> {
+        if self.is_text_locked(id) {
+            return None;
+        }
+        self.get_text_mut(id)
+    }
+
     /// Get all text IDs.
     pub fn get_text_ids(&self) -> Vec<TextId> {
         self.texts
@@ -2821,7 +3687,7 @@ impl Graph {
 
     /// Set the content of a text element.
     pub fn set_text_content(&mut self, id: TextId, content: &str) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.content = content.to_string();
             return true;
         }
@@ -2830,7 +3696,7 @@ impl Graph {
 
     /// Set the position of a text element.
     pub fn set_text_position(&mut self, id: TextId, x: f32, y: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.position = Vec2 { x, y };
             return true;
         }
@@ -2839,7 +3705,7 @@ impl Graph {
 
     /// Set the rotation of a text element (in radians).
     pub fn set_text_rotation(&mut self, id: TextId, radians: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.rotation = radians;
             return true;
         }
@@ -2849,7 +3715,7 @@ impl Graph {
     /// Rotate a text element around a pivot point.
     /// This moves the position and adds to the rotation.
     pub fn rotate_text_around(&mut self, id: TextId, cx: f32, cy: f32, angle: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             let cos_a = angle.cos();
             let sin_a = angle.sin();
             let ox = text.position.x;
@@ -2867,7 +3733,7 @@ impl Graph {
     /// Scale a text element from a pivot point.
     /// This moves the position and scales the font size.
     pub fn scale_text_around(&mut self, id: TextId, cx: f32, cy: f32, sx: f32, sy: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             let ox = text.position.x;
             let oy = text.position.y;
             text.position.x = cx + (ox - cx) * sx;
@@ -2892,7 +3758,7 @@ impl Graph {
 
     /// Set the text alignment.
     pub fn set_text_align(&mut self, id: TextId, align: TextAlign) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.align = align;
             return true;
         }
@@ -2901,7 +3767,7 @@ impl Graph {
 
     /// Set the complete style of a text element.
     pub fn set_text_style(&mut self, id: TextId, style: TextStyle) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style = style;
             return true;
         }
@@ -2910,7 +3776,7 @@ impl Graph {
 
     /// Set individual font properties.
     pub fn set_text_font(&mut self, id: TextId, font_family: &str, font_size: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.font_family = font_family.to_string();
             text.style.font_size = font_size;
             return true;
@@ -2920,7 +3786,7 @@ impl Graph {
 
     /// Set font weight (100-900).
     pub fn set_text_font_weight(&mut self, id: TextId, weight: u16) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.font_weight = weight.clamp(100, 900);
             return true;
         }
@@ -2929,7 +3795,7 @@ impl Graph {
 
     /// Set font style (normal, italic, oblique).
     pub fn set_text_font_style(&mut self, id: TextId, style: FontStyle) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.font_style = style;
             return true;
         }
@@ -2938,7 +3804,7 @@ impl Graph {
 
     /// Set the fill color of text.
     pub fn set_text_fill_color(&mut self, id: TextId, r: u8, g: u8, b: u8, a: u8) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.fill_color = Some(Color { r, g, b, a });
             return true;
         }
@@ -2947,7 +3813,7 @@ impl Graph {
 
     /// Clear the fill color (make text transparent fill).
     pub fn clear_text_fill_color(&mut self, id: TextId) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.fill_color = None;
             return true;
         }
@@ -2956,7 +3822,7 @@ impl Graph {
 
     /// Set the stroke color of text.
     pub fn set_text_stroke_color(&mut self, id: TextId, r: u8, g: u8, b: u8, a: u8) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.stroke_color = Some(Color { r, g, b, a });
             return true;
         }
@@ -2965,7 +3831,7 @@ impl Graph {
 
     /// Set the stroke width of text.
     pub fn set_text_stroke_width(&mut self, id: TextId, width: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.stroke_width = width.max(0.0);
             return true;
         }
@@ -2974,7 +3840,7 @@ impl Graph {
 
     /// Set letter spacing (in em units).
     pub fn set_text_letter_spacing(&mut self, id: TextId, spacing: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.letter_spacing = spacing;
             return true;
         }
@@ -2983,7 +3849,7 @@ impl Graph {
 
     /// Set line height multiplier.
     pub fn set_text_line_height(&mut self, id: TextId, line_height: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.style.line_height = line_height.max(0.1);
             return true;
         }
@@ -2992,7 +3858,7 @@ impl Graph {
 
     /// Convert a text label to a text box.
     pub fn convert_text_to_box(&mut self, id: TextId, width: f32, height: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.text_type = TextType::Box {
                 width,
                 height,
@@ -3022,7 +3888,7 @@ impl Graph {
                 return false;
             }
         }
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.text_type = TextType::OnPath {
                 edge_ids,
                 start_offset: start_offset.clamp(0.0, 1.0),
@@ -3034,7 +3900,7 @@ impl Graph {
 
     /// Convert a text element back to a simple label.
     pub fn convert_text_to_label(&mut self, id: TextId) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             text.text_type = TextType::Label;
             return true;
         }
@@ -3043,7 +3909,7 @@ impl Graph {
 
     /// Set text box dimensions (only for text box type).
     pub fn set_text_box_size(&mut self, id: TextId, width: f32, height: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             if let TextType::Box {
                 vertical_align,
                 overflow,
@@ -3064,7 +3930,7 @@ impl Graph {
 
     /// Set text box vertical alignment.
     pub fn set_text_box_vertical_align(&mut self, id: TextId, align: VerticalAlign) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             if let TextType::Box {
                 width,
                 height,
@@ -3086,7 +3952,7 @@ impl Graph {
 
     /// Set text box overflow behavior.
     pub fn set_text_box_overflow(&mut self, id: TextId, overflow: TextOverflow) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             if let TextType::Box {
                 width,
                 height,
@@ -3108,7 +3974,7 @@ impl Graph {
 
     /// Set the start offset for text on path (0.0 to 1.0).
     pub fn set_text_path_offset(&mut self, id: TextId, offset: f32) -> bool {
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             if let TextType::OnPath { edge_ids, .. } = &text.text_type {
                 let edge_ids = edge_ids.clone();
                 text.text_type = TextType::OnPath {
@@ -3134,7 +4000,7 @@ impl Graph {
                 return false;
             }
         }
-        if let Some(Some(text)) = self.texts.get_mut(id as usize) {
+        if let Some(text) = self.editable_text(id) {
             if let TextType::OnPath { start_offset, .. } = text.text_type {
                 text.text_type = TextType::OnPath {
                     edge_ids,
@@ -18,6 +18,72 @@ pub enum FillRule {
     EvenOdd = 1,
 }
 
+/// How a layer, group or shape composites onto what is drawn below it.
+/// Serialized under the CSS `mix-blend-mode` names.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    #[default]
+    Normal = 0,
+    Multiply = 1,
+    Screen = 2,
+    Overlay = 3,
+    Darken = 4,
+    Lighten = 5,
+    ColorDodge = 6,
+    ColorBurn = 7,
+    Difference = 8,
+    Exclusion = 9,
+    Hue = 10,
+    Saturation = 11,
+    Color = 12,
+    Luminosity = 13,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 14] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+        BlendMode::Darken,
+        BlendMode::Lighten,
+        BlendMode::ColorDodge,
+        BlendMode::ColorBurn,
+        BlendMode::Difference,
+        BlendMode::Exclusion,
+        BlendMode::Hue,
+        BlendMode::Saturation,
+        BlendMode::Color,
+        BlendMode::Luminosity,
+    ];
+
+    /// Mode from its CSS `mix-blend-mode` keyword
+    pub fn from_css_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.css_name() == name)
+    }
+
+    /// The CSS `mix-blend-mode` keyword
+    pub fn css_name(self) -> &'static str {
+        match self {
+            BlendMode::Normal => "normal",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+            BlendMode::Overlay => "overlay",
+            BlendMode::Darken => "darken",
+            BlendMode::Lighten => "lighten",
+            BlendMode::ColorDodge => "color-dodge",
+            BlendMode::ColorBurn => "color-burn",
+            BlendMode::Difference => "difference",
+            BlendMode::Exclusion => "exclusion",
+            BlendMode::Hue => "hue",
+            BlendMode::Saturation => "saturation",
+            BlendMode::Color => "color",
+            BlendMode::Luminosity => "luminosity",
+        }
+    }
+}
+
 /// A shape is an ordered collection of edges forming a closed or open path
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Shape {
@@ -28,6 +94,40 @@ pub struct Shape {
     pub closed: bool,
     /// Fill rule for determining inside/outside
     pub fill_rule: FillRule,
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+}
+
+/// How region fill membership is decided
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RegionFillMode {
+    /// Every face of the planar map is a region filled by its own fill state
+    #[default]
+    Faces = 0,
+    /// Faces are filled when a closed shape covers them under its fill rule
+    Shapes = 1,
+}
+
+/// Which edges combine into regions
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RegionScope {
+    /// All visible edges form one planar map
+    #[default]
+    All = 0,
+    /// Each layer forms its own planar map; keys are namespaced by layer
+    Layer = 1,
+    /// Each group forms its own planar map; keys are namespaced by group
+    Group = 2,
+}
+
+/// How a marquee or lasso selects an element
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SelectMode {
+    /// The element lies entirely inside the area
+    #[default]
+    Contained = 0,
+    /// Any part of the element lies inside the area
+    Intersecting = 1,
 }
 
 /// Result from creating a primitive (rectangle, ellipse, etc.)
@@ -86,6 +186,10 @@ pub struct Edge {
     pub kind: EdgeKind,
     pub stroke: Option<Color>,
     pub stroke_width: f32,
+    /// Construction/guide geometry: pickable and snappable, but left out of
+    /// regions and SVG export
+    #[serde(default)]
+    pub construction: bool,
 }
 
 // --- Layer/Group System ---
@@ -93,21 +197,77 @@ pub struct Edge {
 /// Layer identifier type
 pub type LayerId = u32;
 
-/// A group is a container for edges within a layer hierarchy
+/// A member of a group, tagged by object type
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum GroupItem {
+    Group(LayerId),
+    Edge(u32),
+    Shape(u32),
+    Text(u32),
+}
+
+/// What limits where a group's content shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum GroupMask {
+    /// Vector clip: content shows only inside the closed shape, under its
+    /// fill rule
+    Clip { shape: u32 },
+    /// Content shows where the source group's content is opaque; the source
+    /// group is not drawn on its own
+    Alpha { group: LayerId },
+    /// Content shows where the source group's content is bright; the source
+    /// group is not drawn on its own
+    Luminance { group: LayerId },
+}
+
+impl GroupMask {
+    /// The group supplying an alpha or luminance mask
+    pub fn source_group(self) -> Option<LayerId> {
+        match self {
+            GroupMask::Clip { .. } => None,
+            GroupMask::Alpha { group } | GroupMask::Luminance { group } => Some(group),
+        }
+    }
+}
+
+/// A group is a container for edges, shapes, texts and subgroups within a
+/// layer hierarchy
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Group {
     pub id: LayerId,
     pub name: String,
     /// Parent group ID, None if this is a layer's root group
     pub parent: Option<LayerId>,
-    /// Child group IDs
-    pub children: Vec<LayerId>,
-    /// Edge IDs directly in this group
-    pub edges: Vec<u32>,
+    /// Members in drawing order, bottom to top
+    pub items: Vec<GroupItem>,
     pub visible: bool,
     pub locked: bool,
     /// Opacity from 0.0 to 1.0
     pub opacity: f32,
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+    #[serde(default)]
+    pub mask: Option<GroupMask>,
+}
+
+impl Group {
+    /// Child group IDs in order
+    pub fn child_groups(&self) -> impl Iterator<Item = LayerId> + '_ {
+        self.items.iter().filter_map(|item| match item {
+            GroupItem::Group(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Edge IDs directly in this group, in order
+    pub fn edge_ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.items.iter().filter_map(|item| match item {
+            GroupItem::Edge(id) => Some(*id),
+            _ => None,
+        })
+    }
 }
 
 /// A layer is a top-level organizational container with a root group
@@ -121,6 +281,8 @@ pub struct Layer {
     pub locked: bool,
     /// Opacity from 0.0 to 1.0
     pub opacity: f32,
+    #[serde(default)]
+    pub blend_mode: BlendMode,
     /// The root group for this layer
     pub root_group: LayerId,
 }
@@ -1,12 +1,21 @@
 use crate::geometry::limits;
-use crate::model::{EdgeKind, FontStyle, TextAlign};
+use crate::algorithms::masking::mask_area;
+use crate::algorithms::region_fill::shape_fill;
+use crate::model::{
+    BlendMode, Edge, EdgeKind, FillRule, FontStyle, Group, GroupItem, GroupMask, LayerId, Shape,
+    TextAlign, TextElement, Vec2,
+};
 use crate::Graph;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub fn to_svg_paths_impl(g: &Graph) -> Vec<String> {
     let mut paths = Vec::new();
     for e in g.edges.iter() {
         if let Some(e) = e {
+            // Construction/guide edges are not exported
+            if e.construction {
+                continue;
+            }
             let a = if let Some(n) = g.nodes.get(e.a as usize).and_then(|n| *n) {
                 n
             } else {
@@ -43,175 +52,95 @@ pub fn to_svg_paths_impl(g: &Graph) -> Vec<String> {
     paths
 }
 
-/// Generate a complete SVG document string with paths, styles, and text
-pub fn to_svg_document_impl(g: &Graph) -> String {
-    let mut elements = Vec::new();
-    let mut min_x = f32::MAX;
-    let mut min_y = f32::MAX;
-    let mut max_x = f32::MIN;
-    let mut max_y = f32::MIN;
-
-    // Helper to update bounding box
-    let mut update_bbox = |x: f32, y: f32| {
-        min_x = min_x.min(x);
-        min_y = min_y.min(y);
-        max_x = max_x.max(x);
-        max_y = max_y.max(y);
-    };
-
-    // Export edges as path elements
-    for e in g.edges.iter() {
-        if let Some(e) = e {
-            let a = match g.nodes.get(e.a as usize).and_then(|n| *n) {
-                Some(n) => n,
-                None => continue,
-            };
-            let b = match g.nodes.get(e.b as usize).and_then(|n| *n) {
-                Some(n) => n,
-                None => continue,
-            };
-
-            update_bbox(a.x, a.y);
-            update_bbox(b.x, b.y);
-
-            // Build path d attribute
-            let d = match &e.kind {
-                EdgeKind::Line => format!("M {} {} L {} {}", a.x, a.y, b.x, b.y),
-                EdgeKind::Cubic { ha, hb, .. } => {
-                    let p1x = a.x + ha.x;
-                    let p1y = a.y + ha.y;
-                    let p2x = b.x + hb.x;
-                    let p2y = b.y + hb.y;
-                    update_bbox(p1x, p1y);
-                    update_bbox(p2x, p2y);
-                    format!(
-                        "M {} {} C {} {}, {} {}, {} {}",
-                        a.x, a.y, p1x, p1y, p2x, p2y, b.x, b.y
-                    )
-                }
-                EdgeKind::Polyline { points } => {
-                    let mut d = format!("M {} {}", a.x, a.y);
-                    for p in points {
-                        update_bbox(p.x, p.y);
-                        d.push_str(&format!(" L {} {}", p.x, p.y));
-                    }
-                    d.push_str(&format!(" L {} {}", b.x, b.y));
-                    d
-                }
-            };
-
-            // Build style attributes
-            let stroke_color = e
-                .stroke
-                .as_ref()
-                .map(|c| {
-                    if c.a == 255 {
-                        format!("rgb({},{},{})", c.r, c.g, c.b)
-                    } else {
-                        format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
-                    }
-                })
-                .unwrap_or_else(|| "black".to_string());
-            let stroke_width = e.stroke_width;
+/// Bounds of everything written to the document
+struct Bounds {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+}
 
-            elements.push(format!(
-                r#"  <path d="{}" stroke="{}" stroke-width="{}" fill="none" stroke-linecap="round" stroke-linejoin="round"/>"#,
-                d, stroke_color, stroke_width
-            ));
+impl Bounds {
+    fn empty() -> Self {
+        Bounds {
+            min_x: f32::MAX,
+            min_y: f32::MAX,
+            max_x: f32::MIN,
+            max_y: f32::MIN,
         }
     }
 
-    // Export text elements
-    for text in g.texts.iter().filter_map(|t| t.as_ref()) {
-        update_bbox(text.position.x, text.position.y);
-        // Estimate text extent for bbox (rough approximation)
-        let est_width = text.content.len() as f32 * text.style.font_size * 0.6;
-        update_bbox(text.position.x + est_width, text.position.y + text.style.font_size);
-
-        // Build text-anchor from align
-        let text_anchor = match text.align {
-            TextAlign::Left => "start",
-            TextAlign::Center => "middle",
-            TextAlign::Right => "end",
-        };
-
-        // Build font-style
-        let font_style = match text.style.font_style {
-            FontStyle::Normal => "normal",
-            FontStyle::Italic => "italic",
-            FontStyle::Oblique => "oblique",
-        };
-
-        // Build fill color
-        let fill = text
-            .style
-            .fill_color
-            .as_ref()
-            .map(|c| {
-                if c.a == 255 {
-                    format!("rgb({},{},{})", c.r, c.g, c.b)
-                } else {
-                    format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
-                }
-            })
-            .unwrap_or_else(|| "black".to_string());
-
-        // Build optional stroke
-        let stroke_attrs = match &text.style.stroke_color {
-            Some(c) if text.style.stroke_width > 0.0 => {
-                let stroke_col = if c.a == 255 {
-                    format!("rgb({},{},{})", c.r, c.g, c.b)
-                } else {
-                    format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
-                };
-                format!(
-                    r#" stroke="{}" stroke-width="{}""#,
-                    stroke_col, text.style.stroke_width
-                )
-            }
-            _ => String::new(),
-        };
-
-        // Build transform for rotation (convert radians to degrees)
-        let transform = if text.rotation.abs() > 1e-6 {
-            let degrees = text.rotation.to_degrees();
-            format!(
-                r#" transform="rotate({:.2}, {}, {})""#,
-                degrees, text.position.x, text.position.y
-            )
-        } else {
-            String::new()
-        };
-
-        // Build letter-spacing if non-zero
-        let letter_spacing = if text.style.letter_spacing.abs() > 1e-6 {
-            format!(r#" letter-spacing="{}em""#, text.style.letter_spacing)
-        } else {
-            String::new()
-        };
+    fn add(&mut self, x: f32, y: f32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+}
 
-        // Escape XML content
-        let content = escape_xml(&text.content);
+/// Generate a complete SVG document string with paths, styles, and text.
+/// Content is written in hierarchy order: layers bottom to top, each as a
+/// `<g>` holding its groups and members in sibling order. Hidden layers and
+/// groups are left out; layer and group opacity go on their `<g>`. Group
+/// masks become `<clipPath>` and `<mask>` definitions, and mask source groups
+/// are left out. Objects outside any layer follow at the end.
+pub fn to_svg_document_impl(g: &Graph) -> String {
+    let mut elements = Vec::new();
+    let mut bounds = Bounds::empty();
+    let mut defs = Defs::new(g);
 
+    let ls = &g.layer_system;
+    for layer in ls.layers_ordered() {
+        if !layer.visible {
+            continue;
+        }
+        let mut inner = Vec::new();
+        write_group_items(g, layer.root_group, 2, &mut inner, &mut bounds, &mut defs);
+        if inner.is_empty() {
+            continue;
+        }
+        let mask = ls
+            .get_group(layer.root_group)
+            .map_or_else(String::new, |root| mask_attr(g, root, &mut defs));
         elements.push(format!(
-            r#"  <text x="{}" y="{}" font-family="{}" font-size="{}" font-weight="{}" font-style="{}" text-anchor="{}" fill="{}"{}{}{}>{}</text>"#,
-            text.position.x,
-            text.position.y,
-            escape_xml_attr(&text.style.font_family),
-            text.style.font_size,
-            text.style.font_weight,
-            font_style,
-            text_anchor,
-            fill,
-            stroke_attrs,
-            transform,
-            letter_spacing,
-            content
+            r#"  <g id="layer-{}" data-name="{}"{}{}{}>"#,
+            layer.id,
+            escape_xml_attr(&layer.name),
+            opacity_attr(layer.opacity),
+            blend_attr(layer.blend_mode),
+            mask
         ));
+        elements.extend(inner);
+        elements.push("  </g>".to_string());
+    }
+
+    // Objects outside the hierarchy, in id order
+    let unfiled = (0..g.edges.len() as u32)
+        .map(GroupItem::Edge)
+        .chain((0..g.shapes.len() as u32).map(GroupItem::Shape))
+        .chain((0..g.texts.len() as u32).map(GroupItem::Text))
+        .filter(|&item| ls.item_group(item).is_none());
+    for item in unfiled {
+        if let Some(el) = item_element(g, item, &defs.edge_blends, &mut bounds) {
+            elements.push(format!("  {}", el));
+        }
+    }
+
+    if !defs.out.is_empty() {
+        let mut head = vec!["  <defs>".to_string()];
+        head.append(&mut defs.out);
+        head.push("  </defs>".to_string());
+        elements.splice(0..0, head);
     }
 
     // Calculate viewBox with padding
     let padding = 10.0;
+    let Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    } = bounds;
     if min_x > max_x || min_y > max_y {
         // Empty document
         return r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"></svg>"#
@@ -232,6 +161,362 @@ pub fn to_svg_document_impl(g: &Graph) -> String {
     )
 }
 
+/// Write a group's visible members at `depth`, nesting subgroups as `<g>`.
+/// Mask source groups are skipped; their shapes make up the `<mask>`.
+fn write_group_items(
+    g: &Graph,
+    group_id: LayerId,
+    depth: usize,
+    out: &mut Vec<String>,
+    bounds: &mut Bounds,
+    defs: &mut Defs,
+) {
+    let Some(group) = g.layer_system.get_group(group_id) else {
+        return;
+    };
+    let indent = "  ".repeat(depth);
+    for &item in &group.items {
+        match item {
+            GroupItem::Group(child_id) => {
+                let Some(child) = g.layer_system.get_group(child_id) else {
+                    continue;
+                };
+                if !child.visible || defs.sources.contains(&child_id) {
+                    continue;
+                }
+                let mut inner = Vec::new();
+                write_group_items(g, child_id, depth + 1, &mut inner, bounds, defs);
+                if inner.is_empty() {
+                    continue;
+                }
+                out.push(format!(
+                    r#"{}<g id="group-{}" data-name="{}"{}{}{}>"#,
+                    indent,
+                    child_id,
+                    escape_xml_attr(&child.name),
+                    opacity_attr(child.opacity),
+                    blend_attr(child.blend_mode),
+                    mask_attr(g, child, defs)
+                ));
+                out.extend(inner);
+                out.push(format!("{}</g>", indent));
+            }
+            item => {
+                if let Some(el) = item_element(g, item, &defs.edge_blends, bounds) {
+                    out.push(format!("{}{}", indent, el));
+                }
+            }
+        }
+    }
+}
+
+/// `<clipPath>` and `<mask>` definitions
+struct Defs {
+    /// Groups used as mask sources
+    sources: HashSet<LayerId>,
+    /// Blend mode of the topmost shape each edge outlines
+    edge_blends: HashMap<u32, BlendMode>,
+    out: Vec<String>,
+}
+
+impl Defs {
+    fn new(g: &Graph) -> Self {
+        Defs {
+            sources: g.layer_system.mask_sources(),
+            edge_blends: edge_blend_modes(g),
+            out: Vec::new(),
+        }
+    }
+}
+
+/// Shapes are painted by their edges' strokes, so each edge blends with the
+/// mode of the last shape drawn over it: hierarchy order, then shapes
+/// outside any layer by id.
+fn edge_blend_modes(g: &Graph) -> HashMap<u32, BlendMode> {
+    let ls = &g.layer_system;
+    let unfiled = (0..g.shapes.len() as u32)
+        .map(GroupItem::Shape)
+        .filter(|&item| ls.item_group(item).is_none());
+    let mut out = HashMap::new();
+    for item in ls.draw_order().into_iter().chain(unfiled) {
+        let GroupItem::Shape(id) = item else {
+            continue;
+        };
+        if let Some(shape) = g.get_shape(id) {
+            out.extend(shape.edges.iter().map(|&e| (e, shape.blend_mode)));
+        }
+    }
+    out
+}
+
+/// `clip-path` or `mask` reference for a masked group, writing its
+/// definition; nothing for unmasked groups and ignored masks.
+/// Masks hold the source group's closed shapes filled white, the same
+/// coverage picking uses.
+fn mask_attr(g: &Graph, group: &Group, defs: &mut Defs) -> String {
+    let Some(mask) = group.mask else {
+        return String::new();
+    };
+    let Some(area) = mask_area(g, mask) else {
+        return String::new();
+    };
+    let id = group.id;
+    let mask_type = match mask {
+        GroupMask::Clip { .. } => None,
+        GroupMask::Alpha { .. } => Some("alpha"),
+        GroupMask::Luminance { .. } => Some("luminance"),
+    };
+    // Mask outlines do not widen the document
+    let mut scratch = Bounds::empty();
+    let paths: String = area
+        .outlines
+        .iter()
+        .map(|o| {
+            let d = rings_path(&o.rings, &mut scratch);
+            let rule = fill_rule_name(o.rule);
+            match mask_type {
+                None => format!(r#"<path d="{}" clip-rule="{}"/>"#, d, rule),
+                Some(_) => format!(r#"<path d="{}" fill="white" fill-rule="{}"/>"#, d, rule),
+            }
+        })
+        .collect();
+    match mask_type {
+        None => {
+            defs.out.push(format!(
+                r#"    <clipPath id="clip-{}">{}</clipPath>"#,
+                id, paths
+            ));
+            format!(r#" clip-path="url(#clip-{})""#, id)
+        }
+        Some(kind) => {
+            defs.out.push(format!(
+                r#"    <mask id="mask-{}" mask-type="{}">{}</mask>"#,
+                id, kind, paths
+            ));
+            format!(r#" mask="url(#mask-{})""#, id)
+        }
+    }
+}
+
+fn opacity_attr(opacity: f32) -> String {
+    if opacity < 1.0 {
+        format!(r#" opacity="{}""#, opacity)
+    } else {
+        String::new()
+    }
+}
+
+/// `mix-blend-mode` style; nothing for normal blending
+fn blend_attr(mode: BlendMode) -> String {
+    if mode == BlendMode::Normal {
+        String::new()
+    } else {
+        format!(r#" style="mix-blend-mode:{}""#, mode.css_name())
+    }
+}
+
+/// One edge, shape or text as an SVG element; None for missing objects,
+/// construction edges and shapes without a closed outline. Edges blend
+/// with the mode `blends` gives them.
+fn item_element(
+    g: &Graph,
+    item: GroupItem,
+    blends: &HashMap<u32, BlendMode>,
+    bounds: &mut Bounds,
+) -> Option<String> {
+    match item {
+        GroupItem::Edge(id) => {
+            let blend = blends.get(&id).copied().unwrap_or_default();
+            edge_element(g, g.edges.get(id as usize)?.as_ref()?, blend, bounds)
+        }
+        GroupItem::Shape(id) => shape_element(g, g.get_shape(id)?, bounds),
+        GroupItem::Text(id) => Some(text_element(g.get_text(id)?, bounds)),
+        GroupItem::Group(_) => None,
+    }
+}
+
+/// An edge as a stroked path, skipping construction/guide edges
+fn edge_element(g: &Graph, e: &Edge, blend: BlendMode, bounds: &mut Bounds) -> Option<String> {
+    if e.construction {
+        return None;
+    }
+    let a = g.nodes.get(e.a as usize).and_then(|n| *n)?;
+    let b = g.nodes.get(e.b as usize).and_then(|n| *n)?;
+    let mut update_bbox = |x: f32, y: f32| bounds.add(x, y);
+
+    update_bbox(a.x, a.y);
+    update_bbox(b.x, b.y);
+
+    // Build path d attribute
+    let d = match &e.kind {
+        EdgeKind::Line => format!("M {} {} L {} {}", a.x, a.y, b.x, b.y),
+        EdgeKind::Cubic { ha, hb, .. } => {
+            let p1x = a.x + ha.x;
+            let p1y = a.y + ha.y;
+            let p2x = b.x + hb.x;
+            let p2y = b.y + hb.y;
+            update_bbox(p1x, p1y);
+            update_bbox(p2x, p2y);
+            format!(
+                "M {} {} C {} {}, {} {}, {} {}",
+                a.x, a.y, p1x, p1y, p2x, p2y, b.x, b.y
+            )
+        }
+        EdgeKind::Polyline { points } => {
+            let mut d = format!("M {} {}", a.x, a.y);
+            for p in points {
+                update_bbox(p.x, p.y);
+                d.push_str(&format!(" L {} {}", p.x, p.y));
+            }
+            d.push_str(&format!(" L {} {}", b.x, b.y));
+            d
+        }
+    };
+
+    // Build style attributes
+    let stroke_color = e
+        .stroke
+        .as_ref()
+        .map(|c| {
+            if c.a == 255 {
+                format!("rgb({},{},{})", c.r, c.g, c.b)
+            } else {
+                format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
+            }
+        })
+        .unwrap_or_else(|| "black".to_string());
+    let stroke_width = e.stroke_width;
+
+    Some(format!(
+        r#"<path d="{}" stroke="{}" stroke-width="{}" fill="none" stroke-linecap="round" stroke-linejoin="round"{}/>"#,
+        d, stroke_color, stroke_width, blend_attr(blend)
+    ))
+}
+
+/// A closed shape as an unpainted outline carrying its fill rule; its edges
+/// draw the strokes and carry its blend mode
+fn shape_element(g: &Graph, shape: &Shape, bounds: &mut Bounds) -> Option<String> {
+    let fill = shape_fill(g, shape)?;
+    Some(format!(
+        r#"<path data-shape="{}" d="{}" fill="none" fill-rule="{}"/>"#,
+        shape.id,
+        rings_path(&fill.rings, bounds),
+        fill_rule_name(fill.rule)
+    ))
+}
+
+/// Closed rings as path data
+fn rings_path(rings: &[Vec<Vec2>], bounds: &mut Bounds) -> String {
+    let mut out = Vec::new();
+    for ring in rings {
+        let mut d = String::new();
+        for (i, p) in ring.iter().enumerate() {
+            bounds.add(p.x, p.y);
+            let cmd = if i == 0 { "M" } else { " L" };
+            d.push_str(&format!("{} {} {}", cmd, p.x, p.y));
+        }
+        d.push_str(" Z");
+        out.push(d);
+    }
+    out.join(" ")
+}
+
+fn fill_rule_name(rule: FillRule) -> &'static str {
+    match rule {
+        FillRule::NonZero => "nonzero",
+        FillRule::EvenOdd => "evenodd",
+    }
+}
+
+/// A text element with its style, rotation and spacing
+fn text_element(text: &TextElement, bounds: &mut Bounds) -> String {
+    bounds.add(text.position.x, text.position.y);
+    // Estimate text extent for bbox (rough approximation)
+    let est_width = text.content.len() as f32 * text.style.font_size * 0.6;
+    bounds.add(text.position.x + est_width, text.position.y + text.style.font_size);
+
+    // Build text-anchor from align
+    let text_anchor = match text.align {
+        TextAlign::Left => "start",
+        TextAlign::Center => "middle",
+        TextAlign::Right => "end",
+    };
+
+    // Build font-style
+    let font_style = match text.style.font_style {
+        FontStyle::Normal => "normal",
+        FontStyle::Italic => "italic",
+        FontStyle::Oblique => "oblique",
+    };
+
+    // Build fill color
+    let fill = text
+        .style
+        .fill_color
+        .as_ref()
+        .map(|c| {
+            if c.a == 255 {
+                format!("rgb({},{},{})", c.r, c.g, c.b)
+            } else {
+                format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
+            }
+        })
+        .unwrap_or_else(|| "black".to_string());
+
+    // Build optional stroke
+    let stroke_attrs = match &text.style.stroke_color {
+        Some(c) if text.style.stroke_width > 0.0 => {
+            let stroke_col = if c.a == 255 {
+                format!("rgb({},{},{})", c.r, c.g, c.b)
+            } else {
+                format!("rgba({},{},{},{})", c.r, c.g, c.b, c.a as f32 / 255.0)
+            };
+            format!(
+                r#" stroke="{}" stroke-width="{}""#,
+                stroke_col, text.style.stroke_width
+            )
+        }
+        _ => String::new(),
+    };
+
+    // Build transform for rotation (convert radians to degrees)
+    let transform = if text.rotation.abs() > 1e-6 {
+        let degrees = text.rotation.to_degrees();
+        format!(
+            r#" transform="rotate({:.2}, {}, {})""#,
+            degrees, text.position.x, text.position.y
+        )
+    } else {
+        String::new()
+    };
+
+    // Build letter-spacing if non-zero
+    let letter_spacing = if text.style.letter_spacing.abs() > 1e-6 {
+        format!(r#" letter-spacing="{}em""#, text.style.letter_spacing)
+    } else {
+        String::new()
+    };
+
+    // Escape XML content
+    let content = escape_xml(&text.content);
+
+    format!(
+        r#"<text x="{}" y="{}" font-family="{}" font-size="{}" font-weight="{}" font-style="{}" text-anchor="{}" fill="{}"{}{}{}>{}</text>"#,
+        text.position.x,
+        text.position.y,
+        escape_xml_attr(&text.style.font_family),
+        text.style.font_size,
+        text.style.font_weight,
+        font_style,
+        text_anchor,
+        fill,
+        stroke_attrs,
+        transform,
+        letter_spacing,
+        content
+    )
+}
+
 /// Escape special XML characters in content
 fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
@@ -377,7 +662,7 @@ pub fn add_svg_path_impl(g: &mut Graph, d: &str, rgba: Option<(u8, u8, u8, u8, f
                         if let Some(eid) = g.add_edge(a_id, b_id) {
                             if let Some((r, gg, b, aa, w)) = rgba {
                                 if limits::in_width_bounds(w) {
-                                    g.set_edge_style(eid, r, gg, b, aa, w);
+                                    g.apply_edge_style(eid, r, gg, b, aa, w);
                                 }
                             }
                             edges_added += 1;
@@ -417,7 +702,7 @@ pub fn add_svg_path_impl(g: &mut Graph, d: &str, rgba: Option<(u8, u8, u8, u8, f
                         if let Some(eid) = g.add_edge(a_id, b_id) {
                             if let Some((r, gg, b, aa, w)) = rgba {
                                 if limits::in_width_bounds(w) {
-                                    g.set_edge_style(eid, r, gg, b, aa, w);
+                                    g.apply_edge_style(eid, r, gg, b, aa, w);
                                 }
                             }
                             edges_added += 1;
@@ -492,7 +777,7 @@ pub fn add_svg_path_impl(g: &mut Graph, d: &str, rgba: Option<(u8, u8, u8, u8, f
                             g.set_edge_cubic(eid, x1, y1, x2, y2);
                             if let Some((r, gg, b, aa, w)) = rgba {
                                 if limits::in_width_bounds(w) {
-                                    g.set_edge_style(eid, r, gg, b, aa, w);
+                                    g.apply_edge_style(eid, r, gg, b, aa, w);
                                 }
                             }
                             edges_added += 1;
@@ -524,7 +809,7 @@ pub fn add_svg_path_impl(g: &mut Graph, d: &str, rgba: Option<(u8, u8, u8, u8, f
                     if let Some(eid) = g.add_edge(a_id, b_id) {
                         if let Some((r, gg, b, aa, w)) = rgba {
                             if limits::in_width_bounds(w) {
-                                g.set_edge_style(eid, r, gg, b, aa, w);
+                                g.apply_edge_style(eid, r, gg, b, aa, w);
                             }
                         }
                         edges_added += 1;
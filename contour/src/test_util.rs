@@ -0,0 +1,22 @@
+//! Fixtures shared by the unit tests.
+
+use crate::Graph;
+
+/// Axis-aligned square from line edges; returns (node ids, edge ids).
+pub(crate) fn square(g: &mut Graph, x: f32, y: f32, s: f32) -> (Vec<u32>, Vec<u32>) {
+    let ids: Vec<u32> = [(x, y), (x + s, y), (x + s, y + s), (x, y + s)]
+        .iter()
+        .map(|&(x, y)| g.add_node(x, y))
+        .collect();
+    let edges = (0..4)
+        .map(|i| g.add_edge(ids[i], ids[(i + 1) % 4]).unwrap())
+        .collect();
+    (ids, edges)
+}
+
+/// Region keys from `get_regions` output, sorted
+pub(crate) fn sorted_keys(regions: &[serde_json::Value]) -> Vec<u64> {
+    let mut keys: Vec<u64> = regions.iter().map(|r| r["key"].as_u64().unwrap()).collect();
+    keys.sort_unstable();
+    keys
+}